@@ -0,0 +1,86 @@
+// cargo bench --bench lookup
+//
+// 同じ設定を ConfList / Config / ArenaConfig で持ち、全てのキーを読む時間と、作った後に残っているメモリを比べる
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use conf_loader_with_validation::parse_str;
+
+struct CountingAlloc;
+
+// 解放された分を引いた、いま確保されているバイト数とブロック数
+static LIVE_BYTES: AtomicUsize = AtomicUsize::new(0);
+static LIVE_BLOCKS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        LIVE_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        LIVE_BLOCKS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        LIVE_BYTES.fetch_sub(layout.size(), Ordering::Relaxed);
+        LIVE_BLOCKS.fetch_sub(1, Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const FIELDS: [&str; 8] = ["host", "port", "timeout", "retries", "user", "password", "pool.min", "pool.max"];
+const ROUNDS: usize = 10;
+
+fn generate(services: usize) -> (String, Vec<String>) {
+    let mut conf = String::new();
+    let mut paths = Vec::new();
+    for i in 0..services {
+        for field in FIELDS {
+            let path = format!("services.svc{}.{}", i, field);
+            conf.push_str(&format!("{} = value-{}\n", path, i));
+            paths.push(path);
+        }
+    }
+    (conf, paths)
+}
+
+// build で作った値が持っているメモリと、read で全てのパスを ROUNDS 回読む時間
+fn measure<T>(name: &str, keys: usize, build: impl FnOnce() -> T, read: impl Fn(&T)) {
+    let before = (LIVE_BYTES.load(Ordering::Relaxed), LIVE_BLOCKS.load(Ordering::Relaxed));
+    let value = build();
+    let bytes = LIVE_BYTES.load(Ordering::Relaxed) - before.0;
+    let blocks = LIVE_BLOCKS.load(Ordering::Relaxed) - before.1;
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        read(&value);
+    }
+    let per_lookup = start.elapsed() / (ROUNDS * keys) as u32;
+    println!(
+        "{:<11} {:>6} keys: {:>10.2?} per lookup  holding {:>10} bytes in {:>8} blocks",
+        name, keys, per_lookup.max(Duration::from_nanos(1)), bytes, blocks,
+    );
+}
+
+fn main() {
+    for services in [100, 1_000, 10_000] {
+        let (conf, paths) = generate(services);
+        measure("ConfList", paths.len(), || parse_str(&conf, None).unwrap(), |list| {
+            for path in &paths {
+                black_box(list.get_str(path).unwrap());
+            }
+        });
+        measure("Config", paths.len(), || parse_str(&conf, None).unwrap().freeze(), |config| {
+            for path in &paths {
+                black_box(config.get_str(path).unwrap());
+            }
+        });
+        measure("ArenaConfig", paths.len(), || parse_str(&conf, None).unwrap().freeze_arena(), |arena| {
+            for path in &paths {
+                black_box(arena.get_str(path).unwrap());
+            }
+        });
+    }
+}
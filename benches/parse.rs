@@ -0,0 +1,75 @@
+// cargo bench --bench parse
+//
+// 同じプレフィックスの下に多数のキーがある設定を読み込み、時間と確保したメモリを表示する
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use conf_loader_with_validation::parse_str;
+
+struct CountingAlloc;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const FIELDS: [&str; 8] = ["host", "port", "timeout", "retries", "user", "password", "pool.min", "pool.max"];
+
+fn generate(services: usize) -> String {
+    let mut conf = String::new();
+    for i in 0..services {
+        for field in FIELDS {
+            conf.push_str(&format!("services.svc{}.{} = value-{}\n", i, field, i));
+        }
+    }
+    conf
+}
+
+// キーの区切りごとに String を持った場合のバイト数と、重複を除いたバイト数
+fn key_segment_bytes(conf: &str) -> (usize, usize) {
+    let mut total = 0;
+    let mut unique = HashSet::new();
+    for line in conf.lines() {
+        let key = line.split('=').next().unwrap().trim();
+        for segment in key.split('.') {
+            total += segment.len();
+            unique.insert(segment.to_string());
+        }
+    }
+    (total, unique.iter().map(String::len).sum())
+}
+
+fn main() {
+    for services in [100, 1_000, 10_000] {
+        let conf = generate(services);
+        let (total, unique) = key_segment_bytes(&conf);
+
+        let before = (ALLOCATED.load(Ordering::Relaxed), ALLOCATIONS.load(Ordering::Relaxed));
+        let start = Instant::now();
+        let list = parse_str(&conf, None).unwrap();
+        let elapsed = start.elapsed();
+        let bytes = ALLOCATED.load(Ordering::Relaxed) - before.0;
+        let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before.1;
+        drop(list);
+
+        println!(
+            "parse {:>6} keys: {:>10.2?}  allocated {:>10} bytes in {:>8} allocations  key segments {:>9} bytes ({} bytes interned)",
+            services * FIELDS.len(), elapsed, bytes, allocations, total, unique,
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Lit, LitStr};
+
+// #[derive(FromConf)]
+//
+//   #[conf(rename = "db.host")]      読み込むパス (省略時はフィールド名)
+//   #[conf(default)]                 キーがなければ Default::default()
+//   #[conf(default = 8080)]          キーがなければこの値
+//   #[conf(default = "path::to_fn")] キーがなければこの関数の戻り値
+#[proc_macro_derive(FromConf, attributes(conf))]
+pub fn derive_from_conf(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input).unwrap_or_else(syn::Error::into_compile_error).into()
+}
+
+enum DefaultValue {
+    Trait,
+    Value(Expr),
+    Function(syn::Path),
+}
+
+struct FieldAttrs {
+    rename: Option<String>,
+    default: Option<DefaultValue>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(&input.ident, "FromConf requires a struct with named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "FromConf can only be derived for structs")),
+    };
+
+    let mut initializers = Vec::new();
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let attrs = parse_attrs(field)?;
+        let path = attrs.rename.unwrap_or_else(|| ident.to_string().trim_start_matches("r#").to_string());
+        let value = match attrs.default {
+            None => quote! { ::conf_loader_with_validation::typed::field(conf, #path)? },
+            Some(DefaultValue::Trait) => quote! {
+                ::conf_loader_with_validation::typed::field_or(conf, #path, ::std::default::Default::default)?
+            },
+            Some(DefaultValue::Value(expr)) => quote! {
+                ::conf_loader_with_validation::typed::field_or(conf, #path, || #expr)?
+            },
+            Some(DefaultValue::Function(function)) => quote! {
+                ::conf_loader_with_validation::typed::field_or(conf, #path, #function)?
+            },
+        };
+        initializers.push(quote! { #ident: #value });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::conf_loader_with_validation::FromConf for #name #ty_generics #where_clause {
+            fn from_conf(
+                conf: &::conf_loader_with_validation::Config,
+            ) -> ::std::result::Result<Self, ::std::boxed::Box<dyn ::std::error::Error>> {
+                ::std::result::Result::Ok(#name { #(#initializers,)* })
+            }
+        }
+
+        // 入れ子のセクションとしても読めるようにする
+        impl #impl_generics ::conf_loader_with_validation::FromConfValue for #name #ty_generics #where_clause {
+            fn from_conf_value(
+                value: &::conf_loader_with_validation::ConfigValue,
+            ) -> ::std::result::Result<Self, ::std::string::String> {
+                ::conf_loader_with_validation::typed::nested(value)
+            }
+
+            fn from_conf_field(
+                value: &::conf_loader_with_validation::ConfigValue,
+                path: &str,
+            ) -> ::std::result::Result<Self, ::std::boxed::Box<dyn ::std::error::Error>> {
+                ::conf_loader_with_validation::typed::nested_field(value, path)
+            }
+        }
+    })
+}
+
+fn parse_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs { rename: None, default: None };
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("conf")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                let value: LitStr = meta.value()?.parse()?;
+                attrs.rename = Some(value.value());
+                return Ok(());
+            }
+            if meta.path.is_ident("default") {
+                if !meta.input.peek(syn::Token![=]) {
+                    attrs.default = Some(DefaultValue::Trait);
+                    return Ok(());
+                }
+                // serde と同じく文字列は関数のパスとして扱う
+                attrs.default = Some(match meta.value()?.parse::<Expr>()? {
+                    Expr::Lit(syn::ExprLit { lit: Lit::Str(path), .. }) => DefaultValue::Function(path.parse()?),
+                    expr => DefaultValue::Value(expr),
+                });
+                return Ok(());
+            }
+            Err(meta.error("unknown conf attribute (expected rename or default)"))
+        })?;
+    }
+    Ok(attrs)
+}
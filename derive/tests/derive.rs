@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use conf_loader_with_validation::{parse_str, FromConf};
+
+#[derive(Debug, PartialEq, FromConf)]
+struct AppConfig {
+    endpoint: String,
+    debug: bool,
+    #[conf(rename = "http.port", default = 8080)]
+    port: u16,
+    #[conf(default = "default_timeout")]
+    timeout: Duration,
+    workers: Option<usize>,
+    #[conf(default)]
+    tags: String,
+    log: LogConfig,
+}
+
+#[derive(Debug, PartialEq, FromConf)]
+struct LogConfig {
+    file: String,
+    #[conf(rename = "level")]
+    verbosity: String,
+}
+
+fn default_timeout() -> Duration {
+    Duration::from_secs(30)
+}
+
+#[test]
+fn can_derive_from_conf() {
+    let conf = parse_str("endpoint = localhost:3000\ndebug = true\nlog.file = /var/log/app.log\nlog.level = warn\n", None)
+        .unwrap()
+        .freeze();
+    let app = AppConfig::from_conf(&conf).unwrap();
+    assert_eq!(app, AppConfig {
+        endpoint: "localhost:3000".to_string(),
+        debug: true,
+        port: 8080,
+        timeout: Duration::from_secs(30),
+        workers: None,
+        tags: String::new(),
+        log: LogConfig { file: "/var/log/app.log".to_string(), verbosity: "warn".to_string() },
+    });
+}
+
+#[test]
+fn can_report_missing_and_invalid_fields() {
+    let conf = parse_str("endpoint = x\ndebug = yes\nlog.file = a\nlog.level = b\n", None).unwrap().freeze();
+    assert_eq!(AppConfig::from_conf(&conf).unwrap_err().to_string(), "debug: Expected a boolean");
+
+    let conf = parse_str("endpoint = x\ndebug = true\nhttp.port = 70000\nlog.file = a\n", None).unwrap().freeze();
+    assert_eq!(AppConfig::from_conf(&conf).unwrap_err().to_string(), "http.port: Expected an integer in u16");
+
+    let conf = parse_str("endpoint = x\ndebug = true\nlog.file = a\n", None).unwrap().freeze();
+    assert_eq!(AppConfig::from_conf(&conf).unwrap_err().to_string(), "Missing key: log.level");
+
+    // 入れ子の構造体のエラーも外側からのパスで出る
+    let conf = parse_str("endpoint = x\ndebug = true\nlog.file.name = a\nlog.level = b\n", None).unwrap().freeze();
+    assert_eq!(AppConfig::from_conf(&conf).unwrap_err().to_string(), "log.file: Expected a string");
+    let conf = parse_str("endpoint = x\ndebug = true\nlog = a\n", None).unwrap().freeze();
+    assert_eq!(AppConfig::from_conf(&conf).unwrap_err().to_string(), "log: Expected a section");
+}
@@ -0,0 +1,353 @@
+// パスで値を取り出すときのエラー。キーがないのか型が違うのかを呼び出し側で区別できるようにする
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "decimal")]
+use crate::integer::decimal_as_f64;
+use crate::path::{parent_paths, split_key, to_dotted};
+use crate::{Config, ConfigValue, ConfList, ConfValue};
+
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum AccessErrorKind {
+    NotFound,
+    // expected / found は "string" / "bool" / "number" / "decimal" / "bytes" / "list" / "section"
+    WrongType { expected: &'static str, found: &'static str },
+    // リストの長さを超える番号
+    OutOfBounds { index: usize, len: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfAccessError {
+    pub path: String,
+    pub kind: AccessErrorKind,
+}
+
+impl ConfAccessError {
+    pub(crate) fn not_found(path: &str) -> Self {
+        ConfAccessError { path: path.to_string(), kind: AccessErrorKind::NotFound }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        self.kind == AccessErrorKind::NotFound
+    }
+}
+
+impl fmt::Display for ConfAccessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // get_index で値から直接取り出したときはパスがない
+        let at = match self.path.is_empty() {
+            true => String::new(),
+            false => format!("{}: ", self.path),
+        };
+        match &self.kind {
+            AccessErrorKind::NotFound => write!(f, "Missing key: {}", self.path),
+            AccessErrorKind::WrongType { expected, found } => write!(f, "{}Expected {} but found {}", at, expected, found),
+            AccessErrorKind::OutOfBounds { index, len } => write!(f, "{}Index {} is out of bounds (length {})", at, index, len),
+        }
+    }
+}
+
+impl Error for ConfAccessError {}
+
+impl ConfValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ConfValue::StrValue(_) => "string",
+            ConfValue::BoolValue(_) => "bool",
+            ConfValue::NumberValue(_) => "number",
+            #[cfg(feature = "decimal")]
+            ConfValue::DecimalValue(_) => "decimal",
+            ConfValue::Bytes(_) => "bytes",
+            ConfValue::List(_) => "list",
+            ConfValue::Conf(_) => "section",
+        }
+    }
+
+    pub fn get_index(&self, index: usize) -> Result<&ConfValue, ConfAccessError> {
+        let items = self.as_list().map_err(|_| wrong_type("", "list", self.type_name()))?;
+        items.get(index).ok_or_else(|| out_of_bounds("", index, items.len()))
+    }
+}
+
+impl ConfigValue {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            ConfigValue::StrValue(_) => "string",
+            ConfigValue::BoolValue(_) => "bool",
+            ConfigValue::NumberValue(_) => "number",
+            #[cfg(feature = "decimal")]
+            ConfigValue::DecimalValue(_) => "decimal",
+            ConfigValue::Bytes(_) => "bytes",
+            ConfigValue::List(_) => "list",
+            ConfigValue::Conf(_) => "section",
+        }
+    }
+
+    pub fn get_index(&self, index: usize) -> Result<&ConfigValue, ConfAccessError> {
+        let items = self.as_list().map_err(|_| wrong_type("", "list", self.type_name()))?;
+        items.get(index).ok_or_else(|| out_of_bounds("", index, items.len()))
+    }
+}
+
+// キーがなければ Ok(None)、あっても型が違えばエラー (省略できるが、書くなら正しい値が必要な設定)
+fn optional<T>(result: Result<T, ConfAccessError>) -> Result<Option<T>, ConfAccessError> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.is_not_found() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub(crate) fn wrong_type(path: &str, expected: &'static str, found: &'static str) -> ConfAccessError {
+    ConfAccessError { path: path.to_string(), kind: AccessErrorKind::WrongType { expected, found } }
+}
+
+fn out_of_bounds(path: &str, index: usize, len: usize) -> ConfAccessError {
+    ConfAccessError { path: path.to_string(), kind: AccessErrorKind::OutOfBounds { index, len } }
+}
+
+// get_index のエラーに、リストのパスを付ける
+fn at(path: &str, e: ConfAccessError) -> ConfAccessError {
+    ConfAccessError { path: path.to_string(), ..e }
+}
+
+// リストの中のセクションから返ったエラーに、そのセクションまでのパスを付ける
+fn within(path: &str, e: ConfAccessError) -> ConfAccessError {
+    ConfAccessError { path: format!("{}.{}", path, e.path), ..e }
+}
+
+// 値に続くパスの最初が番号ならリスト、そうでなければセクションを期待していた
+fn expected_before(rest: &str) -> &'static str {
+    match split_key(rest).0.parse::<usize>() {
+        Ok(_) => "list",
+        Err(_) => "section",
+    }
+}
+
+// list の中を rest (upstream.1.host の 1.host) でたどる
+fn list_item(list: &ConfValue, path: &str, rest: &str) -> Result<ConfValue, ConfAccessError> {
+    let (segment, tail) = split_key(rest);
+    let index = segment.parse().map_err(|_| wrong_type(path, "section", "list"))?;
+    let item = list.get_index(index).map_err(|e| at(path, e))?;
+    let Some(tail) = tail else {
+        return Ok(item.clone());
+    };
+    let path = format!("{}.{}", path, index);
+    match item {
+        ConfValue::List(_) => list_item(item, &path, tail),
+        ConfValue::Conf(child) => child.get_path(tail).map_err(|e| within(&path, e)),
+        item => Err(wrong_type(&path, expected_before(tail), item.type_name())),
+    }
+}
+
+fn config_list_item<'a>(list: &'a ConfigValue, path: &str, rest: &str) -> Result<&'a ConfigValue, ConfAccessError> {
+    let (segment, tail) = split_key(rest);
+    let index = segment.parse().map_err(|_| wrong_type(path, "section", "list"))?;
+    let item = list.get_index(index).map_err(|e| at(path, e))?;
+    let Some(tail) = tail else {
+        return Ok(item);
+    };
+    let path = format!("{}.{}", path, index);
+    match item {
+        ConfigValue::List(_) => config_list_item(item, &path, tail),
+        ConfigValue::Conf(child) => child.get_path(tail).map_err(|e| within(&path, e)),
+        item => Err(wrong_type(&path, expected_before(tail), item.type_name())),
+    }
+}
+
+impl ConfList {
+    // 値は RefCell の中にあるので、取り出した値をコピーして返す
+    fn lookup<T>(&self, path: &str, expected: &'static str, f: impl FnOnce(&ConfValue) -> Option<T>) -> Result<T, ConfAccessError> {
+        match self.read_at(path, |value| f(value).ok_or_else(|| value.type_name())) {
+            Some(Ok(value)) => Ok(value),
+            Some(Err(found)) => Err(wrong_type(path, expected, found)),
+            None => Err(ConfAccessError::not_found(path)),
+        }
+    }
+
+    pub fn get_str(&self, path: &str) -> Result<String, ConfAccessError> {
+        self.lookup(path, "string", |value| value.as_str().ok().cloned())
+    }
+
+    pub fn get_bool(&self, path: &str) -> Result<bool, ConfAccessError> {
+        self.lookup(path, "bool", |value| value.as_bool().ok())
+    }
+
+    // スキーマの型が decimal の値も f64 にして返す
+    pub fn get_number(&self, path: &str) -> Result<f64, ConfAccessError> {
+        self.lookup(path, "number", |value| match value {
+            #[cfg(feature = "decimal")]
+            ConfValue::DecimalValue(v) => Some(decimal_as_f64(v)),
+            value => value.as_number().ok(),
+        })
+    }
+
+    // スキーマの型が uuid でなくても、UUID として読める文字列なら読む
+    #[cfg(feature = "uuid")]
+    pub fn get_uuid(&self, path: &str) -> Result<uuid::Uuid, ConfAccessError> {
+        self.lookup(path, "uuid", |value| value.as_str().ok()?.parse().ok())
+    }
+
+    pub fn try_get_str(&self, path: &str) -> Result<Option<String>, ConfAccessError> {
+        optional(self.get_str(path))
+    }
+
+    pub fn try_get_bool(&self, path: &str) -> Result<Option<bool>, ConfAccessError> {
+        optional(self.get_bool(path))
+    }
+
+    pub fn try_get_number(&self, path: &str) -> Result<Option<f64>, ConfAccessError> {
+        optional(self.get_number(path))
+    }
+
+    // セクションと同じくドットでつなぎ、リストの要素は番号で指す (upstream.1.host)。JSON Pointer (/upstream/1/host) でもよい
+    pub fn get_path(&self, path: &str) -> Result<ConfValue, ConfAccessError> {
+        let path = &to_dotted(path);
+        for parent in parent_paths(path) {
+            let rest = &path[parent.len() + 1..];
+            match self.value_at(parent, |value| value.type_name()) {
+                Some("list") => return self.read_at(parent, |list| list_item(list, parent, rest)).unwrap(),
+                Some("section") | None => {},
+                Some(found) => return Err(wrong_type(parent, expected_before(rest), found)),
+            }
+        }
+        self.read_at(path, ConfValue::clone).ok_or_else(|| ConfAccessError::not_found(path))
+    }
+}
+
+impl Config {
+    pub fn get_value(&self, path: &str) -> Result<&ConfigValue, ConfAccessError> {
+        self.get(path).ok_or_else(|| ConfAccessError::not_found(path))
+    }
+
+    fn lookup<'a, T>(&'a self, path: &str, expected: &'static str, f: impl FnOnce(&'a ConfigValue) -> Option<T>) -> Result<T, ConfAccessError> {
+        let value = self.get_value(path)?;
+        f(value).ok_or_else(|| wrong_type(path, expected, value.type_name()))
+    }
+
+    pub fn get_str(&self, path: &str) -> Result<&str, ConfAccessError> {
+        self.lookup(path, "string", |value| value.as_str().ok())
+    }
+
+    pub fn get_bool(&self, path: &str) -> Result<bool, ConfAccessError> {
+        self.lookup(path, "bool", |value| value.as_bool().ok())
+    }
+
+    pub fn get_number(&self, path: &str) -> Result<f64, ConfAccessError> {
+        self.lookup(path, "number", |value| match value {
+            #[cfg(feature = "decimal")]
+            ConfigValue::DecimalValue(v) => Some(decimal_as_f64(v)),
+            value => value.as_number().ok(),
+        })
+    }
+
+    // スキーマの型が uuid でなくても、UUID として読める文字列なら読む
+    #[cfg(feature = "uuid")]
+    pub fn get_uuid(&self, path: &str) -> Result<uuid::Uuid, ConfAccessError> {
+        self.lookup(path, "uuid", |value| value.as_str().ok()?.parse().ok())
+    }
+
+    pub fn get_conf(&self, path: &str) -> Result<&Config, ConfAccessError> {
+        self.lookup(path, "section", |value| value.as_conf().ok())
+    }
+
+    pub fn try_get_str(&self, path: &str) -> Result<Option<&str>, ConfAccessError> {
+        optional(self.get_str(path))
+    }
+
+    pub fn try_get_bool(&self, path: &str) -> Result<Option<bool>, ConfAccessError> {
+        optional(self.get_bool(path))
+    }
+
+    pub fn try_get_number(&self, path: &str) -> Result<Option<f64>, ConfAccessError> {
+        optional(self.get_number(path))
+    }
+
+    // ConfList::get_path と同じ
+    pub fn get_path(&self, path: &str) -> Result<&ConfigValue, ConfAccessError> {
+        let path = &to_dotted(path);
+        for parent in parent_paths(path) {
+            let rest = &path[parent.len() + 1..];
+            match self.find(parent).map(|entry| &entry.value) {
+                Some(ConfigValue::List(_)) => return config_list_item(self.get_value(parent)?, parent, rest),
+                Some(ConfigValue::Conf(_)) | None => {},
+                Some(value) => return Err(wrong_type(parent, expected_before(rest), value.type_name())),
+            }
+        }
+        self.get_value(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn can_tell_missing_keys_from_wrong_types() {
+        let conf = parse_str("port = 8080\nname = web\nlog.level = debug\n", Some("port -> number\n")).unwrap();
+        assert_eq!(conf.get_number("port").unwrap(), 8080.0);
+        assert_eq!(conf.get_str("log.level").unwrap(), "debug");
+
+        let err = conf.get_bool("port").unwrap_err();
+        assert_eq!(err.kind, AccessErrorKind::WrongType { expected: "bool", found: "number" });
+        assert_eq!(err.to_string(), "port: Expected bool but found number");
+        let err = conf.get_str("log").unwrap_err();
+        assert_eq!(err.to_string(), "log: Expected string but found section");
+        let err = conf.get_str("log.file").unwrap_err();
+        assert!(err.is_not_found());
+        assert_eq!(err.to_string(), "Missing key: log.file");
+
+        let config = conf.freeze();
+        assert_eq!(config.get_str("name").unwrap(), "web");
+        assert!(config.get_conf("log").is_ok());
+        assert_eq!(config.get_number("name").unwrap_err().kind, AccessErrorKind::WrongType { expected: "number", found: "string" });
+        assert!(config.get_value("missing").unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn can_read_optional_settings() {
+        let conf = parse_str("port = 8080\ndebug = yes\n", Some("port -> number\n")).unwrap();
+        assert_eq!(conf.try_get_number("port").unwrap(), Some(8080.0));
+        assert_eq!(conf.try_get_bool("verbose").unwrap(), None);
+        assert_eq!(conf.try_get_bool("debug").unwrap_err().to_string(), "debug: Expected bool but found string");
+
+        let config = conf.freeze();
+        assert_eq!(config.try_get_str("debug").unwrap(), Some("yes"));
+        assert_eq!(config.try_get_str("log.file").unwrap(), None);
+        assert!(config.try_get_str("port").is_err());
+    }
+
+    #[test]
+    fn can_index_into_lists() {
+        let conf = parse_str("upstream = [{host = a, port = 80}, {host = b, ports = [81, 82]}]
+name = web
+", None).unwrap();
+        assert_eq!(conf.get_path("upstream.1.host").unwrap().as_str().unwrap(), "b");
+        assert_eq!(conf.get_path("upstream.1.ports.0").unwrap().as_str().unwrap(), "81");
+        assert_eq!(conf.get_path("name").unwrap().as_str().unwrap(), "web");
+
+        let err = conf.get_path("upstream.2.host").unwrap_err();
+        assert_eq!(err.kind, AccessErrorKind::OutOfBounds { index: 2, len: 2 });
+        assert_eq!(err.to_string(), "upstream: Index 2 is out of bounds (length 2)");
+        assert_eq!(conf.get_path("upstream.host").unwrap_err().to_string(), "upstream: Expected section but found list");
+        assert_eq!(conf.get_path("name.0").unwrap_err().to_string(), "name: Expected list but found string");
+        assert_eq!(conf.get_path("upstream.0.path").unwrap_err().to_string(), "Missing key: upstream.0.path");
+        // JSON Pointer のパスでも指せる
+        assert_eq!(conf.get_path("/upstream/1/host").unwrap().as_str().unwrap(), "b");
+        assert_eq!(conf.get_path("/upstream/2").unwrap_err().to_string(), "upstream: Index 2 is out of bounds (length 2)");
+
+        let value = ConfValue::List(vec![ConfValue::NumberValue(1.0)]);
+        assert_eq!(value.get_index(0).unwrap().as_number().unwrap(), 1.0);
+        assert_eq!(value.get_index(1).unwrap_err().to_string(), "Index 1 is out of bounds (length 1)");
+        assert_eq!(ConfValue::BoolValue(true).get_index(0).unwrap_err().to_string(), "Expected list but found bool");
+
+        let config = conf.freeze();
+        assert_eq!(config.get_path("upstream.0.port").unwrap().as_str().unwrap(), "80");
+        assert_eq!(config.get_path("upstream.1.ports.1").unwrap().as_str().unwrap(), "82");
+        assert_eq!(config.get_path("upstream.1.ports.2").unwrap_err().to_string(), "upstream.1.ports: Index 2 is out of bounds (length 2)");
+        assert!(config.get_path("missing.0").unwrap_err().is_not_found());
+        assert_eq!(config.get_path("/upstream/1/ports/0").unwrap().as_str().unwrap(), "81");
+    }
+}
@@ -0,0 +1,156 @@
+// config クレートの Source と figment の Provider として .conf を読み込む
+// 読み込みとスキーマの検証はこのクレートで行い、層の重ね合わせは各フレームワークに任せる
+use std::error::Error;
+use std::path::Path;
+
+#[cfg(feature = "decimal")]
+use crate::integer::{decimal_as_f64, decimal_as_integer};
+use crate::{parse, ConfList, ConfValue, WriteOptions};
+
+#[derive(Debug, Clone)]
+pub struct ConfFile {
+    path: String,
+    schema: Option<String>,
+    required: bool,
+}
+
+impl ConfFile {
+    // ファイルがなければ空の設定として扱う (config::File の既定と同じ)
+    pub fn new(path: &str) -> Self {
+        ConfFile { path: path.to_string(), schema: None, required: false }
+    }
+
+    pub fn schema(mut self, schema_path: &str) -> Self {
+        self.schema = Some(schema_path.to_string());
+        self
+    }
+
+    pub fn required(mut self, required: bool) -> Self {
+        self.required = required;
+        self
+    }
+
+    fn load(&self) -> Result<ConfList, Box<dyn Error>> {
+        if self.required && !Path::new(&self.path).is_file() {
+            return Err(format!("Config file not found: {}", self.path).into());
+        }
+        parse(&self.path, self.schema.as_deref())
+    }
+}
+
+// 整数で表せる数値は整数として渡す (u16 などへの変換で失敗しないように)
+fn as_integer(v: f64) -> Option<i64> {
+    (v.fract() == 0.0 && v.abs() < 1e15).then_some(v as i64)
+}
+
+#[cfg(feature = "config")]
+mod config_source {
+    use config::{ConfigError, Map, Source, Value, ValueKind};
+
+    use super::*;
+
+    impl Source for ConfFile {
+        fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+            Box::new(self.clone())
+        }
+
+        fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+            let list = self.load().map_err(|e| ConfigError::Message(e.to_string()))?;
+            Ok(to_table(&list, &self.path))
+        }
+    }
+
+    fn to_table(list: &ConfList, origin: &String) -> Map<String, Value> {
+        let mut table = Map::new();
+        for node in list.effective_nodes(&WriteOptions::default()) {
+            table.insert(node.key.to_string(), to_value(&node.value.borrow(), origin));
+        }
+        table
+    }
+
+    fn to_value(value: &ConfValue, origin: &String) -> Value {
+        let kind = match value {
+            ConfValue::StrValue(v) => ValueKind::String(v.clone()),
+            ConfValue::BoolValue(v) => ValueKind::Boolean(*v),
+            ConfValue::NumberValue(v) => as_integer(*v).map_or(ValueKind::Float(*v), ValueKind::I64),
+            #[cfg(feature = "decimal")]
+            ConfValue::DecimalValue(v) => decimal_as_integer(v).map_or(ValueKind::Float(decimal_as_f64(v)), ValueKind::I64),
+            ConfValue::Bytes(v) => ValueKind::String(crate::bytes::encode(v)),
+            ConfValue::List(items) => ValueKind::Array(items.iter().map(|item| to_value(item, origin)).collect()),
+            ConfValue::Conf(child) => ValueKind::Table(to_table(child, origin)),
+        };
+        Value::new(Some(origin), kind)
+    }
+}
+
+#[cfg(feature = "figment")]
+mod figment_provider {
+    use figment::value::{Dict, Map, Tag, Value};
+    use figment::{Metadata, Profile, Provider};
+
+    use super::*;
+
+    impl Provider for ConfFile {
+        fn metadata(&self) -> Metadata {
+            Metadata::named("conf file").source(Path::new(&self.path))
+        }
+
+        fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+            let list = self.load().map_err(|e| figment::Error::from(e.to_string()))?;
+            Ok(Profile::Default.collect(to_dict(&list)))
+        }
+    }
+
+    fn to_dict(list: &ConfList) -> Dict {
+        let mut dict = Dict::new();
+        for node in list.effective_nodes(&WriteOptions::default()) {
+            dict.insert(node.key.to_string(), to_value(&node.value.borrow()));
+        }
+        dict
+    }
+
+    fn to_value(value: &ConfValue) -> Value {
+        match value {
+            ConfValue::StrValue(v) => Value::from(v.clone()),
+            ConfValue::BoolValue(v) => Value::from(*v),
+            ConfValue::NumberValue(v) => as_integer(*v).map_or(Value::from(*v), Value::from),
+            #[cfg(feature = "decimal")]
+            ConfValue::DecimalValue(v) => decimal_as_integer(v).map_or(Value::from(decimal_as_f64(v)), Value::from),
+            ConfValue::Bytes(v) => Value::from(crate::bytes::encode(v)),
+            ConfValue::List(items) => Value::Array(Tag::Default, items.iter().map(to_value).collect()),
+            ConfValue::Conf(child) => Value::Dict(Tag::Default, to_dict(child)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn can_use_as_config_source() {
+        let settings = config::Config::builder()
+            .set_default("log.level", 1).unwrap()
+            .add_source(ConfFile::new("tests/case-1.conf").schema("tests/data.schema"))
+            .add_source(ConfFile::new("tests/missing.conf"))
+            .build()
+            .unwrap();
+        assert_eq!(settings.get_string("endpoint").unwrap(), "localhost:3000");
+        assert!(settings.get_bool("debug").unwrap());
+        assert_eq!(settings.get_int("log.level").unwrap(), 1);
+
+        let err = config::Config::builder().add_source(ConfFile::new("tests/missing.conf").required(true)).build().unwrap_err();
+        assert_eq!(err.to_string(), "Config file not found: tests/missing.conf");
+    }
+
+    #[cfg(feature = "figment")]
+    #[test]
+    fn can_use_as_figment_provider() {
+        let figment = figment::Figment::new().merge(ConfFile::new("tests/case-1.conf").schema("tests/data.schema"));
+        assert_eq!(figment.extract_inner::<String>("endpoint").unwrap(), "localhost:3000");
+        assert!(figment.extract_inner::<bool>("debug").unwrap());
+        assert!(figment.extract_inner::<String>("log.file").is_ok());
+        assert!(figment::Figment::from(ConfFile::new("tests/missing.conf").required(true)).extract_inner::<String>("endpoint").is_err());
+    }
+}
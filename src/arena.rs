@@ -0,0 +1,264 @@
+// 全てのノードを 1 つの Vec に、キーと文字列の値を 1 つの String に詰めた読み取り専用の設定
+// ConfList / Config はノードやセクションごとに確保するので、キーが多いと探すたびにポインタをたどることになる
+// ここではセクションの子を連続して並べ、インデックスで参照する (比較は benches/lookup.rs)
+// track-access の印や監査フックは持たない
+use std::collections::HashMap;
+
+use crate::access::wrong_type;
+use crate::path::{split_key, to_dotted};
+use crate::{ConfAccessError, ConfList, Config, ConfigValue, Interpolation, Origin};
+
+// nodes または text の中の範囲
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Span {
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Slot {
+    Str(Span),
+    Bool(bool),
+    Number(f64),
+    #[cfg(feature = "decimal")]
+    Decimal(rust_decimal::Decimal),
+    Bytes(Vec<u8>),
+    List(Vec<ConfigValue>),
+    // 子ノードの範囲
+    Section(Span),
+}
+
+impl Slot {
+    fn type_name(&self) -> &'static str {
+        match self {
+            Slot::Str(_) => "string",
+            Slot::Bool(_) => "bool",
+            Slot::Number(_) => "number",
+            #[cfg(feature = "decimal")]
+            Slot::Decimal(_) => "decimal",
+            Slot::Bytes(_) => "bytes",
+            Slot::List(_) => "list",
+            Slot::Section(_) => "section",
+        }
+    }
+}
+
+// 探すときに読むのはキーと値だけなので、出どころなどは meta に分けて nodes を小さくする
+#[derive(Debug, Clone, PartialEq)]
+struct ArenaNode {
+    key: Span,
+    value: Slot,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Meta {
+    // 出どころのファイル名は sources に 1 つずつだけ持つ
+    source: Option<usize>,
+    line: Option<usize>,
+    raw: Option<Span>,
+    interpolations: Vec<Interpolation>,
+}
+
+// nodes と meta は同じ順に並ぶ
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ArenaConfig {
+    nodes: Vec<ArenaNode>,
+    meta: Vec<Meta>,
+    text: String,
+    sources: Vec<String>,
+    root: Span,
+}
+
+impl ConfList {
+    // freeze() と同じだが、ArenaConfig に詰め直す
+    pub fn freeze_arena(self) -> ArenaConfig {
+        ArenaConfig::from(self.freeze())
+    }
+}
+
+// 幅優先でたどり、同じセクションの子が nodes の中で隣り合うようにする
+// 子のキーも text の中で隣り合うよう、値より先にまとめて詰める
+impl From<Config> for ArenaConfig {
+    fn from(config: Config) -> Self {
+        let mut arena = ArenaConfig::default();
+        let mut sources: HashMap<String, usize> = HashMap::new();
+        let mut queue = std::collections::VecDeque::from([(None, config)]);
+        while let Some((parent, config)) = queue.pop_front() {
+            let start = arena.nodes.len();
+            let keys: Vec<Span> = config.entries.iter().map(|entry| arena.push_text(&entry.key)).collect();
+            for (key, entry) in keys.into_iter().zip(config.entries) {
+                let value = match entry.value {
+                    ConfigValue::StrValue(s) => Slot::Str(arena.push_text(&s)),
+                    ConfigValue::BoolValue(b) => Slot::Bool(b),
+                    ConfigValue::NumberValue(n) => Slot::Number(n),
+                    #[cfg(feature = "decimal")]
+                    ConfigValue::DecimalValue(d) => Slot::Decimal(d),
+                    ConfigValue::Bytes(b) => Slot::Bytes(b),
+                    ConfigValue::List(items) => Slot::List(items),
+                    ConfigValue::Conf(child) => {
+                        queue.push_back((Some(arena.nodes.len()), child));
+                        Slot::Section(Span::default())
+                    },
+                };
+                let (source, line, interpolations) = match entry.origin {
+                    Some(origin) => {
+                        let next = arena.sources.len();
+                        let index = *sources.entry(origin.source).or_insert_with_key(|source| {
+                            arena.sources.push(source.clone());
+                            next
+                        });
+                        (Some(index), origin.line, origin.interpolations)
+                    },
+                    None => (None, None, Vec::new()),
+                };
+                let raw = entry.raw.map(|raw| arena.push_text(&raw));
+                arena.nodes.push(ArenaNode { key, value });
+                arena.meta.push(Meta { source, line, raw, interpolations });
+            }
+            let children = Span { start, end: arena.nodes.len() };
+            match parent {
+                Some(index) => arena.nodes[index].value = Slot::Section(children),
+                None => arena.root = children,
+            }
+        }
+        arena
+    }
+}
+
+impl ArenaConfig {
+    fn push_text(&mut self, s: &str) -> Span {
+        let start = self.text.len();
+        self.text.push_str(s);
+        Span { start, end: self.text.len() }
+    }
+
+    fn text(&self, span: Span) -> &str {
+        &self.text[span.start..span.end]
+    }
+
+    // &self.text[..] は文字の境界を確かめるために text を読むので、バイト列のまま長さから比べる
+    fn key_is(&self, key: Span, segment: &str) -> bool {
+        key.end - key.start == segment.len() && &self.text.as_bytes()[key.start..key.end] == segment.as_bytes()
+    }
+
+    // nodes の中の位置を返す
+    fn find(&self, path: &str) -> Option<usize> {
+        let mut children = self.root;
+        // JSON Pointer はここで一度だけドット区切りにする
+        let dotted = to_dotted(path);
+        let mut path = dotted.as_ref();
+        loop {
+            let (segment, rest) = split_key(path);
+            let index = children.start + self.nodes[children.start..children.end].iter().position(|node| self.key_is(node.key, &segment))?;
+            let Some(rest) = rest else {
+                return Some(index);
+            };
+            match self.nodes[index].value {
+                Slot::Section(span) => children = span,
+                _ => return None,
+            }
+            path = rest;
+        }
+    }
+
+    fn lookup<'a, T>(&'a self, path: &str, expected: &'static str, f: impl FnOnce(&'a Slot) -> Option<T>) -> Result<T, ConfAccessError> {
+        let node = &self.nodes[self.find(path).ok_or_else(|| ConfAccessError::not_found(path))?];
+        f(&node.value).ok_or_else(|| wrong_type(path, expected, node.value.type_name()))
+    }
+
+    pub fn contains_key(&self, path: &str) -> bool {
+        self.find(path).is_some()
+    }
+
+    pub fn get_str(&self, path: &str) -> Result<&str, ConfAccessError> {
+        self.lookup(path, "string", |value| match value {
+            Slot::Str(span) => Some(self.text(*span)),
+            _ => None,
+        })
+    }
+
+    pub fn get_bool(&self, path: &str) -> Result<bool, ConfAccessError> {
+        self.lookup(path, "bool", |value| match value {
+            Slot::Bool(b) => Some(*b),
+            _ => None,
+        })
+    }
+
+    pub fn get_number(&self, path: &str) -> Result<f64, ConfAccessError> {
+        self.lookup(path, "number", |value| match value {
+            Slot::Number(n) => Some(*n),
+            #[cfg(feature = "decimal")]
+            Slot::Decimal(d) => Some(crate::integer::decimal_as_f64(d)),
+            _ => None,
+        })
+    }
+
+    #[cfg(feature = "decimal")]
+    pub fn get_decimal(&self, path: &str) -> Result<rust_decimal::Decimal, ConfAccessError> {
+        self.lookup(path, "decimal", |value| match value {
+            Slot::Decimal(d) => Some(*d),
+            _ => None,
+        })
+    }
+
+    pub fn get_bytes(&self, path: &str) -> Result<&[u8], ConfAccessError> {
+        self.lookup(path, "bytes", |value| match value {
+            Slot::Bytes(b) => Some(b.as_slice()),
+            _ => None,
+        })
+    }
+
+    pub fn get_list(&self, path: &str) -> Result<&[ConfigValue], ConfAccessError> {
+        self.lookup(path, "list", |value| match value {
+            Slot::List(items) => Some(items.as_slice()),
+            _ => None,
+        })
+    }
+
+    pub fn origin_of(&self, path: &str) -> Option<Origin> {
+        let meta = &self.meta[self.find(path)?];
+        Some(Origin { source: self.sources[meta.source?].clone(), line: meta.line, interpolations: meta.interpolations.clone() })
+    }
+
+    // Config::raw_of と同じく、書かれていたままの値 (secret は伏せる)
+    pub fn raw_of(&self, path: &str) -> Option<&str> {
+        self.meta[self.find(path)?].raw.map(|raw| self.text(raw))
+    }
+
+    // 直下のキーをソース順で返す
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.nodes[self.root.start..self.root.end].iter().map(|node| self.text(node.key))
+    }
+
+    pub fn len(&self) -> usize {
+        self.root.end - self.root.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_str, AccessErrorKind};
+
+    #[test]
+    fn can_look_up_values_in_arena() {
+        let conf = "endpoint = localhost:3000\nport = 8080\nport = 9090\nlog.file = /var/log/app.log\nlog.rotate.keep = 7\ndebug = true\ntags = [a, b]\n";
+        let schema = "port -> number\nlog.rotate.keep -> number\ndebug -> bool\n";
+        let arena = parse_str(conf, Some(schema)).unwrap().freeze_arena();
+        assert_eq!(arena.keys().collect::<Vec<_>>(), ["endpoint", "port", "log", "debug", "tags"]);
+        assert_eq!(arena.get_str("endpoint").unwrap(), "localhost:3000");
+        assert_eq!(arena.get_number("port").unwrap(), 9090.0);
+        assert_eq!(arena.get_number("log.rotate.keep").unwrap(), 7.0);
+        assert!(arena.get_bool("debug").unwrap());
+        assert_eq!(arena.get_list("tags").unwrap().len(), 2);
+        assert_eq!(arena.origin_of("port").unwrap().to_string(), "<string>:3");
+        assert_eq!(arena.raw_of("log.file"), Some("/var/log/app.log"));
+
+        assert!(arena.contains_key("log.rotate") && !arena.contains_key("log.file.x"));
+        assert!(arena.get_str("log.missing").unwrap_err().is_not_found());
+        assert_eq!(arena.get_str("log").unwrap_err().kind, AccessErrorKind::WrongType { expected: "string", found: "section" });
+    }
+}
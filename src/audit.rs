@@ -0,0 +1,118 @@
+// 値が読まれるたびにパスと値を渡すフック。どのコードがどの設定を使っているかを監査ログに残すためのもの
+// secret の値は REDACTED に置き換えて渡す (raw_of と同じ)
+use std::fmt;
+use std::sync::Arc;
+
+use crate::path::{append_path, join_path};
+use crate::{raw_text, ConfList, ConfValue, Config, ConfigValue};
+
+// 引数はドット区切りのパスと、書かれていたままの値
+pub type AuditHook = Arc<dyn Fn(&str, &str) + Send + Sync>;
+
+// ネストしたセクションにも同じフックを配り、それぞれのパスの前に付ける prefix (ドット区切り) を持たせる
+#[derive(Clone)]
+pub(crate) struct Audit {
+    hook: AuditHook,
+    prefix: String,
+}
+
+impl Audit {
+    fn child(&self, key: &str) -> Audit {
+        Audit { hook: Arc::clone(&self.hook), prefix: join_path(&self.prefix, key) }
+    }
+
+    // セクションそのものは記録せず、中の値を読んだときに記録する。path はドット区切りか JSON Pointer
+    pub(crate) fn record(&self, path: &str, raw: Option<&str>) {
+        if let Some(raw) = raw {
+            (self.hook)(&append_path(&self.prefix, path), raw);
+        }
+    }
+
+    // path ではなく 1 つのキーを記録する
+    pub(crate) fn record_key(&self, key: &str, value: &ConfValue, secret: bool, raw: Option<&str>) {
+        self.record_value(&join_path("", key), value, secret, raw);
+    }
+
+    pub(crate) fn record_value(&self, path: &str, value: &ConfValue, secret: bool, raw: Option<&str>) {
+        self.record(path, raw_text(value, secret, raw).as_deref());
+    }
+}
+
+// フックは値の比較に含めない
+impl PartialEq for Audit {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for Audit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Audit({:?})", self.prefix)
+    }
+}
+
+impl ConfList {
+    // get / get_str などで値を読むたびに hook を呼ぶ。freeze した Config にも引き継ぐ
+    // 設定した後に set などで追加したセクションには届かない
+    pub fn set_audit_hook<F>(&mut self, hook: F)
+    where F: Fn(&str, &str) + Send + Sync + 'static, {
+        self.set_audit(Audit { hook: Arc::new(hook), prefix: String::new() });
+    }
+
+    fn set_audit(&mut self, audit: Audit) {
+        let mut current = &self.head;
+        while let Some(node) = current {
+            if let ConfValue::Conf(child) = &mut *node.value.borrow_mut() {
+                child.set_audit(audit.child(&node.key));
+            }
+            current = &node.next;
+        }
+        self.audit = Some(audit);
+    }
+}
+
+impl Config {
+    pub fn set_audit_hook<F>(&mut self, hook: F)
+    where F: Fn(&str, &str) + Send + Sync + 'static, {
+        self.set_audit(Audit { hook: Arc::new(hook), prefix: String::new() });
+    }
+
+    fn set_audit(&mut self, audit: Audit) {
+        for entry in &mut self.entries {
+            if let ConfigValue::Conf(child) = &mut entry.value {
+                child.set_audit(audit.child(&entry.key));
+            }
+        }
+        self.audit = Some(audit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_str;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn can_audit_reads_with_redacted_values() {
+        let conf = "port = 8080\ndb.user = app\ndb.password = hunter2\n";
+        let schema = "port -> number\ndb.password -> string secret\n";
+        let mut conf = parse_str(conf, Some(schema)).unwrap();
+        let reads = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&reads);
+        conf.set_audit_hook(move |path, value| sink.lock().unwrap().push(format!("{}={}", path, value)));
+
+        conf.get_number("port").unwrap();
+        conf.get_str("db.password").unwrap();
+        // 見つからなかったキーや、値を読まない origin_of は記録しない
+        assert!(conf.get_str("db.missing").is_err());
+        conf.origin_of("db.user");
+        assert_eq!(*reads.lock().unwrap(), ["port=8080", "db.password=********"]);
+
+        // freeze した後も、取り出したセクションから読んだ値もフルパスで記録する
+        reads.lock().unwrap().clear();
+        let config = conf.freeze();
+        config.get_conf("db").unwrap().get_str("user").unwrap();
+        config.get_str("db.password").unwrap();
+        assert_eq!(*reads.lock().unwrap(), ["db.user=app", "db.password=********"]);
+    }
+}
@@ -0,0 +1,304 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use crate::condition::Conditions;
+use crate::inline::{self, ListItem};
+use crate::path::split_path;
+use crate::{
+    check_entry_limits, check_line_length, infer_value, parse_include, parse_line_checked, parse_schema_lines, profile, resolve_value, validate,
+    ConfList, ConfValue, Origin, ParseOptions, Schema, SchemaType, TypeMismatchError,
+};
+
+// 読み込んだ文字列を借用したままの値
+#[derive(Debug, Clone, PartialEq)]
+pub enum BorrowedValue<'a> {
+    StrValue(Cow<'a, str>),
+    BoolValue(bool),
+    NumberValue(f64),
+    #[cfg(feature = "decimal")]
+    DecimalValue(rust_decimal::Decimal),
+    // base64 から戻したバイト列は元の文字列と違うので借用できない
+    Bytes(Vec<u8>),
+    List(Vec<BorrowedValue<'a>>),
+    Conf(BorrowedConf<'a>),
+}
+
+impl<'a> BorrowedValue<'a> {
+    pub fn as_str(&self) -> Result<&str, TypeMismatchError> {
+        if let BorrowedValue::StrValue(value) = self {
+            Ok(value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, TypeMismatchError> {
+        if let BorrowedValue::BoolValue(value) = self {
+            Ok(*value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_number(&self) -> Result<f64, TypeMismatchError> {
+        if let BorrowedValue::NumberValue(value) = self {
+            Ok(*value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[BorrowedValue<'a>], TypeMismatchError> {
+        if let BorrowedValue::List(items) = self {
+            Ok(items)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_conf(&self) -> Result<&BorrowedConf<'a>, TypeMismatchError> {
+        if let BorrowedValue::Conf(conf) = self {
+            Ok(conf)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    fn into_owned(self) -> ConfValue {
+        match self {
+            BorrowedValue::StrValue(v) => ConfValue::StrValue(v.into_owned()),
+            BorrowedValue::BoolValue(v) => ConfValue::BoolValue(v),
+            BorrowedValue::NumberValue(v) => ConfValue::NumberValue(v),
+            #[cfg(feature = "decimal")]
+            BorrowedValue::DecimalValue(v) => ConfValue::DecimalValue(v),
+            BorrowedValue::Bytes(v) => ConfValue::Bytes(v),
+            BorrowedValue::List(items) => ConfValue::List(items.into_iter().map(BorrowedValue::into_owned).collect()),
+            BorrowedValue::Conf(conf) => ConfValue::Conf(Box::new(conf.into_owned())),
+        }
+    }
+
+    // リストの要素のテーブルはキーがバッファにないので、このモードでは使えない
+    fn from_owned(value: ConfValue) -> Result<Self, String> {
+        Ok(match value {
+            ConfValue::StrValue(v) => BorrowedValue::StrValue(Cow::Owned(v)),
+            ConfValue::BoolValue(v) => BorrowedValue::BoolValue(v),
+            ConfValue::NumberValue(v) => BorrowedValue::NumberValue(v),
+            #[cfg(feature = "decimal")]
+            ConfValue::DecimalValue(v) => BorrowedValue::DecimalValue(v),
+            ConfValue::Bytes(v) => BorrowedValue::Bytes(v),
+            ConfValue::List(items) => BorrowedValue::List(items.into_iter().map(BorrowedValue::from_owned).collect::<Result<_, _>>()?),
+            ConfValue::Conf(_) => return Err(TABLES_UNSUPPORTED.to_string()),
+        })
+    }
+
+    fn from_item(item: ListItem<'a>) -> Result<Self, String> {
+        match item {
+            ListItem::Str(v) => Ok(BorrowedValue::StrValue(v)),
+            ListItem::List(items) => Ok(BorrowedValue::List(items.into_iter().map(BorrowedValue::from_item).collect::<Result<_, _>>()?)),
+            ListItem::Table(_) => Err(TABLES_UNSUPPORTED.to_string()),
+        }
+    }
+}
+
+const TABLES_UNSUPPORTED: &str = "inline tables are not supported when parsing a borrowed buffer; use parse_str";
+// キーをバッファから借りたまま持つので、\. や "..." を戻せない
+const ESCAPED_KEYS_UNSUPPORTED: &str = "escaped or quoted keys are not supported when parsing a borrowed buffer; use parse_str";
+
+#[derive(Debug, Clone, PartialEq)]
+struct BorrowedEntry<'a> {
+    key: &'a str,
+    value: BorrowedValue<'a>,
+    line: usize,
+    // 書かれていたままの値 (セクションにはない)
+    raw: Option<&'a str>,
+}
+
+// キーも値も入力のバッファを指したままのツリー。同じキーは後の値で置き換える
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BorrowedConf<'a> {
+    entries: Vec<BorrowedEntry<'a>>,
+}
+
+impl<'a> BorrowedConf<'a> {
+    pub fn get(&self, path: &str) -> Option<&BorrowedValue<'a>> {
+        let (key, rest) = split_path(path);
+        let entry = self.entries.iter().find(|entry| entry.key == key)?;
+        match (rest, &entry.value) {
+            (None, value) => Some(value),
+            (Some(rest), BorrowedValue::Conf(child)) => child.get(&rest),
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &BorrowedValue<'a>)> {
+        self.entries.iter().map(|entry| (entry.key, &entry.value))
+    }
+
+    // 文字列をコピーして通常の ConfList に変換する
+    pub fn into_owned(self) -> ConfList {
+        let mut list = ConfList::new();
+        for entry in self.entries {
+            let origin = Origin::new("<string>", Some(entry.line));
+            let raw = entry.raw.filter(|raw| !matches!(&entry.value, BorrowedValue::StrValue(v) if v == raw));
+            list.push_node(Arc::from(entry.key), entry.value.into_owned(), Some(origin), false, raw.map(Box::from));
+        }
+        list
+    }
+
+    fn add_value(&mut self, key: &'a str, value: BorrowedValue<'a>, line: usize, raw: &'a str) {
+        let mut conf = self;
+        let mut rest = key;
+        while let Some((head, tail)) = rest.split_once('.') {
+            let index = match conf.entries.iter().position(|entry| entry.key == head) {
+                Some(index) => {
+                    if !matches!(conf.entries[index].value, BorrowedValue::Conf(_)) {
+                        conf.entries[index].value = BorrowedValue::Conf(BorrowedConf::default());
+                    }
+                    index
+                },
+                None => {
+                    conf.entries.push(BorrowedEntry { key: head, value: BorrowedValue::Conf(BorrowedConf::default()), line, raw: None });
+                    conf.entries.len() - 1
+                },
+            };
+            conf = match &mut conf.entries[index].value {
+                BorrowedValue::Conf(child) => child,
+                _ => unreachable!(),
+            };
+            rest = tail;
+        }
+        match conf.entries.iter_mut().find(|entry| entry.key == rest) {
+            Some(entry) => {
+                entry.value = value;
+                entry.line = line;
+                entry.raw = Some(raw);
+            },
+            None => conf.entries.push(BorrowedEntry { key: rest, value, line, raw: Some(raw) }),
+        }
+    }
+}
+
+// メモリ上のバッファから、文字列をコピーせずに読み込む。include は使えない
+pub fn parse_borrowed<'a>(conf: &'a str, schema: Option<&str>, options: &ParseOptions) -> Result<BorrowedConf<'a>, Box<dyn Error>> {
+    let schema: Schema = match schema {
+        Some(s) => parse_schema_lines(s.lines().map(str::to_string), options)?,
+        None => HashMap::new(),
+    };
+    let mut map = BorrowedConf::default();
+    let mut key_count = 0;
+    let mut documents = profile::Documents::new(options);
+    let mut conditions = Conditions::new(options);
+    for (index, line) in conf.lines().enumerate() {
+        let location = Origin::new("<string>", Some(index + 1));
+        check_line_length(line, &options.limits).map_err(|e| format!("{}: {}", location, e))?;
+        if let Some(result) = documents.enter(line) {
+            result.map_err(|e| format!("{}: {}", location, e))?;
+            continue;
+        }
+        if !documents.is_selected() {
+            continue;
+        }
+        if let Some(result) = conditions.enter(line, index + 1) {
+            result.map_err(|e| format!("{}: {}", location, e))?;
+            continue;
+        }
+        if !conditions.is_active() {
+            continue;
+        }
+        if parse_include(line).is_some() {
+            return Err(format!("{}: include is not supported when parsing a borrowed buffer", location).into());
+        }
+        let (key, value) = match parse_line_checked(line, options).map_err(|e| format!("{}: {}", location, e))? {
+            Some(key_value) => key_value,
+            None => continue,
+        };
+        key_count += 1;
+        if key_count > options.limits.max_keys {
+            return Err(format!("{}: Too many keys (limit: {})", location, options.limits.max_keys).into());
+        }
+        // キーを書き換える正規化はコピーが必要になるので、このモードでは使えない
+        let key = match options.keys.normalize(key).map_err(|e| format!("{}: {}", location, e))? {
+            Cow::Borrowed(key) => key,
+            Cow::Owned(normalized) => {
+                return Err(format!("{}: Key {} would be rewritten to {}; use parse_str to normalize keys", location, key, normalized).into());
+            },
+        };
+        let typed = typed_value(key, value, &schema, options).map_err(|e| format!("{}: {}", location, e))?;
+        map.add_value(key, typed, index + 1, value);
+    }
+    if let Some((line, e)) = conditions.finish() {
+        return Err(format!("<string>:{}: {}", line, e).into());
+    }
+    Ok(map)
+}
+
+fn typed_value<'a>(key: &str, value: &'a str, schema: &Schema, options: &ParseOptions) -> Result<BorrowedValue<'a>, Box<dyn Error>> {
+    if key.contains(['\\', '"']) {
+        return Err(ESCAPED_KEYS_UNSUPPORTED.into());
+    }
+    check_entry_limits(key, value, &options.limits)?;
+    let written = value;
+    let value = resolve_value(value, schema.get(key).is_some_and(|entry| entry.secret), options)?;
+    options.policy.check(key, written, &value)?;
+    let entry = match schema.get(key) {
+        Some(entry) if entry.ty == SchemaType::Object && inline::parse_table(&value, options.limits.max_depth)?.is_some() => {
+            return Err(TABLES_UNSUPPORTED.into());
+        },
+        Some(entry) if entry.ty != SchemaType::String || !entry.transforms.is_empty() => entry,
+        // 文字列はコピーしない
+        Some(entry) => {
+            entry.check_pattern(&value)?;
+            return Ok(BorrowedValue::StrValue(value));
+        },
+        // テーブルの要素のパスはバッファにないので、このモードでは使えない
+        None if options.inline_tables && inline::parse_table(&value, options.limits.max_depth)?.is_some() => {
+            return Err(TABLES_UNSUPPORTED.into());
+        },
+        None => return untyped_value(value, options),
+    };
+    Ok(BorrowedValue::from_owned(validate(&value, entry, options)?)?)
+}
+
+// スキーマにないキーでも [a, b] はリストにする。展開などで値をコピーしたときだけ要素もコピーする
+fn untyped_value<'a>(value: Cow<'a, str>, options: &ParseOptions) -> Result<BorrowedValue<'a>, Box<dyn Error>> {
+    let max_depth = options.limits.max_depth;
+    // untyped_value (lib.rs) と同じく、リストとして読めなければ文字列のまま
+    if options.infer_types && !matches!(inline::parse_list(&value, max_depth), Ok(Some(_))) {
+        if let Some(inferred) = infer_value(&value) {
+            return Ok(BorrowedValue::from_owned(inferred)?);
+        }
+    }
+    match value {
+        Cow::Borrowed(v) => Ok(match inline::parse_list(v, max_depth).ok().flatten() {
+            Some(items) => BorrowedValue::from_item(ListItem::List(items))?,
+            None => BorrowedValue::StrValue(Cow::Borrowed(v)),
+        }),
+        Cow::Owned(v) => Ok(match inline::parse_list_value(&v, max_depth).ok().flatten() {
+            Some(list) => BorrowedValue::from_owned(list)?,
+            None => BorrowedValue::StrValue(Cow::Owned(v)),
+        }),
+    }
+}
+
+#[cfg(all(test, feature = "std-fs"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_parse_without_copying() {
+        let input = std::fs::read_to_string("tests/case-2.conf").unwrap();
+        let schema = std::fs::read_to_string("tests/data.schema").unwrap();
+        let conf = parse_borrowed(&input, Some(&schema), &ParseOptions::default()).unwrap();
+        match conf.get("log.file").unwrap() {
+            BorrowedValue::StrValue(Cow::Borrowed(file)) => assert_eq!(*file, "/var/log/console.log"),
+            other => panic!("expected a borrowed string: {:?}", other),
+        }
+        assert_eq!(conf.iter().map(|(key, _)| key).collect::<Vec<_>>(), vec!["endpoint", "log"]);
+
+        let owned = conf.into_owned();
+        assert!(owned.diff(&crate::parse("tests/case-2.conf", Some("tests/data.schema")).unwrap()).is_empty());
+        assert_eq!(owned.origin_of("log.name").unwrap().line, Some(4));
+    }
+}
@@ -0,0 +1,85 @@
+// スキーマの型が bytes の値。base64 で書き、hex: で始めれば 16 進数として読む
+// HMAC の鍵や DER の証明書のような小さなバイナリを置くためのもので、値は既定で secret として伏せる
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn decode(s: &str) -> Result<Vec<u8>, String> {
+    match s.strip_prefix("hex:") {
+        Some(hex) => decode_hex(hex),
+        None => decode_base64(s.strip_prefix("base64:").unwrap_or(s)),
+    }
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    if !s.len().is_multiple_of(2) {
+        return Err("Invalid hex value: odd number of digits".to_string());
+    }
+    let digit = |b: u8| (b as char).to_digit(16).ok_or_else(|| "Invalid hex value".to_string());
+    s.as_bytes().chunks(2).map(|pair| Ok((digit(pair[0])? << 4 | digit(pair[1])?) as u8)).collect()
+}
+
+// 末尾の = は省略できる。URL 用の - と _ も受け付ける
+fn decode_base64(s: &str) -> Result<Vec<u8>, String> {
+    let data = s.trim_end_matches('=');
+    let padding = s.len() - data.len();
+    if data.len() % 4 == 1 || padding > 2 || padding > 0 && !s.len().is_multiple_of(4) {
+        return Err("Invalid base64 value".to_string());
+    }
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let (mut buf, mut bits) = (0u32, 0);
+    for b in data.bytes() {
+        let v = match b {
+            b'A'..=b'Z' => b - b'A',
+            b'a'..=b'z' => b - b'a' + 26,
+            b'0'..=b'9' => b - b'0' + 52,
+            b'+' | b'-' => 62,
+            b'/' | b'_' => 63,
+            _ => return Err("Invalid base64 value".to_string()),
+        };
+        buf = buf << 6 | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buf >> bits) as u8);
+            buf &= (1 << bits) - 1;
+        }
+    }
+    Ok(out)
+}
+
+// 書き出すときは = を付けた標準の base64 にする
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_decode_base64_and_hex() {
+        assert_eq!(decode("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode("aGVsbG8").unwrap(), b"hello");
+        assert_eq!(decode("base64:-_8").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode("hex:00ff7A").unwrap(), [0x00, 0xff, 0x7a]);
+        assert_eq!(decode("").unwrap(), b"");
+        assert_eq!(decode("aGVsbG8*").unwrap_err(), "Invalid base64 value");
+        assert_eq!(decode("a").unwrap_err(), "Invalid base64 value");
+        assert_eq!(decode("hex:0g").unwrap_err(), "Invalid hex value");
+        assert_eq!(decode("hex:abc").unwrap_err(), "Invalid hex value: odd number of digits");
+
+        for bytes in [&b""[..], b"h", b"he", b"hel", b"hello"] {
+            assert_eq!(decode(&encode(bytes)).unwrap(), bytes);
+        }
+        assert_eq!(encode(b"hello"), "aGVsbG8=");
+    }
+}
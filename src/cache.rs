@@ -0,0 +1,92 @@
+// リモートのソースで最後に取得して検証に成功した内容を保存するディスクのキャッシュ
+// リモートに届かないとき、または ttl の間はリモートの代わりにこれを読む。参照 (env: など) は展開前のまま保存する
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RemoteCache {
+    pub path: PathBuf,
+    // 保存してからこの時間はリモートに問い合わせない。None なら届かないときだけ使う
+    pub ttl: Option<Duration>,
+}
+
+impl RemoteCache {
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        RemoteCache { path: path.into(), ttl: None }
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    // 保存してから ttl が過ぎていなければ true
+    pub fn is_fresh(&self) -> bool {
+        let Some(ttl) = self.ttl else {
+            return false;
+        };
+        let age = std::fs::metadata(&self.path).and_then(|m| m.modified()).map(|modified| modified.elapsed());
+        matches!(age, Ok(Ok(age)) if age < ttl)
+    }
+
+    // schema などを path の隣に保存する
+    pub(crate) fn sibling(&self, suffix: &str) -> RemoteCache {
+        let mut path = self.path.as_os_str().to_owned();
+        path.push(suffix);
+        RemoteCache { path: PathBuf::from(path), ttl: self.ttl }
+    }
+
+    pub(crate) fn read(&self) -> io::Result<String> {
+        std::fs::read_to_string(&self.path)
+    }
+
+    // 書きかけのファイルを読まないよう、同じディレクトリに別名で書いてから置き換える
+    // secret の値も入るので、ほかのユーザーが読めないよう 0600 で作る。一時ファイルの名前は書くたびに変える
+    pub(crate) fn write(&self, contents: &str) -> io::Result<()> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let suffix = format!(".{}.{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed));
+        let tmp = self.sibling(&suffix).path;
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let result = options.open(&tmp)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .and_then(|_| std::fs::rename(&tmp, &self.path));
+        if result.is_err() {
+            let _ = std::fs::remove_file(&tmp);
+        }
+        result
+    }
+}
+
+// キャッシュから読んだ値の出どころ (origin_of で分かるように印を付ける)
+pub(crate) fn cached_source(source: &str) -> String {
+    format!("{} (cached)", source)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_write_privately_from_many_threads() {
+        let path = std::env::temp_dir().join(format!("conf-cache-{}.conf", std::process::id()));
+        let cache = RemoteCache::new(&path);
+        std::thread::scope(|scope| {
+            for i in 0..8 {
+                let cache = &cache;
+                scope.spawn(move || cache.write(&format!("n = {}\n", i)).unwrap());
+            }
+        });
+        assert!(cache.read().unwrap().starts_with("n = "));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,131 @@
+// スキーマのキーから clap の引数 (--log-level など) を作り、指定された値を最優先の層として重ねる
+use std::error::Error;
+
+use clap::parser::ValueSource;
+use clap::{Arg, ArgMatches, Command};
+
+use crate::{validate, ConfList, Origin, ParseOptions, Schema, SchemaType};
+
+// log.level / log_level -> log-level
+pub fn flag_name(key: &str) -> String {
+    key.replace(['.', '_'], "-")
+}
+
+// clap が自動で追加するフラグ
+const RESERVED_FLAGS: &[&str] = &["help", "version"];
+
+// スキーマの各キーを --flag として追加する (名前順)。引数の id はキーのまま
+// log.level と log_level のように同じフラグ名になるキーや、command にすでにあるフラグ、--help / --version とぶつかればエラー
+pub fn augment_command(command: Command, schema: &Schema) -> Result<Command, Box<dyn Error>> {
+    let mut taken: Vec<(String, String)> = RESERVED_FLAGS.iter().map(|flag| (flag.to_string(), format!("--{}", flag))).collect();
+    taken.extend(command.get_arguments().filter_map(|arg| Some((arg.get_long()?.to_string(), format!("argument {}", arg.get_id())))));
+    for key in flag_keys(schema) {
+        let flag = flag_name(key);
+        if let Some((_, owner)) = taken.iter().find(|(name, _)| *name == flag) {
+            return Err(format!("Flag --{} for {} conflicts with {}", flag, key, owner).into());
+        }
+        taken.push((flag, key.to_string()));
+    }
+    let args = flag_keys(schema).into_iter().filter_map(|key| {
+        let arg = Arg::new(key.clone()).long(flag_name(key)).required(false);
+        let arg = match schema[key].ty {
+            SchemaType::String => arg.value_name("VALUE").help(format!("Override {}", key)),
+            SchemaType::Number => arg.value_name("NUMBER").help(format!("Override {} (number)", key)),
+            #[cfg(feature = "decimal")]
+            SchemaType::Decimal => arg.value_name("DECIMAL").help(format!("Override {} (decimal)", key)),
+            SchemaType::List => arg.value_name("[A, B]").help(format!("Override {} (list)", key)),
+            SchemaType::Path => arg.value_name("PATH").help(format!("Override {} (path)", key)),
+            SchemaType::Bytes => arg.value_name("BASE64").help(format!("Override {} (bytes)", key)),
+            SchemaType::Uuid => arg.value_name("UUID").help(format!("Override {} (uuid)", key)),
+            SchemaType::Country => arg.value_name("CODE").help(format!("Override {} (country code)", key)),
+            SchemaType::Currency => arg.value_name("CODE").help(format!("Override {} (currency code)", key)),
+            SchemaType::Locale => arg.value_name("LANG-TAG").help(format!("Override {} (locale)", key)),
+            SchemaType::Mime => arg.value_name("TYPE/SUBTYPE").help(format!("Override {} (MIME type)", key)),
+            SchemaType::Charset => arg.value_name("CHARSET").help(format!("Override {} (charset)", key)),
+            SchemaType::Cron => arg.value_name("SCHEDULE").help(format!("Override {} (cron)", key)),
+            SchemaType::Endpoint => arg.value_name("HOST:PORT").help(format!("Override {} (endpoint)", key)),
+            SchemaType::LogLevel => arg.value_name("LEVEL").help(format!("Override {} (log level)", key)),
+            SchemaType::Semver => arg.value_name("VERSION").help(format!("Override {} (semver)", key)),
+            SchemaType::Any => arg.value_name("VALUE").help(format!("Override {}", key)),
+            // セクションはフラグにしない (flag_keys で除いている)
+            SchemaType::Object => return None,
+            // --debug だけなら true
+            SchemaType::Bool => arg
+                .value_parser(["true", "false"])
+                .num_args(0..=1)
+                .default_missing_value("true")
+                .help(format!("Override {}", key)),
+        };
+        Some(arg)
+    });
+    Ok(command.args(args))
+}
+
+// コマンドラインで指定された値だけをスキーマで検証して conf に追加する
+// 追加した値は既存の値より優先され、出どころは "<command line>" になる
+pub fn apply_matches(conf: &mut ConfList, schema: &Schema, matches: &ArgMatches, options: &ParseOptions) -> Result<(), Box<dyn Error>> {
+    for key in flag_keys(schema) {
+        if matches.value_source(key) != Some(ValueSource::CommandLine) {
+            continue;
+        }
+        let raw = match matches.get_one::<String>(key) {
+            Some(raw) => raw,
+            None => continue,
+        };
+        let entry = &schema[key];
+        let value = validate(raw, entry, options).map_err(|e| format!("--{}: {}", flag_name(key), e))?;
+        let origin = Origin::new("<command line>", None);
+        conf.add_value_interned(key, value, Some(origin), entry.secret, Some(raw.as_str().into()), &mut Default::default());
+    }
+    Ok(())
+}
+
+// セクション (object) はコマンドラインで値にできないので除く
+fn flag_keys(schema: &Schema) -> Vec<&String> {
+    let mut keys: Vec<&String> = schema.keys().filter(|key| schema[*key].ty != SchemaType::Object).collect();
+    keys.sort();
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_schema_str, parse_str};
+
+    #[test]
+    fn can_override_conf_with_flags() {
+        let options = ParseOptions::default();
+        let schema = parse_schema_str("log.level -> string\nport -> number\ndebug -> bool\nmax_conn -> number\n", &options).unwrap();
+        let command = augment_command(Command::new("app"), &schema).unwrap();
+        let matches = command.clone().try_get_matches_from(["app", "--log-level", "debug", "--port", "80", "--debug"]).unwrap();
+
+        let mut conf = parse_str("log.level = info\nport = 8080\nmax_conn = 10\n", None).unwrap();
+        apply_matches(&mut conf, &schema, &matches, &options).unwrap();
+        let flat = conf.to_flat_map(false);
+        assert_eq!(flat["log.level"], "debug");
+        assert_eq!(flat["port"], "80");
+        assert_eq!(flat["debug"], "true");
+        assert_eq!(flat["max_conn"], "10");
+        assert_eq!(conf.origin_of("port").unwrap().source, "<command line>");
+
+        let matches = command.clone().try_get_matches_from(["app", "--max-conn", "many"]).unwrap();
+        let err = apply_matches(&mut conf, &schema, &matches, &options).unwrap_err();
+        assert_eq!(err.to_string(), "--max-conn: Invalid number value");
+        assert!(command.try_get_matches_from(["app", "--debug", "yes"]).is_err());
+    }
+
+    #[test]
+    fn rejects_conflicting_flag_names() {
+        let options = ParseOptions::default();
+        let schema = parse_schema_str("log.file -> string\nlog_file -> string\n", &options).unwrap();
+        let err = augment_command(Command::new("app"), &schema).unwrap_err();
+        assert_eq!(err.to_string(), "Flag --log-file for log_file conflicts with log.file");
+        let schema = parse_schema_str("help -> bool\n", &options).unwrap();
+        let err = augment_command(Command::new("app"), &schema).unwrap_err();
+        assert_eq!(err.to_string(), "Flag --help for help conflicts with --help");
+        let schema = parse_schema_str("config -> path\n", &options).unwrap();
+        let command = Command::new("app").arg(Arg::new("conf").long("config"));
+        let err = augment_command(command, &schema).unwrap_err();
+        assert_eq!(err.to_string(), "Flag --config for config conflicts with argument conf");
+    }
+}
@@ -0,0 +1,236 @@
+// @if env == "prod" ... @else ... @end で、読み込むときの条件によって行を読むかどうかを切り替える
+// 条件に使える変数は ParseOptions::variables と、profile (選んだプロファイルのどれか)、hostname、env.NAME (環境変数)
+// 比較は ==, != だけで、&& と || でつなげる (&& が先)。ブロックは入れ子にできる
+use crate::interpolate::hostname;
+use crate::ParseOptions;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Literal(String),
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = expr.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let (token, len) = match c {
+            '"' | '\'' => {
+                let end = rest[1..].find(c).ok_or_else(|| format!("Unterminated string in condition: {}", expr))?;
+                (Token::Literal(rest[1..end + 1].to_string()), end + 2)
+            },
+            _ if rest.starts_with("==") => (Token::Eq, 2),
+            _ if rest.starts_with("!=") => (Token::Ne, 2),
+            _ if rest.starts_with("&&") => (Token::And, 2),
+            _ if rest.starts_with("||") => (Token::Or, 2),
+            _ => {
+                let end = rest.find(|c: char| c.is_whitespace() || "\"'=!&|".contains(c)).unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(format!("Unexpected '{}' in condition: {}", c, expr));
+                }
+                (Token::Name(rest[..end].to_string()), end)
+            },
+        };
+        tokens.push(token);
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+// name == "value" を || で区切った、&& で区切ったものの並び
+#[derive(Debug, Clone, PartialEq)]
+struct Comparison {
+    name: String,
+    equal: bool,
+    value: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Condition {
+    any: Vec<Vec<Comparison>>,
+}
+
+impl Condition {
+    pub(crate) fn parse(expr: &str) -> Result<Condition, String> {
+        let malformed = || format!("Malformed condition: {}", expr);
+        let mut any = vec![Vec::new()];
+        let mut tokens = tokenize(expr)?.into_iter();
+        loop {
+            let (Some(Token::Name(name)), Some(op @ (Token::Eq | Token::Ne)), Some(Token::Literal(value) | Token::Name(value))) = (tokens.next(), tokens.next(), tokens.next()) else {
+                return Err(malformed());
+            };
+            any.last_mut().unwrap().push(Comparison { name, equal: op == Token::Eq, value });
+            match tokens.next() {
+                None => return Ok(Condition { any }),
+                Some(Token::And) => {},
+                Some(Token::Or) => any.push(Vec::new()),
+                Some(_) => return Err(malformed()),
+            }
+        }
+    }
+
+    pub(crate) fn eval(&self, options: &ParseOptions) -> Result<bool, String> {
+        for all in &self.any {
+            let mut matched = true;
+            for comparison in all {
+                if values(&comparison.name, options)?.contains(&comparison.value) != comparison.equal {
+                    matched = false;
+                    break;
+                }
+            }
+            if matched {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+// profile はいくつも選べるので、変数の値を並びで返す
+fn values(name: &str, options: &ParseOptions) -> Result<Vec<String>, String> {
+    if let Some(value) = options.variables.get(name) {
+        return Ok(vec![value.clone()]);
+    }
+    match name {
+        "profile" => Ok(options.profiles.clone()),
+        "hostname" => hostname().map(|host| vec![host]),
+        // 設定されていない環境変数は空文字列
+        _ => match name.strip_prefix("env.") {
+            Some(var) => Ok(vec![options.var(var).unwrap_or_default()]),
+            None => Err(format!("Unknown condition variable: {}", name)),
+        },
+    }
+}
+
+struct Block {
+    // このブロックの外側が読まれているか
+    outer: bool,
+    // 今の枝 (@if か @else) を読むか
+    taken: bool,
+    has_else: bool,
+    line: usize,
+}
+
+// 1 つのファイルの中の @if の入れ子
+pub(crate) struct Conditions<'a> {
+    // None なら条件を評価せず、どの枝も読む (validate_file がすべての行を確かめるため)
+    options: Option<&'a ParseOptions>,
+    blocks: Vec<Block>,
+}
+
+impl<'a> Conditions<'a> {
+    pub(crate) fn new(options: &'a ParseOptions) -> Self {
+        Conditions { options: Some(options), blocks: Vec::new() }
+    }
+
+    #[cfg(feature = "std-fs")]
+    pub(crate) fn all() -> Self {
+        Conditions { options: None, blocks: Vec::new() }
+    }
+
+    pub(crate) fn is_active(&self) -> bool {
+        self.blocks.last().is_none_or(|block| block.outer && block.taken)
+    }
+
+    // @if / @else / @end の行なら Some を返す。line は 1 始まりの行番号
+    // 条件が評価できないときは、そのブロックを読まない
+    pub(crate) fn enter(&mut self, line: &str, number: usize) -> Option<Result<(), String>> {
+        let l = line.trim();
+        let directive = l.split_whitespace().next()?;
+        let result = match directive {
+            "@if" => {
+                let outer = self.is_active();
+                let condition = Condition::parse(l["@if".len()..].trim());
+                let taken = match (&condition, self.options) {
+                    (Ok(condition), Some(options)) if outer => condition.eval(options),
+                    (Ok(_), _) => Ok(self.options.is_none()),
+                    (Err(e), _) => Err(e.clone()),
+                };
+                self.blocks.push(Block { outer: outer && taken.is_ok(), taken: *taken.as_ref().unwrap_or(&false), has_else: false, line: number });
+                taken.map(|_| ())
+            },
+            "@else" if l == "@else" => match self.blocks.last_mut() {
+                Some(block) if !block.has_else => {
+                    block.has_else = true;
+                    block.taken = self.options.is_none() || !block.taken;
+                    Ok(())
+                },
+                Some(_) => Err("Duplicate @else".to_string()),
+                None => Err("@else without @if".to_string()),
+            },
+            "@end" if l == "@end" => match self.blocks.pop() {
+                Some(_) => Ok(()),
+                None => Err("@end without @if".to_string()),
+            },
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    // 閉じていない @if があれば、その行番号とエラー
+    pub(crate) fn finish(&mut self) -> Option<(usize, String)> {
+        let block = self.blocks.drain(..).next()?;
+        Some((block.line, "Missing @end for @if".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str_partial, parse_str_with_options};
+    use std::collections::HashMap;
+
+    const CONF: &str = "log.level = debug\n@if env == \"prod\"\nlog.level = warn\n@if region == eu || profile == 'canary'\nendpoint = eu.example.com\n@else\nendpoint = example.com\n@end\n@else\ndebug = true\n@end\n";
+
+    fn options(env: &str, region: &str) -> ParseOptions {
+        let variables = HashMap::from([("env".to_string(), env.to_string()), ("region".to_string(), region.to_string())]);
+        ParseOptions { variables, ..Default::default() }
+    }
+
+    #[test]
+    fn can_read_conditional_blocks() {
+        let conf = parse_str_with_options(CONF, None, &options("prod", "eu")).unwrap();
+        assert_eq!(conf.get_str("log.level").unwrap(), "warn");
+        assert_eq!(conf.get_str("endpoint").unwrap(), "eu.example.com");
+        assert!(!conf.contains_key("debug"));
+
+        let conf = parse_str_with_options(CONF, None, &options("prod", "us")).unwrap();
+        assert_eq!(conf.get_str("endpoint").unwrap(), "example.com");
+
+        let conf = parse_str_with_options(CONF, None, &ParseOptions { profiles: vec!["canary".to_string()], ..options("prod", "us") }).unwrap();
+        assert_eq!(conf.get_str("endpoint").unwrap(), "eu.example.com");
+
+        let conf = parse_str_with_options(CONF, None, &options("dev", "eu")).unwrap();
+        assert_eq!(conf.get_str("log.level").unwrap(), "debug");
+        assert_eq!(conf.get_str("debug").unwrap(), "true");
+        assert!(!conf.contains_key("endpoint"));
+    }
+
+    #[test]
+    fn can_parse_conditions() {
+        let condition = Condition::parse("a == 1 && b != \"x y\" || c == ''").unwrap();
+        assert_eq!(condition.any.len(), 2);
+        assert_eq!(condition.any[0][1], Comparison { name: "b".to_string(), equal: false, value: "x y".to_string() });
+        assert!(Condition::parse("env").is_err());
+        assert!(Condition::parse("env == prod &&").is_err());
+        assert!(Condition::parse("env == \"prod").is_err());
+        assert!(Condition::parse("env.HOME != ''").unwrap().eval(&ParseOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn can_report_unbalanced_blocks() {
+        let partial = parse_str_partial("@if env == prod\na = 1\n@end\n@end\n@if missing == x\nb = 2\n@if env == prod\n", None, &options("prod", "eu")).unwrap();
+        let messages: Vec<String> = partial.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, [
+            "<string>:4: @end without @if",
+            "<string>:5: Unknown condition variable: missing",
+            "<string>:5: Missing @end for @if",
+        ]);
+        assert_eq!(partial.conf.get_str("a").unwrap(), "1");
+        assert!(!partial.conf.contains_key("b"));
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt;
+
+use crate::path::split_path;
+use crate::{raw_text, ConfList, ConfValue, Origin, TypeMismatchError};
+
+// 検証後に変更できないようにした値
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigValue {
+    StrValue(String),
+    BoolValue(bool),
+    NumberValue(f64),
+    #[cfg(feature = "decimal")]
+    DecimalValue(rust_decimal::Decimal),
+    Bytes(Vec<u8>),
+    List(Vec<ConfigValue>),
+    Conf(Config),
+}
+
+impl ConfigValue {
+    pub fn as_str(&self) -> Result<&str, TypeMismatchError> {
+        if let ConfigValue::StrValue(value) = self {
+            Ok(value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, TypeMismatchError> {
+        if let ConfigValue::BoolValue(value) = self {
+            Ok(*value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_number(&self) -> Result<f64, TypeMismatchError> {
+        if let ConfigValue::NumberValue(value) = self {
+            Ok(*value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    #[cfg(feature = "decimal")]
+    pub fn as_decimal(&self) -> Result<rust_decimal::Decimal, TypeMismatchError> {
+        if let ConfigValue::DecimalValue(value) = self {
+            Ok(*value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_bytes(&self) -> Result<&[u8], TypeMismatchError> {
+        if let ConfigValue::Bytes(value) = self {
+            Ok(value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&[ConfigValue], TypeMismatchError> {
+        if let ConfigValue::List(items) = self {
+            Ok(items)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_conf(&self) -> Result<&Config, TypeMismatchError> {
+        if let ConfigValue::Conf(conf) = self {
+            Ok(conf)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+}
+
+impl fmt::Display for ConfigValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigValue::StrValue(v) => write!(f, "{}", v),
+            ConfigValue::BoolValue(v) => write!(f, "{}", v),
+            ConfigValue::NumberValue(v) => write!(f, "{}", v),
+            #[cfg(feature = "decimal")]
+            ConfigValue::DecimalValue(v) => write!(f, "{}", v),
+            ConfigValue::Bytes(v) => write!(f, "{}", crate::bytes::encode(v)),
+            ConfigValue::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    write!(f, "{}{}", if i == 0 { "" } else { ", " }, item)?;
+                }
+                write!(f, "]")
+            },
+            ConfigValue::Conf(conf) => {
+                write!(f, "{{")?;
+                for (i, entry) in conf.entries.iter().enumerate() {
+                    write!(f, "{}{} = {}", if i == 0 { " " } else { ", " }, entry.key, entry.value)?;
+                }
+                write!(f, "{}}}", if conf.entries.is_empty() { "" } else { " " })
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct ConfigEntry {
+    pub(crate) key: String,
+    pub(crate) value: ConfigValue,
+    pub(crate) origin: Option<Origin>,
+    pub(crate) raw: Option<String>,
+    #[cfg(feature = "track-access")]
+    pub(crate) accessed: crate::tracking::AccessFlag,
+}
+
+// ConfList::freeze() で作る読み取り専用の設定。RefCell を持たないのでスレッド間で共有できる
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub(crate) entries: Vec<ConfigEntry>,
+    pub(crate) audit: Option<crate::audit::Audit>,
+}
+
+impl Config {
+    // ドット区切りのパスで値を取得する
+    pub fn get(&self, path: &str) -> Option<&ConfigValue> {
+        let entry = self.find(path)?;
+        #[cfg(feature = "track-access")]
+        entry.accessed.mark();
+        if let Some(audit) = &self.audit {
+            audit.record(path, entry.raw.as_deref());
+        }
+        Some(&entry.value)
+    }
+
+    pub fn contains_key(&self, path: &str) -> bool {
+        self.find(path).is_some()
+    }
+
+    pub fn origin_of(&self, path: &str) -> Option<&Origin> {
+        self.find(path).and_then(|entry| entry.origin.as_ref())
+    }
+
+    // ConfList::raw_of と同じく、書かれていたままの値 (secret は伏せる)
+    pub fn raw_of(&self, path: &str) -> Option<&str> {
+        self.find(path).and_then(|entry| entry.raw.as_deref())
+    }
+
+    // 直下のキーをソース順で返す
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(|entry| entry.key.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &ConfigValue)> {
+        self.entries.iter().map(|entry| (entry.key.as_str(), &entry.value))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub(crate) fn find(&self, path: &str) -> Option<&ConfigEntry> {
+        let (key, rest) = split_path(path);
+        let entry = self.entries.iter().find(|entry| entry.key == key)?;
+        match (rest, &entry.value) {
+            (None, _) => Some(entry),
+            (Some(rest), ConfigValue::Conf(child)) => child.find(&rest),
+            _ => None,
+        }
+    }
+}
+
+impl ConfList {
+    // 上書きされた値を捨てて読み取り専用の Config に変換する
+    pub fn freeze(mut self) -> Config {
+        let audit = self.audit.take();
+        let mut entries: Vec<ConfigEntry> = Vec::new();
+        #[cfg(feature = "track-access")]
+        let mut flags = self.access_flags().into_iter();
+        for (key, value, origin, secret, raw) in self.into_entries() {
+            let raw = raw_text(&value, secret, raw.as_deref());
+            let value = freeze_value(value);
+            // 後から追加された値が有効
+            entries.retain(|entry| *entry.key != *key);
+            entries.push(ConfigEntry {
+                key: key.to_string(),
+                value,
+                origin,
+                raw,
+                #[cfg(feature = "track-access")]
+                accessed: flags.next().unwrap_or_default(),
+            });
+        }
+        Config { entries, audit }
+    }
+}
+
+fn freeze_value(value: ConfValue) -> ConfigValue {
+    match value {
+        ConfValue::StrValue(v) => ConfigValue::StrValue(v),
+        ConfValue::BoolValue(v) => ConfigValue::BoolValue(v),
+        ConfValue::NumberValue(v) => ConfigValue::NumberValue(v),
+        #[cfg(feature = "decimal")]
+        ConfValue::DecimalValue(v) => ConfigValue::DecimalValue(v),
+        ConfValue::Bytes(v) => ConfigValue::Bytes(v),
+        ConfValue::List(items) => ConfigValue::List(items.into_iter().map(freeze_value).collect()),
+        ConfValue::Conf(child) => ConfigValue::Conf(child.freeze()),
+    }
+}
+
+#[cfg(all(test, feature = "std-fs"))]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    fn assert_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn can_freeze_conf() {
+        assert_sync::<Config>();
+        let config = parse("tests/case-1.conf", Some("tests/data.schema")).unwrap().freeze();
+        assert_eq!(config.keys().collect::<Vec<_>>(), vec!["endpoint", "debug", "log"]);
+        assert_eq!(config.get("endpoint").unwrap().as_str().unwrap(), "localhost:3000");
+        assert!(config.get("debug").unwrap().as_bool().unwrap());
+        assert_eq!(config.get("log.file").unwrap().as_str().unwrap(), "/var/log/console.log");
+        assert!(config.get("log.missing").is_none());
+        assert_eq!(config.origin_of("log.file").unwrap().line, Some(4));
+        assert_eq!(config.get("log").unwrap().to_string(), "{ file = /var/log/console.log }");
+    }
+}
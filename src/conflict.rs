@@ -0,0 +1,191 @@
+// endpoint = x の後に endpoint.port = 80 と書くと、値がセクションに置き換わって x が消える (逆も同じ)
+// 読み込むときに、そうした値とセクションのぶつかりをどう扱うかを ParseOptions::conflicts で選ぶ
+// 1 つの読み込みの中の行どうしだけを見る。merge で重ねた層どうしは、これまでどおり後の層が優先する
+// include で取り込んだファイルと parse_dir のファイルも層として merge で重ねるので見ない
+// parse_streaming ではツリーを作らないので、前の行と比べられず、値をそのまま渡す
+use std::borrow::Cow;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::path::{parent_paths, split_key};
+use crate::{ConfList, ConfValue, KeyInterner, Node, Origin};
+
+// セクションの中に残す値のキー
+pub const VALUE_KEY: &str = "_value";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ConflictPolicy {
+    // 後の行が優先する (これまでの動き)
+    #[default]
+    LastWins,
+    // 両方の行を示してエラーにする
+    Error,
+    // 値をセクションの中の _value に移して両方残す
+    KeepBoth,
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "last-wins" => Ok(ConflictPolicy::LastWins),
+            "error" => Ok(ConflictPolicy::Error),
+            "keep-both" => Ok(ConflictPolicy::KeepBoth),
+            _ => Err(format!("Invalid conflict policy: {}", s)),
+        }
+    }
+}
+
+// 値とセクションがぶつかったときのエラー (診断の code は key-conflict)
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyConflict(pub String);
+
+impl fmt::Display for KeyConflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for KeyConflict {}
+
+// key に値を追加する前に呼び、実際に追加するキーを返す
+// KeepBoth なら、途中のキーにある値を _value に移し、セクションのあるキーへの値は key._value にする
+pub(crate) fn check<'a>(map: &mut ConfList, key: &'a str, policy: ConflictPolicy, interner: &mut KeyInterner) -> Result<Cow<'a, str>, KeyConflict> {
+    if policy == ConflictPolicy::LastWins {
+        return Ok(Cow::Borrowed(key));
+    }
+    move_values(map, key, parent_paths(key), policy, interner)?;
+    let section = map.node_at(key, |node, value| matches!(value, ConfValue::Conf(_)).then(|| node.origin.clone())).flatten();
+    match (section, policy) {
+        (Some(origin), ConflictPolicy::Error) => {
+            Err(KeyConflict(format!("Cannot set {} to a value because it is already a section{}", key, set_at(&origin))))
+        },
+        (Some(_), _) => Ok(Cow::Owned(format!("{}.{}", key, VALUE_KEY))),
+        (None, _) => Ok(Cow::Borrowed(key)),
+    }
+}
+
+// key = {} でセクションを作る前に呼ぶ。key にある値も、途中のキーにある値と同じように扱う
+pub(crate) fn check_section(map: &mut ConfList, key: &str, policy: ConflictPolicy, interner: &mut KeyInterner) -> Result<(), KeyConflict> {
+    if policy == ConflictPolicy::LastWins {
+        return Ok(());
+    }
+    let mut paths = parent_paths(key);
+    paths.push(key);
+    move_values(map, key, paths, policy, interner)
+}
+
+// paths にある値を、Error ならエラーにし、KeepBoth ならセクションの中の _value に移す
+fn move_values(map: &mut ConfList, key: &str, paths: Vec<&str>, policy: ConflictPolicy, interner: &mut KeyInterner) -> Result<(), KeyConflict> {
+    for parent in paths {
+        let Some(origin) = map.node_at(parent, |node, value| (!matches!(value, ConfValue::Conf(_))).then(|| node.origin.clone())).flatten() else {
+            continue;
+        };
+        match policy {
+            ConflictPolicy::Error if parent == key => {
+                return Err(KeyConflict(format!("Cannot set {} to a section because it is already a value{}", key, set_at(&origin))));
+            },
+            ConflictPolicy::Error => {
+                return Err(KeyConflict(format!("Cannot add {} because {} is already a value{}", key, parent, set_at(&origin))));
+            },
+            _ => {},
+        }
+        let node = node_mut_at(map, parent).unwrap();
+        let value = std::mem::replace(node.value.get_mut(), ConfValue::Conf(Box::default()));
+        let mut section = ConfList::new();
+        section.push_node(interner.intern(VALUE_KEY), value, node.origin.clone(), node.secret, node.raw.take());
+        *node.value.get_mut() = ConfValue::Conf(Box::new(section));
+        node.secret = false;
+    }
+    Ok(())
+}
+
+fn set_at(origin: &Option<Origin>) -> String {
+    match origin {
+        Some(origin) => format!(" (set at {})", origin),
+        None => String::new(),
+    }
+}
+
+fn node_mut_at<'a>(list: &'a mut ConfList, path: &str) -> Option<&'a mut Node> {
+    let (key, rest) = split_key(path);
+    let node = list.find_mut(&key)?;
+    match rest {
+        None => Some(node),
+        Some(rest) => match node.value.get_mut() {
+            ConfValue::Conf(child) => node_mut_at(child, rest),
+            _ => None,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str_partial, parse_str_with_options, ParseOptions};
+
+    fn options(conflicts: ConflictPolicy) -> ParseOptions {
+        ParseOptions { conflicts, ..Default::default() }
+    }
+
+    #[test]
+    fn can_report_value_and_section_conflicts() {
+        let conf = "endpoint = x\nendpoint.port = 80\nlog.level = info\nlog = quiet\n";
+        let last = parse_str_with_options(conf, None, &ParseOptions::default()).unwrap();
+        assert_eq!(last.get_str("log").unwrap(), "quiet");
+        assert_eq!(last.get_str("endpoint.port").unwrap(), "80");
+
+        let partial = parse_str_partial(conf, None, &options(ConflictPolicy::Error)).unwrap();
+        let messages: Vec<String> = partial.diagnostics.iter().map(|d| d.to_string()).collect();
+        assert_eq!(messages, [
+            "<string>:2: Cannot add endpoint.port because endpoint is already a value (set at <string>:1)",
+            "<string>:4: Cannot set log to a value because it is already a section (set at <string>:3)",
+        ]);
+        assert_eq!(partial.diagnostics[0].code, "key-conflict");
+        assert_eq!(partial.conf.get_str("endpoint").unwrap(), "x");
+
+        let both = parse_str_with_options(conf, None, &options(ConflictPolicy::KeepBoth)).unwrap();
+        assert_eq!(both.get_str("endpoint._value").unwrap(), "x");
+        assert_eq!(both.get_str("endpoint.port").unwrap(), "80");
+        assert_eq!(both.origin_of("endpoint._value").unwrap().to_string(), "<string>:1");
+        assert_eq!(both.get_str("log._value").unwrap(), "quiet");
+        assert_eq!(both.get_str("log.level").unwrap(), "info");
+
+        assert_eq!("keep-both".parse::<ConflictPolicy>().unwrap(), ConflictPolicy::KeepBoth);
+    }
+
+    #[test]
+    fn can_check_empty_tables() {
+        let error = ParseOptions { inline_tables: true, ..options(ConflictPolicy::Error) };
+        let conf = "log = quiet\nlog = {}\n";
+        let err = parse_str_with_options(conf, None, &error).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:2: Cannot set log to a section because it is already a value (set at <string>:1)");
+        let both = parse_str_with_options(conf, None, &ParseOptions { inline_tables: true, ..options(ConflictPolicy::KeepBoth) }).unwrap();
+        assert_eq!(both.get_str("log._value").unwrap(), "quiet");
+        let err = parse_str_with_options("log = quiet\nlog.file = {}\n", None, &error).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:2: Cannot add log.file because log is already a value (set at <string>:1)");
+    }
+
+    // include したファイルは層として重ね、parse_streaming は前の行と比べないので、後の値が優先する
+    #[test]
+    #[cfg(feature = "std-fs")]
+    fn does_not_check_layers_or_streams() {
+        let dir = std::env::temp_dir().join(format!("conf-conflict-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.conf"), "endpoint = x\ninclude extra.conf\n").unwrap();
+        std::fs::write(dir.join("extra.conf"), "endpoint.port = 80\n").unwrap();
+        let path = dir.join("app.conf");
+        let conf = crate::parse_with_options(path.to_str().unwrap(), None, &options(ConflictPolicy::Error)).unwrap();
+        assert_eq!(conf.get_str("endpoint.port").unwrap(), "80");
+
+        let mut keys = Vec::new();
+        crate::parse_streaming(path.to_str().unwrap(), None, &options(ConflictPolicy::Error), |key, _, _| {
+            keys.push(key.to_string());
+            Ok(())
+        }).unwrap();
+        assert_eq!(keys, ["endpoint", "endpoint.port"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
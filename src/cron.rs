@@ -0,0 +1,109 @@
+// スキーマの型 cron。5 つ (分 時 日 月 曜日) または 6 つ (先頭に秒) のフィールドを読み込むときに確かめる
+// 各フィールドは * / ? (日と曜日のみ) / 数 / a-b をカンマでつなぎ、/n で間隔を書ける。月と曜日は JAN や MON とも書ける
+// @daily のような別名も受け付ける。値は文字列のまま持つ
+const MONTHS: [&str; 12] = ["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const DAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const ALIASES: [&str; 7] = ["@yearly", "@annually", "@monthly", "@weekly", "@daily", "@midnight", "@hourly"];
+
+struct Field {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    // names[0] が表す数は min
+    names: &'static [&'static str],
+    // ? (指定しない) を書けるか
+    blank: bool,
+}
+
+const SECOND: Field = Field { name: "second", min: 0, max: 59, names: &[], blank: false };
+const MINUTE: Field = Field { name: "minute", min: 0, max: 59, names: &[], blank: false };
+const HOUR: Field = Field { name: "hour", min: 0, max: 23, names: &[], blank: false };
+const DAY_OF_MONTH: Field = Field { name: "day-of-month", min: 1, max: 31, names: &[], blank: true };
+const MONTH: Field = Field { name: "month", min: 1, max: 12, names: &MONTHS, blank: false };
+// 日曜日は 0 と 7 のどちらでもよい
+const DAY_OF_WEEK: Field = Field { name: "day-of-week", min: 0, max: 7, names: &DAYS, blank: true };
+
+pub(crate) fn validate(s: &str) -> Result<(), String> {
+    let s = s.trim();
+    if s.starts_with('@') {
+        return match ALIASES.contains(&s.to_ascii_lowercase().as_str()) {
+            true => Ok(()),
+            false => Err(format!("Unknown cron alias: {}", s)),
+        };
+    }
+    let parts: Vec<&str> = s.split_whitespace().collect();
+    let fields: &[Field] = match parts.len() {
+        5 => &[MINUTE, HOUR, DAY_OF_MONTH, MONTH, DAY_OF_WEEK],
+        6 => &[SECOND, MINUTE, HOUR, DAY_OF_MONTH, MONTH, DAY_OF_WEEK],
+        n => return Err(format!("Invalid cron expression: expected 5 or 6 fields but found {}", n)),
+    };
+    for (part, field) in parts.into_iter().zip(fields) {
+        for item in part.split(',') {
+            check_item(item, field).map_err(|reason| format!("Invalid cron {} field {}: {}", field.name, part, reason))?;
+        }
+    }
+    Ok(())
+}
+
+fn check_item(item: &str, field: &Field) -> Result<(), String> {
+    let (range, step) = match item.split_once('/') {
+        Some((range, step)) => (range, Some(step)),
+        None => (item, None),
+    };
+    if let Some(step) = step {
+        match step.parse::<u32>() {
+            Ok(step) if step > 0 => {},
+            _ => return Err(format!("invalid step {}", step)),
+        }
+    }
+    if range == "*" || range == "?" && field.blank {
+        return Ok(());
+    }
+    let (start, end) = match range.split_once('-') {
+        Some((start, end)) => (value(start, field)?, value(end, field)?),
+        None => {
+            let start = value(range, field)?;
+            (start, start)
+        },
+    };
+    if start > end {
+        return Err(format!("range {} is backwards", range));
+    }
+    Ok(())
+}
+
+fn value(s: &str, field: &Field) -> Result<u32, String> {
+    let lower = s.to_ascii_lowercase();
+    if let Some(i) = field.names.iter().position(|name| *name == lower) {
+        return Ok(field.min + i as u32);
+    }
+    match s.parse::<u32>() {
+        Ok(n) if (field.min..=field.max).contains(&n) => Ok(n),
+        Ok(n) => Err(format!("{} is out of range {}-{}", n, field.min, field.max)),
+        Err(_) => Err(format!("invalid value {}", s)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_validate_cron_expressions() {
+        for valid in ["*/15 * * * *", "0 9-17 * * MON-FRI", "30 0 1,15 * ?", "0 0 12 * JAN,jul *", "@daily", "0 */5 * * * 7"] {
+            assert!(validate(valid).is_ok(), "{}", valid);
+        }
+        assert_eq!(validate("* * * *").unwrap_err(), "Invalid cron expression: expected 5 or 6 fields but found 4");
+        assert_eq!(validate("0 24 * * *").unwrap_err(), "Invalid cron hour field 24: 24 is out of range 0-23");
+        assert_eq!(validate("0 0 * * 1-xyz").unwrap_err(), "Invalid cron day-of-week field 1-xyz: invalid value xyz");
+        assert_eq!(validate("*/0 * * * *").unwrap_err(), "Invalid cron minute field */0: invalid step 0");
+        assert_eq!(validate("0 17-9 * * *").unwrap_err(), "Invalid cron hour field 17-9: range 17-9 is backwards");
+        assert_eq!(validate("? * * * *").unwrap_err(), "Invalid cron minute field ?: invalid value ?");
+        assert_eq!(validate("@often").unwrap_err(), "Unknown cron alias: @often");
+
+        let schema = "backup.schedule -> cron\n";
+        assert!(crate::parse_str("backup.schedule = 0 3 * * SUN\n", Some(schema)).is_ok());
+        let err = crate::parse_str("backup.schedule = 0 3 * * 8\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Invalid cron day-of-week field 8: 8 is out of range 0-7");
+    }
+}
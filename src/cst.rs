@@ -0,0 +1,224 @@
+// 空白・コメント・改行まで含めて .conf をそのまま表す構文木。フォーマッタやコメントを残したままの編集の土台にする
+// すべてのトークンの text をつなげると元の文字列に戻る。値の検証や include の展開はしない
+use std::fmt;
+
+use crate::{is_blank_or_comment, parse_include, strip_export};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Whitespace,
+    // # または ; から行末まで
+    Comment,
+    // export KEY=value の export (ParseOptions::allow_export のときに読み飛ばされる部分)
+    Export,
+    Key,
+    Equals,
+    Value,
+    // include / include?
+    Include,
+    IncludePath,
+    // キーや値として読めない行の中身
+    Text,
+    // \n または \r\n
+    Newline,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Blank,
+    Comment,
+    Entry,
+    Include,
+    // = がない、キーや値が空 (strict でなければ読み飛ばされる行)
+    Malformed,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub kind: LineKind,
+    // 行末の改行も含む
+    pub tokens: Vec<Token>,
+}
+
+impl Line {
+    fn token(&self, kind: TokenKind) -> Option<&Token> {
+        self.tokens.iter().find(|token| token.kind == kind)
+    }
+
+    pub fn key(&self) -> Option<&str> {
+        self.token(TokenKind::Key).map(|token| token.text.as_str())
+    }
+
+    pub fn value(&self) -> Option<&str> {
+        self.token(TokenKind::Value).map(|token| token.text.as_str())
+    }
+
+    pub fn comment(&self) -> Option<&str> {
+        self.token(TokenKind::Comment).map(|token| token.text.as_str())
+    }
+
+    // 値だけを書き換える。前後の空白や改行はそのまま残す
+    pub fn set_value(&mut self, value: &str) -> bool {
+        match self.tokens.iter_mut().find(|token| token.kind == TokenKind::Value) {
+            Some(token) => {
+                token.text = value.to_string();
+                true
+            },
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.tokens.iter().try_for_each(|token| f.write_str(&token.text))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Document {
+    pub lines: Vec<Line>,
+}
+
+impl Document {
+    pub fn parse(text: &str) -> Document {
+        Document { lines: text.split_inclusive('\n').map(parse_line).collect() }
+    }
+
+    // キーと値をソース順で返す (同じキーは後の行が有効)
+    pub fn entries(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.lines.iter().filter_map(|line| Some((line.key()?, line.value()?)))
+    }
+
+    // key の最後の行の値を書き換える。その行がなければ false
+    pub fn set_value(&mut self, key: &str, value: &str) -> bool {
+        match self.lines.iter_mut().rev().find(|line| line.key() == Some(key)) {
+            Some(line) => line.set_value(value),
+            None => false,
+        }
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.lines.iter().try_for_each(|line| write!(f, "{}", line))
+    }
+}
+
+struct LineBuilder {
+    tokens: Vec<Token>,
+}
+
+impl LineBuilder {
+    fn push(&mut self, kind: TokenKind, text: &str) {
+        if !text.is_empty() {
+            self.tokens.push(Token { kind, text: text.to_string() });
+        }
+    }
+
+    // 前後の空白を Whitespace として分け、中身を kind のトークンにする
+    fn push_trimmed(&mut self, kind: TokenKind, text: &str) {
+        let trimmed = text.trim();
+        let start = text.len() - text.trim_start().len();
+        self.push(TokenKind::Whitespace, &text[..start]);
+        self.push(kind, trimmed);
+        self.push(TokenKind::Whitespace, &text[start + trimmed.len()..]);
+    }
+}
+
+// parse_line / parse_include と同じ規則で 1 行を分ける
+fn parse_line(raw: &str) -> Line {
+    let content = raw.strip_suffix('\n').map_or(raw, |l| l.strip_suffix('\r').unwrap_or(l));
+    let newline = &raw[content.len()..];
+    let mut line = LineBuilder { tokens: Vec::new() };
+    let kind = line_tokens(content, &mut line);
+    line.push(TokenKind::Newline, newline);
+    Line { kind, tokens: line.tokens }
+}
+
+fn line_tokens(content: &str, line: &mut LineBuilder) -> LineKind {
+    let body = content.trim_start();
+    line.push(TokenKind::Whitespace, &content[..content.len() - body.len()]);
+    if body.trim().is_empty() {
+        line.push(TokenKind::Whitespace, body);
+        return LineKind::Blank;
+    }
+    if is_blank_or_comment(body) {
+        line.push(TokenKind::Comment, body);
+        return LineKind::Comment;
+    }
+    if parse_include(body).is_some() {
+        let keyword = if body.starts_with("include?") { "include?" } else { "include" };
+        line.push(TokenKind::Include, keyword);
+        line.push_trimmed(TokenKind::IncludePath, &body[keyword.len()..]);
+        return LineKind::Include;
+    }
+    let entry = strip_export(body);
+    if entry.len() != body.len() {
+        let export = &body[..body.len() - entry.len()];
+        line.push_trimmed(TokenKind::Export, export);
+    }
+    match entry.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() && !value.trim().is_empty() => {
+            line.push_trimmed(TokenKind::Key, key);
+            line.push(TokenKind::Equals, "=");
+            line.push_trimmed(TokenKind::Value, value);
+            LineKind::Entry
+        },
+        _ => {
+            line.push_trimmed(TokenKind::Text, entry);
+            LineKind::Malformed
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str_with_options, ParseOptions};
+
+    const INPUT: &str = "# app\r\n\n  endpoint = localhost:3000  \nlog.file=/var/log/app.log\n; old\ninclude? local.conf \nexport\tDB_HOST = db\nport 8080\n= x\ntail = 1";
+
+    #[test]
+    fn can_round_trip_every_byte() {
+        let doc = Document::parse(INPUT);
+        assert_eq!(doc.to_string(), INPUT);
+        let kinds: Vec<LineKind> = doc.lines.iter().map(|line| line.kind).collect();
+        assert_eq!(kinds, [
+            LineKind::Comment, LineKind::Blank, LineKind::Entry, LineKind::Entry, LineKind::Comment,
+            LineKind::Include, LineKind::Entry, LineKind::Malformed, LineKind::Malformed, LineKind::Entry,
+        ]);
+        assert_eq!(doc.lines[0].comment(), Some("# app"));
+        assert_eq!(doc.lines[0].tokens.last().unwrap().text, "\r\n");
+        assert_eq!(doc.lines[5].token(TokenKind::IncludePath).unwrap().text, "local.conf");
+        assert_eq!(doc.lines[6].token(TokenKind::Export).unwrap().text, "export");
+        assert_eq!(Document::parse("").lines.len(), 0);
+    }
+
+    #[test]
+    fn can_read_the_same_entries_as_the_parser() {
+        let doc = Document::parse(INPUT);
+        let options = ParseOptions { allow_export: true, ..Default::default() };
+        let without_include: String = INPUT.lines().filter(|l| !l.starts_with("include")).map(|l| format!("{}\n", l)).collect();
+        let flat = parse_str_with_options(&without_include, None, &options).unwrap().to_flat_map(false);
+        let entries: Vec<(&str, &str)> = doc.entries().collect();
+        assert_eq!(entries.len(), flat.len());
+        for (key, value) in entries {
+            assert_eq!(flat[key], value);
+        }
+    }
+
+    #[test]
+    fn can_edit_values_keeping_layout() {
+        let mut doc = Document::parse("# server\n  endpoint = localhost:3000  # not a comment\nport=1\n");
+        assert!(doc.set_value("port", "8080"));
+        assert!(!doc.set_value("missing", "x"));
+        assert_eq!(doc.to_string(), "# server\n  endpoint = localhost:3000  # not a comment\nport=8080\n");
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+use crate::{ConfList, ConfValue};
+
+// 2 つの conf の差分
+#[derive(Debug, Clone, Default)]
+pub struct ConfDiff {
+    pub added: Vec<(String, ConfValue)>,
+    pub removed: Vec<(String, ConfValue)>,
+    // パス、変更前、変更後
+    pub changed: Vec<(String, ConfValue, ConfValue)>,
+}
+
+impl ConfDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+impl fmt::Display for ConfDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (path, value) in &self.removed {
+            writeln!(f, "- {} = {}", path, value)?;
+        }
+        for (path, value) in &self.added {
+            writeln!(f, "+ {} = {}", path, value)?;
+        }
+        for (path, old, new) in &self.changed {
+            writeln!(f, "~ {} = {} -> {}", path, old, new)?;
+        }
+        Ok(())
+    }
+}
+
+impl ConfList {
+    // self を変更前、other を変更後として末端の値を比較する
+    pub fn diff(&self, other: &ConfList) -> ConfDiff {
+        let old = self.leaves();
+        let new = other.leaves();
+        let mut diff = ConfDiff::default();
+        for (path, value) in &old {
+            match new.iter().find(|(p, _)| p == path) {
+                Some((_, new_value)) => {
+                    if !same_value(value, new_value) {
+                        diff.changed.push((path.clone(), value.clone(), new_value.clone()));
+                    }
+                },
+                None => diff.removed.push((path.clone(), value.clone())),
+            }
+        }
+        for (path, value) in new {
+            if !old.iter().any(|(p, _)| *p == path) {
+                diff.added.push((path, value));
+            }
+        }
+        diff
+    }
+}
+
+fn same_value(a: &ConfValue, b: &ConfValue) -> bool {
+    match (a, b) {
+        (ConfValue::StrValue(a), ConfValue::StrValue(b)) => a == b,
+        (ConfValue::BoolValue(a), ConfValue::BoolValue(b)) => a == b,
+        (ConfValue::NumberValue(a), ConfValue::NumberValue(b)) => a == b,
+        #[cfg(feature = "decimal")]
+        (ConfValue::DecimalValue(a), ConfValue::DecimalValue(b)) => a == b,
+        (ConfValue::Bytes(a), ConfValue::Bytes(b)) => a == b,
+        (ConfValue::List(a), ConfValue::List(b)) => a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b)),
+        // 末端に現れるのは空のリストだけ
+        (ConfValue::Conf(a), ConfValue::Conf(b)) => a.diff(b).is_empty(),
+        _ => false,
+    }
+}
+
+#[cfg(all(test, feature = "std-fs"))]
+mod tests {
+    use crate::parse;
+
+    #[test]
+    fn can_diff_two_confs() {
+        let old = parse("tests/case-1.conf", Some("tests/data.schema")).unwrap();
+        let new = parse("tests/case-2.conf", Some("tests/data.schema")).unwrap();
+        let diff = old.diff(&new);
+        assert_eq!(diff.to_string(), "- debug = true\n+ log.name = default.log\n");
+        assert!(new.diff(&new).is_empty());
+    }
+}
@@ -0,0 +1,110 @@
+// アプリの名前から設定ファイルの場所を探す。次の順に見て、最初にあったファイルを読む
+// 1. $XDG_CONFIG_HOME/<app>/<app>.conf
+// 2. OS ごとの場所
+//    Linux など: ~/.config/<app>/<app>.conf
+//    macOS: ~/Library/Application Support/<app>/<app>.conf、~/.config/<app>/<app>.conf
+//    Windows: %APPDATA%\<app>\<app>.conf
+// 3. ~/.<app>.conf
+// 4. /etc/<app>/<app>.conf (Windows 以外)
+use std::error::Error;
+use std::path::PathBuf;
+
+use crate::expand::home_dir;
+use crate::{parse_with_options, ConfList, ParseOptions};
+
+pub fn config_candidates(app: &str) -> Vec<PathBuf> {
+    candidates(app, std::env::consts::OS, |name| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from))
+}
+
+// 候補のうち最初にあるファイル
+pub fn find_config(app: &str) -> Option<PathBuf> {
+    config_candidates(app).into_iter().find(|path| path.is_file())
+}
+
+// 見つけたファイルを読み込み、どのファイルを読んだかも返す
+pub fn parse_discovered(app: &str, schema_path: Option<&str>, options: &ParseOptions) -> Result<(ConfList, PathBuf), Box<dyn Error>> {
+    let candidates = config_candidates(app);
+    let path = first_existing(&candidates, app)?;
+    let conf = parse_with_options(&path.to_string_lossy(), schema_path, options)?;
+    Ok((conf, path))
+}
+
+fn first_existing(candidates: &[PathBuf], app: &str) -> Result<PathBuf, String> {
+    match candidates.iter().find(|path| path.is_file()) {
+        Some(path) => Ok(path.clone()),
+        None => {
+            let tried: Vec<String> = candidates.iter().map(|path| path.display().to_string()).collect();
+            Err(format!("No config file found for {} (tried: {})", app, tried.join(", ")))
+        },
+    }
+}
+
+// 環境変数は var から読む (テストで差し替えるため)
+fn candidates(app: &str, os: &str, var: impl Fn(&str) -> Option<PathBuf>) -> Vec<PathBuf> {
+    let file = format!("{}.conf", app);
+    let home = home_dir(os, &var);
+    let mut paths = Vec::new();
+    if let Some(dir) = var("XDG_CONFIG_HOME") {
+        paths.push(dir.join(app).join(&file));
+    }
+    match os {
+        "windows" => paths.extend(var("APPDATA").map(|dir| dir.join(app).join(&file))),
+        "macos" => paths.extend(home.iter().map(|home| home.join("Library/Application Support").join(app).join(&file))),
+        _ => {},
+    }
+    if os != "windows" {
+        paths.extend(home.iter().map(|home| home.join(".config").join(app).join(&file)));
+    }
+    paths.extend(home.iter().map(|home| home.join(format!(".{}", file))));
+    if os != "windows" {
+        paths.push(PathBuf::from("/etc").join(app).join(&file));
+    }
+    // XDG_CONFIG_HOME が ~/.config と同じなら 1 度だけ見る
+    let mut seen = Vec::new();
+    paths.retain(|path| {
+        let first = !seen.contains(path);
+        seen.push(path.clone());
+        first
+    });
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<PathBuf> + 'a {
+        |name| vars.iter().find(|(n, _)| *n == name).map(|(_, value)| PathBuf::from(value))
+    }
+
+    fn strings(paths: Vec<PathBuf>) -> Vec<String> {
+        paths.into_iter().map(|path| path.to_string_lossy().into_owned()).collect()
+    }
+
+    #[test]
+    fn can_list_platform_candidates() {
+        let linux = candidates("app", "linux", env(&[("HOME", "/home/u"), ("XDG_CONFIG_HOME", "/home/u/.config")]));
+        assert_eq!(strings(linux), ["/home/u/.config/app/app.conf", "/home/u/.app.conf", "/etc/app/app.conf"]);
+
+        let macos = candidates("app", "macos", env(&[("HOME", "/Users/u")]));
+        assert_eq!(strings(macos), [
+            "/Users/u/Library/Application Support/app/app.conf",
+            "/Users/u/.config/app/app.conf",
+            "/Users/u/.app.conf",
+            "/etc/app/app.conf",
+        ]);
+
+        let windows = candidates("app", "windows", env(&[("APPDATA", "C:/Users/u/AppData/Roaming"), ("USERPROFILE", "C:/Users/u")]));
+        assert_eq!(windows, [PathBuf::from("C:/Users/u/AppData/Roaming").join("app").join("app.conf"), PathBuf::from("C:/Users/u").join(".app.conf")]);
+
+        assert_eq!(strings(candidates("app", "linux", env(&[]))), ["/etc/app/app.conf"]);
+    }
+
+    #[test]
+    fn can_report_the_loaded_path() {
+        let candidates = [PathBuf::from("tests/missing.conf"), PathBuf::from("tests/case-1.conf"), PathBuf::from("tests/case-2.conf")];
+        assert_eq!(first_existing(&candidates, "app").unwrap(), PathBuf::from("tests/case-1.conf"));
+        let err = first_existing(&candidates[..1], "app").unwrap_err();
+        assert_eq!(err, "No config file found for app (tried: tests/missing.conf)");
+    }
+}
@@ -0,0 +1,80 @@
+// std-fs なしではファイルを読まないので decode は使われない
+#![cfg_attr(not(feature = "std-fs"), allow(dead_code))]
+
+// ファイルの文字コード。既定は UTF-8 で、先頭の BOM は取り除く
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    // BOM でエンディアンを判定する。BOM がなければリトルエンディアン
+    Utf16,
+    Latin1,
+}
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+pub(crate) fn decode(bytes: Vec<u8>, encoding: Encoding) -> Result<String, String> {
+    match encoding {
+        Encoding::Utf8 => decode_utf8(bytes),
+        Encoding::Utf16 => decode_utf16(&bytes),
+        Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+    }
+}
+
+fn decode_utf8(mut bytes: Vec<u8>) -> Result<String, String> {
+    if bytes.starts_with(UTF8_BOM) {
+        bytes.drain(..UTF8_BOM.len());
+    } else if bytes.starts_with(b"\xff\xfe") || bytes.starts_with(b"\xfe\xff") {
+        return Err("File is UTF-16 encoded (set ParseOptions::encoding to Encoding::Utf16)".to_string());
+    }
+    String::from_utf8(bytes).map_err(|e| {
+        let valid = &e.as_bytes()[..e.utf8_error().valid_up_to()];
+        let line = valid.iter().filter(|&&b| b == b'\n').count() + 1;
+        let column = valid.len() - valid.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1) + 1;
+        format!("Invalid UTF-8 at line {}, column {}: byte 0x{:02x}", line, column, e.as_bytes()[valid.len()])
+    })
+}
+
+fn decode_utf16(bytes: &[u8]) -> Result<String, String> {
+    let (bytes, big_endian) = match bytes {
+        [0xfe, 0xff, rest @ ..] => (rest, true),
+        [0xff, 0xfe, rest @ ..] => (rest, false),
+        _ => (bytes, false),
+    };
+    if bytes.len() % 2 != 0 {
+        return Err("Invalid UTF-16: odd number of bytes".to_string());
+    }
+    let units = bytes.chunks_exact(2).map(|pair| match big_endian {
+        true => u16::from_be_bytes([pair[0], pair[1]]),
+        false => u16::from_le_bytes([pair[0], pair[1]]),
+    });
+    let mut text = String::with_capacity(bytes.len() / 2);
+    let mut line = 1;
+    for c in char::decode_utf16(units) {
+        let c = c.map_err(|e| format!("Invalid UTF-16 at line {}: unpaired surrogate 0x{:04x}", line, e.unpaired_surrogate()))?;
+        if c == '\n' {
+            line += 1;
+        }
+        text.push(c);
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_decode_files_with_bom_and_other_encodings() {
+        assert_eq!(decode(b"\xef\xbb\xbfendpoint = x\n".to_vec(), Encoding::Utf8).unwrap(), "endpoint = x\n");
+        let err = decode(b"a = 1\nb = \xff\n".to_vec(), Encoding::Utf8).unwrap_err();
+        assert_eq!(err, "Invalid UTF-8 at line 2, column 5: byte 0xff");
+        assert!(decode(b"\xff\xfea\x00".to_vec(), Encoding::Utf8).unwrap_err().contains("UTF-16"));
+
+        let utf16: Vec<u8> = [0xfeff_u16].into_iter().chain("名前 = 値\n".encode_utf16()).flat_map(u16::to_le_bytes).collect();
+        assert_eq!(decode(utf16, Encoding::Utf16).unwrap(), "名前 = 値\n");
+        let utf16_be: Vec<u8> = [0xfeff_u16].into_iter().chain("a = b".encode_utf16()).flat_map(u16::to_be_bytes).collect();
+        assert_eq!(decode(utf16_be, Encoding::Utf16).unwrap(), "a = b");
+        assert_eq!(decode(b"caf\xe9 = 1".to_vec(), Encoding::Latin1).unwrap(), "café = 1");
+    }
+}
@@ -0,0 +1,125 @@
+// スキーマの型 endpoint。host:port の形 (ホスト名、IPv4、[...] で囲んだ IPv6) を確かめる
+// 値は文字列のまま持ち、as_endpoint() で host と port に分けて読む
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use crate::typed::FromConfValue;
+use crate::{ConfValue, ConfigValue, TypeMismatchError};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Endpoint {
+    // IPv6 は [ ] を外して持つ
+    host: String,
+    port: u16,
+}
+
+impl Endpoint {
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+}
+
+impl FromStr for Endpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (host, port) = match s.strip_prefix('[') {
+            Some(rest) => {
+                let (host, port) = rest.split_once("]:").ok_or_else(|| format!("Invalid endpoint: {} (expected [ipv6]:port)", s))?;
+                host.parse::<Ipv6Addr>().map_err(|_| format!("Invalid IPv6 address in endpoint: {}", s))?;
+                (host, port)
+            },
+            None => {
+                let (host, port) = s.rsplit_once(':').ok_or_else(|| format!("Invalid endpoint: {} (expected host:port)", s))?;
+                if !is_host(host) {
+                    return Err(format!("Invalid host in endpoint: {}", s));
+                }
+                (host, port)
+            },
+        };
+        // u16::from_str は +80 も受け付けるので、数字だけか先に確かめる
+        let port = match port.bytes().all(|b| b.is_ascii_digit()) {
+            true => port.parse().ok(),
+            false => None,
+        };
+        let port = port.ok_or_else(|| format!("Invalid port in endpoint: {}", s))?;
+        Ok(Endpoint { host: host.to_string(), port })
+    }
+}
+
+impl fmt::Display for Endpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.host.contains(':') {
+            true => write!(f, "[{}]:{}", self.host, self.port),
+            false => write!(f, "{}:{}", self.host, self.port),
+        }
+    }
+}
+
+// 数字とドットだけなら IPv4、そうでなければホスト名 (英数字と - のラベルをドットでつないだもの)
+fn is_host(host: &str) -> bool {
+    if host.bytes().all(|b| b.is_ascii_digit() || b == b'.') {
+        return host.parse::<Ipv4Addr>().is_ok();
+    }
+    host.len() <= 253
+        && host.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+        })
+}
+
+impl ConfValue {
+    pub fn as_endpoint(&self) -> Result<Endpoint, TypeMismatchError> {
+        self.as_str()?.parse().map_err(|_| TypeMismatchError)
+    }
+}
+
+impl ConfigValue {
+    pub fn as_endpoint(&self) -> Result<Endpoint, TypeMismatchError> {
+        self.as_str()?.parse().map_err(|_| TypeMismatchError)
+    }
+}
+
+impl FromConfValue for Endpoint {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+        value.as_str().map_err(|_| "Expected an endpoint".to_string())?.parse()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_split_host_and_port() {
+        let endpoint: Endpoint = "db-1.internal:5432".parse().unwrap();
+        assert_eq!((endpoint.host(), endpoint.port()), ("db-1.internal", 5432));
+        let endpoint: Endpoint = "[::1]:8080".parse().unwrap();
+        assert_eq!((endpoint.host(), endpoint.port()), ("::1", 8080));
+        assert_eq!(endpoint.to_string(), "[::1]:8080");
+        assert_eq!("10.0.0.1:80".parse::<Endpoint>().unwrap().host(), "10.0.0.1");
+
+        assert_eq!("localhost".parse::<Endpoint>().unwrap_err(), "Invalid endpoint: localhost (expected host:port)");
+        assert_eq!("::1:8080".parse::<Endpoint>().unwrap_err(), "Invalid host in endpoint: ::1:8080");
+        assert_eq!("[::1]".parse::<Endpoint>().unwrap_err(), "Invalid endpoint: [::1] (expected [ipv6]:port)");
+        assert_eq!("[nope]:80".parse::<Endpoint>().unwrap_err(), "Invalid IPv6 address in endpoint: [nope]:80");
+        assert_eq!("300.0.0.1:80".parse::<Endpoint>().unwrap_err(), "Invalid host in endpoint: 300.0.0.1:80");
+        assert_eq!("-web:80".parse::<Endpoint>().unwrap_err(), "Invalid host in endpoint: -web:80");
+        assert_eq!("web:+80".parse::<Endpoint>().unwrap_err(), "Invalid port in endpoint: web:+80");
+        assert_eq!("web:65536".parse::<Endpoint>().unwrap_err(), "Invalid port in endpoint: web:65536");
+
+        let conf = crate::parse_str("upstream = api.example.com:443\n", Some("upstream -> endpoint\n")).unwrap();
+        let endpoint = conf.value_at("upstream", |v| v.as_endpoint().unwrap()).unwrap();
+        assert_eq!((endpoint.host(), endpoint.port()), ("api.example.com", 443));
+        assert_eq!(conf.freeze().get("upstream").unwrap().as_endpoint().unwrap(), endpoint);
+        let err = crate::parse_str("upstream = api.example.com\n", Some("upstream -> endpoint\n")).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Invalid endpoint: api.example.com (expected host:port)");
+    }
+}
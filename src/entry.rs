@@ -0,0 +1,165 @@
+use crate::path::{split_key, to_dotted};
+use crate::{ConfList, ConfValue, Node};
+
+// HashMap::entry と同じように、値があるかどうかで分けて扱う
+pub enum Entry<'a> {
+    Occupied(OccupiedEntry<'a>),
+    Vacant(VacantEntry<'a>),
+}
+
+pub struct OccupiedEntry<'a> {
+    node: &'a mut Node,
+}
+
+pub struct VacantEntry<'a> {
+    list: &'a mut ConfList,
+    path: String,
+}
+
+impl<'a> Entry<'a> {
+    pub fn or_insert(self, default: ConfValue) -> &'a mut ConfValue {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> ConfValue>(self, default: F) -> &'a mut ConfValue {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn and_modify<F: FnOnce(&mut ConfValue)>(self, f: F) -> Self {
+        match self {
+            Entry::Occupied(mut entry) => {
+                f(entry.get_mut());
+                Entry::Occupied(entry)
+            },
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+impl<'a> OccupiedEntry<'a> {
+    pub fn get_mut(&mut self) -> &mut ConfValue {
+        self.node.value.get_mut()
+    }
+
+    pub fn into_mut(self) -> &'a mut ConfValue {
+        self.node.value.get_mut()
+    }
+
+    // 値を置き換えて古い値を返す。出どころと書かれていたままの値は手で設定したものとして消す
+    pub fn insert(&mut self, value: ConfValue) -> ConfValue {
+        self.node.origin = None;
+        self.node.raw = None;
+        std::mem::replace(self.node.value.get_mut(), value)
+    }
+}
+
+impl<'a> VacantEntry<'a> {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn insert(self, value: ConfValue) -> &'a mut ConfValue {
+        self.list.add_value(&self.path, value, None);
+        self.list.find_path_mut(&self.path).unwrap().value.get_mut()
+    }
+}
+
+impl ConfList {
+    // path はドット区切りか JSON Pointer で、途中のリストは insert のときに作られる
+    pub fn entry(&mut self, path: &str) -> Entry<'_> {
+        // JSON Pointer はここで一度だけドット区切りにする。VacantEntry::path もドット区切り
+        let path = to_dotted(path);
+        // 見つかった参照をそのまま返すと借用が衝突するので、先に有無だけ確かめる
+        if self.value_at(&path, |_| ()).is_none() {
+            return Entry::Vacant(VacantEntry { list: self, path: path.into_owned() });
+        }
+        Entry::Occupied(OccupiedEntry { node: self.find_path_mut(&path).unwrap() })
+    }
+
+    // HashMap::insert と同じく、すでに値があれば置き換えて古い値を返す
+    pub fn insert<V: Into<ConfValue>>(&mut self, path: &str, value: V) -> Option<ConfValue> {
+        match self.entry(path) {
+            Entry::Occupied(mut entry) => Some(entry.insert(value.into())),
+            Entry::Vacant(entry) => {
+                entry.insert(value.into());
+                None
+            },
+        }
+    }
+
+    // path はドット区切り
+    fn find_path_mut(&mut self, path: &str) -> Option<&mut Node> {
+        let (key, rest) = split_key(path);
+        let node = self.find_mut(&key)?;
+        match rest {
+            None => Some(node),
+            Some(rest) => match node.value.get_mut() {
+                ConfValue::Conf(child) => child.find_path_mut(rest),
+                _ => None,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn can_build_conf_from_scratch() {
+        let mut conf = ConfList::default();
+        assert!(conf.insert("endpoint", "localhost:3000").is_none());
+        conf.insert("log.level", 2u8);
+        conf.insert("log.debug", true);
+        let old = conf.insert("endpoint", "localhost:4000").unwrap();
+        assert_eq!(old.as_str().unwrap(), "localhost:3000");
+        assert_eq!(conf.get_str("endpoint").unwrap(), "localhost:4000");
+        assert_eq!(conf.get_number("log.level").unwrap(), 2.0);
+        assert!(conf.origin_of("endpoint").is_none());
+
+        let config = conf.freeze();
+        assert_eq!(config.keys().collect::<Vec<_>>(), ["endpoint", "log"]);
+        assert!(config.get_bool("log.debug").unwrap());
+    }
+
+    #[test]
+    fn can_fill_defaults_and_update_in_place() {
+        let mut conf = parse_str("log.level = 2\nendpoint = localhost:3000\n", Some("log.level -> number\n")).unwrap();
+        conf.entry("log.level").and_modify(|v| *v = ConfValue::NumberValue(v.as_number().unwrap() + 1.0));
+        conf.entry("log.file").or_insert(ConfValue::StrValue("/var/log/app.log".to_string()));
+        let endpoint = conf.entry("endpoint").or_insert(ConfValue::StrValue("unused".to_string()));
+        assert_eq!(endpoint.as_str().unwrap(), "localhost:3000");
+
+        let flat = conf.to_flat_map(false);
+        assert_eq!(flat["log.level"], "3");
+        assert_eq!(flat["log.file"], "/var/log/app.log");
+        assert!(conf.origin_of("log.file").is_none());
+        match conf.entry("missing.key") {
+            Entry::Vacant(entry) => assert_eq!(entry.path(), "missing.key"),
+            Entry::Occupied(_) => panic!("expected a vacant entry"),
+        }
+        assert!(!conf.contains_key("missing"));
+    }
+
+    #[test]
+    fn can_insert_with_pointer_and_escaped_paths() {
+        let mut conf = parse_str("db.host = a\nroutes./api = v1\n", None).unwrap();
+        assert_eq!(conf.insert("/db/host", "b").unwrap().as_str().unwrap(), "a");
+        assert!(conf.insert("/db/example.com/port", 80u16).is_none());
+        assert!(conf.insert("hosts.db\\.local.port", 5432u16).is_none());
+        assert_eq!(conf.get_str("db.host").unwrap(), "b");
+        assert_eq!(conf.get_number("db.example\\.com.port").unwrap(), 80.0);
+        assert_eq!(conf.get_number("/hosts/db.local/port").unwrap(), 5432.0);
+        assert!(conf.to_flat_map(false).keys().all(|key| !key.starts_with('/')));
+        assert_eq!(conf.get_str("routes./api").unwrap(), "v1");
+        assert_eq!(conf.get_str("/routes/~1api").unwrap(), "v1");
+        match conf.entry("/routes/~1web") {
+            Entry::Vacant(entry) => assert_eq!(entry.path(), "routes./web"),
+            Entry::Occupied(_) => panic!("expected a vacant entry"),
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// 環境変数から設定を読み込む。APP_LOG__LEVEL=debug -> log.level = debug
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::{add_entry, metrics, parse_schema_lines, ConfList, ConfValue, KeyCase, Origin, ParseContext, ParseOptions, Schema};
+
+// 環境変数名から設定のパスを返す。None なら読み飛ばす
+pub type EnvMapper = Box<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+// 環境変数名と設定のパスの対応。組織ごとに命名規則が違うので変えられるようにする
+pub struct EnvOptions {
+    // この文字列で始まる変数だけを読み、取り除いてからパスにする
+    pub prefix: String,
+    // パスの区切り (. に置き換える)
+    pub separator: String,
+    pub case: KeyCase,
+    // 指定されていれば prefix / separator / case の代わりにこれを使う
+    pub mapper: Option<EnvMapper>,
+}
+
+impl Default for EnvOptions {
+    fn default() -> Self {
+        EnvOptions { prefix: String::new(), separator: "__".to_string(), case: KeyCase::Lower, mapper: None }
+    }
+}
+
+impl EnvOptions {
+    pub fn with_prefix(prefix: &str) -> Self {
+        EnvOptions { prefix: prefix.to_string(), ..Default::default() }
+    }
+
+    pub fn path_for(&self, name: &str) -> Option<String> {
+        if let Some(mapper) = &self.mapper {
+            return mapper(name);
+        }
+        let rest = name.strip_prefix(&self.prefix)?;
+        if rest.is_empty() {
+            return None;
+        }
+        let path = match self.separator.is_empty() {
+            true => rest.to_string(),
+            false => rest.replace(&self.separator, "."),
+        };
+        Some(match self.case {
+            KeyCase::Preserve => path,
+            KeyCase::Lower => path.to_lowercase(),
+            KeyCase::Upper => path.to_uppercase(),
+        })
+    }
+}
+
+// プロセスの環境変数から読み込む。ほかの層に重ねて使うので required や default は適用しない
+pub fn parse_env(schema: Option<&str>, env: &EnvOptions, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+    // UTF-8 でない変数は読み飛ばす
+    let vars = std::env::vars_os().filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+    parse_env_from(vars, schema, env, options)
+}
+
+// 変数の一覧を渡して読み込む (テストやコンテナの env ファイルなど)
+pub fn parse_env_from<I>(vars: I, schema: Option<&str>, env: &EnvOptions, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>>
+where I: IntoIterator<Item = (String, String)>, {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema {
+            Some(s) => parse_schema_lines(s.lines().map(str::to_string), options)?,
+            None => HashMap::new(),
+        };
+        let mut ctx = ParseContext::new(&schema, options);
+        let result = add_vars(vars, env, &mut ctx);
+        ctx.record(stats);
+        result
+    })
+}
+
+fn add_vars<I>(vars: I, env: &EnvOptions, ctx: &mut ParseContext) -> Result<ConfList, Box<dyn Error>>
+where I: IntoIterator<Item = (String, String)>, {
+    // 環境変数の順序は決まっていないので名前順に追加する
+    let mut vars: Vec<(String, String)> = vars.into_iter().collect();
+    vars.sort();
+    let mut map = ConfList::new();
+    for (name, value) in vars {
+        let path = match env.path_for(&name) {
+            Some(path) => path,
+            None => continue,
+        };
+        let origin = Origin::new(format!("env {}", name), None);
+        ctx.count_key().map_err(|e| format!("{}: {}", origin, e))?;
+        add_entry(&mut map, &path, value.trim(), ctx, origin.clone())
+            .map_err(|e| format!("{}: {}", origin, e))?;
+    }
+    Ok(map)
+}
+
+impl ConfList {
+    // 検証した設定を環境変数で子プロセスに渡すための名前と値の組 (ソース順)
+    // 名前は prefix にパスを大文字で _ でつないだもの。英数字以外は _ にする。log.file -> APP_LOG_FILE
+    // 値はそのまま書き出すので secret も伏せない。空のセクションは含めない
+    // log.file と log_file のように同じ名前になるキーや、数字で始まる名前はエラーにする
+    pub fn to_env_vars(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        let mut vars = Vec::new();
+        let mut paths: HashMap<String, String> = HashMap::new();
+        for (path, value) in self.leaves() {
+            if let ConfValue::Conf(_) = value {
+                continue;
+            }
+            let name: String = path.chars().map(|c| match c {
+                c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+                _ => '_',
+            }).collect();
+            let name = format!("{}{}", prefix, name);
+            if name.starts_with(|c: char| c.is_ascii_digit()) {
+                return Err(format!("Environment variable name for {} starts with a digit: {}", path, name));
+            }
+            if let Some(other) = paths.get(&name) {
+                return Err(format!("Keys {} and {} map to the same environment variable: {}", other, path, name));
+            }
+            paths.insert(name.clone(), path);
+            vars.push((name, value.to_string()));
+        }
+        Ok(vars)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn can_map_env_var_names() {
+        let env = vars(&[("APP_LOG__LEVEL", "debug"), ("APP_PORT", "8080"), ("PATH", "/usr/bin"), ("APP_", "x")]);
+        let options = ParseOptions::default();
+        let conf = parse_env_from(env, Some("port -> number\n"), &EnvOptions::with_prefix("APP_"), &options).unwrap();
+        let flat = conf.to_flat_map(false);
+        assert_eq!(flat.len(), 2);
+        assert_eq!(flat["log.level"], "debug");
+        assert!(conf.value_at("port", |v| v.as_number().is_ok()).unwrap());
+        assert_eq!(conf.origin_of("port").unwrap().source, "env APP_PORT");
+
+        let single = EnvOptions { separator: "_".to_string(), case: KeyCase::Preserve, ..EnvOptions::with_prefix("APP_") };
+        assert_eq!(single.path_for("APP_LOG_LEVEL").unwrap(), "LOG.LEVEL");
+
+        let mapper = EnvOptions {
+            mapper: Some(Box::new(|name| name.strip_prefix("MYAPP-").map(|rest| rest.replace('-', ".")))),
+            ..Default::default()
+        };
+        let conf = parse_env_from(vars(&[("MYAPP-db-host", "localhost"), ("APP_PORT", "1")]), None, &mapper, &options).unwrap();
+        assert_eq!(conf.to_flat_map(false).into_iter().collect::<Vec<_>>(), [("db.host".to_string(), "localhost".to_string())]);
+
+        let err = parse_env_from(vars(&[("APP_PORT", "eighty")]), Some("port -> number\n"), &EnvOptions::with_prefix("APP_"), &options).unwrap_err();
+        assert_eq!(err.to_string(), "env APP_PORT: Invalid number value");
+    }
+
+    #[test]
+    fn can_export_env_vars() {
+        let conf = crate::parse_str("log.file = /var/log/x\nport = 8080\nlog.file = /var/log/app.log\nempty = {}\nfeature-flags.new_ui = true\ntags = [a, b]\n", Some("port -> number\nempty -> object\n")).unwrap();
+        assert_eq!(conf.to_env_vars("APP_").unwrap(), vars(&[
+            ("APP_LOG_FILE", "/var/log/app.log"),
+            ("APP_PORT", "8080"),
+            ("APP_FEATURE_FLAGS_NEW_UI", "true"),
+            ("APP_TAGS", "[a, b]"),
+        ]));
+
+        let conf = crate::parse_str("log.file = a\nlog_file = b\n", None).unwrap();
+        assert_eq!(conf.to_env_vars("APP_").unwrap_err(), "Keys log.file and log_file map to the same environment variable: APP_LOG_FILE");
+        let conf = crate::parse_str("2fa.enabled = true\n", None).unwrap();
+        assert_eq!(conf.to_env_vars("").unwrap_err(), "Environment variable name for 2fa.enabled starts with a digit: 2FA_ENABLED");
+        assert_eq!(conf.to_env_vars("APP_").unwrap(), vars(&[("APP_2FA_ENABLED", "true")]));
+    }
+}
@@ -0,0 +1,95 @@
+// ConfValue と ConfList の比較とハッシュ。HashMap のキーにしたり、重複を取り除いたり、テストで assert_eq! で比べたりできる
+// 比べるのは値だけで、出どころ・secret・書いたままの値・監査フックは見ない
+// f64 は to_bits で比べる。NaN どうしは等しく、0.0 と -0.0 は別の値になる (== と Hash を食い違わせないため)
+// セクションは上書きされていないキーを名前順に比べるので、書いた順が違っても同じ値になる
+use std::hash::{Hash, Hasher};
+
+use crate::serialize::WriteOptions;
+use crate::{ConfList, ConfValue, Node};
+
+impl PartialEq for ConfValue {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (ConfValue::StrValue(a), ConfValue::StrValue(b)) => a == b,
+            (ConfValue::BoolValue(a), ConfValue::BoolValue(b)) => a == b,
+            (ConfValue::NumberValue(a), ConfValue::NumberValue(b)) => a.to_bits() == b.to_bits(),
+            // Decimal は 1.5 と 1.50 を等しいとみなす (Hash もそれに合わせてある)
+            #[cfg(feature = "decimal")]
+            (ConfValue::DecimalValue(a), ConfValue::DecimalValue(b)) => a == b,
+            (ConfValue::Bytes(a), ConfValue::Bytes(b)) => a == b,
+            (ConfValue::List(a), ConfValue::List(b)) => a == b,
+            (ConfValue::Conf(a), ConfValue::Conf(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for ConfValue {}
+
+impl Hash for ConfValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            ConfValue::StrValue(v) => v.hash(state),
+            ConfValue::BoolValue(v) => v.hash(state),
+            ConfValue::NumberValue(v) => v.to_bits().hash(state),
+            #[cfg(feature = "decimal")]
+            ConfValue::DecimalValue(v) => v.hash(state),
+            ConfValue::Bytes(v) => v.hash(state),
+            ConfValue::List(items) => items.hash(state),
+            ConfValue::Conf(conf) => conf.hash(state),
+        }
+    }
+}
+
+impl ConfList {
+    fn sorted_nodes(&self) -> Vec<&Node> {
+        self.effective_nodes(&WriteOptions { sorted: true })
+    }
+}
+
+impl PartialEq for ConfList {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b) = (self.sorted_nodes(), other.sorted_nodes());
+        a.len() == b.len() && a.iter().zip(&b).all(|(a, b)| a.key == b.key && *a.value.borrow() == *b.value.borrow())
+    }
+}
+
+impl Eq for ConfList {}
+
+impl Hash for ConfList {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let nodes = self.sorted_nodes();
+        nodes.len().hash(state);
+        for node in nodes {
+            node.key.hash(state);
+            node.value.borrow().hash(state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn can_compare_and_hash_values() {
+        let a = parse_str("port = 8080\nlog.level = info\nlog.level = debug\ntags = [a, b]\n", Some("port -> number\n")).unwrap();
+        let b = parse_str("tags = [a, b]\nlog.level = debug\nport = 8080\n", Some("port -> number\n")).unwrap();
+        let c = parse_str("port = 8080\nlog.level = info\ntags = [a, b]\n", Some("port -> number\n")).unwrap();
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(ConfValue::NumberValue(8080.0), ConfValue::StrValue("8080".to_string()));
+
+        assert_eq!(ConfValue::NumberValue(f64::NAN), ConfValue::NumberValue(f64::NAN));
+        assert_ne!(ConfValue::NumberValue(0.0), ConfValue::NumberValue(-0.0));
+
+        // 値は RefCell の中にあるが、&self から書き換えることはないのでキーにしてよい
+        #[allow(clippy::mutable_key_type)]
+        let set: HashSet<ConfValue> = [a, b, c].into_iter().map(|conf| ConfValue::Conf(Box::new(conf))).collect();
+        assert_eq!(set.len(), 2);
+    }
+}
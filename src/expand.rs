@@ -0,0 +1,113 @@
+// スキーマの型が path の値を、ParseOptions::expand_paths のときに展開する
+// ~ はホームディレクトリ、$NAME と ${NAME} は環境変数にし、相対パスは (プロセスのカレントディレクトリではなく) その値を書いた conf のディレクトリから解決する
+// 文字列やリーダーから読んだ値は相対パスのまま
+
+// ~ と環境変数を展開する。設定されていない変数はエラー
+// 環境変数は var から読む (ParseOptions::env を使うため)。os は std::env::consts::OS と同じ名前
+pub(crate) fn expand_path(s: &str, os: &str, var: impl Fn(&str) -> Option<String>) -> Result<String, String> {
+    let mut out = String::new();
+    let mut rest = s;
+    if let Some(after) = s.strip_prefix('~') {
+        if after.is_empty() || after.starts_with(['/', '\\']) {
+            let home = home_dir(os, &var).ok_or_else(|| format!("Cannot expand ~ because {} is not set", home_var(os)))?;
+            out.push_str(&home);
+            rest = after;
+        }
+    }
+    while let Some(start) = rest.find('$') {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => return Err(format!("Unterminated variable in path: {}", s)),
+            },
+            None => {
+                let end = after.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(after.len());
+                (&after[..end], end)
+            },
+        };
+        // $ の後に名前がなければそのまま
+        if name.is_empty() {
+            out.push('$');
+            rest = after;
+            continue;
+        }
+        out.push_str(&var(name).ok_or_else(|| format!("Environment variable not found: {}", name))?);
+        rest = &after[len..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+// ホームディレクトリを指す環境変数。discover の候補や expand_home の変換も同じものを見る
+pub(crate) fn home_var(os: &str) -> &'static str {
+    match os {
+        "windows" => "USERPROFILE",
+        _ => "HOME",
+    }
+}
+
+pub(crate) fn home_dir<T>(os: &str, var: impl Fn(&str) -> Option<T>) -> Option<T> {
+    var(home_var(os))
+}
+
+// 相対パスを file のあるディレクトリからのパスにする
+#[cfg(feature = "std-fs")]
+pub(crate) fn resolve_relative(path: String, file: &std::path::Path) -> String {
+    let Some(dir) = file.parent() else {
+        return path;
+    };
+    if path.is_empty() || std::path::Path::new(&path).is_absolute() {
+        return path;
+    }
+    let mut relative = path.as_str();
+    while let Some(rest) = relative.strip_prefix("./") {
+        relative = rest;
+    }
+    dir.join(relative).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_expand_home_and_env() {
+        let env = |name: &str| match name {
+            "HOME" => Some("/home/u".to_string()),
+            "USERPROFILE" => Some("C:/Users/u".to_string()),
+            _ => None,
+        };
+        assert_eq!(expand_path("~/logs", "linux", env).unwrap(), "/home/u/logs");
+        assert_eq!(expand_path("~", "linux", env).unwrap(), "/home/u");
+        assert_eq!(expand_path("~/logs", "windows", env).unwrap(), "C:/Users/u/logs");
+        assert_eq!(expand_path("~user/logs", "linux", env).unwrap(), "~user/logs");
+        assert_eq!(expand_path("$HOME/a/${HOME}", "linux", env).unwrap(), "/home/u/a//home/u");
+        assert_eq!(expand_path("/cost/$/x$", "linux", env).unwrap(), "/cost/$/x$");
+        assert_eq!(expand_path("$UNSET/x", "linux", env).unwrap_err(), "Environment variable not found: UNSET");
+        assert_eq!(expand_path("~", "linux", |_| None).unwrap_err(), "Cannot expand ~ because HOME is not set");
+        assert!(expand_path("${HOME", "linux", env).is_err());
+    }
+
+    #[cfg(feature = "std-fs")]
+    #[test]
+    fn can_resolve_relative_to_conf_dir() {
+        let file = std::path::Path::new("/etc/app/app.conf");
+        assert_eq!(resolve_relative("./certs/server.pem".to_string(), file), "/etc/app/certs/server.pem");
+        assert_eq!(resolve_relative("../shared".to_string(), file), "/etc/app/../shared");
+        assert_eq!(resolve_relative("/var/log".to_string(), file), "/var/log");
+
+        let env = std::collections::HashMap::from([(home_var(std::env::consts::OS).to_string(), "/home/u".to_string())]);
+        let options = crate::ParseOptions { expand_paths: true, env: Some(env), ..Default::default() };
+        let conf = crate::parse_with_options("tests/paths.conf", Some("tests/paths.schema"), &options).unwrap();
+        let dir = std::fs::canonicalize("tests").unwrap();
+        assert_eq!(conf.get_str("log.dir").unwrap(), dir.join("logs").to_string_lossy());
+        assert_eq!(conf.get_str("cache").unwrap(), "/home/u/.cache/app");
+        assert_eq!(conf.get_str("name").unwrap(), "./not-a-path");
+        assert_eq!(conf.raw_of("log.dir").unwrap(), "./logs");
+
+        let conf = crate::parse("tests/paths.conf", Some("tests/paths.schema")).unwrap();
+        assert_eq!(conf.get_str("log.dir").unwrap(), "./logs");
+    }
+}
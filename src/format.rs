@@ -0,0 +1,177 @@
+// .conf を決まった書式に整える (conf fmt)。コメントは付いていた行と一緒に残す
+// 値は 1 行に書く形式で継続行がないので、長い値は折り返さない
+use std::collections::HashSet;
+use crate::cst::{Document, Line, LineKind, TokenKind};
+use crate::path::parent_paths;
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FormatOptions {
+    // 空行で区切られたまとまりごとに = の位置をそろえる
+    pub align: bool,
+    // まとまりごとにキーの名前順に並べる。直前のコメントはキーと一緒に動く
+    // include の行は前後の値の優先順位が変わるので、そこで区切る
+    // a.b のあとに a を書き直すような、並べ替えると結果が変わる範囲はそのままにする
+    pub sort: bool,
+    // ParseOptions::allow_export と同じ。読み込む側が export を読み飛ばすときだけ export とキーの間を整える
+    pub allow_export: bool,
+}
+
+pub fn format_str(text: &str, options: &FormatOptions) -> String {
+    let doc = Document::parse(text);
+    let mut out = String::new();
+    for (i, block) in blocks(&doc).into_iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        format_block(block, options, &mut out);
+    }
+    out
+}
+
+// 空行で区切ったまとまり。前後と連続した空行は 1 つにまとめる
+fn blocks(doc: &Document) -> Vec<Vec<&Line>> {
+    let mut blocks: Vec<Vec<&Line>> = Vec::new();
+    let mut current: Vec<&Line> = Vec::new();
+    for line in &doc.lines {
+        if line.kind == LineKind::Blank {
+            if !current.is_empty() {
+                blocks.push(std::mem::take(&mut current));
+            }
+        } else {
+            current.push(line);
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current);
+    }
+    blocks
+}
+
+fn format_block(block: Vec<&Line>, options: &FormatOptions, out: &mut String) {
+    let lines = if options.sort { sorted(block) } else { block };
+    let width = match options.align {
+        true => lines.iter().filter(|line| line.kind == LineKind::Entry).map(|line| key_text(line, options).chars().count()).max().unwrap_or(0),
+        false => 0,
+    };
+    for line in lines {
+        match line.kind {
+            LineKind::Entry => {
+                let key = key_text(line, options);
+                let padding = width.saturating_sub(key.chars().count());
+                out.push_str(&format!("{}{} = {}\n", key, " ".repeat(padding), line.value().unwrap_or_default()));
+            },
+            LineKind::Include => {
+                let keyword = text_of(line, TokenKind::Include);
+                out.push_str(&format!("{} {}\n", keyword, text_of(line, TokenKind::IncludePath)));
+            },
+            // コメントと読めない行は前後の空白だけ取り除く
+            _ => out.push_str(&format!("{}\n", line.to_string().trim())),
+        }
+    }
+}
+
+// export は残し、キーとの間の空白を 1 つにする
+// export を読み飛ばさないなら export から後の空白までがキーなので、書かれたとおりに残す
+fn key_text(line: &Line, options: &FormatOptions) -> String {
+    let key = line.key().unwrap_or_default();
+    let Some(start) = line.tokens.iter().position(|token| token.kind == TokenKind::Export) else {
+        return key.to_string();
+    };
+    match options.allow_export {
+        true => format!("export {}", key),
+        false => line.tokens[start..].iter().take_while(|token| token.kind != TokenKind::Equals).map(|token| token.text.as_str()).collect::<String>().trim_end().to_string(),
+    }
+}
+
+fn text_of(line: &Line, kind: TokenKind) -> &str {
+    line.tokens.iter().find(|token| token.kind == kind).map_or("", |token| token.text.as_str())
+}
+
+// コメントをその次の値と組にして、include や読めない行で区切った範囲ごとに並べ替える
+// 同じキーは後のものが有効なので、安定ソートで順序を保つ
+fn sorted(block: Vec<&Line>) -> Vec<&Line> {
+    let mut out: Vec<&Line> = Vec::new();
+    let mut group: Vec<Vec<&Line>> = Vec::new();
+    let mut pending: Vec<&Line> = Vec::new();
+    for line in block {
+        match line.kind {
+            LineKind::Comment => pending.push(line),
+            LineKind::Entry => {
+                pending.push(line);
+                group.push(std::mem::take(&mut pending));
+            },
+            _ => {
+                flush(&mut group, &mut out);
+                out.append(&mut pending);
+                out.push(line);
+            },
+        }
+    }
+    flush(&mut group, &mut out);
+    // まとまりの最後のコメントはそのまま最後に置く
+    out.append(&mut pending);
+    out
+}
+
+fn flush<'a>(group: &mut Vec<Vec<&'a Line>>, out: &mut Vec<&'a Line>) {
+    if !overrides_earlier_child(group) {
+        group.sort_by(|a, b| a.last().unwrap().key().cmp(&b.last().unwrap().key()));
+    }
+    out.extend(group.drain(..).flatten());
+}
+
+// a.b = y の後に a = x があると、並べ替えで a が先になり a.b が残ってしまう
+// 名前順では親のキーが必ず子より先になるので、子より後にある親だけが順序に依存する
+fn overrides_earlier_child(group: &[Vec<&Line>]) -> bool {
+    let mut later: HashSet<&str> = HashSet::new();
+    for entry in group.iter().rev() {
+        let key = entry.last().unwrap().key().unwrap_or_default();
+        if parent_paths(key).into_iter().any(|parent| later.contains(parent)) {
+            return true;
+        }
+        later.insert(key);
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "\n\n# server\n  port=8080  \nendpoint   =  localhost:3000\n\n\n\n; logging\nlog.level= info\n# where to write\nlog.file =/var/log/app.log\ninclude   local.conf\nexport\tDB_HOST=db\n";
+
+    #[test]
+    fn can_format_spacing_and_blank_lines() {
+        let formatted = format_str(INPUT, &FormatOptions::default());
+        assert_eq!(formatted, "# server\nport = 8080\nendpoint = localhost:3000\n\n; logging\nlog.level = info\n# where to write\nlog.file = /var/log/app.log\ninclude local.conf\nexport\tDB_HOST = db\n");
+        assert_eq!(format_str(&formatted, &FormatOptions::default()), formatted);
+    }
+
+    #[test]
+    fn can_align_and_sort_keys() {
+        let options = FormatOptions { align: true, sort: true, allow_export: true };
+        assert_eq!(format_str(INPUT, &options), "\
+endpoint = localhost:3000
+# server
+port     = 8080
+
+# where to write
+log.file       = /var/log/app.log
+; logging
+log.level      = info
+include local.conf
+export DB_HOST = db
+");
+    }
+
+    #[test]
+    fn does_not_sort_lines_that_override_earlier_children() {
+        let options = FormatOptions { sort: true, ..Default::default() };
+        let input = "z = 1\na.b = y\na = x\n";
+        assert_eq!(format_str(input, &options), input);
+        let input = "db.host = old\ndb = {host = new}\n";
+        assert_eq!(format_str(input, &options), input);
+        // 親が先にある組と同じキーは、安定ソートで順序が保たれる
+        assert_eq!(format_str("b = 1\na = x\na.b = y\nb = 2\n", &options), "a = x\na.b = y\nb = 1\nb = 2\n");
+    }
+}
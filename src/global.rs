@@ -0,0 +1,161 @@
+// プロセスで 1 つの設定。起動時に init_global で 1 度だけ設定し、どこからでも global() で読む
+// Reloader::install_global を使えば、読み直すたびに置き換わる。読んでいる側が持つ Arc はそのまま使える
+use std::error::Error;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::config::ConfigEntry;
+use crate::path::split_key;
+use crate::{Config, ConfigValue, Origin};
+
+static GLOBAL: OnceLock<RwLock<Arc<Config>>> = OnceLock::new();
+
+pub fn init_global(config: Config) -> Result<(), Box<dyn Error>> {
+    init_global_arc(Arc::new(config))
+}
+
+pub(crate) fn init_global_arc(config: Arc<Config>) -> Result<(), Box<dyn Error>> {
+    GLOBAL.set(RwLock::new(config)).map_err(|_| "Global config is already initialized".into())
+}
+
+// init_global より前に呼ぶと panic する
+pub fn global() -> Arc<Config> {
+    try_global().expect("Global config is not initialized")
+}
+
+pub fn try_global() -> Option<Arc<Config>> {
+    // 書き込み中に panic しても、置き換える前か後の設定のどちらかが残っている
+    GLOBAL.get().map(|lock| Arc::clone(&lock.read().unwrap_or_else(|e| e.into_inner())))
+}
+
+// 置き換えて前の設定を返す。初期化されていなければ None で、何もしない
+pub fn swap_global(config: Arc<Config>) -> Option<Arc<Config>> {
+    let lock = GLOBAL.get()?;
+    let mut current = lock.write().unwrap_or_else(|e| e.into_inner());
+    Some(std::mem::replace(&mut current, config))
+}
+
+// テストで global() の一部の値を一時的に変える。戻り値を破棄すると変える前の設定に戻す
+// その間に読み直しや別の override_global で置き換わっていれば、置き換えた側の設定を残す
+// 同じプロセスで並列に走るテストからも変えた値が見えるので、同じパスを変えるテストは同時に走らせない
+// init_global より前に呼ぶと panic する
+pub fn override_global(overrides: &[(&str, ConfigValue)]) -> GlobalOverride {
+    let lock = GLOBAL.get().expect("Global config is not initialized");
+    let mut current = lock.write().unwrap_or_else(|e| e.into_inner());
+    let mut config = Config::clone(&current);
+    for (path, value) in overrides {
+        set_path(&mut config, path, value.clone());
+    }
+    let installed = Arc::new(config);
+    let previous = std::mem::replace(&mut *current, Arc::clone(&installed));
+    GlobalOverride { previous, installed }
+}
+
+#[must_use = "the override is reverted when the guard is dropped"]
+pub struct GlobalOverride {
+    previous: Arc<Config>,
+    installed: Arc<Config>,
+}
+
+impl Drop for GlobalOverride {
+    fn drop(&mut self) {
+        let Some(lock) = GLOBAL.get() else {
+            return;
+        };
+        let mut current = lock.write().unwrap_or_else(|e| e.into_inner());
+        if Arc::ptr_eq(&current, &self.installed) {
+            *current = Arc::clone(&self.previous);
+        }
+    }
+}
+
+// 途中のセクションがなければ作り、値があればセクションで置き換える
+fn set_path(config: &mut Config, path: &str, value: ConfigValue) {
+    let (key, rest) = split_key(path);
+    let index = match config.entries.iter().position(|entry| entry.key == key) {
+        Some(index) => index,
+        None => {
+            config.entries.push(ConfigEntry {
+                key: key.to_string(),
+                value: ConfigValue::Conf(Config::default()),
+                origin: None,
+                raw: None,
+                #[cfg(feature = "track-access")]
+                accessed: Default::default(),
+            });
+            config.entries.len() - 1
+        },
+    };
+    let entry = &mut config.entries[index];
+    let Some(rest) = rest else {
+        entry.value = value;
+        entry.origin = Some(Origin::new("<override>", None));
+        entry.raw = None;
+        return;
+    };
+    if !matches!(entry.value, ConfigValue::Conf(_)) {
+        entry.value = ConfigValue::Conf(Config::default());
+    }
+    if let ConfigValue::Conf(child) = &mut entry.value {
+        set_path(child, rest, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    // プロセスで 1 つなので、1 つのテストでまとめて確かめる
+    #[test]
+    fn can_init_and_swap_global() {
+        assert!(try_global().is_none());
+        assert!(swap_global(Arc::new(Config::default())).is_none());
+
+        init_global(parse_str("log.level = info\n", None).unwrap().freeze()).unwrap();
+        let before = global();
+        assert_eq!(before.get("log.level").unwrap().as_str().unwrap(), "info");
+        assert!(init_global(Config::default()).unwrap_err().to_string().contains("already initialized"));
+
+        let old = swap_global(Arc::new(parse_str("log.level = debug\n", None).unwrap().freeze())).unwrap();
+        assert!(Arc::ptr_eq(&old, &before));
+        assert_eq!(global().get("log.level").unwrap().as_str().unwrap(), "debug");
+        assert_eq!(before.get("log.level").unwrap().as_str().unwrap(), "info");
+
+        #[cfg(feature = "std-fs")]
+        {
+            let path = std::env::temp_dir().join(format!("conf-global-{}.conf", std::process::id()));
+            let path = path.to_str().unwrap();
+            std::fs::write(path, "log.level = warn\n").unwrap();
+            let mut reloader = crate::reload::Reloader::new(path, None, crate::ParseOptions::default()).unwrap();
+            reloader.install_global();
+            assert_eq!(global().get("log.level").unwrap().as_str().unwrap(), "warn");
+            std::fs::write(path, "log.level = error\n").unwrap();
+            reloader.reload().unwrap();
+            assert_eq!(global().get("log.level").unwrap().as_str().unwrap(), "error");
+            std::fs::remove_file(path).unwrap();
+        }
+
+        swap_global(Arc::new(parse_str("log.level = info\nport = 8080\n", None).unwrap().freeze()));
+        {
+            let _guard = override_global(&[("log.level", ConfigValue::StrValue("trace".to_string())), ("db.pool.size", ConfigValue::NumberValue(2.0))]);
+            assert_eq!(global().get("log.level").unwrap().as_str().unwrap(), "trace");
+            assert_eq!(global().get("db.pool.size").unwrap().as_number().unwrap(), 2.0);
+            assert_eq!(global().origin_of("log.level").unwrap().source, "<override>");
+            {
+                let _inner = override_global(&[("port", ConfigValue::NumberValue(0.0))]);
+                assert_eq!(global().get("port").unwrap().as_number().unwrap(), 0.0);
+            }
+            assert_eq!(global().get("port").unwrap().as_str().unwrap(), "8080");
+            assert_eq!(global().get("log.level").unwrap().as_str().unwrap(), "trace");
+        }
+        assert_eq!(global().get("log.level").unwrap().as_str().unwrap(), "info");
+        assert!(!global().contains_key("db"));
+
+        // 戻す前に置き換えた設定は元に戻さない
+        let guard = override_global(&[("log.level", ConfigValue::StrValue("trace".to_string()))]);
+        let reloaded = Arc::new(parse_str("log.level = warn\n", None).unwrap().freeze());
+        swap_global(Arc::clone(&reloaded));
+        drop(guard);
+        assert!(Arc::ptr_eq(&global(), &reloaded));
+    }
+}
@@ -0,0 +1,373 @@
+// 1 行に書くリスト [a, "b, c", [d, e]] とテーブル { host = localhost, port = 5432 }
+// リストはカンマを含むただの文字列とは区別する。要素は文字列か入れ子のリスト、テーブル
+// "..." ではエスケープ (\" \\ \n \t \r \uXXXX) が使え、'...' はそのまま
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+
+use crate::serialize::write_json_string;
+use crate::{ConfList, ConfValue};
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ListItem<'a> {
+    Str(Cow<'a, str>),
+    List(Vec<ListItem<'a>>),
+    // [{ host = a, port = 80 }] のように要素として書いたテーブル
+    Table(Vec<(&'a str, ListItem<'a>)>),
+}
+
+impl ListItem<'_> {
+    pub(crate) fn into_conf_value(self) -> ConfValue {
+        match self {
+            ListItem::Str(v) => ConfValue::StrValue(v.into_owned()),
+            ListItem::List(items) => ConfValue::List(items.into_iter().map(ListItem::into_conf_value).collect()),
+            ListItem::Table(entries) => {
+                let mut conf = ConfList::new();
+                for (key, item) in entries {
+                    conf.insert(key, item.into_conf_value());
+                }
+                ConfValue::Conf(Box::new(conf))
+            },
+        }
+    }
+}
+
+// 角かっこで囲まれていなければ None。max_depth は入れ子の深さの上限
+pub(crate) fn parse_list(s: &str, max_depth: usize) -> Result<Option<Vec<ListItem<'_>>>, String> {
+    let s = s.trim();
+    if !(s.starts_with('[') && s.ends_with(']')) {
+        return Ok(None);
+    }
+    let mut parser = Parser { s, pos: 0, max_depth };
+    let items = parser.list(1).map_err(|e| format!("Invalid list value: {}", e))?;
+    if parser.pos != s.len() {
+        return Err(format!("Invalid list value: unexpected characters after column {}", parser.pos));
+    }
+    Ok(Some(items))
+}
+
+// テーブルの値。入れ子のリストやテーブルは書いたままの文字列で返し、呼び出し側で改めて読む
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TableValue<'a> {
+    Raw(&'a str),
+    // 引用符で囲まれた値。リストやテーブルとしては読まない
+    Quoted(Cow<'a, str>),
+}
+
+// 波かっこで囲まれていなければ None。キーはドット区切りでもよい
+pub(crate) fn parse_table(s: &str, max_depth: usize) -> Result<Option<Vec<(&str, TableValue<'_>)>>, String> {
+    let s = s.trim();
+    if !(s.starts_with('{') && s.ends_with('}')) {
+        return Ok(None);
+    }
+    let mut parser = Parser { s, pos: 0, max_depth };
+    let entries = parser.table().map_err(|e| format!("Invalid inline table: {}", e))?;
+    if parser.pos != s.len() {
+        return Err(format!("Invalid inline table: unexpected characters after column {}", parser.pos));
+    }
+    Ok(Some(entries))
+}
+
+// "..." または '...' だけの値なら中身を返す
+pub(crate) fn parse_quoted(s: &str) -> Option<Cow<'_, str>> {
+    let mut parser = Parser { s, pos: 0, max_depth: 0 };
+    let value = match parser.peek()? {
+        '"' => parser.double_quoted().ok()?,
+        '\'' => parser.single_quoted().ok()?,
+        _ => return None,
+    };
+    (parser.pos == s.len()).then_some(value)
+}
+
+pub(crate) fn parse_list_value(s: &str, max_depth: usize) -> Result<Option<ConfValue>, String> {
+    Ok(parse_list(s, max_depth)?.map(|items| ListItem::List(items).into_conf_value()))
+}
+
+struct Parser<'a> {
+    s: &'a str,
+    pos: usize,
+    max_depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.s[self.pos..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = &self.s[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    // pos は '[' を指している
+    fn list(&mut self, depth: usize) -> Result<Vec<ListItem<'a>>, String> {
+        if depth > self.max_depth {
+            return Err(format!("nested too deeply (limit: {})", self.max_depth));
+        }
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_whitespace();
+            // [] と末尾のカンマ [a, b,] を許す
+            if self.peek() == Some(']') {
+                self.pos += 1;
+                return Ok(items);
+            }
+            items.push(self.item(depth)?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(items);
+                },
+                Some(c) => return Err(format!("expected ',' or ']' but found '{}'", c)),
+                None => return Err("missing ']'".to_string()),
+            }
+        }
+    }
+
+    fn item(&mut self, depth: usize) -> Result<ListItem<'a>, String> {
+        match self.peek() {
+            Some('[') => Ok(ListItem::List(self.list(depth + 1)?)),
+            Some('{') => self.table_item(depth + 1),
+            Some('"') => Ok(ListItem::Str(self.double_quoted()?)),
+            Some('\'') => Ok(ListItem::Str(self.single_quoted()?)),
+            Some(',') => Err("empty item".to_string()),
+            _ => {
+                let rest = &self.s[self.pos..];
+                let end = rest.find([',', ']', '[', '"', '\'']).unwrap_or(rest.len());
+                if rest[end..].starts_with(['[', '"', '\'']) {
+                    return Err(format!("unexpected '{}' in an unquoted item", &rest[end..end + 1]));
+                }
+                self.pos += end;
+                Ok(ListItem::Str(Cow::Borrowed(rest[..end].trim_end())))
+            },
+        }
+    }
+
+    // リストの要素のテーブル。入れ子のリストやテーブルもここで読む
+    fn table_item(&mut self, depth: usize) -> Result<ListItem<'a>, String> {
+        if depth > self.max_depth {
+            return Err(format!("nested too deeply (limit: {})", self.max_depth));
+        }
+        let mut entries = Vec::new();
+        for (key, value) in self.table()? {
+            let item = match value {
+                TableValue::Quoted(v) => ListItem::Str(v),
+                TableValue::Raw(v) => {
+                    let mut parser = Parser { s: v, pos: 0, max_depth: self.max_depth };
+                    match parser.peek() {
+                        Some('[') => ListItem::List(parser.list(depth + 1)?),
+                        Some('{') => parser.table_item(depth + 1)?,
+                        _ => ListItem::Str(Cow::Borrowed(v)),
+                    }
+                },
+            };
+            entries.push((key, item));
+        }
+        Ok(ListItem::Table(entries))
+    }
+
+    // pos は '{' を指している
+    fn table(&mut self) -> Result<Vec<(&'a str, TableValue<'a>)>, String> {
+        self.pos += 1;
+        let mut entries = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.peek() == Some('}') {
+                self.pos += 1;
+                return Ok(entries);
+            }
+            let rest = &self.s[self.pos..];
+            let end = rest.find(['=', ',', '}']).filter(|&end| rest[end..].starts_with('='))
+                .ok_or_else(|| format!("missing '=' after {}", rest.split([',', '}']).next().unwrap_or_default().trim()))?;
+            let key = rest[..end].trim();
+            if key.is_empty() || key.contains(['[', ']', '{', '"', '\'']) {
+                return Err(format!("invalid key '{}'", key));
+            }
+            self.pos += end + 1;
+            self.skip_whitespace();
+            let value = match self.peek() {
+                Some('"') => TableValue::Quoted(self.double_quoted()?),
+                Some('\'') => TableValue::Quoted(self.single_quoted()?),
+                Some('[' | '{') => TableValue::Raw(self.balanced()?),
+                _ => {
+                    let rest = &self.s[self.pos..];
+                    let end = rest.find([',', '}']).unwrap_or(rest.len());
+                    let value = rest[..end].trim_end();
+                    if value.is_empty() {
+                        return Err(format!("missing value for {}", key));
+                    }
+                    self.pos += end;
+                    TableValue::Raw(value)
+                },
+            };
+            entries.push((key, value));
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(entries);
+                },
+                Some(c) => return Err(format!("expected ',' or '}}' but found '{}'", c)),
+                None => return Err("missing '}'".to_string()),
+            }
+        }
+    }
+
+    // 対応するかっこまでを返す。引用符の中のかっこは数えない
+    fn balanced(&mut self) -> Result<&'a str, String> {
+        let start = self.pos;
+        let mut closing: Vec<char> = Vec::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '[' | '{' => {
+                    closing.push(if c == '[' { ']' } else { '}' });
+                    if closing.len() > self.max_depth {
+                        return Err(format!("nested too deeply (limit: {})", self.max_depth));
+                    }
+                },
+                ']' | '}' if closing.pop() != Some(c) => return Err(format!("unexpected '{}'", c)),
+                '"' => {
+                    self.double_quoted()?;
+                    continue;
+                },
+                '\'' => {
+                    self.single_quoted()?;
+                    continue;
+                },
+                _ => {},
+            }
+            self.pos += c.len_utf8();
+            if closing.is_empty() {
+                return Ok(&self.s[start..self.pos]);
+            }
+        }
+        Err(format!("missing '{}'", closing.pop().unwrap_or(']')))
+    }
+
+    fn single_quoted(&mut self) -> Result<Cow<'a, str>, String> {
+        let rest = &self.s[self.pos + 1..];
+        let end = rest.find('\'').ok_or("unterminated string")?;
+        self.pos += end + 2;
+        Ok(Cow::Borrowed(&rest[..end]))
+    }
+
+    // エスケープがなければ借用のまま返す
+    fn double_quoted(&mut self) -> Result<Cow<'a, str>, String> {
+        let rest = &self.s[self.pos + 1..];
+        let end = rest.find(['"', '\\']).ok_or("unterminated string")?;
+        if rest[end..].starts_with('"') {
+            self.pos += end + 2;
+            return Ok(Cow::Borrowed(&rest[..end]));
+        }
+        let mut out = rest[..end].to_string();
+        let mut chars = rest[end..].char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.pos += 1 + end + i + 1;
+                    return Ok(Cow::Owned(out));
+                },
+                '\\' => match chars.next().map(|(_, c)| c) {
+                    Some('"') => out.push('"'),
+                    Some('\\') => out.push('\\'),
+                    Some('n') => out.push('\n'),
+                    Some('t') => out.push('\t'),
+                    Some('r') => out.push('\r'),
+                    Some('u') => {
+                        let hex: String = chars.by_ref().take(4).map(|(_, c)| c).collect();
+                        let c = u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32)
+                            .ok_or_else(|| format!("invalid escape \\u{}", hex))?;
+                        out.push(c);
+                    },
+                    Some(c) => return Err(format!("invalid escape \\{}", c)),
+                    None => break,
+                },
+                c => out.push(c),
+            }
+        }
+        Err("unterminated string".to_string())
+    }
+}
+
+// parse_list で読み戻せる形で書き出す。区切り文字や前後の空白を含む要素は "..." で囲む
+pub(crate) fn write_list(items: &[ConfValue], f: &mut impl Write) -> fmt::Result {
+    f.write_char('[')?;
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            f.write_str(", ")?;
+        }
+        match item {
+            ConfValue::StrValue(v) if needs_quotes(v) => {
+                let mut s = String::new();
+                write_json_string(v, &mut s);
+                f.write_str(&s)?;
+            },
+            ConfValue::List(items) => write_list(items, f)?,
+            v => write!(f, "{}", v)?,
+        }
+    }
+    f.write_char(']')
+}
+
+fn needs_quotes(s: &str) -> bool {
+    s.is_empty() || s.trim() != s || s.contains([',', '[', ']', '"', '\'', '\\']) || s.chars().any(char::is_control)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strs(items: &[&str]) -> Vec<ListItem<'static>> {
+        items.iter().map(|s| ListItem::Str(Cow::Owned(s.to_string()))).collect()
+    }
+
+    #[test]
+    fn can_parse_list_literals() {
+        assert_eq!(parse_list("[a, b , c]", 32).unwrap().unwrap(), strs(&["a", "b", "c"]));
+        assert_eq!(parse_list("[]", 32).unwrap().unwrap(), strs(&[]));
+        assert_eq!(parse_list("[a, b,]", 32).unwrap().unwrap(), strs(&["a", "b"]));
+        assert_eq!(parse_list(r#"["a, b", ' c ', "q\"A"]"#, 32).unwrap().unwrap(), strs(&["a, b", " c ", "q\"A"]));
+        assert_eq!(
+            parse_list("[[1, 2], [3], x]", 32).unwrap().unwrap(),
+            vec![ListItem::List(strs(&["1", "2"])), ListItem::List(strs(&["3"])), ListItem::Str("x".into())],
+        );
+        // 角かっこで囲まれていなければただの文字列
+        assert_eq!(parse_list("a, b, c", 32).unwrap(), None);
+
+        assert_eq!(parse_list("[a, \"b]", 32).unwrap_err(), "Invalid list value: unterminated string");
+        assert_eq!(parse_list("[a b\"c\"]", 32).unwrap_err(), "Invalid list value: unexpected '\"' in an unquoted item");
+        assert_eq!(parse_list("[a], [b]", 32).unwrap_err(), "Invalid list value: unexpected characters after column 3");
+        assert_eq!(parse_list("[a, , b]", 32).unwrap_err(), "Invalid list value: empty item");
+        assert_eq!(parse_list("[[[a]]]", 2).unwrap_err(), "Invalid list value: nested too deeply (limit: 2)");
+    }
+
+    #[test]
+    fn can_parse_inline_tables() {
+        let entries = parse_table(r#"{ host = localhost, port = 5432, name = "a, b", pool = { max = [1, 2] }, }"#, 32).unwrap().unwrap();
+        assert_eq!(entries, vec![
+            ("host", TableValue::Raw("localhost")),
+            ("port", TableValue::Raw("5432")),
+            ("name", TableValue::Quoted("a, b".into())),
+            ("pool", TableValue::Raw("{ max = [1, 2] }")),
+        ]);
+        assert_eq!(parse_table("{}", 32).unwrap().unwrap(), vec![]);
+        assert_eq!(parse_table("{ a.b = '}' }", 32).unwrap().unwrap(), vec![("a.b", TableValue::Quoted("}".into()))]);
+        assert_eq!(parse_table("host = x", 32).unwrap(), None);
+
+        assert_eq!(parse_table("{ host }", 32).unwrap_err(), "Invalid inline table: missing '=' after host");
+        assert_eq!(parse_table("{ host = }", 32).unwrap_err(), "Invalid inline table: missing value for host");
+        assert_eq!(parse_table("{ a = [1, 2 }", 32).unwrap_err(), "Invalid inline table: unexpected '}'");
+        assert_eq!(parse_table("{ a = {{{}}} }", 2).unwrap_err(), "Invalid inline table: nested too deeply (limit: 2)");
+    }
+
+    #[test]
+    fn can_write_list_that_parses_back() {
+        let value = parse_list_value(r#"[plain, "a, b", " padded", [x, "[y]"], ""]"#, 32).unwrap().unwrap();
+        let text = value.to_string();
+        assert_eq!(text, r#"[plain, "a, b", " padded", [x, "[y]"], ""]"#);
+        assert_eq!(parse_list_value(&text, 32).unwrap().unwrap().to_string(), text);
+    }
+}
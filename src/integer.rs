@@ -0,0 +1,99 @@
+// 数値を整数として読む。as f64 -> as u16 のように黙って切り捨てず、小数や範囲外はエラーにする
+use std::error::Error;
+use std::fmt;
+
+#[cfg(feature = "decimal")]
+use rust_decimal::prelude::ToPrimitive;
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
+
+use crate::{ConfValue, ConfigValue};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IntegerCastError {
+    // 数値ではなかった
+    TypeMismatch,
+    Fractional(f64),
+    OutOfRange { value: f64, target: &'static str },
+}
+
+impl fmt::Display for IntegerCastError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegerCastError::TypeMismatch => write!(f, "Type mismatch error"),
+            IntegerCastError::Fractional(value) => write!(f, "Value {} is not an integer", value),
+            IntegerCastError::OutOfRange { value, target } => write!(f, "Value {} is out of range for {}", value, target),
+        }
+    }
+}
+
+impl Error for IntegerCastError {}
+
+macro_rules! impl_integer_accessors {
+    ($value:ty, $(($name:ident, $t:ty)),*) => {
+        impl $value {
+            $(
+                pub fn $name(&self) -> Result<$t, IntegerCastError> {
+                    // decimal は f64 を経由せずに変換する (2^53 を超える ID も正確に読める)
+                    #[cfg(feature = "decimal")]
+                    if let Ok(decimal) = self.as_decimal() {
+                        if !decimal.fract().is_zero() {
+                            return Err(IntegerCastError::Fractional(decimal_as_f64(&decimal)));
+                        }
+                        return <$t>::try_from(decimal)
+                            .map_err(|_| IntegerCastError::OutOfRange { value: decimal_as_f64(&decimal), target: stringify!($t) });
+                    }
+                    let number = self.as_number().map_err(|_| IntegerCastError::TypeMismatch)?;
+                    // MAX as f64 は 2^64 などに丸められるので、上限は MAX + 1 未満で比べる
+                    if !number.is_finite() || number < <$t>::MIN as f64 || number >= <$t>::MAX as f64 + 1.0 {
+                        return Err(IntegerCastError::OutOfRange { value: number, target: stringify!($t) });
+                    }
+                    if number.fract() != 0.0 {
+                        return Err(IntegerCastError::Fractional(number));
+                    }
+                    Ok(number as $t)
+                }
+            )*
+        }
+    };
+}
+
+// JSON や config / figment などに渡すとき、整数で表せる decimal は i64 にし、それ以外は f64 にする
+#[cfg(all(feature = "decimal", any(feature = "json", feature = "toml", feature = "config", feature = "figment")))]
+pub(crate) fn decimal_as_integer(v: &Decimal) -> Option<i64> {
+    v.fract().is_zero().then(|| v.to_i64()).flatten()
+}
+
+#[cfg(feature = "decimal")]
+pub(crate) fn decimal_as_f64(v: &Decimal) -> f64 {
+    v.to_f64().unwrap_or(f64::NAN)
+}
+
+impl_integer_accessors!(ConfValue, (as_i64, i64), (as_u64, u64), (as_u16, u16));
+impl_integer_accessors!(ConfigValue, (as_i64, i64), (as_u64, u64), (as_u16, u16));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_cast_numbers_without_truncating() {
+        assert_eq!(ConfValue::NumberValue(8080.0).as_u16().unwrap(), 8080);
+        assert_eq!(ConfValue::NumberValue(-3.0).as_i64().unwrap(), -3);
+        assert_eq!(ConfValue::NumberValue(9_007_199_254_740_992.0).as_u64().unwrap(), 9_007_199_254_740_992);
+        assert_eq!(ConfValue::NumberValue(1.5).as_u16().unwrap_err(), IntegerCastError::Fractional(1.5));
+        assert_eq!(ConfValue::NumberValue(65536.0).as_u16().unwrap_err().to_string(), "Value 65536 is out of range for u16");
+        assert!(ConfValue::NumberValue(-1.0).as_u64().is_err());
+        assert!(ConfValue::NumberValue(18_446_744_073_709_551_616.0).as_u64().is_err());
+        assert!(ConfValue::NumberValue(9_223_372_036_854_775_808.0).as_i64().is_err());
+        assert!(ConfValue::NumberValue(f64::NAN).as_i64().is_err());
+        assert_eq!(ConfValue::StrValue("80".to_string()).as_u16().unwrap_err(), IntegerCastError::TypeMismatch);
+        assert_eq!(ConfigValue::NumberValue(443.0).as_u16().unwrap(), 443);
+
+        // FromConfValue も同じ上限で比べる
+        use crate::typed::FromConfValue;
+        assert!(u64::from_conf_value(&ConfigValue::NumberValue(18_446_744_073_709_551_616.0)).is_err());
+        assert!(i64::from_conf_value(&ConfigValue::NumberValue(9_223_372_036_854_775_808.0)).is_err());
+        assert_eq!(u16::from_conf_value(&ConfigValue::NumberValue(65535.0)).unwrap(), 65535);
+    }
+}
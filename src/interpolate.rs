@@ -0,0 +1,377 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// ${name(args...)} から呼ばれる関数
+pub type InterpolationFn = Box<dyn Fn(&[&str]) -> Result<String, String> + Send + Sync>;
+
+// 値の中の ${...} を展開する
+//
+//   ${NAME}           環境変数 (ないときは on_missing に従う)
+//   ${NAME:-default}  環境変数がないか空なら default (省略すれば空文字列。${A:-${B}} のように default の中も展開する)
+//   ${NAME:?message}  環境変数がないか空ならエラー
+//   ${name(a, b)}     登録された関数
+//   ${db.host}        同じ conf の別のキー (parse などで読み込むときだけ。循環していればエラー)
+//   $${               ${ そのもの
+pub struct Interpolator {
+    functions: HashMap<String, InterpolationFn>,
+    on_missing: MissingVariable,
+}
+
+// ${NAME} の環境変数がないときの扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MissingVariable {
+    #[default]
+    Error,
+    // 空文字列に置き換える
+    Empty,
+    // ${NAME} のまま残す
+    Verbatim,
+}
+
+// 1 つの ${...} をどう展開したか。値の Origin に記録する (値そのものはシークレットを含みうるので持たない)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interpolation {
+    // ${ と } の中身
+    pub placeholder: String,
+    pub resolution: Resolution,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resolution {
+    Environment,
+    Function,
+    // ${db.host} で同じ conf の別のキーを参照した
+    Key,
+    // ${NAME:-default} の default を使った
+    Default,
+    // on_missing が Empty だった
+    Empty,
+    // on_missing が Verbatim だった
+    Verbatim,
+}
+
+impl Default for Interpolator {
+    fn default() -> Self {
+        let mut interpolator = Interpolator { functions: HashMap::new(), on_missing: MissingVariable::Error };
+        interpolator.register("hostname", Box::new(|_| hostname()));
+        interpolator.register("uuid", Box::new(|_| Ok(uuid_v4())));
+        interpolator.register("now", Box::new(|_| Ok(now_rfc3339())));
+        interpolator
+    }
+}
+
+impl Interpolator {
+    pub fn new() -> Self {
+        Interpolator::default()
+    }
+
+    // 同じ名前の関数は上書きする
+    pub fn register(&mut self, name: &str, function: InterpolationFn) {
+        self.functions.insert(name.to_string(), function);
+    }
+
+    pub fn on_missing(mut self, policy: MissingVariable) -> Self {
+        self.on_missing = policy;
+        self
+    }
+
+    pub fn expand(&self, value: &str) -> Result<String, String> {
+        self.expand_recorded(value).map(|(expanded, _)| expanded)
+    }
+
+    // 展開した値と、それぞれの ${...} をどう展開したか
+    pub fn expand_recorded(&self, value: &str) -> Result<(String, Vec<Interpolation>), String> {
+        self.expand_recorded_with(value, &|name| std::env::var(name).ok())
+    }
+
+    // 環境変数を var から読む (ParseOptions::env を使うため)
+    pub(crate) fn expand_recorded_with(&self, value: &str, var: &dyn Fn(&str) -> Option<String>) -> Result<(String, Vec<Interpolation>), String> {
+        let mut interpolations = Vec::new();
+        let expanded = replace_placeholders(value, true, |inner| {
+            let placeholder = inner.trim();
+            let (resolved, resolution) = self.resolve(placeholder, var)?;
+            interpolations.push(Interpolation { placeholder: placeholder.to_string(), resolution });
+            Ok(resolved)
+        })?;
+        Ok((expanded, interpolations))
+    }
+
+    // None はそのまま残す
+    fn resolve(&self, expr: &str, var: &dyn Fn(&str) -> Option<String>) -> Result<(Option<String>, Resolution), String> {
+        if let Some((name, _)) = key_reference(expr) {
+            return Err(format!("Key reference cannot be resolved here: {}", name));
+        }
+        if let Some(call) = expr.strip_suffix(')') {
+            let (name, args) = call.split_once('(').ok_or_else(|| format!("Invalid placeholder: {}", expr))?;
+            let function = self.functions.get(name.trim())
+                .ok_or_else(|| format!("Unknown interpolation function: {}", name.trim()))?;
+            let args: Vec<&str> = match args.trim() {
+                "" => Vec::new(),
+                args => args.split(',').map(str::trim).collect(),
+            };
+            return Ok((Some(function(&args)?), Resolution::Function));
+        }
+        let (name, modifier) = match expr.split_once(':') {
+            Some((name, modifier)) => (name.trim(), Some(modifier)),
+            None => (expr, None),
+        };
+        match (var(name), modifier) {
+            // シェルと同じく、:- と :? は空の値もないものとして扱う
+            (Some(v), Some(_)) if v.is_empty() => self.missing(name, modifier, var),
+            (Some(v), _) => Ok((Some(v), Resolution::Environment)),
+            (None, _) => self.missing(name, modifier, var),
+        }
+    }
+
+    fn missing(&self, name: &str, modifier: Option<&str>, var: &dyn Fn(&str) -> Option<String>) -> Result<(Option<String>, Resolution), String> {
+        let not_found = || format!("Environment variable not found: {}", name);
+        match modifier {
+            Some(modifier) => match (modifier.strip_prefix('-'), modifier.strip_prefix('?')) {
+                (Some(default), _) => {
+                    let (default, _) = self.expand_recorded_with(default.trim(), var)?;
+                    Ok((Some(default), Resolution::Default))
+                },
+                (_, Some(message)) if message.trim().is_empty() => Err(not_found()),
+                (_, Some(message)) => Err(format!("{}: {}", name, message.trim())),
+                _ => Err(format!("Invalid placeholder: {}:{}", name, modifier)),
+            },
+            None => match self.on_missing {
+                MissingVariable::Error => Err(not_found()),
+                MissingVariable::Empty => Ok((Some(String::new()), Resolution::Empty)),
+                MissingVariable::Verbatim => Ok((None, Resolution::Verbatim)),
+            },
+        }
+    }
+}
+
+// ${...} の中身 (前後の空白も含む) を順に f に渡し、返した文字列で置き換える。None ならそのまま残す
+// unescape が false なら $${ も $${ のまま残す (後で expand するとき用)
+fn replace_placeholders(value: &str, unescape: bool, mut f: impl FnMut(&str) -> Result<Option<String>, String>) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        let after = &rest[start..];
+        if let Some(escaped) = after.strip_prefix("$${") {
+            result.push_str(if unescape { "${" } else { "$${" });
+            rest = escaped;
+            continue;
+        }
+        let inner = match after.strip_prefix("${") {
+            Some(inner) => inner,
+            None => {
+                result.push('$');
+                rest = &after[1..];
+                continue;
+            },
+        };
+        let end = closing_brace(inner).ok_or_else(|| format!("Unterminated placeholder in: {}", value))?;
+        match f(&inner[..end])? {
+            Some(resolved) => result.push_str(&resolved),
+            None => result.push_str(&format!("${{{}}}", &inner[..end])),
+        }
+        rest = &inner[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+// ${A:-${B}} のように入れ子になった ${...} を飛ばして、対応する } の位置を返す
+fn closing_brace(inner: &str) -> Option<usize> {
+    let bytes = inner.as_bytes();
+    let mut depth = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'$' if bytes.get(i + 1) == Some(&b'{') => {
+                depth += 1;
+                i += 1;
+            },
+            b'}' if depth == 0 => return Some(i),
+            b'}' => depth -= 1,
+            _ => {},
+        }
+        i += 1;
+    }
+    None
+}
+
+// ${db.host} のようにドットを含む名前は、同じ conf の別のキーを指す (環境変数の名前にはドットを使えない)
+// ${db.host:-default} も書ける。キーと : 以降を返す
+fn key_reference(expr: &str) -> Option<(&str, Option<&str>)> {
+    let (name, modifier) = match expr.split_once(':') {
+        Some((name, modifier)) => (name.trim(), Some(modifier)),
+        None => (expr.trim(), None),
+    };
+    let is_key = name.contains('.') && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    is_key.then_some((name, modifier))
+}
+
+// 値の中で参照しているキー
+pub(crate) fn key_references(value: &str) -> Vec<String> {
+    let mut keys = Vec::new();
+    // 置き換えないので、f が返すのは常に None
+    let _ = replace_placeholders(value, false, |inner| {
+        if let Some((name, _)) = key_reference(inner) {
+            keys.push(name.to_string());
+        }
+        Ok(None)
+    });
+    keys
+}
+
+// キーの参照を lookup で置き換える。lookup が None ならキーがない (:- があればその値を使う)
+// 環境変数や関数は残すので、この後で expand する
+pub(crate) fn replace_key_references(value: &str, mut lookup: impl FnMut(&str) -> Result<Option<String>, String>) -> Result<(String, Vec<Interpolation>), String> {
+    let mut interpolations = Vec::new();
+    let replaced = replace_placeholders(value, false, |inner| {
+        let Some((name, modifier)) = key_reference(inner) else {
+            return Ok(None);
+        };
+        let (resolved, resolution) = match (lookup(name)?, modifier.map(str::trim)) {
+            (Some(v), Some(modifier)) if v.is_empty() && modifier.starts_with('-') => (modifier[1..].trim().to_string(), Resolution::Default),
+            (Some(v), _) => (v, Resolution::Key),
+            (None, Some(modifier)) if modifier.starts_with('-') => (modifier[1..].trim().to_string(), Resolution::Default),
+            (None, Some(modifier)) if modifier.len() > 1 && modifier.starts_with('?') => return Err(format!("{}: {}", name, modifier[1..].trim())),
+            (None, _) => return Err(format!("Referenced key not found: {}", name)),
+        };
+        interpolations.push(Interpolation { placeholder: inner.trim().to_string(), resolution });
+        Ok(Some(resolved))
+    })?;
+    Ok((replaced, interpolations))
+}
+
+pub(crate) fn hostname() -> Result<String, String> {
+    for name in ["HOSTNAME", "COMPUTERNAME"] {
+        if let Ok(host) = std::env::var(name) {
+            return Ok(host);
+        }
+    }
+    #[cfg(feature = "std-fs")]
+    for path in ["/proc/sys/kernel/hostname", "/etc/hostname"] {
+        if let Ok(host) = std::fs::read_to_string(path) {
+            return Ok(host.trim().to_string());
+        }
+    }
+    Err("Failed to determine hostname".to_string())
+}
+
+// RandomState は呼び出しごとに異なるキーを使うので乱数源として使える
+// 暗号論的に安全な乱数ではない。${uuid()} はインスタンスの識別などに使い、トークンや鍵には使わない
+fn random_u64() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    hasher.write_u128(nanos);
+    hasher.finish()
+}
+
+// 乱数は random_u64 なので、推測されては困る値には使えない
+fn uuid_v4() -> String {
+    let mut bytes = [0u8; 16];
+    bytes[..8].copy_from_slice(&random_u64().to_le_bytes());
+    bytes[8..].copy_from_slice(&random_u64().to_le_bytes());
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+// UTC の RFC 3339 形式
+fn now_rfc3339() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let (days, rem) = (secs / 86400, secs % 86400);
+    let (year, month, day) = civil_from_days(days as i64);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+// 1970-01-01 からの日数を年月日に変換する (Howard Hinnant のアルゴリズム)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // プロセスの環境変数を書き換えずに、決まった値だけを返す
+    fn expand(interpolator: &Interpolator, value: &str) -> Result<String, String> {
+        let env = |name: &str| match name {
+            "REGION" => Some("ap-northeast-1".to_string()),
+            "EMPTY" => Some(String::new()),
+            _ => None,
+        };
+        interpolator.expand_recorded_with(value, &env).map(|(expanded, _)| expanded)
+    }
+
+    #[test]
+    fn can_expand_functions_and_env() {
+        let mut interpolator = Interpolator::new();
+        interpolator.register("join", Box::new(|args| Ok(args.join("-"))));
+        assert_eq!(expand(&interpolator, "${join(a, b)}.${REGION}").unwrap(), "a-b.ap-northeast-1");
+        assert_eq!(interpolator.expand("cost: $5, $${literal}").unwrap(), "cost: $5, ${literal}");
+        assert!(interpolator.expand("${missing()}").is_err());
+        assert!(interpolator.expand("${unterminated").is_err());
+
+        let uuid = interpolator.expand("${uuid()}").unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert_ne!(uuid, interpolator.expand("${uuid()}").unwrap());
+        assert_eq!(civil_from_days(20742), (2026, 10, 16));
+    }
+
+    #[test]
+    fn can_choose_what_missing_variables_become() {
+        let interpolator = Interpolator::new();
+        assert_eq!(expand(&interpolator, "${UNSET:-localhost}:${EMPTY:-}").unwrap(), "localhost:");
+        assert_eq!(expand(&interpolator, "${UNSET}").unwrap_err(), "Environment variable not found: UNSET");
+        assert_eq!(expand(&interpolator, "${EMPTY:?must be set}").unwrap_err(), "EMPTY: must be set");
+        assert!(expand(&interpolator, "${UNSET:x}").is_err());
+
+        let verbatim = Interpolator::new().on_missing(MissingVariable::Verbatim);
+        let (expanded, interpolations) = verbatim.expand_recorded_with("${ UNSET }/${EMPTY}", &|name| (name == "EMPTY").then(String::new)).unwrap();
+        assert_eq!(expanded, "${ UNSET }/");
+        assert_eq!(interpolations.iter().map(|i| i.resolution).collect::<Vec<_>>(), [Resolution::Verbatim, Resolution::Environment]);
+        // 書き方で指定したものは on_missing より優先する
+        assert!(expand(&verbatim, "${UNSET:?}").is_err());
+        assert_eq!(expand(&Interpolator::new().on_missing(MissingVariable::Empty), "[${UNSET}]").unwrap(), "[]");
+    }
+
+    #[test]
+    fn can_expand_nested_defaults() {
+        let interpolator = Interpolator::new();
+        assert_eq!(expand(&interpolator, "${UNSET:-${REGION}}").unwrap(), "ap-northeast-1");
+        assert_eq!(expand(&interpolator, "${UNSET:-${EMPTY:-${UNSET:-x}}}/${REGION:-${UNSET}}").unwrap(), "x/ap-northeast-1");
+        assert_eq!(expand(&interpolator, "${UNSET:-${UNSET}}").unwrap_err(), "Environment variable not found: UNSET");
+        assert!(expand(&interpolator, "${UNSET:-${REGION}").is_err());
+        assert_eq!(expand(&interpolator, "{${REGION}}").unwrap(), "{ap-northeast-1}");
+    }
+
+    #[cfg(feature = "std-fs")]
+    #[test]
+    fn can_interpolate_while_parsing() {
+        use crate::{parse_with_options, ParseOptions};
+
+        let options = ParseOptions {
+            interpolation: Some(Interpolator::new()),
+            env: Some(HashMap::from([("CONF_LOADER_TEST_PORT".to_string(), "8080".to_string())])),
+            ..Default::default()
+        };
+        let mut conf = parse_with_options("tests/interpolation.conf", Some("tests/interpolation.schema"), &options).unwrap();
+        assert_eq!(conf.get("port").unwrap().as_number().unwrap(), 8080.0);
+        assert!(conf.get("instance").unwrap().as_str().unwrap().starts_with("worker-"));
+        let origin = conf.origin_of("instance").unwrap();
+        assert_eq!(origin.interpolations, [Interpolation { placeholder: "uuid()".to_string(), resolution: Resolution::Function }]);
+        assert!(conf.origin_of("started").unwrap().interpolations[0].resolution == Resolution::Function);
+    }
+}
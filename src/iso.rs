@@ -0,0 +1,97 @@
+// スキーマの型 country (ISO 3166-1 alpha-2) と currency (ISO 4217)。通販などで地域ごとの既定値を設定するときに使う
+// 小文字でも受け付け、値は大文字にそろえる
+// 表はどちらも名前順に並べておく (binary_search で探す)。廃止されたコードは入れない
+const COUNTRIES: &[&str] = &[
+    "AD", "AE", "AF", "AG", "AI", "AL", "AM", "AO", "AQ", "AR", "AS", "AT", "AU", "AW", "AX", "AZ",
+    "BA", "BB", "BD", "BE", "BF", "BG", "BH", "BI", "BJ", "BL", "BM", "BN", "BO", "BQ", "BR", "BS", "BT", "BV", "BW", "BY", "BZ",
+    "CA", "CC", "CD", "CF", "CG", "CH", "CI", "CK", "CL", "CM", "CN", "CO", "CR", "CU", "CV", "CW", "CX", "CY", "CZ",
+    "DE", "DJ", "DK", "DM", "DO", "DZ",
+    "EC", "EE", "EG", "EH", "ER", "ES", "ET",
+    "FI", "FJ", "FK", "FM", "FO", "FR",
+    "GA", "GB", "GD", "GE", "GF", "GG", "GH", "GI", "GL", "GM", "GN", "GP", "GQ", "GR", "GS", "GT", "GU", "GW", "GY",
+    "HK", "HM", "HN", "HR", "HT", "HU",
+    "ID", "IE", "IL", "IM", "IN", "IO", "IQ", "IR", "IS", "IT",
+    "JE", "JM", "JO", "JP",
+    "KE", "KG", "KH", "KI", "KM", "KN", "KP", "KR", "KW", "KY", "KZ",
+    "LA", "LB", "LC", "LI", "LK", "LR", "LS", "LT", "LU", "LV", "LY",
+    "MA", "MC", "MD", "ME", "MF", "MG", "MH", "MK", "ML", "MM", "MN", "MO", "MP", "MQ", "MR", "MS", "MT", "MU", "MV", "MW", "MX", "MY", "MZ",
+    "NA", "NC", "NE", "NF", "NG", "NI", "NL", "NO", "NP", "NR", "NU", "NZ",
+    "OM",
+    "PA", "PE", "PF", "PG", "PH", "PK", "PL", "PM", "PN", "PR", "PS", "PT", "PW", "PY",
+    "QA",
+    "RE", "RO", "RS", "RU", "RW",
+    "SA", "SB", "SC", "SD", "SE", "SG", "SH", "SI", "SJ", "SK", "SL", "SM", "SN", "SO", "SR", "SS", "ST", "SV", "SX", "SY", "SZ",
+    "TC", "TD", "TF", "TG", "TH", "TJ", "TK", "TL", "TM", "TN", "TO", "TR", "TT", "TV", "TW", "TZ",
+    "UA", "UG", "UM", "US", "UY", "UZ",
+    "VA", "VC", "VE", "VG", "VI", "VN", "VU",
+    "WF", "WS",
+    "YE", "YT",
+    "ZA", "ZM", "ZW",
+];
+
+// 基金のコード (BOV など) と金属 (XAU など) も含む。XTS (テスト用) と XXX (通貨なし) は入れない
+const CURRENCIES: &[&str] = &[
+    "AED", "AFN", "ALL", "AMD", "AOA", "ARS", "AUD", "AWG", "AZN",
+    "BAM", "BBD", "BDT", "BGN", "BHD", "BIF", "BMD", "BND", "BOB", "BOV", "BRL", "BSD", "BTN", "BWP", "BYN", "BZD",
+    "CAD", "CDF", "CHE", "CHF", "CHW", "CLF", "CLP", "CNY", "COP", "COU", "CRC", "CUP", "CVE", "CZK",
+    "DJF", "DKK", "DOP", "DZD",
+    "EGP", "ERN", "ETB", "EUR",
+    "FJD", "FKP",
+    "GBP", "GEL", "GHS", "GIP", "GMD", "GNF", "GTQ", "GYD",
+    "HKD", "HNL", "HTG", "HUF",
+    "IDR", "ILS", "INR", "IQD", "IRR", "ISK",
+    "JMD", "JOD", "JPY",
+    "KES", "KGS", "KHR", "KMF", "KPW", "KRW", "KWD", "KYD", "KZT",
+    "LAK", "LBP", "LKR", "LRD", "LSL", "LYD",
+    "MAD", "MDL", "MGA", "MKD", "MMK", "MNT", "MOP", "MRU", "MUR", "MVR", "MWK", "MXN", "MXV", "MYR", "MZN",
+    "NAD", "NGN", "NIO", "NOK", "NPR", "NZD",
+    "OMR",
+    "PAB", "PEN", "PGK", "PHP", "PKR", "PLN", "PYG",
+    "QAR",
+    "RON", "RSD", "RUB", "RWF",
+    "SAR", "SBD", "SCR", "SDG", "SEK", "SGD", "SHP", "SLE", "SOS", "SRD", "SSP", "STN", "SVC", "SYP", "SZL",
+    "THB", "TJS", "TMT", "TND", "TOP", "TRY", "TTD", "TWD", "TZS",
+    "UAH", "UGX", "USD", "USN", "UYI", "UYU", "UYW", "UZS",
+    "VED", "VES", "VND", "VUV",
+    "WST",
+    "XAF", "XAG", "XAU", "XBA", "XBB", "XBC", "XBD", "XCD", "XCG", "XDR", "XOF", "XPD", "XPF", "XPT", "XSU", "XUA",
+    "YER",
+    "ZAR", "ZMW", "ZWG",
+];
+
+pub(crate) fn country(s: &str) -> Result<String, String> {
+    lookup(COUNTRIES, s).ok_or_else(|| format!("Unknown country code: {}", s))
+}
+
+pub(crate) fn currency(s: &str) -> Result<String, String> {
+    lookup(CURRENCIES, s).ok_or_else(|| format!("Unknown currency code: {}", s))
+}
+
+fn lookup(table: &[&str], s: &str) -> Option<String> {
+    let code = s.to_ascii_uppercase();
+    table.binary_search(&code.as_str()).ok().map(|_| code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_check_country_and_currency_codes() {
+        assert!(COUNTRIES.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(CURRENCIES.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(COUNTRIES.len(), 249);
+
+        assert_eq!(country("jp").unwrap(), "JP");
+        assert_eq!(country("UK").unwrap_err(), "Unknown country code: UK");
+        assert_eq!(currency("eur").unwrap(), "EUR");
+        assert_eq!(currency("HRK").unwrap_err(), "Unknown currency code: HRK");
+
+        let schema = "shop.country -> country\nshop.currency -> currency\n";
+        let conf = crate::parse_str("shop.country = de\nshop.currency = eur\n", Some(schema)).unwrap();
+        assert_eq!(conf.get_str("shop.country").unwrap(), "DE");
+        assert_eq!(conf.get_str("shop.currency").unwrap(), "EUR");
+        let err = crate::parse_str("shop.currency = EURO\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Unknown currency code: EURO");
+    }
+}
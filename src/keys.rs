@@ -0,0 +1,72 @@
+use std::borrow::Cow;
+
+// キーの大文字・小文字の扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum KeyCase {
+    #[default]
+    Preserve,
+    Lower,
+    Upper,
+}
+
+// conf とスキーマの両方のキーに同じように適用する正規化
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeyPolicy {
+    // ドットで区切った各部分の前後の空白を取り除く ("log . file" -> "log.file")
+    pub trim: bool,
+    pub case: KeyCase,
+    // 制御文字を含むキーをエラーにする
+    pub reject_control: bool,
+    // ASCII 以外の文字を含むキーをエラーにする
+    pub ascii_only: bool,
+}
+
+impl KeyPolicy {
+    // 変更がなければ借用したまま返す
+    pub fn normalize<'a>(&self, key: &'a str) -> Result<Cow<'a, str>, String> {
+        if self.reject_control && key.chars().any(char::is_control) {
+            return Err(format!("Key contains a control character: {:?}", key));
+        }
+        if self.ascii_only && !key.is_ascii() {
+            return Err(format!("Key contains a non-ASCII character: {}", key));
+        }
+        let mut key = Cow::Borrowed(key);
+        if self.trim && key.split('.').any(|segment| segment.trim() != segment) {
+            key = Cow::Owned(key.split('.').map(str::trim).collect::<Vec<_>>().join("."));
+        }
+        match self.case {
+            KeyCase::Preserve => {},
+            KeyCase::Lower if key.chars().any(char::is_uppercase) => key = Cow::Owned(key.to_lowercase()),
+            KeyCase::Upper if key.chars().any(char::is_lowercase) => key = Cow::Owned(key.to_uppercase()),
+            _ => {},
+        }
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str_with_options, ParseOptions};
+
+    #[test]
+    fn can_normalize_keys() {
+        let policy = KeyPolicy { trim: true, case: KeyCase::Lower, reject_control: true, ascii_only: true };
+        assert_eq!(policy.normalize("Log . File").unwrap(), "log.file");
+        assert!(matches!(policy.normalize("log.file").unwrap(), Cow::Borrowed(_)));
+        assert!(policy.normalize("ログ.file").is_err());
+        assert!(policy.normalize("log\u{7}.file").is_err());
+        assert_eq!(KeyPolicy::default().normalize("Log . File").unwrap(), "Log . File");
+    }
+
+    #[test]
+    fn can_apply_policy_to_conf_and_schema() {
+        let options = ParseOptions {
+            keys: KeyPolicy { case: KeyCase::Lower, ..Default::default() },
+            ..Default::default()
+        };
+        let conf = parse_str_with_options("Log.Level = 3\nlog.level = 4\n", Some("LOG.LEVEL -> number\n"), &options).unwrap();
+        assert_eq!(conf.to_flat_map(false)["log.level"], "4");
+        assert!(conf.value_at("log.level", |v| v.as_number().is_ok()).unwrap());
+    }
+}
@@ -0,0 +1,421 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::BufRead;
+use std::time::Duration;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+use crate::cache::{cached_source, RemoteCache};
+use crate::retry::RetryPolicy;
+use crate::{add_entry, parse_schema, ConfList, Origin, ParseContext, ParseOptions, Schema};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KvBackend {
+    Consul,
+    Etcd,
+}
+
+// キーと値の一覧と、その時点の index
+type KvPairs = (Vec<(String, String)>, u64);
+
+// KV ストアのキープレフィックス以下を conf として読み込むソース
+pub struct KvSource {
+    pub backend: KvBackend,
+    pub endpoint: String,
+    pub prefix: String,
+    pub schema_path: Option<String>,
+    // 1 回のリクエスト全体の上限 (watch() では wait を足す)
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    pub retry: RetryPolicy,
+    // watch() で変更を待つ最大時間
+    pub wait: Duration,
+    // 検証に成功したキーと値を保存する。load() で再試行しても取得できないときと ttl の間は代わりに読む
+    pub cache: Option<RemoteCache>,
+    // Consul の ModifyIndex / etcd の revision
+    index: u64,
+    stale: bool,
+}
+
+impl KvSource {
+    pub fn consul(endpoint: &str, prefix: &str) -> Self {
+        KvSource::new(KvBackend::Consul, endpoint, prefix)
+    }
+
+    pub fn etcd(endpoint: &str, prefix: &str) -> Self {
+        KvSource::new(KvBackend::Etcd, endpoint, prefix)
+    }
+
+    fn new(backend: KvBackend, endpoint: &str, prefix: &str) -> Self {
+        KvSource {
+            backend,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            prefix: prefix.to_string(),
+            schema_path: None,
+            timeout: Duration::from_secs(10),
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(10),
+            retry: RetryPolicy::default(),
+            wait: Duration::from_secs(60),
+            cache: None,
+            index: 0,
+            stale: false,
+        }
+    }
+
+    // 最後の load がキャッシュから読んだものなら true
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    pub fn load(&mut self, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+        if self.cache.as_ref().is_some_and(RemoteCache::is_fresh) {
+            if let Ok(list) = self.load_cache(options) {
+                return Ok(list);
+            }
+        }
+        let fetched = self.retry.run(|| match self.backend {
+            KvBackend::Consul => self.consul_range(None),
+            KvBackend::Etcd => self.etcd_range(),
+        });
+        match fetched {
+            Ok((pairs, index)) => self.accept(pairs, index, options),
+            Err(e) => self.fallback(e, options),
+        }
+    }
+
+    // プレフィックス以下が変更されるまで待ってから読み直す
+    pub fn watch(&mut self, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+        if self.index == 0 {
+            self.load(options)?;
+        }
+        match self.backend {
+            KvBackend::Consul => loop {
+                // blocking query は wait が切れると同じ index のまま返ってくる
+                let (pairs, index) = self.retry.run(|| self.consul_range(Some(self.index)))?;
+                // index が戻ったときは (スナップショットの復元など) 0 に戻し、最初から読み直す
+                if index < self.index {
+                    self.index = 0;
+                    return self.load(options);
+                }
+                if index != self.index {
+                    return self.accept(pairs, index, options);
+                }
+            },
+            KvBackend::Etcd => {
+                self.retry.run(|| self.etcd_wait())?;
+                self.load(options)
+            }
+        }
+    }
+
+    fn accept(&mut self, pairs: Vec<(String, String)>, index: u64, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+        let list = self.build(&pairs, options, false)?;
+        self.index = index;
+        self.stale = false;
+        // キャッシュが書けなくても読み込み自体は成功させる
+        if let Some(cache) = &self.cache {
+            if let Err(_e) = cache.write(&serde_json::to_string(&pairs)?) {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %_e, "failed to write the kv config cache");
+            }
+        }
+        Ok(list)
+    }
+
+    fn load_cache(&mut self, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+        let cache = self.cache.as_ref().ok_or("No cache is configured")?;
+        let pairs: Vec<(String, String)> = serde_json::from_str(&cache.read()?)?;
+        let list = self.build(&pairs, options, true)?;
+        self.stale = true;
+        Ok(list)
+    }
+
+    // キャッシュも読めなければ取得したときのエラーを返す
+    fn fallback(&mut self, error: Box<dyn Error>, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+        if self.cache.is_none() {
+            return Err(error);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::warn!(error = %error, "kv store unavailable, using the cached config");
+        self.load_cache(options).map_err(|_| error)
+    }
+
+    // キーのパスからネストしたツリーを組み立てて検証する
+    fn build(&self, pairs: &[(String, String)], options: &ParseOptions, cached: bool) -> Result<ConfList, Box<dyn Error>> {
+        let schema: Schema = match &self.schema_path {
+            Some(path) => parse_schema(path, options)?,
+            None => HashMap::new(),
+        };
+        let mut ctx = ParseContext::new(&schema, options);
+        let mut map = ConfList::new();
+        for (key, value) in pairs {
+            let path = match key.strip_prefix(&self.prefix) {
+                Some(path) => path.trim_matches('/').replace('/', "."),
+                None => continue,
+            };
+            // フォルダ用のキーは読み飛ばす
+            if path.is_empty() || key.ends_with('/') {
+                continue;
+            }
+            let source = format!("{}/{}", self.endpoint, key);
+            let source = if cached { cached_source(&source) } else { source };
+            let origin = Origin::new(source, None);
+            ctx.count_key().map_err(|e| format!("{}: {}", origin, e))?;
+            add_entry(&mut map, &path, value.trim(), &mut ctx, origin.clone())
+                .map_err(|e| format!("{}: {}", origin, e))?;
+        }
+        ctx.finish(map, &self.endpoint)
+    }
+
+    fn agent(&self, wait: Duration) -> ureq::Agent {
+        ureq::AgentBuilder::new()
+            .timeout_connect(self.connect_timeout)
+            .timeout_read(self.read_timeout + wait)
+            .timeout(self.timeout + wait)
+            .build()
+    }
+
+    fn consul_range(&self, index: Option<u64>) -> Result<KvPairs, Box<dyn Error>> {
+        let url = format!("{}/v1/kv/{}", self.endpoint, self.prefix);
+        let wait = if index.is_some() { self.wait } else { Duration::ZERO };
+        let mut request = self.agent(wait).get(&url).query("recurse", "true");
+        if let Some(index) = index {
+            request = request
+                .query("index", &index.to_string())
+                .query("wait", &consul_wait(self.wait));
+        }
+        let response = match request.call() {
+            Ok(response) => response,
+            // プレフィックス以下にキーがひとつもない
+            Err(ureq::Error::Status(404, response)) => {
+                let index = consul_index(response.header("X-Consul-Index"))?;
+                return Ok((Vec::new(), index));
+            },
+            Err(e) => return Err(e.into()),
+        };
+        let index = consul_index(response.header("X-Consul-Index"))?;
+        let body: Value = serde_json::from_str(&response.into_string()?)?;
+        let mut pairs = Vec::new();
+        for item in body.as_array().ok_or("Unexpected Consul response")? {
+            let key = item["Key"].as_str().ok_or("Unexpected Consul response")?;
+            let value = match item["Value"].as_str() {
+                Some(encoded) => String::from_utf8(STANDARD.decode(encoded)?)?,
+                None => continue,
+            };
+            pairs.push((key.to_string(), value));
+        }
+        Ok((pairs, index))
+    }
+
+    fn etcd_range(&self) -> Result<KvPairs, Box<dyn Error>> {
+        let url = format!("{}/v3/kv/range", self.endpoint);
+        let request = json!({
+            "key": STANDARD.encode(&self.prefix),
+            "range_end": STANDARD.encode(prefix_range_end(&self.prefix)),
+        });
+        let response = self.agent(Duration::ZERO).post(&url).send_string(&request.to_string())?;
+        let body: Value = serde_json::from_str(&response.into_string()?)?;
+        let revision = etcd_number(&body["header"]["revision"]);
+        let mut pairs = Vec::new();
+        if let Some(kvs) = body["kvs"].as_array() {
+            for item in kvs {
+                let key = String::from_utf8(STANDARD.decode(item["key"].as_str().unwrap_or_default())?)?;
+                let value = String::from_utf8(STANDARD.decode(item["value"].as_str().unwrap_or_default())?)?;
+                pairs.push((key, value));
+            }
+        }
+        Ok((pairs, revision))
+    }
+
+    // watch ストリームでイベントが届くまで待つ
+    fn etcd_wait(&self) -> Result<(), Box<dyn Error>> {
+        let url = format!("{}/v3/watch", self.endpoint);
+        let request = json!({
+            "create_request": {
+                "key": STANDARD.encode(&self.prefix),
+                "range_end": STANDARD.encode(prefix_range_end(&self.prefix)),
+                "start_revision": (self.index + 1).to_string(),
+            }
+        });
+        let response = self.agent(self.wait).post(&url).send_string(&request.to_string())?;
+        let reader = std::io::BufReader::new(response.into_reader());
+        for line in reader.lines() {
+            let message: Value = serde_json::from_str(&line?)?;
+            let events = &message["result"]["events"];
+            if events.as_array().is_some_and(|events| !events.is_empty()) {
+                return Ok(());
+            }
+        }
+        Err("etcd watch stream closed".into())
+    }
+}
+
+// index がなければ blocking query で待てず、同じ問い合わせを繰り返すことになるのでエラーにする
+// 0 のまま待つとすぐに返ってくるので、1 以上にする
+fn consul_index(header: Option<&str>) -> Result<u64, Box<dyn Error>> {
+    match header.and_then(|v| v.parse::<u64>().ok()) {
+        Some(index) => Ok(index.max(1)),
+        None => Err("Consul response has no valid X-Consul-Index header".into()),
+    }
+}
+
+// Consul の wait は Go の duration。秒に切り捨てると 1 秒未満が 0s になってすぐ返ってくるので、ミリ秒で 1 以上にする
+fn consul_wait(wait: Duration) -> String {
+    format!("{}ms", wait.as_millis().max(1))
+}
+
+// etcd は int64 を文字列で返す
+fn etcd_number(value: &Value) -> u64 {
+    match value {
+        Value::String(s) => s.parse().unwrap_or(0),
+        _ => value.as_u64().unwrap_or(0),
+    }
+}
+
+// プレフィックス検索用に最後のバイトを 1 つ進める
+fn prefix_range_end(prefix: &str) -> Vec<u8> {
+    let mut end = prefix.as_bytes().to_vec();
+    while let Some(last) = end.pop() {
+        if last < 0xff {
+            end.push(last + 1);
+            return end;
+        }
+    }
+    // 空のプレフィックスは全キーを対象にする
+    vec![0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // 1 リクエストごとに決まったレスポンスを返すだけのサーバ
+    fn serve(responses: Vec<(&'static str, String)>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for (headers, body) in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(v) = line.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = v.trim().parse().unwrap();
+                    }
+                    head.push_str(&line);
+                }
+                let mut body_in = vec![0; length];
+                reader.read_exact(&mut body_in).unwrap();
+                head.push_str(&String::from_utf8(body_in).unwrap());
+                let response = format!("HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\n\r\n{}", headers, body.len(), body);
+                stream.write_all(response.as_bytes()).unwrap();
+                requests.push(head);
+            }
+            requests
+        });
+        (endpoint, handle)
+    }
+
+    #[test]
+    fn can_load_tree_from_consul_prefix() {
+        let body = json!([
+            {"Key": "app/", "Value": null},
+            {"Key": "app/endpoint", "Value": STANDARD.encode("localhost:3000")},
+            {"Key": "app/debug", "Value": STANDARD.encode("true")},
+            {"Key": "app/log/file", "Value": STANDARD.encode("/var/log/console.log")},
+        ]).to_string();
+        let changed = json!([{"Key": "app/debug", "Value": STANDARD.encode("false")}]).to_string();
+        let (endpoint, server) = serve(vec![
+            ("X-Consul-Index: 7\r\n", body),
+            ("X-Consul-Index: 8\r\n", changed),
+        ]);
+        let mut source = KvSource::consul(&endpoint, "app/");
+        source.schema_path = Some("tests/data.schema".to_string());
+        let mut conf = source.load(&ParseOptions::default()).unwrap();
+        assert_eq!(conf.get("endpoint").unwrap().as_str().unwrap(), "localhost:3000");
+        assert!(conf.get("debug").unwrap().as_bool().unwrap());
+        assert!(conf.get("log").unwrap().as_conf().unwrap().contains_key("file"));
+        assert_eq!(conf.origin_of("log.file").unwrap().to_string(), format!("{}/app/log/file", endpoint));
+        let mut conf = source.watch(&ParseOptions::default()).unwrap();
+        assert!(!conf.get("debug").unwrap().as_bool().unwrap());
+        let requests = server.join().unwrap();
+        assert!(requests[1].contains("index=7"));
+    }
+
+    #[test]
+    fn can_restart_consul_watch_when_the_index_goes_back() {
+        let body = |value: &str| json!([{"Key": "app/debug", "Value": STANDARD.encode(value)}]).to_string();
+        let (endpoint, server) = serve(vec![
+            ("X-Consul-Index: 20\r\n", body("true")),
+            ("X-Consul-Index: 3\r\n", body("false")),
+            ("X-Consul-Index: 4\r\n", body("false")),
+        ]);
+        let mut source = KvSource::consul(&endpoint, "app/");
+        source.wait = Duration::from_millis(500);
+        source.load(&ParseOptions::default()).unwrap();
+        let conf = source.watch(&ParseOptions::default()).unwrap();
+        assert_eq!(conf.get_str("debug").unwrap(), "false");
+        let requests = server.join().unwrap();
+        assert!(requests[1].contains("index=20"));
+        assert!(requests[1].contains("wait=500ms"));
+        // 読み直しは index を付けない
+        assert!(!requests[2].contains("index="));
+        assert_eq!(source.index, 4);
+
+        let (endpoint, server) = serve(vec![("", body("true"))]);
+        let mut source = KvSource::consul(&endpoint, "app/");
+        source.retry = RetryPolicy { max_attempts: 1, ..Default::default() };
+        let err = source.load(&ParseOptions::default()).unwrap_err();
+        assert_eq!(err.to_string(), "Consul response has no valid X-Consul-Index header");
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn can_load_tree_from_etcd_prefix() {
+        let body = json!({
+            "header": {"revision": "12"},
+            "kvs": [
+                {"key": STANDARD.encode("app/endpoint"), "value": STANDARD.encode("localhost:3000")},
+                {"key": STANDARD.encode("app/log/file"), "value": STANDARD.encode("/var/log/console.log")},
+            ],
+        }).to_string();
+        let (endpoint, server) = serve(vec![("", body)]);
+        let mut conf = KvSource::etcd(&endpoint, "app/").load(&ParseOptions::default()).unwrap();
+        assert_eq!(conf.get("endpoint").unwrap().as_str().unwrap(), "localhost:3000");
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains(&STANDARD.encode("app0")));
+    }
+
+    #[test]
+    fn can_fall_back_to_the_cached_pairs() {
+        let body = json!([{"Key": "app/endpoint", "Value": STANDARD.encode("localhost:3000")}]).to_string();
+        let (endpoint, server) = serve(vec![("X-Consul-Index: 7\r\n", body)]);
+        let cache = std::env::temp_dir().join(format!("conf-kv-cache-{}.json", std::process::id()));
+        let mut source = KvSource::consul(&endpoint, "app/");
+        source.cache = Some(RemoteCache::new(&cache));
+        source.load(&ParseOptions::default()).unwrap();
+        server.join().unwrap();
+
+        // サーバはもう応答しないので、再試行したあとキャッシュから読む
+        source.retry = RetryPolicy { initial_backoff: Duration::from_millis(1), ..Default::default() };
+        let mut conf = source.load(&ParseOptions::default()).unwrap();
+        assert!(source.is_stale());
+        assert_eq!(conf.get("endpoint").unwrap().as_str().unwrap(), "localhost:3000");
+        assert_eq!(conf.origin_of("endpoint").unwrap().to_string(), format!("{}/app/endpoint (cached)", endpoint));
+        std::fs::remove_file(&cache).unwrap();
+    }
+}
@@ -0,0 +1,71 @@
+// スキーマの型 loglevel。trace / debug / info / warn / error / off を大文字小文字を区別せずに受け付け、小文字の名前にそろえる
+// 数でも書ける (log クレートの LevelFilter と同じく 0 = off, 1 = error, ... 5 = trace)
+// log フィーチャーがあれば as_log_level() で log::LevelFilter として読める
+const LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
+pub(crate) fn normalize(s: &str) -> Result<&'static str, String> {
+    if let Ok(n) = s.parse::<usize>() {
+        return LEVELS.get(n).copied().ok_or_else(|| format!("Invalid log level: {}", s));
+    }
+    let lower = match s.to_ascii_lowercase().as_str() {
+        "warning" => "warn".to_string(),
+        lower => lower.to_string(),
+    };
+    LEVELS.into_iter().find(|level| *level == lower).ok_or_else(|| format!("Invalid log level: {}", s))
+}
+
+#[cfg(feature = "log")]
+mod log_level {
+    use log::LevelFilter;
+
+    use crate::typed::FromConfValue;
+    use crate::{ConfValue, ConfigValue, TypeMismatchError};
+
+    // スキーマの型が loglevel でなくても、ログレベルとして読める文字列なら読む
+    fn level_filter(s: &str) -> Result<LevelFilter, TypeMismatchError> {
+        super::normalize(s).ok().and_then(|level| level.parse().ok()).ok_or(TypeMismatchError)
+    }
+
+    impl ConfValue {
+        pub fn as_log_level(&self) -> Result<LevelFilter, TypeMismatchError> {
+            level_filter(self.as_str()?)
+        }
+    }
+
+    impl ConfigValue {
+        pub fn as_log_level(&self) -> Result<LevelFilter, TypeMismatchError> {
+            level_filter(self.as_str()?)
+        }
+    }
+
+    impl FromConfValue for LevelFilter {
+        fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+            value.as_log_level().map_err(|_| "Expected a log level".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_normalize_log_levels() {
+        assert_eq!(normalize("INFO").unwrap(), "info");
+        assert_eq!(normalize("Warning").unwrap(), "warn");
+        assert_eq!(normalize("5").unwrap(), "trace");
+        assert_eq!(normalize("0").unwrap(), "off");
+        assert_eq!(normalize("6").unwrap_err(), "Invalid log level: 6");
+        assert_eq!(normalize("Verbose").unwrap_err(), "Invalid log level: Verbose");
+
+        #[cfg(feature = "log")]
+        {
+            let conf = crate::parse_str("level = DEBUG\nname = web\n", Some("level -> loglevel\n")).unwrap();
+            assert_eq!(conf.get_str("level").unwrap(), "debug");
+            assert_eq!(conf.value_at("level", |v| v.as_log_level().unwrap()).unwrap(), log::LevelFilter::Debug);
+            let config = conf.freeze();
+            assert_eq!(config.get("level").unwrap().as_log_level().unwrap(), log::LevelFilter::Debug);
+            assert!(config.get("name").unwrap().as_log_level().is_err());
+        }
+    }
+}
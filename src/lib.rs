@@ -1,17 +1,140 @@
 use std::cell::{RefCell, RefMut};
 use std::fmt;
-use std::fs::File;
-use std::io::{self, BufRead};
-use std::path::Path;
+#[cfg(feature = "std-fs")]
+use std::io;
+use std::io::{BufRead, Read};
+use std::ops::{Bound, RangeBounds};
+#[cfg(feature = "std-fs")]
+use std::path::{Path, PathBuf};
+use std::borrow::Cow;
 use std::collections::HashMap;
-use std::rc::Rc;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::collections::HashSet;
+use path::{join_path, split_key, split_path};
+#[cfg(feature = "regex")]
 use regex::Regex;
+#[cfg(feature = "decimal")]
+use rust_decimal::Decimal;
 use std::error::Error;
 
+pub mod access;
+pub mod arena;
+mod audit;
+pub mod borrowed;
+mod bytes;
+mod condition;
+mod cron;
+pub mod conflict;
+pub mod config;
+pub mod cst;
+pub mod diff;
+#[cfg(feature = "std-fs")]
+pub mod discover;
+pub mod encoding;
+pub mod endpoint;
+mod eq;
+pub mod entry;
+pub mod env;
+mod expand;
+pub mod format;
+pub mod global;
+mod inline;
+pub mod integer;
+pub mod interpolate;
+mod iso;
+pub mod keys;
+mod level;
+pub mod lint;
+mod locale;
+pub mod manifest;
+pub mod merge;
+mod macros;
+mod media;
+pub mod metrics;
+pub mod patch;
+mod path;
+pub mod policy;
+mod profile;
+mod query;
+mod reference;
+#[cfg(feature = "std-fs")]
+pub mod reload;
+pub mod report;
+pub mod sarif;
+pub mod scope;
+#[cfg(feature = "std-fs")]
+pub mod secrets;
+pub mod semver;
+pub mod serialize;
+pub mod stream;
+#[cfg(feature = "test-util")]
+pub mod testing;
+#[cfg(feature = "track-access")]
+mod tracking;
+pub mod transform;
+pub mod typed;
+pub mod units;
+pub mod visit;
+#[cfg(any(feature = "json", feature = "toml"))]
+mod value;
+#[cfg(any(feature = "config", feature = "figment"))]
+pub mod adapter;
+#[cfg(feature = "clap")]
+pub mod cli;
+#[cfg(feature = "http")]
+pub mod remote;
+#[cfg(any(feature = "http", feature = "kv"))]
+pub mod retry;
+#[cfg(all(feature = "std-fs", any(feature = "http", feature = "kv")))]
+pub mod cache;
+#[cfg(feature = "kv")]
+pub mod kv;
+#[cfg(feature = "verify")]
+pub mod verify;
+
+pub use access::{AccessErrorKind, ConfAccessError};
+pub use arena::ArenaConfig;
+pub use audit::AuditHook;
+pub use config::{Config, ConfigValue};
+pub use conflict::ConflictPolicy;
+pub use encoding::Encoding;
+pub use endpoint::Endpoint;
+pub use integer::IntegerCastError;
+pub use env::EnvOptions;
+pub use format::FormatOptions;
+pub use global::{global, init_global, try_global};
+pub use interpolate::{Interpolation, Interpolator, MissingVariable, Resolution};
+pub use keys::{KeyCase, KeyPolicy};
+pub use lint::{LintOptions, Rule};
+pub use manifest::ManifestOptions;
+pub use merge::{MergeStrategies, MergeStrategy};
+pub use metrics::{LoadStats, MetricsHook};
+pub use policy::{Policy, PolicyViolation};
+pub use report::{Diagnostic, PartialConf, Severity, ValidationReport};
+pub use scope::Scope;
+pub use serialize::WriteOptions;
+pub use stream::parse_reader_streaming;
+pub use transform::TransformFn;
+pub use typed::{FromConf, FromConfValue};
+pub use visit::Visit;
+#[cfg(feature = "derive")]
+pub use conf_loader_with_validation_derive::FromConf;
+#[cfg(any(feature = "http", feature = "kv"))]
+pub use retry::RetryPolicy;
+#[cfg(all(feature = "std-fs", any(feature = "http", feature = "kv")))]
+pub use cache::RemoteCache;
+#[cfg(feature = "std-fs")]
+pub use report::{validate_file, validate_file_with_options};
+#[cfg(feature = "std-fs")]
+pub use stream::parse_streaming;
+#[cfg(feature = "verify")]
+pub use verify::Verification;
+
 // エラー型を定義
 #[derive(Debug)]
-struct TypeMismatchError;
+pub struct TypeMismatchError;
 
 impl fmt::Display for TypeMismatchError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -22,27 +145,71 @@ impl fmt::Display for TypeMismatchError {
 impl Error for TypeMismatchError {}
 
 // ConfValue 型
-#[derive(Debug)]
-enum ConfValue {
+// feature によって増える型 (decimal など) があるので、外からの match には _ の腕が要る
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ConfValue {
     StrValue(String),
     BoolValue(bool),
     NumberValue(f64),
+    // スキーマの型が decimal の値。書いた桁をそのまま保つ
+    #[cfg(feature = "decimal")]
+    DecimalValue(Decimal),
+    // スキーマの型が bytes の値。base64 (hex: で始めれば 16 進数) から戻したバイト列
+    Bytes(Vec<u8>),
+    // [a, b, c] と書いた値
+    List(Vec<ConfValue>),
     Conf(Box<ConfList>), // Linked List 形式に変更
 }
 
 // テスト用
+#[cfg(all(test, feature = "std-fs"))]
 type ConfVec = Vec<(String, ConfVecValue)>;
+#[cfg(all(test, feature = "std-fs"))]
 #[derive(Debug, PartialEq)]
 enum ConfVecValue {
     StrValue(String),
     BoolValue(bool),
     NumberValue(f64),
+    #[cfg(feature = "decimal")]
+    DecimalValue(Decimal),
+    Bytes(Vec<u8>),
+    List(Vec<String>),
     Conf(ConfVec),
 }
 
+impl fmt::Display for ConfValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfValue::StrValue(v) => write!(f, "{}", v),
+            ConfValue::BoolValue(v) => write!(f, "{}", v),
+            ConfValue::NumberValue(v) => write!(f, "{}", v),
+            #[cfg(feature = "decimal")]
+            ConfValue::DecimalValue(v) => write!(f, "{}", v),
+            ConfValue::Bytes(v) => write!(f, "{}", bytes::encode(v)),
+            ConfValue::List(items) => inline::write_list(items, f),
+            // ネストしたリストはインラインで表示する
+            ConfValue::Conf(conf) => {
+                let leaves = conf.leaves();
+                if leaves.is_empty() {
+                    return write!(f, "{{}}");
+                }
+                write!(f, "{{ ")?;
+                for (i, (key, value)) in leaves.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} = {}", key, value)?;
+                }
+                write!(f, " }}")
+            },
+        }
+    }
+}
+
 // ConfValue に型指定アクセス用メソッドを追加
 impl ConfValue {
-    fn as_str(&self) -> Result<&String, TypeMismatchError> {
+    pub fn as_str(&self) -> Result<&String, TypeMismatchError> {
         if let ConfValue::StrValue(ref value) = self {
             Ok(value)
         } else {
@@ -50,7 +217,7 @@ impl ConfValue {
         }
     }
 
-    fn as_bool(&self) -> Result<bool, TypeMismatchError> {
+    pub fn as_bool(&self) -> Result<bool, TypeMismatchError> {
         if let ConfValue::BoolValue(value) = self {
             Ok(*value)
         } else {
@@ -58,7 +225,7 @@ impl ConfValue {
         }
     }
 
-    fn as_number(&self) -> Result<f64, TypeMismatchError> {
+    pub fn as_number(&self) -> Result<f64, TypeMismatchError> {
         if let ConfValue::NumberValue(value) = self {
             Ok(*value)
         } else {
@@ -66,7 +233,32 @@ impl ConfValue {
         }
     }
 
-    fn as_conf(&self) -> Result<&ConfList, TypeMismatchError> {
+    #[cfg(feature = "decimal")]
+    pub fn as_decimal(&self) -> Result<Decimal, TypeMismatchError> {
+        if let ConfValue::DecimalValue(value) = self {
+            Ok(*value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_bytes(&self) -> Result<&[u8], TypeMismatchError> {
+        if let ConfValue::Bytes(ref value) = self {
+            Ok(value)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_list(&self) -> Result<&Vec<ConfValue>, TypeMismatchError> {
+        if let ConfValue::List(ref items) = self {
+            Ok(items)
+        } else {
+            Err(TypeMismatchError)
+        }
+    }
+
+    pub fn as_conf(&self) -> Result<&ConfList, TypeMismatchError> {
         if let ConfValue::Conf(ref conf) = self {
             Ok(conf)
         } else {
@@ -75,30 +267,160 @@ impl ConfValue {
     }
 }
 
+// 値の出どころ (ファイルパスや URL と行番号)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Origin {
+    pub source: String,
+    pub line: Option<usize>,
+    // 値の中の ${...} をそれぞれどう展開したか
+    pub interpolations: Vec<Interpolation>,
+}
+
+impl Origin {
+    // ${...} を展開していない値の出どころ
+    pub fn new(source: impl Into<String>, line: Option<usize>) -> Self {
+        Origin { source: source.into(), line, interpolations: Vec::new() }
+    }
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}", self.source, line),
+            None => write!(f, "{}", self.source),
+        }
+    }
+}
+
 // ノードを表す構造体
 #[derive(Debug)]
 struct Node {
-    key: String,
+    key: Arc<str>, // 同じキー名は KeyInterner で共有する
     value: RefCell<ConfValue>, // RefCell で内部を可変にする
+    origin: Option<Origin>,
+    // スキーマで secret と指定されたか、env: / file: / ENC(...) から読んだ値
+    secret: bool,
+    // ファイルなどに書かれていたままの値。型を付けた値の表示と同じなら持たない
+    raw: Option<Box<str>>,
+    #[cfg(feature = "track-access")]
+    accessed: tracking::AccessFlag,
     next: Option<Box<Node>>,
 }
 
+// ノードから取り出したキー・値・出どころ・secret・書かれていたままの値
+type NodeEntry = (Arc<str>, ConfValue, Option<Origin>, bool, Option<Box<str>>);
+
+// to_flat_map などで secret の値の代わりに出力する文字列
+pub const REDACTED: &str = "********";
+
+// raw_of の値。secret の値は env: / file: / ENC(...) の参照でなければ伏せる
+fn raw_text(value: &ConfValue, secret: bool, raw: Option<&str>) -> Option<String> {
+    if let ConfValue::Conf(_) = value {
+        return None;
+    }
+    let raw = raw.map_or_else(|| value.to_string(), str::to_string);
+    Some(if secret && !is_secret_reference(&raw) { REDACTED.to_string() } else { raw })
+}
+
+// 同じキー名の文字列を 1 つの Arc<str> で共有する
+#[derive(Debug, Default)]
+struct KeyInterner {
+    keys: HashSet<Arc<str>>,
+}
+
+impl KeyInterner {
+    fn intern(&mut self, key: &str) -> Arc<str> {
+        if let Some(interned) = self.keys.get(key) {
+            return Arc::clone(interned);
+        }
+        let interned: Arc<str> = Arc::from(key);
+        self.keys.insert(Arc::clone(&interned));
+        interned
+    }
+}
+
 // Linked List 形式の構造体
 #[derive(Debug)]
-struct ConfList {
+pub struct ConfList {
     head: Option<Box<Node>>,
+    audit: Option<audit::Audit>,
+}
+
+// 長いリストでも再帰しないように、複製と破棄はループで行う
+impl Clone for ConfList {
+    fn clone(&self) -> Self {
+        let mut nodes = Vec::new();
+        let mut current = &self.head;
+        while let Some(node) = current {
+            nodes.push(node);
+            current = &node.next;
+        }
+        let mut list = ConfList::new();
+        for node in nodes.into_iter().rev() {
+            list.push_node(node.key.clone(), node.value.borrow().clone(), node.origin.clone(), node.secret, node.raw.clone());
+        }
+        list.audit = self.audit.clone();
+        list
+    }
+}
+
+impl Drop for ConfList {
+    fn drop(&mut self) {
+        let mut stack: Vec<Box<Node>> = self.head.take().into_iter().collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.next.take());
+            // ネストしたリストもここで取り出しておく
+            if let ConfValue::Conf(child) = node.value.get_mut() {
+                stack.extend(child.head.take());
+            }
+        }
+    }
+}
+
+// キーはドット区切りで展開する。同じキーは後のものが有効になる
+impl FromIterator<(String, ConfValue)> for ConfList {
+    fn from_iter<I: IntoIterator<Item = (String, ConfValue)>>(iter: I) -> Self {
+        let mut list = ConfList::new();
+        list.extend(iter);
+        list
+    }
+}
+
+impl Extend<(String, ConfValue)> for ConfList {
+    fn extend<I: IntoIterator<Item = (String, ConfValue)>>(&mut self, iter: I) {
+        let mut interner = KeyInterner::default();
+        for (key, value) in iter {
+            self.add_value_interned(&key, value, None, false, None, &mut interner);
+        }
+    }
+}
+
+// HashMap の順序は決まらないので、キーの名前順に追加する
+impl From<HashMap<String, String>> for ConfList {
+    fn from(map: HashMap<String, String>) -> Self {
+        let mut entries: Vec<(String, String)> = map.into_iter().collect();
+        entries.sort();
+        entries.into_iter().map(|(key, value)| (key, ConfValue::StrValue(value))).collect()
+    }
+}
+
+impl Default for ConfList {
+    fn default() -> Self {
+        ConfList::new()
+    }
 }
 
 impl ConfList {
-    fn new() -> Self {
-        ConfList { head: None }
+    // 空の conf を作る。insert や entry で値を追加すれば、parse() の結果と同じように扱える
+    pub fn new() -> Self {
+        ConfList { head: None, audit: None }
     }
 
     // 要素が含まれているか確認する contains_key() メソッド
-    fn contains_key(&self, key: &str) -> bool {
+    pub fn contains_key(&self, key: &str) -> bool {
         let mut current = &self.head;
         while let Some(node) = current {
-            if node.key == key {
+            if &*node.key == key {
                 return true;
             }
             current = &node.next;
@@ -106,11 +428,16 @@ impl ConfList {
         false
     }
 
-    fn get(&mut self, key: &str) -> Option<RefMut<'_, ConfValue>> {
+    pub fn get(&mut self, key: &str) -> Option<RefMut<'_, ConfValue>> {
         let mut current = &self.head;
         while let Some(node) = current {
-            let value = (&(*node).value).borrow_mut();
-            if node.key == key {
+            let value = node.value.borrow_mut();
+            if &*node.key == key {
+                #[cfg(feature = "track-access")]
+                node.accessed.mark();
+                if let Some(audit) = &self.audit {
+                    audit.record_key(key, &value, node.secret, node.raw.as_deref());
+                }
                 return Some(value);
             }
             current = &node.next;
@@ -118,60 +445,357 @@ impl ConfList {
         None
     }
 
-    // 要素を追加する insert() メソッド
-    fn insert(&mut self, key: String, value: ConfValue) {
+    // 同じキーに書いたすべての値をソース順で返す (include したファイルの値も含む)
+    // nginx の server のように繰り返して書くディレクティブ向け。get などは最後の値だけを返す
+    pub fn get_all(&self, path: &str) -> Vec<ConfValue> {
+        let (key, rest) = split_path(path);
+        match (rest, self.find(&key)) {
+            (None, _) => self.values_of(&key),
+            (Some(rest), Some(node)) => match &*node.value.borrow() {
+                ConfValue::Conf(child) => child.get_all(&rest),
+                _ => Vec::new(),
+            },
+            (Some(_), None) => Vec::new(),
+        }
+    }
+
+    fn values_of(&self, key: &str) -> Vec<ConfValue> {
+        let mut nodes = Vec::new();
+        let mut current = &self.head;
+        while let Some(node) = current {
+            if &*node.key == key {
+                nodes.push(node);
+            }
+            current = &node.next;
+        }
+        nodes.iter().rev().map(|node| {
+            let value = node.value.borrow();
+            #[cfg(feature = "track-access")]
+            node.accessed.mark();
+            if let Some(audit) = &self.audit {
+                audit.record_key(key, &value, node.secret, node.raw.as_deref());
+            }
+            value.clone()
+        }).collect()
+    }
+
+    // 先頭にノードを追加する。同じキーの古いノードは残り、後から追加したものが有効になる
+    fn push_node(&mut self, key: Arc<str>, value: ConfValue, origin: Option<Origin>, secret: bool, raw: Option<Box<str>>) {
         let new_node = Box::new(Node {
             key,
             value: RefCell::new(value),  // RefCell で包む
+            origin,
+            secret,
+            raw,
+            #[cfg(feature = "track-access")]
+            accessed: Default::default(),
             next: self.head.take(),
         });
         self.head = Some(new_node);
     }
 
-    fn add_value(&mut self, key: &str, value: ConfValue) {
-        let binding: Vec<&str> = key.splitn(2, '.').collect::<Vec<&str>>();
-        let keys: &[&str] = binding.as_slice();
-        // ネストしてないキー
-        if keys.len() == 1 {
-            self.insert(key.to_string(), value);
-            return;
-        }
-        // キーがネストしているとき
-        if self.contains_key(keys[0]) {
-            let mut conf_value: RefMut<'_, ConfValue> = self.get(keys[0]).unwrap();
-            let new_value: ConfValue = match &mut *conf_value {
-                // すでにある値がNodeだった場合
-                ConfValue::Conf(child_node) => {
-                    child_node.add_value(keys[1], value);
-                    return;
-                },
-                // ↓ Node 以外はすべて同じ処理
-                ConfValue::StrValue(_) => {
-                    let mut child_node = Box::new(ConfList::new());
-                    child_node.add_value(keys[1], value);
-                    ConfValue::Conf(child_node)
+    fn add_value(&mut self, key: &str, value: ConfValue, origin: Option<Origin>) {
+        self.add_value_interned(key, value, origin, false, None, &mut KeyInterner::default());
+    }
+
+    // ドット区切りのキーをたどって値を追加する。深いキーでもスタックを使わないようにループで処理する
+    fn add_value_interned(&mut self, key: &str, value: ConfValue, origin: Option<Origin>, secret: bool, raw: Option<Box<str>>, interner: &mut KeyInterner) {
+        let mut list: &mut ConfList = self;
+        let mut rest = key;
+        loop {
+            match split_key(rest) {
+                (head, Some(tail)) => {
+                    list = list.child_list_mut(&head, &origin, interner);
+                    rest = tail;
                 },
-                ConfValue::BoolValue(_) => {
-                    let mut child_node = Box::new(ConfList::new());
-                    child_node.add_value(keys[1], value);
-                    ConfValue::Conf(child_node)
+                (last, None) => return list.push_node(interner.intern(&last), value, origin, secret, raw),
+            }
+        }
+    }
+
+    // key のリストを返す。リスト以外の値しかなければ新しいリストで上書きする
+    fn child_list_mut(&mut self, key: &str, origin: &Option<Origin>, interner: &mut KeyInterner) -> &mut ConfList {
+        let is_conf = self.find(key)
+            .is_some_and(|node| matches!(&*node.value.borrow(), ConfValue::Conf(_)));
+        if !is_conf {
+            self.push_node(interner.intern(key), ConfValue::Conf(Box::default()), origin.clone(), false, None);
+        }
+        match self.find_mut(key).unwrap().value.get_mut() {
+            ConfValue::Conf(child) => child,
+            _ => unreachable!(),
+        }
+    }
+
+    fn find_mut(&mut self, key: &str) -> Option<&mut Node> {
+        let mut current = self.head.as_deref_mut();
+        while let Some(node) = current {
+            if &*node.key == key {
+                return Some(node);
+            }
+            current = node.next.as_deref_mut();
+        }
+        None
+    }
+
+    // ノードを取り出してソース順 (古い順) に並べる
+    fn into_entries(mut self) -> Vec<NodeEntry> {
+        let mut entries = Vec::new();
+        let mut current = self.head.take();
+        while let Some(node) = current {
+            let node = *node;
+            entries.push((node.key, node.value.into_inner(), node.origin, node.secret, node.raw));
+            current = node.next;
+        }
+        entries.reverse();
+        entries
+    }
+
+    // other の値で上書きしながらマージする (ネストしたリストは再帰的にマージ)
+    pub fn merge(&mut self, other: ConfList) {
+        self.merge_by(other, merge::Strategies::NONE);
+    }
+
+    // other をドット区切りの prefix の下にマージする。プラグインごとに検証した conf をホストの conf にまとめるときに使う
+    // prefix に値があればセクションで置き換え、セクションがあれば other の値で上書きする
+    pub fn mount(&mut self, prefix: &str, other: ConfList) {
+        let mut interner = KeyInterner::default();
+        let mut list: &mut ConfList = self;
+        let mut rest = Some(prefix);
+        while let Some((key, tail)) = rest.map(split_key) {
+            list = list.child_list_mut(&key, &None, &mut interner);
+            rest = tail;
+        }
+        list.merge(other);
+    }
+
+    // パスの値を (上書きされたものも含めて) 削除する。空になったリストも取り除く
+    pub fn remove(&mut self, path: &str) -> bool {
+        let (key, rest) = match split_path(path) {
+            (key, Some(rest)) => (key, rest),
+            (key, None) => return self.remove_key(&key),
+        };
+        let (removed, empty) = match self.find(&key) {
+            Some(node) => match &mut *node.value.borrow_mut() {
+                ConfValue::Conf(child) => (child.remove(&rest), child.head.is_none()),
+                _ => (false, false),
+            },
+            None => (false, false),
+        };
+        if empty {
+            self.remove_key(&key);
+        }
+        removed
+    }
+
+    fn remove_key(&mut self, key: &str) -> bool {
+        let mut removed = false;
+        let mut current = &mut self.head;
+        while current.is_some() {
+            if &*current.as_ref().unwrap().key == key {
+                let node = current.take().unwrap();
+                *current = node.next;
+                removed = true;
+            } else {
+                current = &mut current.as_mut().unwrap().next;
+            }
+        }
+        removed
+    }
+
+    // 値が secret として読み込まれたか
+    pub fn is_secret(&self, path: &str) -> bool {
+        let (key, rest) = split_path(path);
+        let node = match self.find(&key) {
+            Some(node) => node,
+            None => return false,
+        };
+        match (rest, &*node.value.borrow()) {
+            (None, _) => node.secret,
+            (Some(rest), ConfValue::Conf(child)) => child.is_secret(&rest),
+            _ => false,
+        }
+    }
+
+    // 末端の値をすべて f で変換した新しい conf を返す (secret の値を伏せる、相対パスを解決する、など)
+    // セクションの構造と出どころ、secret の印は元のまま。書かれていたままの値は変換後の値と合わないので持たない
+    pub fn map_values<F>(&self, mut f: F) -> ConfList
+    where F: FnMut(&str, &ConfValue) -> ConfValue, {
+        self.map_at("", &mut f)
+    }
+
+    fn map_at<F>(&self, prefix: &str, f: &mut F) -> ConfList
+    where F: FnMut(&str, &ConfValue) -> ConfValue, {
+        let mut list = ConfList { head: None, audit: self.audit.clone() };
+        for key in self.live_keys() {
+            let path = join_path(prefix, key);
+            let node = self.find(key).unwrap();
+            let value = match &*node.value.borrow() {
+                ConfValue::Conf(child) => ConfValue::Conf(Box::new(child.map_at(&path, f))),
+                value => f(&path, value),
+            };
+            list.push_node(Arc::clone(&node.key), value, node.origin.clone(), node.secret, None);
+        }
+        list
+    }
+
+    // f が false を返した末端の値を取り除く。中の値がなくなったセクションも取り除く
+    // 外部に渡す前に、実験中や内部用のキーを落とすためのもの
+    pub fn retain<F>(&mut self, mut f: F)
+    where F: FnMut(&str, &ConfValue) -> bool, {
+        self.retain_at("", &mut f);
+    }
+
+    fn retain_at<F>(&mut self, prefix: &str, f: &mut F)
+    where F: FnMut(&str, &ConfValue) -> bool, {
+        let keys: Vec<String> = self.live_keys().into_iter().map(str::to_string).collect();
+        for key in keys {
+            let path = join_path(prefix, &key);
+            let keep = match self.find_mut(&key).unwrap().value.get_mut() {
+                // もともと空のセクション ({}) は残す
+                ConfValue::Conf(child) => child.head.is_none() || {
+                    child.retain_at(&path, f);
+                    child.head.is_some()
                 },
-                ConfValue::NumberValue(_) => {
-                    let mut child_node = Box::new(ConfList::new());
-                    child_node.add_value(keys[1], value);
-                    ConfValue::Conf(child_node)
+                value => f(&path, value),
+            };
+            // 上書きされた古い値が見えるようにならないよう、同じキーのノードをすべて取り除く
+            if !keep {
+                self.remove_key(&key);
+            }
+        }
+    }
+
+    // 上書きされていないキーをソース順で返す
+    fn live_keys(&self) -> Vec<&str> {
+        // head が最新なので、最初に見つかったキーが有効な値
+        let mut seen: Vec<&str> = Vec::new();
+        let mut current = &self.head;
+        while let Some(node) = current {
+            if !seen.contains(&&*node.key) {
+                seen.push(&node.key);
+            }
+            current = &node.next;
+        }
+        seen.reverse();
+        seen
+    }
+
+    // 上書きされた値を除いた末端の値を、ドット区切りのパスとソース順で返す
+    fn leaves(&self) -> Vec<(String, ConfValue)> {
+        let mut leaves = Vec::new();
+        self.collect_leaves("", &mut leaves);
+        leaves
+    }
+
+    fn collect_leaves(&self, prefix: &str, leaves: &mut Vec<(String, ConfValue)>) {
+        for key in self.live_keys() {
+            let path = join_path(prefix, key);
+            let value = self.find(key).unwrap().value.borrow();
+            match &*value {
+                ConfValue::Conf(child) if child.head.is_some() => child.collect_leaves(&path, leaves),
+                v => leaves.push((path, v.clone())),
+            }
+        }
+    }
+
+    // ドット区切りのキーと文字列にした値の組に変換する。redact なら secret の値を伏せる
+    pub fn to_flat_map(&self, redact: bool) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        self.collect_flat("", redact, &mut map);
+        map
+    }
+
+    fn collect_flat(&self, prefix: &str, redact: bool, map: &mut HashMap<String, String>) {
+        // head が最新なので、2 回目以降に出てくるキーは上書きされた古い値
+        let mut seen: Vec<&str> = Vec::new();
+        let mut current = &self.head;
+        while let Some(node) = current {
+            current = &node.next;
+            if seen.contains(&&*node.key) {
+                continue;
+            }
+            seen.push(&node.key);
+            let path = join_path(prefix, &node.key);
+            let value = match &*node.value.borrow() {
+                ConfValue::Conf(child) => {
+                    child.collect_flat(&path, redact, map);
+                    continue;
                 },
+                _ if redact && node.secret => REDACTED.to_string(),
+                v => v.to_string(),
             };
-            drop(conf_value);  // 明示的に借用を解除
-            self.insert(keys[0].to_string(), new_value);
-        } else {
-            let mut child_node = Box::new(ConfList::new());
-            child_node.add_value(keys[1], value);
-            self.insert(keys[0].to_string(), ConfValue::Conf(child_node));
+            map.insert(path, value);
+        }
+    }
+
+    // 有効なノード (最後に追加されたもの) を探す
+    fn find(&self, key: &str) -> Option<&Node> {
+        let mut current = &self.head;
+        while let Some(node) = current {
+            if &*node.key == key {
+                return Some(node);
+            }
+            current = &node.next;
+        }
+        None
+    }
+
+    // ドットで区切ったパスの値を f に渡す
+    fn value_at<T>(&self, path: &str, f: impl FnOnce(&ConfValue) -> T) -> Option<T> {
+        self.node_at(path, |_, value| f(value))
+    }
+
+    // value_at と同じだが、アプリケーションが値を読んだものとして記録する (track-access と監査フック)
+    fn read_at<T>(&self, path: &str, f: impl FnOnce(&ConfValue) -> T) -> Option<T> {
+        self.node_at(path, |node, value| {
+            #[cfg(feature = "track-access")]
+            node.accessed.mark();
+            if let Some(audit) = &self.audit {
+                audit.record_value(path, value, node.secret, node.raw.as_deref());
+            }
+            f(value)
+        })
+    }
+
+    fn node_at<T>(&self, path: &str, f: impl FnOnce(&Node, &ConfValue) -> T) -> Option<T> {
+        let (key, rest) = split_path(path);
+        let node = self.find(&key)?;
+        let value = node.value.borrow();
+        match (rest, &*value) {
+            (None, value) => Some(f(node, value)),
+            (Some(rest), ConfValue::Conf(child)) => child.node_at(&rest, f),
+            _ => None,
+        }
+    }
+
+    // 最終的な値をどのソースのどの行が設定したかを返す
+    pub fn origin_of(&self, path: &str) -> Option<Origin> {
+        let (key, rest) = split_path(path);
+        let node = self.find(&key)?;
+        match rest {
+            None => node.origin.clone(),
+            Some(rest) => match &*node.value.borrow() {
+                ConfValue::Conf(child) => child.origin_of(&rest),
+                _ => None,
+            },
+        }
+    }
+
+    // ファイルなどに書かれていたままの値を返す (型を付けると形が変わる値をエラーメッセージなどでそのまま見せる)
+    // セクションなら None
+    pub fn raw_of(&self, path: &str) -> Option<String> {
+        let (key, rest) = split_path(path);
+        let node = self.find(&key)?;
+        let value = node.value.borrow();
+        match (rest, &*value) {
+            (Some(rest), ConfValue::Conf(child)) => child.raw_of(&rest),
+            (None, value) => raw_text(value, node.secret, node.raw.as_deref()),
+            _ => None,
         }
     }
 
     // テスト用 vecに変換する
+    #[cfg(all(test, feature = "std-fs"))]
     fn to_vec(&self) -> ConfVec {
         let mut vec: ConfVec = Vec::new();
         let mut current = self.head.as_ref();
@@ -191,8 +815,18 @@ impl ConfList {
                 ConfValue::NumberValue(v) => {
                     ConfVecValue::NumberValue(*v)
                 },
+                #[cfg(feature = "decimal")]
+                ConfValue::DecimalValue(v) => {
+                    ConfVecValue::DecimalValue(*v)
+                },
+                ConfValue::Bytes(v) => {
+                    ConfVecValue::Bytes(v.clone())
+                },
+                ConfValue::List(items) => {
+                    ConfVecValue::List(items.iter().map(|v| v.to_string()).collect())
+                },
             };
-            vec.insert(0, (node.key.clone(), new_value));
+            vec.insert(0, (node.key.to_string(), new_value));
             current = node.next.as_ref();
         }
 
@@ -201,24 +835,44 @@ impl ConfList {
 
 }
 
-#[derive(Debug, PartialEq)]
-enum SchemaType {
+// 型はこれからも増えるので、外からの match には _ の腕が要る
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum SchemaType {
     String,
     Bool,
     Number,
-}
-
-trait Value<T>: std::fmt::Debug {
-    fn as_any(&self) -> &dyn std::any::Any;
-    fn as_mut(&mut self) -> &mut T;
-}
-impl Value<ConfValue> for ConfValue {
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
-    }
-    fn as_mut(&mut self) -> &mut ConfValue {
-        self
-    }
+    #[cfg(feature = "decimal")]
+    Decimal,
+    List,
+    // 文字列と同じだが、ParseOptions::expand_paths なら ~ や環境変数を展開し、相対パスを conf のディレクトリから解決する
+    Path,
+    // base64 (hex: で始めれば 16 進数) で書いたバイト列。secret を書かなくても値を伏せる
+    Bytes,
+    // 550e8400-e29b-41d4-a716-446655440000 の形の UUID。値は文字列のまま持つ
+    Uuid,
+    // JP や US のような ISO 3166-1 alpha-2 の国コード。値は大文字にそろえる
+    Country,
+    // JPY や EUR のような ISO 4217 の通貨コード。値は大文字にそろえる
+    Currency,
+    // en-US や ja のような BCP 47 の言語タグ。値は文字列のまま持つ
+    Locale,
+    // text/html; charset=utf-8 のような MIME タイプ。値は文字列のまま持つ
+    Mime,
+    // UTF-8 や Shift_JIS のような IANA の文字セットの名前。値は文字列のまま持つ
+    Charset,
+    // 5 つ (分 時 日 月 曜日) か、先頭に秒を足した 6 つのフィールドの cron 式。値は文字列のまま持つ
+    Cron,
+    // host:port (ホスト名、IPv4、[...] で囲んだ IPv6)。値は文字列のまま持ち、as_endpoint() で分けて読む
+    Endpoint,
+    // trace / debug / info / warn / error / off (大文字でも、0 から 5 の数でもよい)。値は小文字の名前にそろえる
+    LogLevel,
+    // 1.2.3-rc.1 のような版。semver(>=1.2, <2) と書けば範囲も確かめる。値は文字列のまま持つ
+    Semver,
+    // 型を確かめない (プラグインにそのまま渡す値など)。スキーマにないキーと同じように読むが、required や secret は使える
+    Any,
+    // セクションでなければならない。log -> object なら log = true のような値でセクションを上書きできない
+    Object,
 }
 
 impl FromStr for SchemaType {
@@ -229,126 +883,1237 @@ impl FromStr for SchemaType {
             "string" => Ok(SchemaType::String),
             "bool" => Ok(SchemaType::Bool),
             "number" => Ok(SchemaType::Number),
+            #[cfg(feature = "decimal")]
+            "decimal" => Ok(SchemaType::Decimal),
+            #[cfg(not(feature = "decimal"))]
+            "decimal" => Err("The decimal type requires the decimal feature".to_string()),
+            "list" => Ok(SchemaType::List),
+            "path" => Ok(SchemaType::Path),
+            "bytes" => Ok(SchemaType::Bytes),
+            "uuid" => Ok(SchemaType::Uuid),
+            "semver" => Ok(SchemaType::Semver),
+            "loglevel" => Ok(SchemaType::LogLevel),
+            "endpoint" => Ok(SchemaType::Endpoint),
+            "cron" => Ok(SchemaType::Cron),
+            "locale" => Ok(SchemaType::Locale),
+            "country" => Ok(SchemaType::Country),
+            "currency" => Ok(SchemaType::Currency),
+            "mime" => Ok(SchemaType::Mime),
+            "charset" => Ok(SchemaType::Charset),
+            "any" => Ok(SchemaType::Any),
+            "object" => Ok(SchemaType::Object),
             _ => Err(format!("Invalid type: {}", s)),
         }
     }
 }
 
-pub fn parse(file_path: &str, schema_path: Option<&str>) -> Result<ConfList, Box<dyn Error>> {
-    let schema: HashMap<String, SchemaType> = match schema_path {
-        Some(path) => parse_schema(path)?,
-        None => HashMap::new(),
-    };
-    Ok(parse_conf(file_path, schema))
+// スキーマの 1 行分。"key -> string | trim | lowercase ~ ^[a-z]+$" のように
+// 型のあとに変換、最後に pattern を書く
+#[derive(Debug)]
+pub struct SchemaEntry {
+    ty: SchemaType,
+    // 検証の前に順に適用する変換の名前
+    transforms: Vec<String>,
+    // "key -> string secret" のように型のあとに書く。to_flat_map などで値を伏せる
+    secret: bool,
+    // "key -> string required" のように書く。キーがなく default もなければエラー
+    required: bool,
+    // "key -> list append" のように書く。parse_dir や include で重ねたときのマージのしかた
+    merge: Option<MergeStrategy>,
+    // number の値の範囲 (コードからのみ指定できる)
+    range: Option<(Bound<f64>, Bound<f64>)>,
+    // "key -> semver(>=1.2, <2)" のように書く。semver の値が満たす条件
+    versions: Option<semver::VersionReq>,
+    // キーがないときに使う値。ファイルの値と同じように変換・検証する (コードからのみ指定できる)
+    default: Option<String>,
+    // "key[] -> { host -> string, port -> number }" のように書く。list の要素のテーブルごとに検証する
+    items: Option<Schema>,
+    #[cfg(feature = "regex")]
+    pattern: Option<Regex>,
 }
 
-fn parse_conf(file_path: &str, schema: HashMap<String, SchemaType>) -> ConfList {
-    let mut map = ConfList::new();
-    if let Ok(lines) = read_lines(file_path) {
-        for line in lines.flatten() {
-            let key_value = parse_line(&line);
-            if key_value.is_none() {
-                continue;
-            }
-            let (key, value): (&str, &str) = key_value.unwrap();
-            let typed_value = match schema.contains_key(key) {
-                true => validate(value, schema.get(key).unwrap()).unwrap(),
-                false => ConfValue::StrValue(value.to_string()),
-            };
-            map.add_value(key, typed_value);
+// コードからスキーマを組み立てる (schema! マクロもこれを使う)
+impl SchemaEntry {
+    pub fn new(ty: SchemaType) -> Self {
+        SchemaEntry {
+            ty,
+            transforms: Vec::new(),
+            secret: ty == SchemaType::Bytes,
+            required: false,
+            merge: None,
+            range: None,
+            versions: None,
+            default: None,
+            items: None,
+            #[cfg(feature = "regex")]
+            pattern: None,
         }
     }
-    map
-}
 
-fn validate(s: &str, t: &SchemaType) -> Result<ConfValue, String> {
-    match t {
-        SchemaType::String => Ok(ConfValue::StrValue(s.to_string())),
-        SchemaType::Bool => match s {
-            "true" => Ok(ConfValue::BoolValue(true)),
-            "false" => Ok(ConfValue::BoolValue(false)),
-            _ => Err("Invalid boolean value".to_string()),
-        },
-        SchemaType::Number => {
-            if let Ok(number) = f64::from_str(s) {
-                Ok(ConfValue::NumberValue(number))
-            } else {
-                Err("Invalid number value".to_string())
-            }
-        }
+    pub fn secret(mut self) -> Self {
+        self.secret = true;
+        self
     }
-}
 
-fn parse_schema(file_path: &str) -> Result<HashMap<String, SchemaType>, Box<dyn Error>> {
-    let mut map: HashMap<String, SchemaType> = HashMap::new();
-    if let Ok(lines) = read_lines(file_path) {
-        for line in lines.flatten() {
-            let key_value = parse_schema_line(&line);
-            if key_value.is_none() {
-                continue;
-            }
-            let (key, t): (&str, &str) = key_value.unwrap();
-            let type_enum = t.parse::<SchemaType>()?;
-            map.insert(key.to_string(), type_enum);
-        }
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
     }
-    Ok(map)
-}
 
-type KeyValue<'a> = (&'a str, &'a str);
-fn parse_line(line: &str) -> Option<KeyValue<'_>> {
-    let l = line.trim();
-    if l.is_empty() {
-        return None;
+    pub fn merge(mut self, strategy: MergeStrategy) -> Self {
+        self.merge = Some(strategy);
+        self
     }
-    if l.starts_with('#') || l.starts_with(';') {
-        return None;
+
+    pub fn range<R: RangeBounds<f64>>(mut self, range: R) -> Self {
+        self.range = Some((range.start_bound().cloned(), range.end_bound().cloned()));
+        self
     }
-    let vec = line.splitn(2, '=').collect::<Vec<&str>>();
-    if vec.len() != 2 {
-        return None;
+
+    pub fn versions(mut self, req: semver::VersionReq) -> Self {
+        self.versions = Some(req);
+        self
     }
-    let key = vec[0].trim();
-    let value = vec[1].trim();
-    if key.is_empty() || value.is_empty() {
-        return None;
+
+    pub fn default_value<V: fmt::Display>(mut self, value: V) -> Self {
+        self.default = Some(value.to_string());
+        self
     }
-    Some((key, value))
-}
 
-fn split_by_str<'a>(s: &'a str, delim: &str) -> Option<std::vec::Vec<&'a str>> {
-    if !s.contains(delim) {
-        return None;
+    pub fn items(mut self, schema: Schema) -> Self {
+        self.items = Some(schema);
+        self
     }
-    let re = Regex::new(delim).unwrap();
-    let parts: Vec<&str> = re.splitn(s, 2).collect();
-    Some(parts)
-}
 
-fn parse_schema_line(line: &str) -> Option<KeyValue<'_>> {
-    let l = line.trim();
-    if l.is_empty() {
-        return None;
+    pub fn transform(mut self, name: &str) -> Self {
+        self.transforms.push(name.to_string());
+        self
     }
-    let vec = split_by_str(l, "->")?;
-    if vec.len() != 2 {
-        return None;
+
+    fn check_range(&self, number: f64) -> Result<(), String> {
+        let (start, end) = match &self.range {
+            Some(range) if !range.contains(&number) => range,
+            _ => return Ok(()),
+        };
+        let start = match start {
+            Bound::Included(v) | Bound::Excluded(v) => v.to_string(),
+            Bound::Unbounded => String::new(),
+        };
+        let end = match end {
+            Bound::Included(v) => format!("={}", v),
+            Bound::Excluded(v) => v.to_string(),
+            Bound::Unbounded => String::new(),
+        };
+        Err(format!("Value {} is out of range {}..{}", number, start, end))
     }
-    let key = vec[0].trim();
-    let value = vec[1].trim();
-    if key.is_empty() || value.is_empty() {
-        return None;
+
+    fn check_versions(&self, version: &semver::Version) -> Result<(), String> {
+        match &self.versions {
+            Some(req) if !req.matches(version) => Err(format!("Version {} does not satisfy {}", version, req)),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(feature = "regex")]
+    fn check_pattern(&self, s: &str) -> Result<(), String> {
+        match &self.pattern {
+            Some(re) if !re.is_match(s) => Err(format!("Value does not match pattern {}", re.as_str())),
+            _ => Ok(()),
+        }
+    }
+
+    #[cfg(not(feature = "regex"))]
+    fn check_pattern(&self, _s: &str) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+pub type Schema = HashMap<String, SchemaEntry>;
+
+// ENC(...) で囲まれた値を復号する関数
+pub type Decryptor = Box<dyn Fn(&str) -> Result<String, Box<dyn Error>> + Send + Sync>;
+
+// 悪意のあるファイルや壊れたファイルから守るための上限
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // キーのネストの深さ (ドットで区切った数)
+    pub max_depth: usize,
+    // 1 回の読み込みで追加できるキーの数 (include したファイルも含む)
+    pub max_keys: usize,
+    // 値の長さ (バイト)
+    pub max_value_length: usize,
+    // ファイルの大きさ (バイト)。誤って渡した巨大なファイルやバイナリを読み切る前に止める
+    // parse_reader では入力全体の大きさ
+    pub max_file_size: u64,
+    // 1 行の長さ (バイト、コメントも含む)
+    pub max_line_length: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_depth: 32,
+            max_keys: 100_000,
+            max_value_length: 64 * 1024,
+            max_file_size: 16 * 1024 * 1024,
+            max_line_length: 1024 * 1024,
+        }
+    }
+}
+
+// パース時のオプション
+#[derive(Default)]
+pub struct ParseOptions {
+    pub decryptor: Option<Decryptor>,
+    // 指定されていれば値の ${...} を展開する
+    pub interpolation: Option<Interpolator>,
+    pub limits: Limits,
+    pub metrics: Option<MetricsHook>,
+    // スキーマの | name で使える変換 (組み込みの trim, lowercase などより優先)
+    pub transforms: HashMap<String, TransformFn>,
+    pub keys: KeyPolicy,
+    // ファイルから読むときの文字コード
+    pub encoding: Encoding,
+    // key = value として読めない行 (空行とコメント以外) を読み飛ばさずエラーにする
+    pub strict: bool,
+    // 行頭の "export " を無視する (シェルで source する env ファイルをそのまま読む)
+    pub allow_export: bool,
+    // スキーマにない値の true/false、数値、引用符で囲んだ文字列を読み分ける。false ならすべて文字列 (リストを除く)
+    pub infer_types: bool,
+    // スキーマにない値の { ... } をインラインテーブルとして読む。false なら文字列のまま ({time} {level} のような書式を壊さない)
+    // スキーマの型が object のキーは、このオプションがなくてもテーブルとして読む
+    pub inline_tables: bool,
+    // スキーマで secret と指定されていないキーでも env:NAME / file:PATH を参照として読む
+    pub secret_references: bool,
+    // スキーマの型が path の値の ~ や $NAME を展開し、相対パスを conf のディレクトリから解決する
+    pub expand_paths: bool,
+    // 禁止するキーや値。反していれば検証エラー
+    pub policy: Policy,
+    // parse_dir や include で重ねるときの、パスごとのマージのしかた (スキーマの指定より優先)
+    pub merge: MergeStrategies,
+    // endpoint = x の後の endpoint.port = 80 のように、値とセクションがぶつかったときの扱い
+    pub conflicts: ConflictPolicy,
+    // 1 つのファイルに "--- name" や "[profile name]" で書いた環境ごとのドキュメントのうち、重ねるもの
+    pub profiles: Vec<String>,
+    // @if の条件で使える変数 (組み込みの profile, hostname, env.NAME より優先)
+    pub variables: HashMap<String, String>,
+    // ファイルを読むたびに、隣に置いたチェックサムや署名を確かめる
+    #[cfg(feature = "verify")]
+    pub verification: Option<Verification>,
+    // env:NAME などで読む環境変数。None ならプロセスの環境変数を読む (テストでは書き換えずに渡す)
+    pub env: Option<HashMap<String, String>>,
+}
+
+impl ParseOptions {
+    pub(crate) fn var(&self, name: &str) -> Option<String> {
+        match &self.env {
+            Some(env) => env.get(name).cloned(),
+            None => std::env::var(name).ok(),
+        }
+    }
+}
+
+#[cfg(feature = "std-fs")]
+pub fn parse(file_path: &str, schema_path: Option<&str>) -> Result<ConfList, Box<dyn Error>> {
+    parse_with_options(file_path, schema_path, &ParseOptions::default())
+}
+
+#[cfg(feature = "std-fs")]
+pub fn parse_with_options(file_path: &str, schema_path: Option<&str>, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema_path {
+            Some(path) => parse_schema(path, options)?,
+            None => HashMap::new(),
+        };
+        parse_file(file_path, &schema, options, stats)
+    })
+}
+
+// スキーマをファイルではなくコード (schema! など) で渡す
+#[cfg(feature = "std-fs")]
+pub fn parse_with_schema(file_path: &str, schema: &Schema, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+    metrics::observe(options, |stats| parse_file(file_path, schema, options, stats))
+}
+
+#[cfg(feature = "std-fs")]
+fn parse_file(file_path: &str, schema: &Schema, options: &ParseOptions, stats: &mut LoadStats) -> Result<ConfList, Box<dyn Error>> {
+    let mut ctx = ParseContext::new(schema, options);
+    let result = parse_conf(file_path, &mut ctx).and_then(|map| ctx.finish(map, file_path));
+    ctx.record(stats);
+    result
+}
+
+// ファイルを介さず文字列から読み込む
+pub fn parse_str(conf: &str, schema: Option<&str>) -> Result<ConfList, Box<dyn Error>> {
+    parse_str_with_options(conf, schema, &ParseOptions::default())
+}
+
+pub fn parse_str_with_options(conf: &str, schema: Option<&str>, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+    parse_str_from(conf, schema, options, "<string>")
+}
+
+pub fn parse_str_with_schema(conf: &str, schema: &Schema, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+    metrics::observe(options, |stats| parse_text(conf, schema, options, "<string>", stats))
+}
+
+// ファイル以外のリーダー (標準入力、wasm のバッファなど) から読み込む
+pub fn parse_reader<R: BufRead>(reader: R, schema: Option<&str>, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema {
+            Some(s) => parse_schema_lines(s.lines().map(str::to_string), options)?,
+            None => HashMap::new(),
+        };
+        let text = read_limited(reader, &options.limits).map_err(|e| format!("<reader>: {}", e))?;
+        let mut ctx = ParseContext::new(&schema, options);
+        let result = parse_conf_lines(text.lines().map(str::to_string), &mut ctx, "<reader>")
+            .and_then(|map| ctx.finish(map, "<reader>"));
+        ctx.record(stats);
+        result
+    })
+}
+
+// source は出どころとして記録する名前 (URL など)
+fn parse_str_from(conf: &str, schema: Option<&str>, options: &ParseOptions, source: &str) -> Result<ConfList, Box<dyn Error>> {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema {
+            Some(s) => parse_schema_lines(s.lines().map(str::to_string), options)?,
+            None => HashMap::new(),
+        };
+        parse_text(conf, &schema, options, source, stats)
+    })
+}
+
+fn parse_text(conf: &str, schema: &Schema, options: &ParseOptions, source: &str, stats: &mut LoadStats) -> Result<ConfList, Box<dyn Error>> {
+    let mut ctx = ParseContext::new(schema, options);
+    let result = parse_conf_lines(conf.lines().map(str::to_string), &mut ctx, source)
+        .and_then(|map| ctx.finish(map, source));
+    ctx.record(stats);
+    result
+}
+
+// 不正な値や取り込めないファイルで止めず、読み込めた部分と問題の一覧を返す (エディタやリンター向け)
+// スキーマ自体の誤りやファイルが読めないときは従来どおりエラーにする
+#[cfg(feature = "std-fs")]
+pub fn parse_partial(file_path: &str, schema_path: Option<&str>, options: &ParseOptions) -> Result<PartialConf, Box<dyn Error>> {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema_path {
+            Some(path) => parse_schema(path, options)?,
+            None => HashMap::new(),
+        };
+        let mut ctx = ParseContext::recovering(&schema, options);
+        let result = parse_conf(file_path, &mut ctx).and_then(|map| ctx.finish(map, file_path));
+        ctx.record(stats);
+        Ok(PartialConf { conf: result?, diagnostics: ctx.diagnostics.unwrap_or_default() })
+    })
+}
+
+pub fn parse_str_partial(conf: &str, schema: Option<&str>, options: &ParseOptions) -> Result<PartialConf, Box<dyn Error>> {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema {
+            Some(s) => parse_schema_lines(s.lines().map(str::to_string), options)?,
+            None => HashMap::new(),
+        };
+        let mut ctx = ParseContext::recovering(&schema, options);
+        let result = parse_conf_lines(conf.lines().map(str::to_string), &mut ctx, "<string>")
+            .and_then(|map| ctx.finish(map, "<string>"));
+        ctx.record(stats);
+        Ok(PartialConf { conf: result?, diagnostics: ctx.diagnostics.unwrap_or_default() })
+    })
+}
+
+// ディレクトリ内でパターンに一致するファイルを名前順に読み込み、後のファイルで上書きする
+#[cfg(feature = "std-fs")]
+pub fn parse_dir(dir: &str, pattern: &str, schema_path: Option<&str>) -> Result<ConfList, Box<dyn Error>> {
+    parse_dir_with_options(dir, pattern, schema_path, &ParseOptions::default())
+}
+
+#[cfg(feature = "std-fs")]
+pub fn parse_dir_with_options(dir: &str, pattern: &str, schema_path: Option<&str>, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema_path {
+            Some(path) => parse_schema(path, options)?,
+            None => HashMap::new(),
+        };
+        let mut map = ConfList::new();
+        let mut ctx = ParseContext::new(&schema, options);
+        // 読み込みは並列でも、マージは必ず名前順に行う。ほかのファイルのキーへの参照はマージしてから展開する
+        for (list, deferred) in parse_files(&list_files(dir, pattern)?, &schema, options, stats)? {
+            map.merge_by(list, merge::Strategies::new(&schema, options));
+            ctx.extend_deferred(deferred);
+        }
+        ctx.finish(map, dir)
+    })
+}
+
+// 読み込んだファイルと、その中でほかのキーを参照していて後で展開する値
+#[cfg(feature = "std-fs")]
+type ParsedFile = (ConfList, Vec<reference::Deferred>);
+
+#[cfg(all(feature = "std-fs", not(feature = "rayon")))]
+fn parse_files(files: &[String], schema: &Schema, options: &ParseOptions, stats: &mut LoadStats) -> Result<Vec<ParsedFile>, Box<dyn Error>> {
+    let mut ctx = ParseContext::new(schema, options);
+    let result = files.iter().map(|path| parse_conf(path, &mut ctx).map(|list| (list, ctx.take_deferred()))).collect();
+    ctx.record(stats);
+    result
+}
+
+// ファイルごとにスレッドプールで読み込む
+#[cfg(all(feature = "std-fs", feature = "rayon"))]
+fn parse_files(files: &[String], schema: &Schema, options: &ParseOptions, stats: &mut LoadStats) -> Result<Vec<ParsedFile>, Box<dyn Error>> {
+    use rayon::prelude::*;
+
+    // Box<dyn Error> はスレッドをまたげないので文字列にしておく
+    // キーの上限はすべてのファイルを合わせて数え、超えたところで逐次の読み込みと同じエラーにする
+    let keys = AtomicUsize::new(0);
+    let results: Vec<Result<(ParsedFile, LoadStats), String>> = files.par_iter()
+        .map(|path| {
+            let mut ctx = ParseContext { shared_keys: Some(&keys), ..ParseContext::new(schema, options) };
+            let list = parse_conf(path, &mut ctx).map_err(|e| e.to_string())?;
+            let mut stats = LoadStats::default();
+            ctx.record(&mut stats);
+            Ok(((list, ctx.take_deferred()), stats))
+        })
+        .collect();
+    let mut lists = Vec::new();
+    for result in results {
+        let (file, file_stats) = result?;
+        stats.files += file_stats.files;
+        stats.bytes += file_stats.bytes;
+        stats.keys += file_stats.keys;
+        lists.push(file);
+    }
+    Ok(lists)
+}
+
+// パターンに一致するファイルを名前順で返す
+#[cfg(feature = "std-fs")]
+fn list_files(dir: &str, pattern: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let matched = path.file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| glob_match(pattern, name));
+        if matched && path.is_file() {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+// 1 回の読み込みで共有する状態
+struct ParseContext<'a> {
+    schema: &'a Schema,
+    options: &'a ParseOptions,
+    // include 中のファイル (循環の検出用)
+    #[cfg(feature = "std-fs")]
+    include_stack: Vec<PathBuf>,
+    key_count: usize,
+    // 複数のファイルを並列で読むときに共有するキーの数
+    shared_keys: Option<&'a AtomicUsize>,
+    // 統計用に読んだファイル数とバイト数
+    files: usize,
+    bytes: usize,
+    interner: KeyInterner,
+    // 別のキーを参照していて、すべて読んでから追加する値
+    deferred: Vec<reference::Deferred>,
+    // 復旧モードでは、エラーで止めずにここへ集めて次の行へ進む
+    diagnostics: Option<Vec<Diagnostic>>,
+    // parse_streaming では値をツリーに追加せずにここへ渡し、スキーマにあるキーだけ読んだことを覚えておく
+    sink: Option<stream::Sink<'a>>,
+    streamed: HashSet<String>,
+}
+
+impl<'a> ParseContext<'a> {
+    fn new(schema: &'a Schema, options: &'a ParseOptions) -> Self {
+        ParseContext {
+            schema,
+            options,
+            #[cfg(feature = "std-fs")]
+            include_stack: Vec::new(),
+            key_count: 0,
+            shared_keys: None,
+            files: 0,
+            bytes: 0,
+            interner: KeyInterner::default(),
+            deferred: Vec::new(),
+            diagnostics: None,
+            sink: None,
+            streamed: HashSet::new(),
+        }
+    }
+
+    fn recovering(schema: &'a Schema, options: &'a ParseOptions) -> Self {
+        ParseContext { diagnostics: Some(Vec::new()), ..ParseContext::new(schema, options) }
+    }
+
+    // 復旧モードなら診断として記録し、そうでなければ出どころを付けたエラーにする
+    fn fail(&mut self, diagnostic: Diagnostic) -> Result<(), Box<dyn Error>> {
+        match &mut self.diagnostics {
+            Some(diagnostics) => {
+                diagnostics.push(diagnostic);
+                Ok(())
+            },
+            None => Err(diagnostic.to_string().into()),
+        }
+    }
+
+    // すべて読み込んだあと、ほかのキーを参照する値を展開し、ないキーに default を入れ、required のキーがそろっているか確かめる
+    // include したファイルの参照も、取り込んだ側のキーを含めてここでまとめて展開する
+    fn finish(&mut self, mut map: ConfList, source: &str) -> Result<ConfList, Box<dyn Error>> {
+        self.resolve_references(&mut map)?;
+        let schema = self.schema;
+        let mut keys: Vec<&String> = schema.keys().filter(|key| !self.streamed.contains(*key) && map.value_at(key, |_| ()).is_none()).collect();
+        keys.sort();
+        for key in keys {
+            let entry = &schema[key];
+            if let Some(default) = &entry.default {
+                let value = validate(default, entry, self.options).map_err(|e| format!("Invalid default for {}: {}", key, e))?;
+                match &mut self.sink {
+                    Some(sink) => sink(key, value, None)?,
+                    None => map.add_value_interned(key, value, None, entry.secret, None, &mut self.interner),
+                }
+            } else if entry.required {
+                let origin = Origin::new(source, None);
+                self.fail(Diagnostic::error(&origin, "missing-key", format!("Missing required key: {}", key)).path(key))?;
+            }
+        }
+        Ok(map)
+    }
+
+    fn count_key(&mut self) -> Result<(), String> {
+        self.key_count += 1;
+        let total = match self.shared_keys {
+            Some(keys) => keys.fetch_add(1, Ordering::Relaxed) + 1,
+            None => self.key_count,
+        };
+        match total > self.options.limits.max_keys {
+            true => Err(format!("Too many keys (limit: {})", self.options.limits.max_keys)),
+            false => Ok(()),
+        }
+    }
+
+    fn record(&self, stats: &mut LoadStats) {
+        stats.files += self.files;
+        stats.bytes += self.bytes;
+        stats.keys += self.key_count;
+        stats.count(self.diagnostics.as_deref().unwrap_or_default());
+    }
+}
+
+#[cfg(feature = "std-fs")]
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(ctx)))]
+fn parse_conf(file_path: &str, ctx: &mut ParseContext) -> Result<ConfList, Box<dyn Error>> {
+    let text = match read_text(file_path, ctx.options) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => return Err(format!("{}: {}", file_path, e).into()),
+        Err(_) => {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("config file not found, using an empty config");
+            return Ok(ConfList::new());
+        },
+    };
+    #[cfg(feature = "tracing")]
+    tracing::debug!("opened config file");
+    ctx.files += 1;
+    ctx.include_stack.push(std::fs::canonicalize(file_path)?);
+    let result = parse_conf_lines(text.lines().map(str::to_string), ctx, file_path);
+    ctx.include_stack.pop();
+    result
+}
+
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(lines, ctx)))]
+fn parse_conf_lines<I>(lines: I, ctx: &mut ParseContext, source: &str) -> Result<ConfList, Box<dyn Error>>
+where I: Iterator<Item = String>, {
+    #[cfg(feature = "tracing")]
+    let (started, key_count) = (std::time::Instant::now(), ctx.key_count);
+    let mut map = ConfList::new();
+    let mut documents = profile::Documents::new(ctx.options);
+    let mut conditions = condition::Conditions::new(ctx.options);
+    for (index, line) in lines.enumerate() {
+        ctx.bytes += line.len() + 1;
+        // Windows のエディタが付ける BOM を最初のキーの一部にしない
+        let line = if index == 0 { line.trim_start_matches('\u{feff}').to_string() } else { line };
+        let origin = Origin::new(source, Some(index + 1));
+        if let Err(e) = check_line_length(&line, &ctx.options.limits) {
+            ctx.fail(Diagnostic::error(&origin, "line-too-long", e))?;
+            continue;
+        }
+        if let Some(result) = documents.enter(&line) {
+            if let Err(e) = result {
+                ctx.fail(Diagnostic::error(&origin, "malformed-line", e).column(column(&line, line.trim_start())))?;
+            }
+            continue;
+        }
+        if !documents.is_selected() {
+            continue;
+        }
+        if let Some(result) = conditions.enter(&line, index + 1) {
+            if let Err(e) = result {
+                ctx.fail(Diagnostic::error(&origin, "invalid-condition", e).column(column(&line, line.trim_start())))?;
+            }
+            continue;
+        }
+        if !conditions.is_active() {
+            continue;
+        }
+        if let Some((path, optional)) = parse_include(&line) {
+            include(&mut map, path, optional, ctx, &origin, column(&line, path))?;
+            continue;
+        }
+        let (key, value) = match parse_line_checked(&line, ctx.options) {
+            Ok(Some(key_value)) => key_value,
+            Ok(None) => continue,
+            Err(e) => {
+                ctx.fail(Diagnostic::error(&origin, "malformed-line", e).column(column(&line, line.trim_start())))?;
+                continue;
+            },
+        };
+        if let Err(e) = ctx.count_key() {
+            // 復旧モードでも上限を超えたことは 1 度だけ報告し、残りのキーは読まない
+            if ctx.diagnostics.is_none() || ctx.key_count == ctx.options.limits.max_keys + 1 {
+                ctx.fail(Diagnostic::error(&origin, "too-many-keys", e).path(key).column(column(&line, key)))?;
+            }
+            continue;
+        }
+        if let Err(e) = add_entry(&mut map, key, value, ctx, origin.clone()) {
+            ctx.fail(Diagnostic::error(&origin, policy::error_code(e.as_ref()), e.to_string()).path(key).column(column(&line, value)))?;
+        }
+    }
+    if let Some((line, e)) = conditions.finish() {
+        ctx.fail(Diagnostic::error(&Origin::new(source, Some(line)), "invalid-condition", e))?;
+    }
+    #[cfg(feature = "tracing")]
+    tracing::debug!(keys = ctx.key_count - key_count, elapsed_us = started.elapsed().as_micros() as u64, "parsed source");
+    Ok(map)
+}
+
+// include <path> / include? <path> (見つからなくてもエラーにしない)
+fn parse_include(line: &str) -> Option<(&str, bool)> {
+    let l = line.trim();
+    let (rest, optional) = match l.strip_prefix("include?") {
+        Some(rest) => (rest, true),
+        None => (l.strip_prefix("include")?, false),
+    };
+    // include = ... はただのキー
+    if !rest.starts_with(char::is_whitespace) || rest.trim_start().starts_with('=') {
+        return None;
+    }
+    Some((rest.trim(), optional))
+}
+
+// 取り込むファイルを読み込んだ位置でマージする。パスは取り込む側のファイルからの相対パス
+#[cfg(feature = "std-fs")]
+fn include(map: &mut ConfList, path: &str, optional: bool, ctx: &mut ParseContext, origin: &Origin, column: usize) -> Result<(), Box<dyn Error>> {
+    let files = match include_targets(path, optional, origin) {
+        Ok(files) => files,
+        Err(e) => return ctx.fail(Diagnostic::error(origin, "include-not-found", e.to_string()).column(column)),
+    };
+    for file in files {
+        if ctx.include_stack.contains(&std::fs::canonicalize(&file)?) {
+            ctx.fail(Diagnostic::error(origin, "circular-include", format!("Circular include: {}", file)).column(column))?;
+            continue;
+        }
+        match parse_conf(&file, ctx) {
+            Ok(list) => map.merge_by(list, merge::Strategies::new(ctx.schema, ctx.options)),
+            // 取り込んだファイル自体が読めないときは include の行で報告する
+            Err(e) if ctx.diagnostics.is_some() => {
+                ctx.fail(Diagnostic::error(origin, "include-failed", e.to_string()).column(column))?;
+            },
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "std-fs"))]
+fn include(_map: &mut ConfList, _path: &str, _optional: bool, ctx: &mut ParseContext, origin: &Origin, column: usize) -> Result<(), Box<dyn Error>> {
+    ctx.fail(Diagnostic::error(origin, "include-unsupported", "include requires the std-fs feature").column(column))
+}
+
+// include で取り込むファイルを名前順で返す
+#[cfg(feature = "std-fs")]
+fn include_targets(path: &str, optional: bool, origin: &Origin) -> Result<Vec<String>, Box<dyn Error>> {
+    let base = match origin.source.as_str() {
+        "<string>" => PathBuf::new(),
+        source => Path::new(source).parent().map(Path::to_path_buf).unwrap_or_default(),
+    };
+    let target = base.join(path);
+    let name = target.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let files = if name.contains(['*', '?']) {
+        let dir = target.parent().map(|dir| dir.to_string_lossy().into_owned()).unwrap_or_default();
+        let dir = if dir.is_empty() { ".".to_string() } else { dir };
+        list_files(&dir, name).unwrap_or_default()
+    } else if target.is_file() {
+        vec![target.to_string_lossy().into_owned()]
+    } else {
+        Vec::new()
+    };
+    if files.is_empty() && !optional {
+        return Err(format!("Included file not found: {}", path).into());
+    }
+    Ok(files)
+}
+
+// 1 件分の値を検証してツリーに追加する
+fn add_entry(map: &mut ConfList, key: &str, value: &str, ctx: &mut ParseContext, origin: Origin) -> Result<(), Box<dyn Error>> {
+    add_entry_value(map, key, value, false, ctx, origin)
+}
+
+// quoted はインラインテーブルの中で引用符に囲まれていた値。リストやテーブルとしては読まない
+fn add_entry_value(map: &mut ConfList, key: &str, value: &str, quoted: bool, ctx: &mut ParseContext, origin: Origin) -> Result<(), Box<dyn Error>> {
+    let options = ctx.options;
+    let key = path::unquote_key(key)?;
+    let key = options.keys.normalize(&key)?;
+    let key = key.as_ref();
+    check_entry_limits(key, value, &options.limits)?;
+    if !quoted && reads_table(ctx.schema, key, options) {
+        if let Some(entries) = inline::parse_table(value, options.limits.max_depth)? {
+            return add_table(map, key, entries, ctx, origin);
+        }
+    }
+    if ctx.has_references(value) {
+        if ctx.sink.is_some() {
+            return Err("Key references are not supported when streaming".into());
+        }
+        return ctx.defer(map, key, value, quoted, origin);
+    }
+    ctx.forget_deferred(key);
+    let written = value;
+    let secret = ctx.schema.get(key).is_some_and(|entry| entry.secret);
+    let mut origin = origin;
+    let value = resolve_value_recorded(value, secret, options, &mut origin.interpolations)?;
+    let secret = secret || options.secret_references && is_secret_reference(written) || written.starts_with("ENC(");
+    options.policy.check(key, written, &value)?;
+    let typed_value = match ctx.schema.get(key) {
+        Some(entry) => {
+            #[cfg(feature = "tracing")]
+            tracing::trace!(key, "validating against schema");
+            validate(&value, entry, options)?
+        },
+        None if quoted => ConfValue::StrValue(value.into_owned()),
+        None => untyped_value(&value, options),
+    };
+    #[cfg(feature = "std-fs")]
+    let typed_value = match (ctx.schema.get(key), typed_value, ctx.include_stack.last()) {
+        (Some(entry), ConfValue::StrValue(path), Some(file)) if entry.ty == SchemaType::Path && options.expand_paths => {
+            ConfValue::StrValue(expand::resolve_relative(path, file))
+        },
+        (_, value, _) => value,
+    };
+    let raw = match &typed_value {
+        ConfValue::StrValue(v) if v == written => None,
+        _ => Some(written.into()),
+    };
+    if let Some(sink) = &mut ctx.sink {
+        if ctx.schema.contains_key(key) {
+            ctx.streamed.insert(key.to_string());
+        }
+        return sink(key, typed_value, Some(&origin));
+    }
+    let key = conflict::check(map, key, options.conflicts, &mut ctx.interner)?;
+    map.add_value_interned(&key, typed_value, Some(origin), secret, raw, &mut ctx.interner);
+    Ok(())
+}
+
+// { host = localhost, port = 5432 } を key.host / key.port として追加する
+// 行として数えた 1 件を最初の要素に当て、残りの要素もキーとして数える
+fn add_table(map: &mut ConfList, key: &str, entries: Vec<(&str, inline::TableValue)>, ctx: &mut ParseContext, origin: Origin) -> Result<(), Box<dyn Error>> {
+    if entries.is_empty() {
+        // {} はセクションだけ作る。すでにあればそのまま
+        conflict::check_section(map, key, ctx.options.conflicts, &mut ctx.interner)?;
+        if map.value_at(key, |v| v.as_conf().is_err()).unwrap_or(true) {
+            map.add_value_interned(key, ConfValue::Conf(Box::default()), Some(origin), false, None, &mut ctx.interner);
+        }
+        return Ok(());
+    }
+    for (i, (sub_key, value)) in entries.into_iter().enumerate() {
+        if i > 0 {
+            ctx.count_key()?;
+        }
+        let path = format!("{}.{}", key, sub_key);
+        match value {
+            inline::TableValue::Raw(v) => add_entry_value(map, &path, v, false, ctx, origin.clone())?,
+            inline::TableValue::Quoted(v) => add_entry_value(map, &path, &v, true, ctx, origin.clone())?,
+        }
+    }
+    Ok(())
+}
+
+fn check_line_length(line: &str, limits: &Limits) -> Result<(), String> {
+    if line.len() > limits.max_line_length {
+        return Err(format!("Line is too long (limit: {} bytes)", limits.max_line_length));
+    }
+    Ok(())
+}
+
+fn check_entry_limits(key: &str, value: &str, limits: &Limits) -> Result<(), Box<dyn Error>> {
+    if path::parent_paths(key).len() >= limits.max_depth {
+        return Err(format!("Key is nested too deeply (limit: {}): {}", limits.max_depth, key).into());
+    }
+    if value.len() > limits.max_value_length {
+        return Err(format!("Value is too long (limit: {} bytes): {}", limits.max_value_length, key).into());
+    }
+    Ok(())
+}
+
+// 型チェックの前にシークレットの参照、${...} の展開、復号を行う。何もしなければ借用のまま返す
+// secret はスキーマで secret と指定されたキーか。env: / file: はそのキー (と ParseOptions::secret_references のとき) だけ参照として読む
+fn resolve_value<'v>(value: &'v str, secret: bool, options: &ParseOptions) -> Result<Cow<'v, str>, Box<dyn Error>> {
+    resolve_value_recorded(value, secret, options, &mut Vec::new())
+}
+
+// resolve_value と同じだが、${...} をどう展開したかを interpolations に追加する
+fn resolve_value_recorded<'v>(value: &'v str, secret: bool, options: &ParseOptions, interpolations: &mut Vec<Interpolation>) -> Result<Cow<'v, str>, Box<dyn Error>> {
+    let mut value = value;
+    if secret || options.secret_references {
+        // 参照は書かれたままの値だけ。${...} を展開した結果や参照先の内容は、もう一度参照として読まない
+        if let Some(resolved) = resolve_secret(value, options)? {
+            return decrypt(Cow::Owned(resolved), options);
+        }
+        // \env: / \file: は参照ではなく、env: / file: で始まる文字列
+        if let Some(literal) = value.strip_prefix('\\').filter(|v| v.starts_with("env:") || v.starts_with("file:")) {
+            value = literal;
+        }
+    }
+    let value = match &options.interpolation {
+        Some(interpolator) if value.contains('$') => {
+            // 展開後の値はシークレットを含みうるので出力しない
+            #[cfg(feature = "tracing")]
+            tracing::trace!("expanding placeholders");
+            let (expanded, recorded) = interpolator.expand_recorded_with(value, &|name| options.var(name))?;
+            interpolations.extend(recorded);
+            Cow::Owned(expanded)
+        },
+        _ => Cow::Borrowed(value),
+    };
+    decrypt(value, options)
+}
+
+// ENC(...) の値を復号する
+fn decrypt<'a>(value: Cow<'a, str>, options: &ParseOptions) -> Result<Cow<'a, str>, Box<dyn Error>> {
+    let cipher = match value.strip_prefix("ENC(").and_then(|v| v.strip_suffix(')')) {
+        Some(cipher) => cipher,
+        None => return Ok(value),
+    };
+    match &options.decryptor {
+        Some(decryptor) => Ok(Cow::Owned(decryptor(cipher)?)),
+        None => Err("Encrypted value found but no decryptor is configured".into()),
+    }
+}
+
+fn is_secret_reference(value: &str) -> bool {
+    value.starts_with("env:") || value.starts_with("file:") || value.starts_with("ENC(")
+}
+
+// シークレットの参照を解決する。参照でなければ None
+fn resolve_secret(value: &str, options: &ParseOptions) -> Result<Option<String>, Box<dyn Error>> {
+    if let Some(name) = value.strip_prefix("env:") {
+        return match options.var(name) {
+            Some(v) => Ok(Some(v)),
+            None => Err(format!("Environment variable not found: {}", name).into()),
+        };
+    }
+    if let Some(path) = value.strip_prefix("file:") {
+        return read_secret_file(path).map(Some);
+    }
+    Ok(None)
+}
+
+#[cfg(feature = "std-fs")]
+fn read_secret_file(path: &str) -> Result<String, Box<dyn Error>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read secret file {}: {}", path, e))?;
+    // ファイル末尾の改行は値に含めない
+    Ok(content.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(not(feature = "std-fs"))]
+fn read_secret_file(path: &str) -> Result<String, Box<dyn Error>> {
+    Err(format!("Reading secret file {} requires the std-fs feature", path).into())
+}
+
+fn validate(s: &str, entry: &SchemaEntry, options: &ParseOptions) -> Result<ConfValue, String> {
+    let mut s = Cow::Borrowed(s);
+    for name in &entry.transforms {
+        s = transform::apply(name, s, options)?;
+    }
+    let s = s.as_ref();
+    entry.check_pattern(s)?;
+    match entry.ty {
+        SchemaType::String => Ok(ConfValue::StrValue(s.to_string())),
+        SchemaType::Path if options.expand_paths => expand::expand_path(s, std::env::consts::OS, |name| options.var(name)).map(ConfValue::StrValue),
+        SchemaType::Path => Ok(ConfValue::StrValue(s.to_string())),
+        SchemaType::Bool => match s {
+            "true" => Ok(ConfValue::BoolValue(true)),
+            "false" => Ok(ConfValue::BoolValue(false)),
+            _ => Err("Invalid boolean value".to_string()),
+        },
+        SchemaType::Number => {
+            if let Ok(number) = f64::from_str(s) {
+                entry.check_range(number)?;
+                Ok(ConfValue::NumberValue(number))
+            } else {
+                Err("Invalid number value".to_string())
+            }
+        },
+        #[cfg(feature = "decimal")]
+        SchemaType::Decimal => {
+            let decimal = Decimal::from_str(s).map_err(|_| "Invalid decimal value".to_string())?;
+            entry.check_range(integer::decimal_as_f64(&decimal))?;
+            Ok(ConfValue::DecimalValue(decimal))
+        },
+        SchemaType::Bytes => bytes::decode(s).map(ConfValue::Bytes),
+        SchemaType::Uuid if is_uuid(s) => Ok(ConfValue::StrValue(s.to_string())),
+        SchemaType::Uuid => Err("Invalid UUID value".to_string()),
+        SchemaType::Country => iso::country(s).map(ConfValue::StrValue),
+        SchemaType::Currency => iso::currency(s).map(ConfValue::StrValue),
+        SchemaType::Locale => locale::check(s).map(|_| ConfValue::StrValue(s.to_string())),
+        SchemaType::Mime => media::check_mime(s).map(|_| ConfValue::StrValue(s.to_string())),
+        SchemaType::Charset => media::check_charset(s).map(|_| ConfValue::StrValue(s.to_string())),
+        SchemaType::Cron => cron::validate(s).map(|_| ConfValue::StrValue(s.to_string())),
+        SchemaType::Endpoint => {
+            s.parse::<endpoint::Endpoint>()?;
+            Ok(ConfValue::StrValue(s.to_string()))
+        },
+        SchemaType::LogLevel => level::normalize(s).map(|level| ConfValue::StrValue(level.to_string())),
+        SchemaType::Semver => {
+            entry.check_versions(&s.parse()?)?;
+            Ok(ConfValue::StrValue(s.to_string()))
+        },
+        // セクション ({ ... } やドット区切りのキー) は validate を通らない
+        SchemaType::Object => Err("Expected a section but found a value".to_string()),
+        SchemaType::Any => Ok(untyped_value(s, options)),
+        SchemaType::List => {
+            let list = inline::parse_list_value(s, options.limits.max_depth)?.ok_or_else(|| "Invalid list value".to_string())?;
+            match (&entry.items, list) {
+                (Some(schema), ConfValue::List(items)) => validate_items(items, schema, options),
+                (_, list) => Ok(list),
+            }
+        },
+    }
+}
+
+// 8-4-4-4-12 桁の 16 進数 (大文字も可)。{...} や urn:uuid: の付いた形は受け付けない
+fn is_uuid(s: &str) -> bool {
+    s.len() == 36 && s.bytes().enumerate().all(|(i, b)| match i {
+        8 | 13 | 18 | 23 => b == b'-',
+        _ => b.is_ascii_hexdigit(),
+    })
+}
+
+// { ... } をインラインテーブルとして読むか。スキーマの型が object のキーと、ParseOptions::inline_tables のときのスキーマにないキーだけ
+fn reads_table(schema: &Schema, key: &str, options: &ParseOptions) -> bool {
+    match schema.get(key) {
+        Some(entry) => entry.ty == SchemaType::Object,
+        None => options.inline_tables,
+    }
+}
+
+// 型の決まっていない値。[ ... ] はリストとして読むが、読めなければ [%d] %m [%l] のような書式とみなして文字列のまま
+fn untyped_value(s: &str, options: &ParseOptions) -> ConfValue {
+    match inline::parse_list_value(s, options.limits.max_depth) {
+        Ok(Some(list)) => list,
+        _ if options.infer_types => infer_value(s).unwrap_or_else(|| ConfValue::StrValue(s.to_string())),
+        _ => ConfValue::StrValue(s.to_string()),
+    }
+}
+
+// スキーマにない値の型を推測する。推測できなければ None で、文字列のまま
+fn infer_value(s: &str) -> Option<ConfValue> {
+    match s {
+        "true" => return Some(ConfValue::BoolValue(true)),
+        "false" => return Some(ConfValue::BoolValue(false)),
+        _ => {},
+    }
+    if let Some(quoted) = inline::parse_quoted(s) {
+        return Some(ConfValue::StrValue(quoted.into_owned()));
+    }
+    // inf や nan は数として読まない
+    let digits = s.strip_prefix(['-', '+']).unwrap_or(s);
+    if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.') || !digits.bytes().all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b)) {
+        return None;
+    }
+    let is_integer = digits.bytes().all(|b| b.is_ascii_digit());
+    // 0 で始まる 007 のような値 (郵便番号など) と、f64 で正確に表せない大きな整数は文字列のまま
+    if is_integer && (digits.len() > 1 && digits.starts_with('0') || digits.len() > 15) {
+        return None;
+    }
+    f64::from_str(s).ok().map(ConfValue::NumberValue)
+}
+
+// 要素のテーブルごとにフィールドを検証して型を付ける。エラーはすべての要素から集め、[1].port のように位置を付ける
+fn validate_items(items: Vec<ConfValue>, schema: &Schema, options: &ParseOptions) -> Result<ConfValue, String> {
+    let mut fields: Vec<&String> = schema.keys().collect();
+    fields.sort();
+    let mut errors = Vec::new();
+    let mut validated = Vec::with_capacity(items.len());
+    for (i, item) in items.into_iter().enumerate() {
+        let mut conf = match item {
+            ConfValue::Conf(conf) => conf,
+            item => {
+                errors.push(format!("[{}]: Expected a table but found {}", i, item));
+                continue;
+            },
+        };
+        for field in &fields {
+            let entry = &schema[*field];
+            let written = conf.value_at(field, |value| match value {
+                ConfValue::Conf(_) => None,
+                value => Some(value.to_string()),
+            });
+            let value = match (written, &entry.default) {
+                (Some(Some(value)), _) => validate(&value, entry, options),
+                (Some(None), _) => Err("Expected a value but found a section".to_string()),
+                (None, Some(default)) => validate(default, entry, options),
+                (None, None) if entry.required => Err("Missing required key".to_string()),
+                (None, None) => continue,
+            };
+            match value {
+                Ok(value) => {
+                    conf.insert(field, value);
+                },
+                Err(e) => errors.push(format!("[{}].{}: {}", i, field, e)),
+            }
+        }
+        validated.push(ConfValue::Conf(conf));
+    }
+    match errors.is_empty() {
+        true => Ok(ConfValue::List(validated)),
+        false => Err(errors.join("; ")),
+    }
+}
+
+// スキーマファイルを読み込む。ファイルがなければ空のスキーマ
+#[cfg(feature = "std-fs")]
+pub fn parse_schema(file_path: &str, options: &ParseOptions) -> Result<Schema, Box<dyn Error>> {
+    match read_text(file_path, options) {
+        Ok(text) => parse_schema_lines(text.lines().map(str::to_string), options),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => Err(format!("{}: {}", file_path, e).into()),
+        Err(_) => Ok(HashMap::new()),
+    }
+}
+
+pub fn parse_schema_str(schema: &str, options: &ParseOptions) -> Result<Schema, Box<dyn Error>> {
+    parse_schema_lines(schema.lines().map(str::to_string), options)
+}
+
+fn parse_schema_lines<I>(lines: I, options: &ParseOptions) -> Result<Schema, Box<dyn Error>>
+where I: Iterator<Item = String>, {
+    let mut map: Schema = HashMap::new();
+    for line in lines {
+        let key_value = parse_schema_line(&line);
+        if key_value.is_none() {
+            continue;
+        }
+        let (key, t): (&str, &str) = key_value.unwrap();
+        if let Some(key) = key.strip_suffix("[]") {
+            let items = parse_item_schema(key, t, options)?;
+            let key = options.keys.normalize(key)?.into_owned();
+            map.entry(key).or_insert_with(|| SchemaEntry::new(SchemaType::List)).items = Some(items);
+            continue;
+        }
+        let key = options.keys.normalize(key)?;
+        let (t, pattern) = match t.split_once('~') {
+            Some((t, pattern)) => (t.trim(), Some(pattern.trim())),
+            None => (t, None),
+        };
+        let mut parts = t.split('|').map(str::trim);
+        let head = parts.next().unwrap_or_default();
+        // semver(>=1.2, <2) のように、型の直後の括弧に条件を書ける
+        let (t, req, markers) = match head.split_once('(') {
+            Some((t, rest)) => match rest.split_once(')') {
+                Some((req, markers)) => (t.trim(), Some(req), markers),
+                None => return Err(format!("Missing ) in schema type for {}", key).into()),
+            },
+            None => head.split_once(char::is_whitespace).map_or((head, None, ""), |(t, markers)| (t, None, markers)),
+        };
+        let mut entry = SchemaEntry::new(t.parse::<SchemaType>()?);
+        if let Some(req) = req {
+            if entry.ty != SchemaType::Semver {
+                return Err(format!("Schema type {} of {} does not take a requirement", t, key).into());
+            }
+            entry.versions = Some(req.parse()?);
+        }
+        for marker in markers.split_whitespace() {
+            match marker {
+                "secret" => entry.secret = true,
+                "required" => entry.required = true,
+                "deep" | "replace" | "append" | "unique" => entry.merge = Some(marker.parse()?),
+                _ => return Err(format!("Unknown schema marker for {}: {}", key, marker).into()),
+            }
+        }
+        entry.transforms = parts.map(str::to_string).collect();
+        #[cfg(feature = "regex")]
+        {
+            entry.pattern = pattern.map(Regex::new).transpose()?;
+        }
+        #[cfg(not(feature = "regex"))]
+        if pattern.is_some() {
+            return Err(format!("Pattern constraint on {} requires the regex feature", key).into());
+        }
+        // key[] の行が先にあっても要素のスキーマは残す
+        let key = key.into_owned();
+        entry.items = map.remove(&key).and_then(|old| old.items);
+        map.insert(key, entry);
+    }
+    Ok(map)
+}
+
+// { host -> string, port -> number required } を、1 つずつの行として読む
+// カンマで区切るので、要素のスキーマの pattern にはカンマを書けない
+fn parse_item_schema(key: &str, t: &str, options: &ParseOptions) -> Result<Schema, Box<dyn Error>> {
+    let fields = t.strip_prefix('{').and_then(|t| t.strip_suffix('}'))
+        .ok_or_else(|| format!("Schema for items of {} must be written in braces: {}", key, t))?;
+    parse_schema_lines(split_fields(fields).into_iter().map(str::to_string), options)
+}
+
+// フィールドをカンマで分ける。semver(>=1, <2) のように括弧の中のカンマでは分けない
+fn split_fields(fields: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in fields.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                parts.push(&fields[start..i]);
+                start = i + 1;
+            },
+            _ => {},
+        }
+    }
+    parts.push(&fields[start..]);
+    parts
+}
+
+type KeyValue<'a> = (&'a str, &'a str);
+fn parse_line(line: &str) -> Option<KeyValue<'_>> {
+    if is_blank_or_comment(line) {
+        return None;
+    }
+    let vec = line.splitn(2, '=').collect::<Vec<&str>>();
+    if vec.len() != 2 {
+        return None;
+    }
+    let key = vec[0].trim();
+    let value = vec[1].trim();
+    if key.is_empty() || value.is_empty() {
+        return None;
+    }
+    Some((key, value))
+}
+
+// part は line の一部分で、その開始位置を 1 始まりの文字数で返す
+fn column(line: &str, part: &str) -> usize {
+    let offset = (part.as_ptr() as usize).saturating_sub(line.as_ptr() as usize).min(line.len());
+    line[..offset].chars().count() + 1
+}
+
+// シェルの "export KEY=value" の export を取り除く。"export = 1" はキーが export の行として残す
+fn strip_export(line: &str) -> &str {
+    let rest = match line.trim_start().strip_prefix("export") {
+        Some(rest) if rest.starts_with([' ', '\t']) => rest.trim_start(),
+        _ => return line,
+    };
+    match rest.starts_with('=') {
+        true => line,
+        false => rest,
+    }
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    let l = line.trim();
+    l.is_empty() || l.starts_with('#') || l.starts_with(';')
+}
+
+// strict なら、空行とコメント以外で読み飛ばす行 (= がない、キーや値が空) をエラーにする
+fn parse_line_checked<'a>(line: &'a str, options: &ParseOptions) -> Result<Option<KeyValue<'a>>, String> {
+    let line = match options.allow_export {
+        true => strip_export(line),
+        false => line,
+    };
+    let key_value = parse_line(line);
+    if key_value.is_some() || !options.strict || is_blank_or_comment(line) {
+        return Ok(key_value);
+    }
+    let reason = match line.split_once('=') {
+        None => "missing '='",
+        Some((key, _)) if key.trim().is_empty() => "missing key",
+        Some(_) => "missing value",
+    };
+    Err(format!("Malformed line ({}): {}", reason, line.trim()))
+}
+
+// * と ? だけをサポートする簡易的なグロブ
+fn glob_match(pattern: &str, s: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = s.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    // 直前の * の位置と、そこから試しているテキストの位置
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+fn parse_schema_line(line: &str) -> Option<KeyValue<'_>> {
+    let l = line.trim();
+    if l.is_empty() {
+        return None;
+    }
+    let (key, value) = l.split_once("->")?;
+    let (key, value) = (key.trim(), value.trim());
+    if key.is_empty() || value.is_empty() {
+        return None;
     }
     Some((key, value))
 }
 
-fn read_lines<P>(file_path: P) -> io::Result<io::Lines<io::BufReader<File>>>
+#[cfg(feature = "std-fs")]
+fn read_text<P>(file_path: P, options: &ParseOptions) -> io::Result<String>
 where P: AsRef<Path>, {
-    let file = File::options().read(true).open(file_path)?;
-    Ok(io::BufReader::new(file).lines())
+    let mut bytes = Vec::new();
+    // 大きさの分からない /dev/zero やパイプでも上限の 1 バイト先までしか読まない
+    std::fs::File::open(&file_path)?.take(options.limits.max_file_size + 1).read_to_end(&mut bytes)?;
+    check_file_size(bytes.len(), &options.limits)?;
+    #[cfg(feature = "verify")]
+    if let Some(verification) = &options.verification {
+        verification.check_file(file_path.as_ref(), &bytes)?;
+    }
+    encoding::decode(bytes, options.encoding).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn read_limited<R: Read>(reader: R, limits: &Limits) -> std::io::Result<String> {
+    let mut text = String::new();
+    reader.take(limits.max_file_size + 1).read_to_string(&mut text)?;
+    check_file_size(text.len(), limits)?;
+    Ok(text)
+}
+
+fn check_file_size(len: usize, limits: &Limits) -> std::io::Result<()> {
+    if len as u64 > limits.max_file_size {
+        let message = format!("File is too large (limit: {} bytes)", limits.max_file_size);
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message));
+    }
+    Ok(())
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std-fs"))]
 mod tests {
     use super::*;
 
@@ -384,4 +2149,527 @@ mod tests {
             ])),
         ]);
     }
+    // tests/secret.conf が env: で読む環境変数
+    fn secret_env() -> ParseOptions {
+        let env = HashMap::from([("CONF_LOADER_TEST_DB_PORT".to_string(), "5432".to_string())]);
+        ParseOptions { env: Some(env), ..Default::default() }
+    }
+    #[test]
+    fn can_resolve_secret_references() {
+        let options = secret_env();
+        let mut conf = parse_with_options("tests/secret.conf", Some("tests/secret.schema"), &options).unwrap();
+        assert_eq!(conf.to_vec(), vec![
+            ("db".to_string(), ConfVecValue::Conf(vec![
+                ("password".to_string(), ConfVecValue::StrValue("s3cr3t".to_string())),
+                ("port".to_string(), ConfVecValue::NumberValue(5432.0)),
+            ])),
+        ]);
+        assert!(conf.get("db").unwrap().as_conf().is_ok());
+
+        // secret でないキーの env: / file: はただの文字列。\file: は secret のキーでも文字列
+        let schema = "token -> string secret\n";
+        let conf = parse_str("path = file:tests/secret.txt\ntoken = \\file:tests/secret.txt\n", Some(schema)).unwrap();
+        assert_eq!(conf.get_str("path").unwrap(), "file:tests/secret.txt");
+        assert_eq!(conf.get_str("token").unwrap(), "file:tests/secret.txt");
+        assert!(!conf.is_secret("path"));
+        // ${...} を展開した結果は参照として読まない
+        let env = HashMap::from([("CONF_LOADER_TEST_SECRET_REF".to_string(), "file:tests/secret.txt".to_string())]);
+        let options = ParseOptions { interpolation: Some(Interpolator::new()), env: Some(env), ..Default::default() };
+        let conf = parse_str_with_options("token = ${CONF_LOADER_TEST_SECRET_REF}\n", Some(schema), &options).unwrap();
+        assert_eq!(conf.get_str("token").unwrap(), "file:tests/secret.txt");
+        let options = ParseOptions { secret_references: true, ..Default::default() };
+        let conf = parse_str_with_options("path = file:tests/secret.txt\n", None, &options).unwrap();
+        assert_eq!(conf.get_str("path").unwrap(), "s3cr3t");
+        assert!(conf.is_secret("path"));
+    }
+    #[test]
+    fn can_flatten_with_redacted_secrets() {
+        let mut conf = parse_with_options("tests/secret.conf", Some("tests/secret.schema"), &secret_env()).unwrap();
+        conf.merge(parse_str("db.user = app\napi.token = abc\napi.url = https://example.com\n", Some("api.token -> string secret\n")).unwrap());
+        let flat = conf.to_flat_map(true);
+        assert_eq!(flat.len(), 5);
+        assert_eq!(flat["db.password"], REDACTED);
+        assert_eq!(flat["db.port"], REDACTED);
+        assert_eq!(flat["api.token"], REDACTED);
+        assert_eq!(flat["api.url"], "https://example.com");
+        assert_eq!(conf.to_flat_map(false)["db.password"], "s3cr3t");
+        assert!(conf.is_secret("api.token") && !conf.is_secret("db.user"));
+        assert!(parse_str("a = b\n", Some("a -> string hidden\n")).is_err());
+    }
+    #[test]
+    fn can_build_conf_from_iterators() {
+        let mut conf: ConfList = vec![
+            ("endpoint".to_string(), ConfValue::StrValue("localhost:3000".to_string())),
+            ("log.file".to_string(), ConfValue::StrValue("/var/log/console.log".to_string())),
+        ].into_iter().collect();
+        conf.extend([("log.level".to_string(), ConfValue::NumberValue(3.0))]);
+        assert_eq!(conf.to_vec(), vec![
+            ("endpoint".to_string(), ConfVecValue::StrValue("localhost:3000".to_string())),
+            ("log".to_string(), ConfVecValue::Conf(vec![
+                ("file".to_string(), ConfVecValue::StrValue("/var/log/console.log".to_string())),
+                ("level".to_string(), ConfVecValue::NumberValue(3.0)),
+            ])),
+        ]);
+
+        let map = HashMap::from([("db.port".to_string(), "5432".to_string()), ("db.host".to_string(), "localhost".to_string())]);
+        let conf = ConfList::from(map);
+        assert_eq!(conf.to_flat_map(false)["db.port"], "5432");
+        assert_eq!(conf.leaves()[0].0, "db.host");
+    }
+    #[test]
+    fn can_read_files_with_bom_and_other_encodings() {
+        let mut conf = parse("tests/bom.conf", Some("tests/data.schema")).unwrap();
+        assert_eq!(conf.get("endpoint").unwrap().as_str().unwrap(), "localhost:3000");
+        let mut conf = parse_str("\u{feff}debug = true\n", Some("debug -> bool\n")).unwrap();
+        assert!(conf.get("debug").unwrap().as_bool().unwrap());
+
+        let err = parse("tests/latin1.conf", None).unwrap_err();
+        assert_eq!(err.to_string(), "tests/latin1.conf: Invalid UTF-8 at line 2, column 11: byte 0xe9");
+        let options = ParseOptions { encoding: Encoding::Latin1, ..Default::default() };
+        let mut conf = parse_with_options("tests/latin1.conf", None, &options).unwrap();
+        assert_eq!(conf.get("name").unwrap().as_str().unwrap(), "café");
+    }
+    #[test]
+    fn fails_on_missing_secret_reference() {
+        let options = ParseOptions { secret_references: true, env: Some(HashMap::new()), ..Default::default() };
+        let result = parse_with_options("tests/secret-missing.conf", None, &options);
+        assert!(result.is_err());
+    }
+    #[test]
+    fn can_decrypt_encrypted_values() {
+        let options = ParseOptions {
+            decryptor: Some(Box::new(|cipher: &str| Ok(cipher.chars().rev().collect()))),
+            ..Default::default()
+        };
+        let mut conf = parse_with_options("tests/encrypted.conf", Some("tests/encrypted.schema"), &options).unwrap();
+        assert_eq!(conf.to_vec(), vec![
+            ("user".to_string(), ConfVecValue::StrValue("admin".to_string())),
+            ("password".to_string(), ConfVecValue::StrValue("s3cr3t".to_string())),
+            ("pin".to_string(), ConfVecValue::NumberValue(1234.0)),
+        ]);
+        assert_eq!(conf.get("password").unwrap().as_str().unwrap(), "s3cr3t");
+        // 復号関数がなければエラーになる
+        assert!(parse("tests/encrypted.conf", None).is_err());
+    }
+    #[test]
+    fn can_parse_conf_from_str() {
+        let mut conf = parse_str("port = 8080\nlog.file = /tmp/app.log\n", Some("port -> number\n")).unwrap();
+        assert_eq!(conf.get("port").unwrap().as_number().unwrap(), 8080.0);
+        assert!(conf.get("log").unwrap().as_conf().is_ok());
+    }
+    #[test]
+    fn can_report_malformed_lines_in_strict_mode() {
+        let conf = "# comment\nport 8080\n= value\nname =\nhost = example.com\n";
+        assert_eq!(parse_str(conf, None).unwrap().to_flat_map(false).len(), 1);
+
+        let options = ParseOptions { strict: true, ..Default::default() };
+        let err = parse_str_with_options(conf, None, &options).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:2: Malformed line (missing '='): port 8080");
+        let partial = parse_str_partial(conf, None, &options).unwrap();
+        let messages: Vec<String> = partial.diagnostics.iter().map(ToString::to_string).collect();
+        assert_eq!(messages, [
+            "<string>:2: Malformed line (missing '='): port 8080",
+            "<string>:3: Malformed line (missing key): = value",
+            "<string>:4: Malformed line (missing value): name =",
+        ]);
+        assert!(borrowed::parse_borrowed(conf, None, &options).is_err());
+    }
+    #[test]
+    fn can_strip_shell_export() {
+        let conf = "export DB_HOST=localhost\nexport\tDB_PORT = 5432\nexport = yes\n";
+        let flat = parse_str(conf, None).unwrap().to_flat_map(false);
+        assert!(flat.contains_key("export DB_HOST"));
+
+        let options = ParseOptions { allow_export: true, strict: true, ..Default::default() };
+        let flat = parse_str_with_options(conf, Some("DB_PORT -> number\n"), &options).unwrap().to_flat_map(false);
+        assert_eq!(flat["DB_HOST"], "localhost");
+        assert_eq!(flat["DB_PORT"], "5432");
+        assert_eq!(flat["export"], "yes");
+        assert_eq!(flat.len(), 3);
+    }
+    #[test]
+    fn can_parse_list_literals() {
+        let text = "hosts = [a, \"b, c\", [d]]\nplain = a, b\nraw = [x, y]\nports = [80, 443]\n";
+        let mut conf = parse_str(text, Some("raw -> string\nports -> list\n")).unwrap();
+        let hosts = conf.get("hosts").unwrap().as_list().unwrap().clone();
+        assert_eq!(hosts.len(), 3);
+        assert_eq!(hosts[1].as_str().unwrap(), "b, c");
+        assert_eq!(hosts[2].as_list().unwrap()[0].as_str().unwrap(), "d");
+        assert_eq!(conf.get("plain").unwrap().as_str().unwrap(), "a, b");
+        // string のスキーマでは書いたままの文字列
+        assert_eq!(conf.get("raw").unwrap().as_str().unwrap(), "[x, y]");
+        let config = conf.clone().freeze();
+        let ports: Vec<u16> = crate::typed::field(&config, "ports").unwrap();
+        assert_eq!(ports, [80, 443]);
+        assert_eq!(conf.to_json(&WriteOptions::default()), r#"{"hosts":["a","b, c",["d"]],"plain":"a, b","raw":"[x, y]","ports":["80","443"]}"#);
+        let written = conf.to_conf_string(&WriteOptions::default());
+        assert!(parse_str(&written, None).unwrap().diff(&parse_str(text, None).unwrap()).is_empty());
+
+        let err = parse_str("ports = 80\n", Some("ports -> list\n")).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Invalid list value");
+        let err = parse_str("hosts = [a, \"b]\n", Some("hosts -> list\n")).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Invalid list value: unterminated string");
+        // スキーマにない値は、リストとして読めなければ文字列のまま
+        let conf = parse_str("pattern = [%d] %m [%l]\nhosts = [a, \"b]\n", None).unwrap();
+        assert_eq!(conf.get_str("pattern").unwrap(), "[%d] %m [%l]");
+        assert_eq!(conf.get_str("hosts").unwrap(), "[a, \"b]");
+        let options = ParseOptions { infer_types: true, ..Default::default() };
+        let conf = parse_str_with_options("pattern = [%d] %m [%l]\n", Some("pattern -> any\n"), &options).unwrap();
+        assert_eq!(conf.get_str("pattern").unwrap(), "[%d] %m [%l]");
+    }
+    #[test]
+    fn can_validate_items_of_list_tables() {
+        let schema = "upstream[] -> { host -> string required, port -> number, weight -> number }\nupstream -> list required\n";
+        let text = "upstream = [{ host = a.local, port = 80 }, { host = \"b, c\", port = 81, tags = [x, y] }]\n";
+        let mut conf = parse_str(text, Some(schema)).unwrap();
+        let upstream = conf.get("upstream").unwrap().as_list().unwrap().clone();
+        let second = upstream[1].as_conf().unwrap();
+        assert_eq!(second.get_str("host").unwrap(), "b, c");
+        assert_eq!(second.get_number("port").unwrap(), 81.0);
+        assert_eq!(second.to_flat_map(false)["tags"], "[x, y]");
+        assert!(parse_str("", Some(schema)).unwrap_err().to_string().contains("Missing required key: upstream"));
+
+        // すべての要素のエラーを位置付きで報告する
+        let err = parse_str("upstream = [{ host = a, port = x }, { port = 81 }, b]\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: [0].port: Invalid number value; [1].host: Missing required key; [2]: Expected a table but found b");
+        assert!(parse_schema_str("upstream[] -> string\n", &ParseOptions::default()).is_err());
+    }
+    #[test]
+    fn can_infer_types_without_schema() {
+        let text = "debug = true\nport = 8080\nratio = -0.25\nexp = 1e3\nname = \"true\"\nquoted = 'a b'\nzip = 00123\nversion = 1.2.3\nnan = nan\nbig = 12345678901234567890\nlevel = info\n";
+        let options = ParseOptions { infer_types: true, ..Default::default() };
+        let conf = parse_str_with_options(text, Some("level -> string\n"), &options).unwrap();
+        assert!(conf.get_bool("debug").unwrap());
+        assert_eq!(conf.get_number("port").unwrap(), 8080.0);
+        assert_eq!(conf.get_number("ratio").unwrap(), -0.25);
+        assert_eq!(conf.get_number("exp").unwrap(), 1000.0);
+        assert_eq!(conf.get_str("name").unwrap(), "true");
+        assert_eq!(conf.get_str("quoted").unwrap(), "a b");
+        assert_eq!(conf.raw_of("quoted").unwrap(), "'a b'");
+        for key in ["zip", "version", "nan", "big", "level"] {
+            assert!(conf.get_str(key).is_ok(), "{}", key);
+        }
+        assert_eq!(conf.get_str("zip").unwrap(), "00123");
+
+        // 指定しなければすべて文字列
+        let conf = parse_str(text, None).unwrap();
+        assert_eq!(conf.get_str("debug").unwrap(), "true");
+        assert_eq!(conf.get_str("name").unwrap(), "\"true\"");
+    }
+    #[test]
+    fn can_skip_type_checks_for_any() {
+        let schema = "plugin.opts -> any required\nplugin.token -> any secret\nplugin.level -> any\n";
+        let conf = parse_str("plugin.opts = [a, 1]\nplugin.token = abc\nplugin.level = 3\n", Some(schema)).unwrap();
+        assert!(conf.value_at("plugin.opts", |v| matches!(v, ConfValue::List(items) if items.len() == 2)).unwrap());
+        assert_eq!(conf.get_str("plugin.level").unwrap(), "3");
+        assert!(conf.is_secret("plugin.token"));
+        let options = ParseOptions { infer_types: true, ..Default::default() };
+        let conf = parse_str_with_options("plugin.opts = x\nplugin.level = 3\n", Some(schema), &options).unwrap();
+        assert_eq!(conf.get_number("plugin.level").unwrap(), 3.0);
+        let err = parse_str("plugin.level = 3\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>: Missing required key: plugin.opts");
+    }
+    #[test]
+    fn can_require_sections_for_object() {
+        let schema = "log -> object required\nlog.level -> string\n";
+        let conf = parse_str("log.level = info\n", Some(schema)).unwrap();
+        assert_eq!(conf.get_str("log.level").unwrap(), "info");
+        let conf = parse_str("log = { level = debug, file = app.log }\n", Some(schema)).unwrap();
+        assert_eq!(conf.get_str("log.file").unwrap(), "app.log");
+        assert!(parse_str("log = {}\n", Some(schema)).is_ok());
+        let err = parse_str("log.level = info\nlog = true\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:2: Expected a section but found a value");
+        let err = parse_str("other = 1\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>: Missing required key: log");
+    }
+    #[test]
+    fn can_decode_bytes() {
+        let schema = "hmac.key -> bytes\ncert -> bytes\n";
+        let conf = parse_str("hmac.key = aGVsbG8=\ncert = hex:3082\n", Some(schema)).unwrap();
+        assert_eq!(conf.value_at("hmac.key", |v| v.as_bytes().unwrap().to_vec()).unwrap(), b"hello");
+        assert!(conf.is_secret("hmac.key"));
+        assert_eq!(conf.to_flat_map(true)["cert"], REDACTED);
+        assert_eq!(conf.to_flat_map(false)["cert"], "MII=");
+        assert_eq!(conf.freeze().get("cert").unwrap().as_bytes().unwrap(), [0x30, 0x82]);
+        let err = parse_str("cert = not*base64\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Invalid base64 value");
+    }
+    #[test]
+    fn can_validate_uuids() {
+        let schema = "tenant -> uuid\n";
+        let conf = parse_str("tenant = 550E8400-e29b-41d4-a716-446655440000\n", Some(schema)).unwrap();
+        assert_eq!(conf.get_str("tenant").unwrap(), "550E8400-e29b-41d4-a716-446655440000");
+        for value in ["550e8400e29b41d4a716446655440000", "{550e8400-e29b-41d4-a716-446655440000}", "550e8400-e29b-41d4-a716-44665544000g"] {
+            let err = parse_str(&format!("tenant = {}\n", value), Some(schema)).unwrap_err();
+            assert_eq!(err.to_string(), "<string>:1: Invalid UUID value");
+        }
+        #[cfg(feature = "uuid")]
+        assert_eq!(conf.get_uuid("tenant").unwrap(), uuid::Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap());
+    }
+    #[test]
+    fn can_check_semver_requirements() {
+        let schema = "plugin.api -> semver(>=1.2, <2) required\nprotocol -> semver\n";
+        let conf = parse_str("plugin.api = 1.4.0-beta.1\nprotocol = 3.0.0+build.7\n", Some(schema)).unwrap();
+        assert_eq!(conf.get_str("plugin.api").unwrap(), "1.4.0-beta.1");
+        let err = parse_str("plugin.api = 2.1.0\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Version 2.1.0 does not satisfy >=1.2, <2");
+        let err = parse_str("plugin.api = 1.2\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Invalid semantic version: 1.2");
+        assert!(parse_str("a = 1\n", Some("a -> string(>=1)\n")).is_err());
+        assert!(parse_str("a = 1\n", Some("a -> semver(>=1\n")).is_err());
+
+        // リストの要素のフィールドでも、括弧の中のカンマで分けない
+        let schema = "plugins[] -> { name -> string, api -> semver(>=1, <2) }\nplugins -> list\n";
+        assert!(parse_str("plugins = [{ name = a, api = 1.5.0 }]\n", Some(schema)).is_ok());
+        let err = parse_str("plugins = [{ name = a, api = 2.0.0 }]\n", Some(schema)).unwrap_err();
+        assert!(err.to_string().contains("does not satisfy >=1, <2"), "{}", err);
+    }
+    #[test]
+    fn can_map_values_into_a_new_tree() {
+        let text = "db.password = hunter2\ndb.host = localhost\nlog.file = logs/app.log\nlog.file = logs/main.log\ntimeout = 3\n";
+        let conf = parse_str(text, Some("db.password -> string secret\ntimeout -> number\n")).unwrap();
+        let mapped = conf.map_values(|path, value| match value {
+            _ if conf.is_secret(path) => ConfValue::StrValue(REDACTED.to_string()),
+            ConfValue::StrValue(v) if path.ends_with(".file") => ConfValue::StrValue(format!("/srv/{}", v)),
+            ConfValue::NumberValue(n) => ConfValue::NumberValue(n * 1000.0),
+            value => value.clone(),
+        });
+        let flat = mapped.to_flat_map(false);
+        assert_eq!(flat["db.password"], REDACTED);
+        assert_eq!(flat["log.file"], "/srv/logs/main.log");
+        assert_eq!(flat["timeout"], "3000");
+        assert_eq!(flat["db.host"], "localhost");
+        assert!(mapped.is_secret("db.password"));
+        assert_eq!(mapped.origin_of("log.file").unwrap().line, Some(4));
+        assert_eq!(mapped.leaves().len(), 4);
+        // 元の conf は変わらない
+        assert_eq!(conf.to_flat_map(false)["timeout"], "3");
+    }
+    #[test]
+    fn can_retain_matching_values() {
+        let text = "port = 8080\nexperimental.a = 1\nexperimental.b.c = 2\ndb.host = localhost\ndb.internal = x\ndb.internal = y\nempty = {}\n";
+        let mut conf = parse_str(text, None).unwrap();
+        conf.retain(|path, _| !path.starts_with("experimental.") && !path.ends_with(".internal"));
+        assert_eq!(conf.leaves().into_iter().map(|(path, _)| path).collect::<Vec<_>>(), ["port", "db.host", "empty"]);
+        assert!(!conf.contains_key("experimental"));
+        assert!(conf.get_all("db.internal").is_empty());
+    }
+    #[test]
+    fn can_get_every_value_of_repeated_keys() {
+        let text = "server = a.local\nhttp.listen = 80\nserver = b.local\nhttp.listen = 443\nserver = c.local\n";
+        let conf = parse_str(text, Some("http.listen -> number\n")).unwrap();
+        let servers: Vec<String> = conf.get_all("server").iter().map(ToString::to_string).collect();
+        assert_eq!(servers, ["a.local", "b.local", "c.local"]);
+        assert_eq!(conf.get_all("http.listen")[1].as_number().unwrap(), 443.0);
+        assert_eq!(conf.get_str("server").unwrap(), "c.local");
+        assert!(conf.get_all("missing").is_empty() && conf.get_all("server.x").is_empty());
+    }
+    #[test]
+    #[cfg(feature = "std-fs")]
+    fn can_get_every_value_across_included_files() {
+        let dir = std::env::temp_dir().join(format!("conf-get-all-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("main.conf"), "server = a\ninclude extra.conf\nserver = d\n").unwrap();
+        std::fs::write(dir.join("extra.conf"), "server = b\nserver = c\n").unwrap();
+        let conf = parse(dir.join("main.conf").to_str().unwrap(), None).unwrap();
+        let servers: Vec<String> = conf.get_all("server").iter().map(ToString::to_string).collect();
+        assert_eq!(servers, ["a", "b", "c", "d"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    #[test]
+    fn can_parse_inline_tables() {
+        let text = "db.user = admin\ndb = { host = localhost, port = 5432, pool = { max = 10 }, tags = [a, b], note = \"[x]\" }\nempty = {}\n";
+        let tables = ParseOptions { inline_tables: true, ..Default::default() };
+        let mut conf = parse_str_with_options(text, Some("db.port -> number\n"), &tables).unwrap();
+        let flat = conf.to_flat_map(false);
+        assert_eq!(flat["db.user"], "admin");
+        assert_eq!(flat["db.host"], "localhost");
+        assert_eq!(flat["db.pool.max"], "10");
+        assert_eq!(conf.get("db").unwrap().as_conf().unwrap().value_at("port", |v| v.as_number().unwrap()), Some(5432.0));
+        assert!(conf.value_at("db.tags", |v| v.as_list().is_ok()).unwrap());
+        assert_eq!(conf.value_at("db.note", |v| v.as_str().unwrap().clone()).unwrap(), "[x]");
+        assert_eq!(conf.origin_of("db.pool.max").unwrap().line, Some(2));
+        assert!(conf.get("empty").unwrap().as_conf().is_ok());
+        assert!(conf.to_conf_string(&WriteOptions::default()).ends_with("empty = {}\n"));
+
+        let err = parse_str_with_options("db = { port = x }\n", Some("db.port -> number\n"), &tables).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Invalid number value");
+        let options = ParseOptions { limits: Limits { max_keys: 2, ..Default::default() }, inline_tables: true, ..Default::default() };
+        assert!(parse_str_with_options("db = { a = 1, b = 2 }\n", None, &options).is_ok());
+        assert!(parse_str_with_options("db = { a = 1, b = 2, c = 3 }\n", None, &options).is_err());
+        // string のスキーマなら書いたまま
+        assert!(parse_str("db = { a = 1 }\n", Some("db -> string\n")).unwrap().get("db").unwrap().as_str().is_ok());
+        assert!(borrowed::parse_borrowed("db = { a = 1 }\n", None, &tables).is_err());
+        // inline_tables がなければ、スキーマにない { ... } は文字列のまま
+        let conf = parse_str("format = {time} {level}\ndb = { a = 1 }\n", None).unwrap();
+        assert_eq!(conf.get_str("format").unwrap(), "{time} {level}");
+        assert_eq!(conf.get_str("db").unwrap(), "{ a = 1 }");
+        assert_eq!(parse_str("db = { a = 1 }\n", Some("db -> object\n")).unwrap().get_str("db.a").unwrap(), "1");
+    }
+    #[test]
+    fn can_keep_raw_values() {
+        let schema = "port -> number\nname -> string | trim | lowercase\npassword -> string secret\ntoken -> string secret\n";
+        let text = "port = 08080\nname = Web\nlog.file = /tmp/app.log\npassword = s3cr3t\ntoken = ENC(abc)\n";
+        let options = ParseOptions { decryptor: Some(Box::new(|_| Ok("plain".to_string()))), ..Default::default() };
+        let conf = parse_str_with_options(text, Some(schema), &options).unwrap();
+        assert_eq!(conf.raw_of("port").unwrap(), "08080");
+        assert_eq!(conf.value_at("port", |v| v.to_string()).unwrap(), "8080");
+        assert_eq!(conf.raw_of("name").unwrap(), "Web");
+        assert_eq!(conf.raw_of("log.file").unwrap(), "/tmp/app.log");
+        assert_eq!(conf.raw_of("password").unwrap(), REDACTED);
+        assert_eq!(conf.raw_of("token").unwrap(), "ENC(abc)");
+        assert_eq!(conf.raw_of("log"), None);
+        assert_eq!(conf.raw_of("missing"), None);
+
+        let config = conf.freeze();
+        assert_eq!(config.raw_of("port"), Some("08080"));
+        assert_eq!(config.raw_of("password"), Some(REDACTED));
+    }
+    #[cfg(feature = "decimal")]
+    #[test]
+    fn can_keep_decimal_digits() {
+        let schema = "price -> decimal\nid -> decimal\n";
+        let conf = parse_str("price = 19.90\nid = 9007199254740993\n", Some(schema)).unwrap();
+        assert_eq!(conf.value_at("price", |v| v.to_string()).unwrap(), "19.90");
+        assert_eq!(conf.value_at("id", |v| v.as_u64().unwrap()).unwrap(), 9_007_199_254_740_993);
+        assert_eq!(conf.to_json(&WriteOptions::default()), r#"{"price":19.90,"id":9007199254740993}"#);
+        let written = conf.to_conf_string(&WriteOptions::default());
+        assert_eq!(written, "price = 19.90\nid = 9007199254740993\n");
+        assert!(parse_str(&written, Some(schema)).unwrap().diff(&conf).is_empty());
+        let err = parse_str("price = 19.9.0\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Invalid decimal value");
+        // get_number は f64 にして返す
+        assert_eq!(conf.get_number("price").unwrap(), 19.9);
+        assert_eq!(conf.clone().freeze().get_number("price").unwrap(), 19.9);
+    }
+    #[cfg(not(feature = "decimal"))]
+    #[test]
+    fn can_reject_decimal_type_without_feature() {
+        let err = parse_str("price = 19.90\n", Some("price -> decimal\n")).unwrap_err();
+        assert!(err.to_string().contains("requires the decimal feature"));
+    }
+    #[cfg(feature = "regex")]
+    #[test]
+    fn can_validate_pattern_constraints() {
+        let schema = "name -> string ~ ^[a-z][a-z0-9-]*$\nport -> number ~ ^[0-9]+$\n";
+        let mut conf = parse_str("name = web-1\nport = 8080\n", Some(schema)).unwrap();
+        assert_eq!(conf.get("name").unwrap().as_str().unwrap(), "web-1");
+        let err = parse_str("name = Web_1\n", Some(schema)).unwrap_err();
+        assert!(err.to_string().contains("does not match pattern"));
+        assert!(parse_str("port = 8080.5\n", Some(schema)).is_err());
+    }
+    #[cfg(not(feature = "regex"))]
+    #[test]
+    fn can_reject_pattern_constraints_without_regex() {
+        let err = parse_str("name = web-1\n", Some("name -> string ~ ^[a-z]+$\n")).unwrap_err();
+        assert!(err.to_string().contains("requires the regex feature"));
+    }
+    #[test]
+    fn can_parse_conf_from_reader() {
+        let reader = io::Cursor::new("debug = true\ninclude other.conf\n");
+        let err = parse_reader(reader, Some("debug -> bool\n"), &ParseOptions::default()).unwrap_err();
+        assert!(err.to_string().starts_with("<reader>:2"));
+        let mut conf = parse_reader("debug = true\n".as_bytes(), Some("debug -> bool\n"), &ParseOptions::default()).unwrap();
+        assert!(conf.get("debug").unwrap().as_bool().unwrap());
+    }
+    #[test]
+    fn can_parse_conf_dir() {
+        let mut conf = parse_dir("tests/conf.d", "*.conf", Some("tests/data.schema")).unwrap();
+        assert_eq!(conf.get("endpoint").unwrap().as_str().unwrap(), "example.com:443");
+        assert!(!conf.get("debug").unwrap().as_bool().unwrap());
+        let mut log = conf.get("log").unwrap();
+        let log = match &mut *log {
+            ConfValue::Conf(log) => log,
+            _ => panic!("log should be a section"),
+        };
+        assert_eq!(log.get("file").unwrap().as_str().unwrap(), "/var/log/app.log");
+        assert_eq!(log.get("name").unwrap().as_str().unwrap(), "default.log");
+    }
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn can_limit_keys_across_parallel_files() {
+        let options = ParseOptions { limits: Limits { max_keys: 5, ..Default::default() }, ..Default::default() };
+        let e = parse_dir_with_options("tests/conf.d", "*.conf", None, &options).unwrap_err().to_string();
+        // どのファイルで超えるかはスレッドの順番しだいだが、逐次のときと同じく超えた行を指す
+        let (file, line) = e.strip_suffix(": Too many keys (limit: 5)").and_then(|at| at.rsplit_once(':')).expect(&e);
+        assert!(file.starts_with("tests/conf.d/") && file.ends_with(".conf"), "{}", e);
+        assert!(line.parse::<usize>().is_ok(), "{}", e);
+    }
+    #[test]
+    fn can_match_glob_pattern() {
+        assert!(glob_match("*.conf", "10-base.conf"));
+        assert!(glob_match("??-*.conf", "10-base.conf"));
+        assert!(!glob_match("*.conf", "10-base.conf.bak"));
+        assert!(glob_match("*", ""));
+    }
+    #[test]
+    fn can_track_origin_of_merged_values() {
+        let conf = parse_dir("tests/conf.d", "*.conf", None).unwrap();
+        let origin = conf.origin_of("log.file").unwrap();
+        assert_eq!(origin.to_string(), "tests/conf.d/20-production.conf:4");
+        assert_eq!(conf.origin_of("log.name").unwrap().to_string(), "tests/conf.d/10-base.conf:4");
+        assert!(conf.origin_of("log.missing").is_none());
+    }
+    #[test]
+    fn can_mount_conf_under_prefix() {
+        let mut host = parse_str("plugins.foo.enabled = false\nplugins.bar = old\nport = 8080\n", None).unwrap();
+        let foo = parse_str("enabled = true\nlimit = 10\n", Some("limit -> number\n")).unwrap();
+        host.mount("plugins.foo", foo);
+        host.mount("plugins.bar", parse_str("name = bar\n", None).unwrap());
+        assert_eq!(host.get_str("plugins.foo.enabled").unwrap(), "true");
+        assert_eq!(host.get_number("plugins.foo.limit").unwrap(), 10.0);
+        assert_eq!(host.get_str("plugins.bar.name").unwrap(), "bar");
+        assert_eq!(host.origin_of("plugins.foo.limit").unwrap().to_string(), "<string>:2");
+        assert_eq!(host.get_str("port").unwrap(), "8080");
+    }
+    #[test]
+    fn can_include_files() {
+        let conf = parse("tests/include/main.conf", None).unwrap();
+        let config = conf.clone().freeze();
+        // conf.d は名前順に取り込まれ、include の後の行が優先される
+        assert_eq!(config.get("db.host").unwrap().as_str().unwrap(), "db.internal");
+        assert_eq!(config.get("db.port").unwrap().as_str().unwrap(), "5433");
+        assert_eq!(config.get("endpoint").unwrap().as_str().unwrap(), "localhost:8080");
+        assert_eq!(conf.origin_of("db.port").unwrap().to_string(), "tests/include/conf.d/20-db.conf:1");
+        assert!(parse("tests/include/missing.conf", None).is_err());
+        assert!(parse("tests/include/cycle.conf", None).is_err());
+    }
+    #[test]
+    fn rejects_input_over_limits() {
+        let deep = format!("{} = value\n", vec!["a"; 10_000].join("."));
+        let err = parse_str(&deep, None).unwrap_err();
+        assert!(err.to_string().starts_with("<string>:1: Key is nested too deeply"));
+
+        let options = ParseOptions {
+            limits: Limits { max_depth: 10_000, max_keys: 2, ..Default::default() },
+            ..Default::default()
+        };
+        // 上限を上げれば深いキーも再帰せずに追加できる
+        let conf = parse_str_with_options(&deep, None, &options).unwrap();
+        assert!(conf.origin_of("a.a.a").is_some());
+        let err = parse_with_options("tests/case-2.conf", None, &options).unwrap_err();
+        assert_eq!(err.to_string(), "tests/case-2.conf:4: Too many keys (limit: 2)");
+
+        let options = ParseOptions {
+            limits: Limits { max_value_length: 8, ..Default::default() },
+            ..Default::default()
+        };
+        assert!(parse_with_options("tests/case-1.conf", None, &options).is_err());
+    }
+
+    #[test]
+    fn can_reject_oversized_input() {
+        let options = ParseOptions {
+            limits: Limits { max_file_size: 16, max_line_length: 12, ..Default::default() },
+            ..Default::default()
+        };
+        let err = parse_with_options("tests/case-1.conf", None, &options).unwrap_err();
+        assert_eq!(err.to_string(), "tests/case-1.conf: File is too large (limit: 16 bytes)");
+        let err = parse_reader("a = 1\nb = 2\nc = 3\n".as_bytes(), None, &options).unwrap_err();
+        assert_eq!(err.to_string(), "<reader>: File is too large (limit: 16 bytes)");
+        let err = parse_str_with_options("a = 1\nname = a long value\n", None, &options).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:2: Line is too long (limit: 12 bytes)");
+        let err = parse_reader(&[b'a', b'=', 0xff, b'\n'][..], None, &options).unwrap_err();
+        assert!(err.to_string().starts_with("<reader>: "));
+    }
 }
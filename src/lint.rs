@@ -0,0 +1,267 @@
+// 読み込みには成功するが間違いやすい書き方を見つける。ルールごとに無効にしたり重大度を変えたりできる
+// 値の検証はしない (スキーマに合わない値は validate_file で見つける)
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+use crate::cst::{Document, Line, TokenKind};
+use crate::{Diagnostic, Origin, Severity, ValidationReport};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rule {
+    // 同じファイルで同じキーを 2 回書いた (後の値が使われる)
+    DuplicateKeys,
+    TrailingWhitespace,
+    // known_keys にないキー (known_keys を指定したときだけ)
+    UnknownKeys,
+    DeprecatedKeys,
+    // yes / no / on / off や True のように、bool のつもりで書かれたように見える文字列
+    SuspiciousBooleanStrings,
+    // key = {}
+    EmptySections,
+}
+
+impl Rule {
+    pub const ALL: [Rule; 6] = [
+        Rule::DuplicateKeys,
+        Rule::TrailingWhitespace,
+        Rule::UnknownKeys,
+        Rule::DeprecatedKeys,
+        Rule::SuspiciousBooleanStrings,
+        Rule::EmptySections,
+    ];
+
+    // 診断の code にも使う
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rule::DuplicateKeys => "duplicate-keys",
+            Rule::TrailingWhitespace => "trailing-whitespace",
+            Rule::UnknownKeys => "unknown-keys",
+            Rule::DeprecatedKeys => "deprecated-keys",
+            Rule::SuspiciousBooleanStrings => "suspicious-boolean-strings",
+            Rule::EmptySections => "empty-sections",
+        }
+    }
+
+    fn default_severity(&self) -> Severity {
+        match self {
+            Rule::UnknownKeys => Severity::Error,
+            _ => Severity::Warning,
+        }
+    }
+}
+
+impl FromStr for Rule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Rule::ALL.into_iter().find(|rule| rule.as_str() == s).ok_or_else(|| format!("Unknown lint rule: {}", s))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct LintOptions {
+    // 有効なルールとその重大度
+    rules: HashMap<Rule, Severity>,
+    known_keys: Option<HashSet<String>>,
+    // 非推奨のキーと、代わりに使うキー
+    deprecated: HashMap<String, Option<String>>,
+}
+
+// すべてのルールを既定の重大度で有効にする
+impl Default for LintOptions {
+    fn default() -> Self {
+        LintOptions {
+            rules: Rule::ALL.into_iter().map(|rule| (rule, rule.default_severity())).collect(),
+            known_keys: None,
+            deprecated: HashMap::new(),
+        }
+    }
+}
+
+impl LintOptions {
+    pub fn enable(mut self, rule: Rule) -> Self {
+        self.rules.insert(rule, rule.default_severity());
+        self
+    }
+
+    pub fn disable(mut self, rule: Rule) -> Self {
+        self.rules.remove(&rule);
+        self
+    }
+
+    // ルールを有効にして重大度を変える
+    pub fn severity(mut self, rule: Rule, severity: Severity) -> Self {
+        self.rules.insert(rule, severity);
+        self
+    }
+
+    pub fn is_enabled(&self, rule: Rule) -> bool {
+        self.rules.contains_key(&rule)
+    }
+
+    // スキーマのキーを渡すなら known_keys(schema.keys())
+    pub fn known_keys<I, S>(mut self, keys: I) -> Self
+    where I: IntoIterator<Item = S>, S: Into<String>, {
+        self.known_keys = Some(keys.into_iter().map(Into::into).collect());
+        self
+    }
+
+    pub fn deprecated(mut self, key: &str, replacement: Option<&str>) -> Self {
+        self.deprecated.insert(key.to_string(), replacement.map(str::to_string));
+        self
+    }
+
+    // "log.level" が既知なら、インラインテーブルで書く "log" も既知とする
+    fn is_known(&self, key: &str) -> bool {
+        match &self.known_keys {
+            Some(known) => known.contains(key) || known.iter().any(|k| k.strip_prefix(key).is_some_and(|rest| rest.starts_with('.'))),
+            None => true,
+        }
+    }
+}
+
+// source は診断に出すファイル名。include は展開せず、text だけを見る
+pub fn lint_str(source: &str, text: &str, options: &LintOptions) -> ValidationReport {
+    let doc = Document::parse(text);
+    let mut report = ValidationReport::default();
+    let mut first_lines: HashMap<&str, usize> = HashMap::new();
+    for (i, line) in doc.lines.iter().enumerate() {
+        let lint = Lint { options, source, number: i + 1 };
+        let mut diagnostics = Vec::new();
+        if let Some(column) = trailing_whitespace(line) {
+            diagnostics.extend(lint.report(Rule::TrailingWhitespace, "Trailing whitespace".to_string(), None, column));
+        }
+        let (Some(key), Some(value)) = (line.key(), line.value()) else {
+            report.diagnostics.extend(diagnostics);
+            continue;
+        };
+        report.keys += 1;
+        let key_column = column_of(line, TokenKind::Key);
+        let value_column = column_of(line, TokenKind::Value);
+        match first_lines.get(key) {
+            Some(first) => {
+                let message = format!("Duplicate key: {} (first defined on line {})", key, first);
+                diagnostics.extend(lint.report(Rule::DuplicateKeys, message, Some(key), key_column));
+            },
+            None => {
+                first_lines.insert(key, i + 1);
+            },
+        }
+        if !options.is_known(key) {
+            diagnostics.extend(lint.report(Rule::UnknownKeys, format!("Unknown key: {}", key), Some(key), key_column));
+        }
+        if let Some(replacement) = options.deprecated.get(key) {
+            let message = match replacement {
+                Some(replacement) => format!("Deprecated key: {} (use {} instead)", key, replacement),
+                None => format!("Deprecated key: {}", key),
+            };
+            diagnostics.extend(lint.report(Rule::DeprecatedKeys, message, Some(key), key_column));
+        }
+        if looks_like_boolean(value) {
+            let message = format!("Value '{}' looks like a boolean but is read as a string (use true or false)", value);
+            diagnostics.extend(lint.report(Rule::SuspiciousBooleanStrings, message, Some(key), value_column));
+        }
+        if value.strip_prefix('{').and_then(|v| v.strip_suffix('}')).is_some_and(|v| v.trim().is_empty()) {
+            diagnostics.extend(lint.report(Rule::EmptySections, format!("Empty section: {}", key), Some(key), value_column));
+        }
+        report.diagnostics.extend(diagnostics);
+    }
+    report
+}
+
+struct Lint<'a> {
+    options: &'a LintOptions,
+    source: &'a str,
+    number: usize,
+}
+
+impl Lint<'_> {
+    fn report(&self, rule: Rule, message: String, path: Option<&str>, column: usize) -> Option<Diagnostic> {
+        let severity = *self.options.rules.get(&rule)?;
+        let origin = Origin::new(self.source, Some(self.number));
+        let diagnostic = match severity {
+            Severity::Error => Diagnostic::error(&origin, rule.as_str(), message),
+            Severity::Warning => Diagnostic::warning(&origin, rule.as_str(), message),
+        }
+        .column(column);
+        Some(match path {
+            Some(path) => diagnostic.path(path),
+            None => diagnostic,
+        })
+    }
+}
+
+// bool として読まれるのは小文字の true / false だけ
+fn looks_like_boolean(value: &str) -> bool {
+    let lower = value.to_ascii_lowercase();
+    match lower.as_str() {
+        "true" | "false" => value != lower,
+        other => ["yes", "no", "on", "off", "y", "n"].contains(&other),
+    }
+}
+
+// 改行の直前が空白なら、その空白の 1 始まりの文字位置
+fn trailing_whitespace(line: &Line) -> Option<usize> {
+    let text = line.to_string();
+    let content = text.trim_end_matches(['\n', '\r']);
+    let trimmed = content.trim_end_matches([' ', '\t']);
+    (trimmed.len() != content.len()).then(|| trimmed.chars().count() + 1)
+}
+
+// kind のトークンの 1 始まりの文字位置
+fn column_of(line: &Line, kind: TokenKind) -> usize {
+    line.tokens.iter().take_while(|token| token.kind != kind).map(|token| token.text.chars().count()).sum::<usize>() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = "port = 8080\nendpoint = localhost  \nport = 9090\ndebug = Yes\nverbose = true\nlog = {}\nold.timeout = 30\ncolour = red\n";
+
+    #[test]
+    fn can_report_every_rule() {
+        let options = LintOptions::default()
+            .known_keys(["port", "endpoint", "debug", "verbose", "log.level", "old.timeout"])
+            .deprecated("old.timeout", Some("timeout"));
+        let report = lint_str("app.conf", INPUT, &options);
+        assert_eq!(report.keys, 8);
+        let found: Vec<(&str, Option<usize>, Option<usize>, Severity)> =
+            report.diagnostics.iter().map(|d| (d.code, d.line, d.column, d.severity)).collect();
+        assert_eq!(found, [
+            ("trailing-whitespace", Some(2), Some(21), Severity::Warning),
+            ("duplicate-keys", Some(3), Some(1), Severity::Warning),
+            ("suspicious-boolean-strings", Some(4), Some(9), Severity::Warning),
+            ("empty-sections", Some(6), Some(7), Severity::Warning),
+            ("deprecated-keys", Some(7), Some(1), Severity::Warning),
+            ("unknown-keys", Some(8), Some(1), Severity::Error),
+        ]);
+        assert_eq!(report.diagnostics[1].to_string(), "app.conf:3: Duplicate key: port (first defined on line 1)");
+        assert_eq!(report.diagnostics[4].message, "Deprecated key: old.timeout (use timeout instead)");
+        assert!(!report.is_ok());
+    }
+
+    #[test]
+    fn passes_with_warnings_unless_strict() {
+        let report = lint_str("app.conf", INPUT, &LintOptions::default());
+        assert!(report.diagnostics.iter().all(|d| d.severity == Severity::Warning));
+        assert!(report.is_ok());
+        assert!(!report.is_strictly_ok());
+        assert!(lint_str("app.conf", "port = 8080\n", &LintOptions::default()).is_strictly_ok());
+    }
+
+    #[test]
+    fn can_disable_rules_and_change_severities() {
+        let options = LintOptions::default()
+            .disable(Rule::TrailingWhitespace)
+            .disable(Rule::EmptySections)
+            .severity(Rule::DuplicateKeys, Severity::Error);
+        assert!(!options.is_enabled(Rule::TrailingWhitespace));
+        let report = lint_str("app.conf", INPUT, &options);
+        let codes: Vec<(&str, Severity)> = report.diagnostics.iter().map(|d| (d.code, d.severity)).collect();
+        assert_eq!(codes, [("duplicate-keys", Severity::Error), ("suspicious-boolean-strings", Severity::Warning)]);
+        assert!(lint_str("app.conf", INPUT, &options.enable(Rule::EmptySections)).diagnostics.iter().any(|d| d.code == "empty-sections"));
+        assert_eq!("empty-sections".parse::<Rule>().unwrap(), Rule::EmptySections);
+        assert_eq!("tabs".parse::<Rule>().unwrap_err(), "Unknown lint rule: tabs");
+    }
+}
@@ -0,0 +1,104 @@
+// スキーマの型 locale。en-US や ja のような BCP 47 (RFC 5646) の言語タグの形を確かめる
+// 言語 [-拡張言語] [-文字体系] [-地域] [-変種]* [-拡張]* [-x-私用] の順に並んでいるかだけを見て、登録されたコードかどうかは見ない
+// 値は文字列のまま持つ
+
+pub(crate) fn check(s: &str) -> Result<(), String> {
+    if s.contains('_') {
+        return Err(format!("Invalid language tag: {} (use - instead of _)", s));
+    }
+    match is_tag(s) {
+        true => Ok(()),
+        false => Err(format!("Invalid language tag: {}", s)),
+    }
+}
+
+fn alpha(s: &str, len: std::ops::RangeInclusive<usize>) -> bool {
+    len.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphabetic())
+}
+
+fn alphanum(s: &str, len: std::ops::RangeInclusive<usize>) -> bool {
+    len.contains(&s.len()) && s.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+fn is_tag(s: &str) -> bool {
+    let mut subtags = s.split('-').peekable();
+    let Some(language) = subtags.next() else {
+        return false;
+    };
+    // x- で始まるタグは全体が私用
+    if language.eq_ignore_ascii_case("x") {
+        return is_private_use(subtags);
+    }
+    if !alpha(language, 2..=8) {
+        return false;
+    }
+    // 2 から 3 文字の言語には、3 文字の拡張言語を 3 つまで続けられる
+    if language.len() <= 3 {
+        for _ in 0..3 {
+            if subtags.next_if(|s| alpha(s, 3..=3)).is_none() {
+                break;
+            }
+        }
+    }
+    subtags.next_if(|s| alpha(s, 4..=4));
+    subtags.next_if(|s| alpha(s, 2..=2) || s.len() == 3 && s.bytes().all(|b| b.is_ascii_digit()));
+    let mut variants = Vec::new();
+    while let Some(variant) = subtags.next_if(|s| alphanum(s, 5..=8) || s.len() == 4 && s.as_bytes()[0].is_ascii_digit() && alphanum(s, 4..=4)) {
+        // 同じ変種は 2 度書けない
+        if variants.contains(&variant.to_ascii_lowercase()) {
+            return false;
+        }
+        variants.push(variant.to_ascii_lowercase());
+    }
+    let mut singletons = Vec::new();
+    while let Some(singleton) = subtags.next() {
+        if singleton.eq_ignore_ascii_case("x") {
+            return is_private_use(subtags);
+        }
+        let singleton = singleton.to_ascii_lowercase();
+        if !alphanum(&singleton, 1..=1) || singletons.contains(&singleton) {
+            return false;
+        }
+        singletons.push(singleton);
+        // 拡張には 2 から 8 文字の部分が 1 つ以上いる
+        let mut count = 0;
+        while subtags.next_if(|s| alphanum(s, 2..=8)).is_some() {
+            count += 1;
+        }
+        if count == 0 {
+            return false;
+        }
+    }
+    true
+}
+
+fn is_private_use<'a>(subtags: impl Iterator<Item = &'a str>) -> bool {
+    let mut count = 0;
+    for subtag in subtags {
+        if !alphanum(subtag, 1..=8) {
+            return false;
+        }
+        count += 1;
+    }
+    count > 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_validate_language_tags() {
+        for valid in ["ja", "en-US", "zh-Hant-TW", "es-419", "sl-rozaj-biske", "de-CH-1996", "zh-yue-HK", "en-US-u-ca-gregory-x-custom", "x-klingon"] {
+            assert!(check(valid).is_ok(), "{}", valid);
+        }
+        for invalid in ["", "e", "en-", "en--US", "toolonglanguage", "en-US-u", "de-1996-1996", "en-a-bb-a-cc", "x", "ja-JP-x-"] {
+            assert_eq!(check(invalid).unwrap_err(), format!("Invalid language tag: {}", invalid), "{}", invalid);
+        }
+        assert_eq!(check("en_US").unwrap_err(), "Invalid language tag: en_US (use - instead of _)");
+
+        let schema = "i18n.default_locale -> locale\n";
+        assert!(crate::parse_str("i18n.default_locale = pt-BR\n", Some(schema)).is_ok());
+        assert!(crate::parse_str("i18n.default_locale = portuguese\n", Some(schema)).is_err());
+    }
+}
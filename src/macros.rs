@@ -0,0 +1,229 @@
+use crate::{ConfList, ConfValue};
+
+impl From<&str> for ConfValue {
+    fn from(value: &str) -> Self {
+        ConfValue::StrValue(value.to_string())
+    }
+}
+
+impl From<String> for ConfValue {
+    fn from(value: String) -> Self {
+        ConfValue::StrValue(value)
+    }
+}
+
+impl From<bool> for ConfValue {
+    fn from(value: bool) -> Self {
+        ConfValue::BoolValue(value)
+    }
+}
+
+impl From<ConfList> for ConfValue {
+    fn from(value: ConfList) -> Self {
+        ConfValue::Conf(Box::new(value))
+    }
+}
+
+#[cfg(feature = "decimal")]
+impl From<rust_decimal::Decimal> for ConfValue {
+    fn from(value: rust_decimal::Decimal) -> Self {
+        ConfValue::DecimalValue(value)
+    }
+}
+
+impl<T: Into<ConfValue>> From<Vec<T>> for ConfValue {
+    fn from(value: Vec<T>) -> Self {
+        ConfValue::List(value.into_iter().map(Into::into).collect())
+    }
+}
+
+macro_rules! impl_from_number_for_conf_value {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for ConfValue {
+                fn from(value: $t) -> Self {
+                    ConfValue::NumberValue(value as f64)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_number_for_conf_value!(f32, f64, i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+// テストなどでファイルを用意せずに ConfList を作る
+//
+//   conf! {
+//       endpoint: "localhost:3000",
+//       "max-connections": 16,
+//       log: { file: "/var/log/app.log", debug: true },
+//   }
+//
+// キーは識別子か文字列リテラル。値は Into<ConfValue> な式で、{ ... } は入れ子のセクションになる
+#[macro_export]
+macro_rules! conf {
+    ($($body:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut entries: ::std::vec::Vec<(::std::string::String, $crate::ConfValue)> = ::std::vec::Vec::new();
+        $crate::__conf_entries!(entries, ""; $($body)*);
+        entries.into_iter().collect::<$crate::ConfList>()
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __conf_entries {
+    ($entries:ident, $prefix:expr;) => {};
+    ($entries:ident, $prefix:expr; $key:tt : { $($inner:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::__conf_entries!($entries, ::std::format!("{}{}.", $prefix, $crate::__conf_key!($key)); $($inner)*);
+        $crate::__conf_entries!($entries, $prefix; $($($rest)*)?);
+    };
+    ($entries:ident, $prefix:expr; $key:tt : $value:expr $(, $($rest:tt)*)?) => {
+        $entries.push((::std::format!("{}{}", $prefix, $crate::__conf_key!($key)), $crate::ConfValue::from($value)));
+        $crate::__conf_entries!($entries, $prefix; $($($rest)*)?);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __conf_key {
+    ($key:ident) => { ::std::stringify!($key) };
+    ($key:literal) => { $key };
+}
+
+// 型と制約をコードで書いてスキーマを作る
+//
+//   schema! {
+//       endpoint: string required,
+//       port: number(1..=65535),
+//       debug: bool = false,
+//       "api-key": string secret,
+//   }
+//
+// 型のあとに number の範囲 (Rust の範囲式と同じく .. は上限を含まない)、secret / required / マージのしかた、= default を書く
+#[macro_export]
+macro_rules! schema {
+    ($($body:tt)*) => {{
+        #[allow(unused_mut)]
+        let mut schema = $crate::Schema::new();
+        $crate::__schema_entries!(schema; $($body)*);
+        schema
+    }};
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __schema_entries {
+    ($schema:ident;) => {};
+    ($schema:ident; $key:tt : $ty:ident $(($($range:tt)*))? $($marker:ident)* $(= $default:expr)? $(, $($rest:tt)*)?) => {
+        let entry = $crate::SchemaEntry::new($crate::__schema_type!($ty));
+        $(let entry = entry.range($crate::__schema_range!($($range)*));)?
+        $(let entry = $crate::__schema_marker!(entry, $marker);)*
+        $(let entry = entry.default_value($default);)?
+        $schema.insert(::std::string::ToString::to_string($crate::__conf_key!($key)), entry);
+        $crate::__schema_entries!($schema; $($($rest)*)?);
+    };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __schema_type {
+    (string) => { $crate::SchemaType::String };
+    (bool) => { $crate::SchemaType::Bool };
+    (number) => { $crate::SchemaType::Number };
+    (decimal) => { $crate::SchemaType::Decimal };
+    (list) => { $crate::SchemaType::List };
+    (path) => { $crate::SchemaType::Path };
+    (bytes) => { $crate::SchemaType::Bytes };
+    (uuid) => { $crate::SchemaType::Uuid };
+    (semver) => { $crate::SchemaType::Semver };
+    (loglevel) => { $crate::SchemaType::LogLevel };
+    (endpoint) => { $crate::SchemaType::Endpoint };
+    (cron) => { $crate::SchemaType::Cron };
+    (locale) => { $crate::SchemaType::Locale };
+    (country) => { $crate::SchemaType::Country };
+    (currency) => { $crate::SchemaType::Currency };
+    (mime) => { $crate::SchemaType::Mime };
+    (charset) => { $crate::SchemaType::Charset };
+    (any) => { $crate::SchemaType::Any };
+    (object) => { $crate::SchemaType::Object };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __schema_marker {
+    ($entry:ident, secret) => { $entry.secret() };
+    ($entry:ident, required) => { $entry.required() };
+    ($entry:ident, deep) => { $entry.merge($crate::MergeStrategy::Deep) };
+    ($entry:ident, replace) => { $entry.merge($crate::MergeStrategy::Replace) };
+    ($entry:ident, append) => { $entry.merge($crate::MergeStrategy::Append) };
+    ($entry:ident, unique) => { $entry.merge($crate::MergeStrategy::Unique) };
+}
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __schema_range {
+    ($min:literal .. $max:literal) => { ($min as f64)..($max as f64) };
+    ($min:literal ..= $max:literal) => { ($min as f64)..=($max as f64) };
+    ($min:literal ..) => { ($min as f64).. };
+    (.. $max:literal) => { ..($max as f64) };
+    (..= $max:literal) => { ..=($max as f64) };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_str_with_schema, ParseOptions};
+
+    #[test]
+    fn can_build_conf_with_macro() {
+        let port = 8080;
+        let conf = conf! {
+            endpoint: "localhost:3000",
+            port: port,
+            "max-connections": 16,
+            hosts: vec!["a", "b, c"],
+            log: {
+                file: "/var/log/x",
+                debug: true,
+                rotate: { keep: 7 },
+            },
+        };
+        let flat = conf.to_flat_map(false);
+        assert_eq!(flat.len(), 7);
+        assert_eq!(flat["hosts"], r#"[a, "b, c"]"#);
+        assert_eq!(flat["endpoint"], "localhost:3000");
+        assert_eq!(flat["port"], "8080");
+        assert_eq!(flat["max-connections"], "16");
+        assert_eq!(flat["log.file"], "/var/log/x");
+        assert_eq!(flat["log.debug"], "true");
+        assert_eq!(flat["log.rotate.keep"], "7");
+        assert!(conf.value_at("log.debug", |v| v.as_bool().is_ok()).unwrap());
+        assert_eq!(conf! {}.to_flat_map(false).len(), 0);
+    }
+
+    #[test]
+    fn can_define_schema_with_macro() {
+        let schema = schema! {
+            endpoint: string required,
+            port: number(1..65535),
+            debug: bool = false,
+            "api-key": string secret,
+        };
+        let options = ParseOptions::default();
+        let conf = parse_str_with_schema("endpoint = localhost:3000\nport = 8080\napi-key = abc\n", &schema, &options).unwrap();
+        let flat = conf.to_flat_map(true);
+        assert_eq!(flat["port"], "8080");
+        assert_eq!(flat["debug"], "false");
+        assert_eq!(flat["api-key"], crate::REDACTED);
+        assert!(conf.value_at("debug", |v| v.as_bool().is_ok()).unwrap());
+
+        let err = parse_str_with_schema("endpoint = x\nport = 65535\n", &schema, &options).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:2: Value 65535 is out of range 1..65535");
+        let err = parse_str_with_schema("port = 80\n", &schema, &options).unwrap_err();
+        assert_eq!(err.to_string(), "<string>: Missing required key: endpoint");
+        let schema = schema! { port: number(..=65535) };
+        assert!(parse_str_with_schema("port = 65535\n", &schema, &options).is_ok());
+        // ファイルのスキーマでも required は書ける
+        assert!(crate::parse_str("port = 80\n", Some("endpoint -> string required\n")).is_err());
+    }
+}
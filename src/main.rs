@@ -0,0 +1,150 @@
+use std::env;
+use std::fs;
+use std::error::Error;
+use std::process::ExitCode;
+
+use conf_loader_with_validation::format::format_str;
+use conf_loader_with_validation::{parse, validate_file_with_options, FormatOptions, ParseOptions};
+
+const USAGE: &str = "Usage:
+    conf diff <old.conf> <new.conf> [--schema <file>]
+    conf env <file.conf> [--schema <file>] [--prefix <PREFIX>]
+    conf fmt <file.conf> [--align] [--sort] [--check | --write]
+    conf validate <file.conf> [--schema <file>] [--strict] [--format text|json|sarif]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run(args: &[String]) -> Result<ExitCode, Box<dyn Error>> {
+    match args.first().map(String::as_str) {
+        Some("diff") => diff(&args[1..]),
+        Some("env") => export_env(&args[1..]),
+        Some("fmt") => fmt(&args[1..]),
+        Some("validate") => validate(&args[1..]),
+        _ => Err(USAGE.into()),
+    }
+}
+
+// 差分があれば終了コード 1 (diff コマンドと同じ)
+fn diff(args: &[String]) -> Result<ExitCode, Box<dyn Error>> {
+    let Args { files, schema, .. } = split_args(args)?;
+    if files.len() != 2 {
+        return Err(USAGE.into());
+    }
+    let old = parse(&files[0], schema.as_deref())?;
+    let new = parse(&files[1], schema.as_deref())?;
+    let diff = old.diff(&new);
+    print!("{}", diff);
+    Ok(if diff.is_empty() { ExitCode::SUCCESS } else { ExitCode::from(1) })
+}
+
+// 検証した設定を export NAME='value' の行で出力する (eval "$(conf env app.conf)" で読み込む)
+fn export_env(args: &[String]) -> Result<ExitCode, Box<dyn Error>> {
+    let Args { files, schema, prefix, .. } = split_args(args)?;
+    if files.len() != 1 {
+        return Err(USAGE.into());
+    }
+    let conf = parse(&files[0], schema.as_deref())?;
+    for (name, value) in conf.to_env_vars(&prefix)? {
+        println!("export {}={}", name, shell_quote(&value));
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// ' で囲み、中の ' は '\'' にする
+fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+// 整えた内容を標準出力に書く。--check なら書式が違うとき終了コード 1、--write ならファイルを書き換える
+fn fmt(args: &[String]) -> Result<ExitCode, Box<dyn Error>> {
+    let Args { files, align, sort, check, write, .. } = split_args(args)?;
+    if files.len() != 1 || (check && write) {
+        return Err(USAGE.into());
+    }
+    let text = fs::read_to_string(&files[0])?;
+    let formatted = format_str(&text, &FormatOptions { align, sort, ..Default::default() });
+    if check {
+        if formatted == text {
+            return Ok(ExitCode::SUCCESS);
+        }
+        eprintln!("{} is not formatted", files[0]);
+        return Ok(ExitCode::from(1));
+    }
+    if write {
+        if formatted != text {
+            fs::write(&files[0], formatted)?;
+        }
+    } else {
+        print!("{}", formatted);
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+// 問題があれば終了コード 1。--format json / sarif なら診断を 1 つの JSON で出力する
+fn validate(args: &[String]) -> Result<ExitCode, Box<dyn Error>> {
+    let Args { files, schema, strict, format, .. } = split_args(args)?;
+    if files.len() != 1 {
+        return Err(USAGE.into());
+    }
+    let options = ParseOptions { strict, ..Default::default() };
+    let report = validate_file_with_options(&files[0], schema.as_deref(), &options)?;
+    match format {
+        Format::Text => print!("{}", report),
+        Format::Json => println!("{}", report.to_json()),
+        Format::Sarif => println!("{}", report.to_sarif()),
+    }
+    let ok = if strict { report.is_strictly_ok() } else { report.is_ok() };
+    Ok(if ok { ExitCode::SUCCESS } else { ExitCode::from(1) })
+}
+
+enum Format {
+    Text,
+    Json,
+    Sarif,
+}
+
+struct Args {
+    files: Vec<String>,
+    schema: Option<String>,
+    prefix: String,
+    strict: bool,
+    format: Format,
+    align: bool,
+    sort: bool,
+    check: bool,
+    write: bool,
+}
+
+// 位置引数とオプションを分ける
+fn split_args(args: &[String]) -> Result<Args, Box<dyn Error>> {
+    let mut parsed = Args { files: Vec::new(), schema: None, prefix: String::new(), strict: false, format: Format::Text, align: false, sort: false, check: false, write: false };
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--schema" => parsed.schema = Some(iter.next().ok_or("--schema requires a file")?.clone()),
+            "--prefix" => parsed.prefix = iter.next().ok_or("--prefix requires a value")?.clone(),
+            "--strict" => parsed.strict = true,
+            "--align" => parsed.align = true,
+            "--sort" => parsed.sort = true,
+            "--check" => parsed.check = true,
+            "--write" => parsed.write = true,
+            "--format" => parsed.format = match iter.next().map(String::as_str) {
+                Some("text") => Format::Text,
+                Some("json") => Format::Json,
+                Some("sarif") => Format::Sarif,
+                _ => return Err("--format requires text, json or sarif".into()),
+            },
+            _ => parsed.files.push(arg.clone()),
+        }
+    }
+    Ok(parsed)
+}
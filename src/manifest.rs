@@ -0,0 +1,101 @@
+// 検証した設定を Kubernetes の ConfigMap の YAML に書き出す。secret の値は同じ名前の Secret に分ける
+// data のキーはドット区切りのパス (log.file) で、値はすべて文字列にする
+// Secret は base64 にしなくてよい stringData に書く。secret の値がなければ Secret は出力しない
+// Kubernetes のキーに使えるのは英数字と - . _ だけ (253 文字まで) なので、それ以外を含むパスはエラーにする
+use std::fmt::Write;
+
+use crate::serialize::write_json_string;
+use crate::{ConfList, ConfValue};
+
+#[derive(Debug, Clone, Default)]
+pub struct ManifestOptions {
+    // metadata.name (ConfigMap と Secret で同じ)
+    pub name: String,
+    pub namespace: Option<String>,
+}
+
+impl ManifestOptions {
+    pub fn new(name: &str) -> Self {
+        ManifestOptions { name: name.to_string(), namespace: None }
+    }
+
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+}
+
+impl ConfList {
+    pub fn to_k8s_manifests(&self, options: &ManifestOptions) -> Result<String, String> {
+        let (secrets, data): (Vec<_>, Vec<_>) = self.leaves().into_iter()
+            .filter(|(_, value)| !matches!(value, ConfValue::Conf(_)))
+            .partition(|(path, _)| self.is_secret(path));
+        if let Some((path, _)) = secrets.iter().chain(&data).find(|(path, _)| !is_valid_key(path)) {
+            return Err(format!("Invalid ConfigMap key: {}", path));
+        }
+        let mut out = String::new();
+        write_manifest(&mut out, "ConfigMap", "data", &data, options);
+        if !secrets.is_empty() {
+            out.push_str("---\n");
+            write_manifest(&mut out, "Secret", "stringData", &secrets, options);
+        }
+        Ok(out)
+    }
+}
+
+fn is_valid_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= 253 && key.bytes().all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'.' | b'_'))
+}
+
+// JSON の文字列は YAML の "..." としてそのまま読める
+fn write_manifest(out: &mut String, kind: &str, field: &str, entries: &[(String, ConfValue)], options: &ManifestOptions) {
+    writeln!(out, "apiVersion: v1\nkind: {}\nmetadata:", kind).unwrap();
+    out.push_str("  name: ");
+    write_json_string(&options.name, out);
+    out.push('\n');
+    if let Some(namespace) = &options.namespace {
+        out.push_str("  namespace: ");
+        write_json_string(namespace, out);
+        out.push('\n');
+    }
+    if kind == "Secret" {
+        out.push_str("type: Opaque\n");
+    }
+    if entries.is_empty() {
+        writeln!(out, "{}: {{}}", field).unwrap();
+        return;
+    }
+    writeln!(out, "{}:", field).unwrap();
+    for (path, value) in entries {
+        out.push_str("  ");
+        write_json_string(path, out);
+        out.push_str(": ");
+        write_json_string(&value.to_string(), out);
+        out.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn can_write_config_map_and_secret() {
+        let conf = parse_str("log.file = /var/log/app.log\nport = 8080\ndb.password = p\"w\nempty = {}\n", Some("port -> number\ndb.password -> string secret\nempty -> object\n")).unwrap();
+        let options = ManifestOptions::new("app").namespace("prod");
+        assert_eq!(conf.to_k8s_manifests(&options).unwrap(), concat!(
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: \"app\"\n  namespace: \"prod\"\ndata:\n",
+            "  \"log.file\": \"/var/log/app.log\"\n  \"port\": \"8080\"\n",
+            "---\napiVersion: v1\nkind: Secret\nmetadata:\n  name: \"app\"\n  namespace: \"prod\"\ntype: Opaque\nstringData:\n",
+            "  \"db.password\": \"p\\\"w\"\n",
+        ));
+
+        let conf = parse_str("a = 1\n", None).unwrap();
+        assert!(!conf.to_k8s_manifests(&ManifestOptions::new("app")).unwrap().contains("Secret"));
+        assert!(ConfList::new().to_k8s_manifests(&ManifestOptions::new("app")).unwrap().ends_with("data: {}\n"));
+
+        let conf = parse_str("\"log file\" = a\n", None).unwrap();
+        assert_eq!(conf.to_k8s_manifests(&ManifestOptions::new("app")).unwrap_err(), "Invalid ConfigMap key: log file");
+    }
+}
@@ -0,0 +1,74 @@
+// スキーマの型 mime と charset。HTTP で返す Content-Type の既定値などを設定するときに使う
+// 値は書いたまま文字列で持つ
+// mime は type/subtype の後に ; name=value のパラメーターを続けられる (text/html; charset=utf-8)
+// charset はよく使われる IANA の名前と別名だけを受け付ける (大文字小文字は区別しない)
+const CHARSETS: &[&str] = &[
+    "utf-8", "utf8", "utf-16", "utf-16be", "utf-16le", "utf-32", "utf-32be", "utf-32le", "utf-7",
+    "us-ascii", "ascii", "us", "iso646-us", "ansi_x3.4-1968", "cp367", "ibm367",
+    "iso-8859-1", "iso_8859-1", "latin1", "l1", "cp819", "ibm819",
+    "iso-8859-2", "latin2", "iso-8859-3", "latin3", "iso-8859-4", "latin4", "iso-8859-5", "cyrillic", "iso-8859-6", "arabic",
+    "iso-8859-7", "greek", "iso-8859-8", "hebrew", "iso-8859-9", "latin5", "iso-8859-10", "latin6", "iso-8859-13",
+    "iso-8859-14", "iso-8859-15", "latin-9", "iso-8859-16",
+    "windows-1250", "windows-1251", "windows-1252", "windows-1253", "windows-1254", "windows-1255", "windows-1256",
+    "windows-1257", "windows-1258", "windows-874", "ibm437", "ibm850", "ibm852", "ibm866", "cp866",
+    "koi8-r", "koi8-u", "macintosh", "tis-620",
+    "shift_jis", "ms_kanji", "windows-31j", "euc-jp", "iso-2022-jp", "iso-2022-kr", "euc-kr", "ks_c_5601-1987",
+    "gbk", "gb2312", "gb18030", "big5", "big5-hkscs",
+];
+
+pub(crate) fn check_mime(s: &str) -> Result<(), String> {
+    let invalid = || format!("Invalid MIME type: {}", s);
+    let mut parts = s.split(';');
+    let (ty, subtype) = parts.next().unwrap_or_default().trim().split_once('/').ok_or_else(invalid)?;
+    if !is_name(ty) || !is_name(subtype) {
+        return Err(invalid());
+    }
+    for param in parts {
+        let (name, value) = param.trim().split_once('=').ok_or_else(invalid)?;
+        let quoted = value.len() >= 2 && value.starts_with('"') && value.ends_with('"');
+        if !is_token(name) || !(quoted || is_token(value)) {
+            return Err(invalid());
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn check_charset(s: &str) -> Result<(), String> {
+    match CHARSETS.contains(&s.to_ascii_lowercase().as_str()) {
+        true => Ok(()),
+        false => Err(format!("Unknown charset: {}", s)),
+    }
+}
+
+// RFC 6838 の restricted-name。英数字で始め、127 文字まで
+fn is_name(s: &str) -> bool {
+    s.len() <= 127 && s.starts_with(|c: char| c.is_ascii_alphanumeric()) && s.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$&-^_.+".contains(&b))
+}
+
+// RFC 9110 の token (パラメーターの名前と、引用符で囲まない値)
+fn is_token(s: &str) -> bool {
+    !s.is_empty() && s.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_validate_mime_types_and_charsets() {
+        for valid in ["text/html", "application/vnd.api+json", "text/html; charset=utf-8", "multipart/form-data; boundary=\"a b\""] {
+            assert!(check_mime(valid).is_ok(), "{}", valid);
+        }
+        for invalid in ["text", "text/", "/html", "text/html; charset", "text/ht ml", "text/html; a=b c"] {
+            assert_eq!(check_mime(invalid).unwrap_err(), format!("Invalid MIME type: {}", invalid));
+        }
+        assert!(check_charset("UTF-8").is_ok());
+        assert!(check_charset("Shift_JIS").is_ok());
+        assert_eq!(check_charset("utf-9").unwrap_err(), "Unknown charset: utf-9");
+
+        let schema = "http.content_type -> mime\nhttp.charset -> charset\n";
+        assert!(crate::parse_str("http.content_type = text/html; charset=utf-8\nhttp.charset = ISO-8859-1\n", Some(schema)).is_ok());
+        let err = crate::parse_str("http.charset = latin-2\n", Some(schema)).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Unknown charset: latin-2");
+    }
+}
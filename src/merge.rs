@@ -0,0 +1,156 @@
+// 重ねて読み込むとき (parse_dir、include、merge_with) に、パスごとに上書きのしかたを変える
+// 指定がなければリストと値は後のもので置き換え、セクションは再帰的にマージする
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use crate::{ConfList, ConfValue, Schema};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MergeStrategy {
+    // セクションの中をキーごとにマージする
+    Deep,
+    // セクションやリストを丸ごと置き換える
+    Replace,
+    // リストの後ろに追加する
+    Append,
+    // リストの後ろに、まだない要素だけを追加する
+    Unique,
+}
+
+impl FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "deep" => Ok(MergeStrategy::Deep),
+            "replace" => Ok(MergeStrategy::Replace),
+            "append" => Ok(MergeStrategy::Append),
+            "unique" => Ok(MergeStrategy::Unique),
+            _ => Err(format!("Unknown merge strategy: {}", s)),
+        }
+    }
+}
+
+// ドット区切りのパスごとの指定
+pub type MergeStrategies = HashMap<String, MergeStrategy>;
+
+// ParseOptions と、スキーマの "key -> list append" のような指定 (ParseOptions が優先)
+#[derive(Clone, Copy)]
+pub(crate) struct Strategies<'a> {
+    options: Option<&'a MergeStrategies>,
+    schema: Option<&'a Schema>,
+}
+
+impl<'a> Strategies<'a> {
+    pub(crate) const NONE: Strategies<'static> = Strategies { options: None, schema: None };
+
+    #[cfg(feature = "std-fs")]
+    pub(crate) fn new(schema: &'a Schema, options: &'a crate::ParseOptions) -> Self {
+        Strategies { options: Some(&options.merge), schema: Some(schema) }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.options.is_some() || self.schema.is_some()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.options.is_none_or(HashMap::is_empty) && self.schema.is_none_or(|schema| schema.values().all(|entry| entry.merge.is_none()))
+    }
+
+    fn get(&self, path: &str) -> Option<MergeStrategy> {
+        self.options.and_then(|options| options.get(path).copied())
+            .or_else(|| self.schema.and_then(|schema| schema.get(path)).and_then(|entry| entry.merge))
+    }
+}
+
+impl ConfList {
+    // other の値で上書きしながらマージする。strategies に書いたパスはそのしかたでマージする
+    pub fn merge_with(&mut self, other: ConfList, strategies: &MergeStrategies) {
+        self.merge_by(other, Strategies { options: Some(strategies), schema: None });
+    }
+
+    pub(crate) fn merge_by(&mut self, other: ConfList, strategies: Strategies) {
+        // 指定がなければパスを組み立てない
+        let strategies = if strategies.is_empty() { Strategies::NONE } else { strategies };
+        self.merge_at(other, "", strategies);
+    }
+
+    fn merge_at(&mut self, other: ConfList, prefix: &str, strategies: Strategies) {
+        for (key, value, origin, secret, raw) in other.into_entries() {
+            let path = match strategies.is_enabled() {
+                true => format!("{}{}", prefix, key),
+                false => String::new(),
+            };
+            let strategy = strategies.get(&path);
+            let Some(node) = self.find_mut(&key) else {
+                self.push_node(key, value, origin, secret, raw);
+                continue;
+            };
+            let current_secret = node.secret;
+            match (node.value.get_mut(), value, strategy) {
+                (ConfValue::Conf(current), ConfValue::Conf(child), strategy) if strategy != Some(MergeStrategy::Replace) => {
+                    current.merge_at(*child, &format!("{}.", path), strategies);
+                },
+                (ConfValue::List(current), ConfValue::List(items), Some(strategy @ (MergeStrategy::Append | MergeStrategy::Unique))) => {
+                    let mut merged = current.clone();
+                    for item in items {
+                        if strategy == MergeStrategy::Append || !merged.iter().any(|v| v.to_string() == item.to_string()) {
+                            merged.push(item);
+                        }
+                    }
+                    // 書かれていたままの値はどちらの層のものとも違うので持たない
+                    self.push_node(key, ConfValue::List(merged), origin, secret || current_secret, None);
+                },
+                (_, value, _) => self.push_node(key, value, origin, secret, raw),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn can_merge_lists_and_sections_by_path() {
+        let base = "plugins = [auth, cache]\ntags = [a, b]\nhosts = [x]\nlog.file = /var/log/app.log\nlog.level = info\ndb.host = localhost\ndb.port = 5432\n";
+        let overlay = "plugins = [cache, metrics]\ntags = [b, c]\nhosts = [y]\nlog.level = debug\ndb.host = db.local\n";
+        let schema = "plugins -> list unique\ntags -> list append\nhosts -> list\n";
+        let mut conf = parse_str(base, Some(schema)).unwrap();
+        let strategies = MergeStrategies::from([("db".to_string(), MergeStrategy::Replace), ("tags".to_string(), MergeStrategy::Append)]);
+        conf.merge_with(parse_str(overlay, Some(schema)).unwrap(), &strategies);
+        let flat = conf.to_flat_map(false);
+        assert_eq!(flat["tags"], "[a, b, b, c]");
+        // merge_with にはスキーマの指定が効かないので置き換え
+        assert_eq!(flat["plugins"], "[cache, metrics]");
+        assert_eq!(flat["hosts"], "[y]");
+        assert_eq!(flat["log.file"], "/var/log/app.log");
+        assert_eq!(flat["log.level"], "debug");
+        // db は丸ごと置き換えるので port はなくなる
+        assert_eq!(flat["db.host"], "db.local");
+        assert!(!flat.contains_key("db.port"));
+        assert_eq!(conf.origin_of("tags").unwrap().to_string(), "<string>:2");
+        assert!("merge".parse::<MergeStrategy>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "std-fs")]
+    fn can_merge_layers_by_schema() {
+        let dir = std::env::temp_dir().join(format!("conf-merge-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("10-base.conf"), "plugins = [auth, cache]\nlog.level = info\n").unwrap();
+        std::fs::write(dir.join("20-site.conf"), "plugins = [cache, metrics]\nlog.file = /tmp/app.log\n").unwrap();
+        std::fs::write(dir.join("app.schema"), "plugins -> list unique\n").unwrap();
+        let dir_path = dir.to_str().unwrap();
+        let schema = dir.join("app.schema");
+        let conf = crate::parse_dir(dir_path, "*.conf", schema.to_str()).unwrap();
+        assert_eq!(conf.to_flat_map(false)["plugins"], "[auth, cache, metrics]");
+
+        // ParseOptions の指定はスキーマより優先する
+        let options = crate::ParseOptions { merge: MergeStrategies::from([("log".to_string(), MergeStrategy::Replace)]), ..Default::default() };
+        let conf = crate::parse_dir_with_options(dir_path, "*.conf", schema.to_str(), &options).unwrap();
+        assert!(!conf.to_flat_map(false).contains_key("log.level"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
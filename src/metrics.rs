@@ -0,0 +1,87 @@
+use std::error::Error;
+use std::time::{Duration, Instant};
+
+use crate::{Diagnostic, ParseOptions, Severity};
+
+// 1 回の読み込み (parse, parse_dir, validate_file など) の統計
+#[derive(Debug, Clone, Default)]
+pub struct LoadStats {
+    pub files: usize,
+    pub bytes: usize,
+    pub keys: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub duration: Duration,
+}
+
+impl LoadStats {
+    // 診断を重大度ごとに数える
+    pub(crate) fn count(&mut self, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            match diagnostic.severity {
+                Severity::Error => self.errors += 1,
+                Severity::Warning => self.warnings += 1,
+            }
+        }
+    }
+}
+
+// 読み込みが終わるたびに (失敗したときも) 呼ばれる。Prometheus などへの出力用
+pub type MetricsHook = Box<dyn Fn(&LoadStats) + Send + Sync>;
+
+// load の中で stats を埋めてもらい、終わったらフックに渡す
+pub(crate) fn observe<T, F>(options: &ParseOptions, load: F) -> Result<T, Box<dyn Error>>
+where F: FnOnce(&mut LoadStats) -> Result<T, Box<dyn Error>>, {
+    // wasm32-unknown-unknown では Instant::now が panic するので、フックがなければ時計を読まない
+    let started = options.metrics.as_ref().map(|_| Instant::now());
+    let mut stats = LoadStats::default();
+    let result = load(&mut stats);
+    if let (Some(hook), Some(started)) = (&options.metrics, started) {
+        if result.is_err() {
+            stats.errors += 1;
+        }
+        stats.duration = started.elapsed();
+        hook(&stats);
+    }
+    result
+}
+
+#[cfg(all(test, feature = "std-fs"))]
+mod tests {
+    use super::*;
+    use crate::{parse_dir_with_options, parse_str_with_options, validate_file_with_options};
+    use std::sync::{Arc, Mutex};
+
+    fn collect() -> (ParseOptions, Arc<Mutex<Vec<LoadStats>>>) {
+        let collected = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&collected);
+        let options = ParseOptions {
+            metrics: Some(Box::new(move |stats| sink.lock().unwrap().push(stats.clone()))),
+            ..Default::default()
+        };
+        (options, collected)
+    }
+
+    #[test]
+    fn can_report_load_stats() {
+        let (options, collected) = collect();
+        parse_dir_with_options("tests/conf.d", "*.conf", Some("tests/data.schema"), &options).unwrap();
+        parse_str_with_options("debug = maybe\n", Some("debug -> bool\n"), &options).unwrap_err();
+        let report = validate_file_with_options("tests/invalid.conf", Some("tests/invalid.schema"), &options).unwrap();
+
+        let collected = collected.lock().unwrap();
+        assert_eq!(collected.len(), 3);
+        assert_eq!(collected[0].files, 2);
+        assert!(collected[0].keys > 0 && collected[0].bytes > 0);
+        assert_eq!(collected[0].errors, 0);
+        assert_eq!((collected[1].files, collected[1].errors), (0, 1));
+        assert_eq!(collected[2].errors, report.diagnostics.len());
+        assert_eq!(collected[2].warnings, 0);
+
+        let mut stats = LoadStats::default();
+        let mut warning = report.diagnostics[0].clone();
+        warning.severity = Severity::Warning;
+        stats.count(&[report.diagnostics[0].clone(), warning]);
+        assert_eq!((stats.errors, stats.warnings), (1, 1));
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::{parse_schema_lines, validate, ConfList, ConfValue, Origin, ParseOptions, Schema};
+#[cfg(feature = "std-fs")]
+use crate::{parse_schema, read_text};
+
+// パッチの 1 操作
+#[derive(Debug, Clone)]
+pub enum PatchOp {
+    Set(String, ConfValue),
+    Unset(String),
+}
+
+// set / unset を並べたテキスト形式のパッチ
+//
+//   set log.level = debug
+//   unset log.file
+#[derive(Debug, Clone, Default)]
+pub struct Patch {
+    pub ops: Vec<PatchOp>,
+}
+
+impl Patch {
+    pub fn apply_to(&self, conf: &mut ConfList) {
+        for op in &self.ops {
+            match op {
+                PatchOp::Set(path, value) => conf.add_value(path, value.clone(), None),
+                PatchOp::Unset(path) => {
+                    conf.remove(path);
+                },
+            }
+        }
+    }
+}
+
+impl ConfList {
+    // patch の値で上書きする
+    pub fn apply_patch(&mut self, patch: ConfList) {
+        self.merge(patch);
+    }
+}
+
+// set の値はスキーマがあれば型チェックする
+#[cfg(feature = "std-fs")]
+pub fn parse_patch(file_path: &str, schema_path: Option<&str>) -> Result<Patch, Box<dyn Error>> {
+    let schema: Schema = match schema_path {
+        Some(path) => parse_schema(path, &ParseOptions::default())?,
+        None => HashMap::new(),
+    };
+    parse_patch_lines(read_text(file_path, &ParseOptions::default())?.lines().map(str::to_string), &schema, file_path)
+}
+
+pub fn parse_patch_str(patch: &str, schema: Option<&str>) -> Result<Patch, Box<dyn Error>> {
+    let schema: Schema = match schema {
+        Some(s) => parse_schema_lines(s.lines().map(str::to_string), &ParseOptions::default())?,
+        None => HashMap::new(),
+    };
+    parse_patch_lines(patch.lines().map(str::to_string), &schema, "<string>")
+}
+
+fn parse_patch_lines<I>(lines: I, schema: &Schema, source: &str) -> Result<Patch, Box<dyn Error>>
+where I: Iterator<Item = String>, {
+    let mut patch = Patch::default();
+    for (index, line) in lines.enumerate() {
+        let l = line.trim();
+        if l.is_empty() || l.starts_with('#') || l.starts_with(';') {
+            continue;
+        }
+        let origin = Origin::new(source, Some(index + 1));
+        let op = parse_op(l, schema).map_err(|e| format!("{}: {}", origin, e))?;
+        patch.ops.push(op);
+    }
+    Ok(patch)
+}
+
+fn parse_op(line: &str, schema: &Schema) -> Result<PatchOp, String> {
+    let (command, rest) = line.split_once(char::is_whitespace).ok_or("Invalid patch operation")?;
+    match command {
+        "set" => {
+            let (key, value) = rest.split_once('=').ok_or("Missing '=' in set operation")?;
+            let (key, value) = (key.trim(), value.trim());
+            if key.is_empty() {
+                return Err("Missing key in set operation".to_string());
+            }
+            let value = match schema.get(key) {
+                // パッチでは組み込みの変換だけが使える
+                Some(t) => validate(value, t, &ParseOptions::default())?,
+                None => ConfValue::StrValue(value.to_string()),
+            };
+            Ok(PatchOp::Set(key.to_string(), value))
+        },
+        "unset" => Ok(PatchOp::Unset(rest.trim().to_string())),
+        _ => Err(format!("Unknown patch operation: {}", command)),
+    }
+}
+
+#[cfg(all(test, feature = "std-fs"))]
+mod tests {
+    use super::*;
+    use crate::{parse, parse_str};
+
+    #[test]
+    fn can_apply_text_patch() {
+        let mut conf = parse("tests/case-2.conf", Some("tests/data.schema")).unwrap();
+        let patch = parse_patch("tests/case-2.patch", Some("tests/data.schema")).unwrap();
+        patch.apply_to(&mut conf);
+        let expected = parse_str_conf("endpoint = localhost:3000\ndebug = false\nlog.name = default.log\nport = 8080\n");
+        assert!(conf.diff(&expected).is_empty(), "{}", conf.diff(&expected));
+        assert!(parse_patch_str("set debug = maybe", Some("debug -> bool")).is_err());
+    }
+
+    #[test]
+    fn can_apply_conf_as_patch() {
+        let mut conf = parse("tests/case-1.conf", None).unwrap();
+        conf.apply_patch(parse_str_conf("log.level = debug\n"));
+        let diff = parse("tests/case-1.conf", None).unwrap().diff(&conf);
+        assert_eq!(diff.added.len(), 1);
+        assert_eq!(diff.added[0].0, "log.level");
+    }
+
+    fn parse_str_conf(s: &str) -> ConfList {
+        parse_str(s, Some("debug -> bool")).unwrap()
+    }
+}
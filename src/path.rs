@@ -0,0 +1,196 @@
+// 値を読むときのパス。ドット区切りの "log.file" か、JSON Pointer 形式の "/log/file"
+// JSON Pointer では . を含むキー (JSON から読み込んだ "example.com" など) も 1 つのキーとして書ける
+// キーの中の / は ~1、~ は ~0 と書く
+// ドット区切りでは、キーの中の . を \. (\ は \\) と書くか、conf のキーで "example.com" のように引用符で囲む
+// / で始まるパスは JSON Pointer なので、ドット区切りで / で始まるキーを先頭に書くときは \/ と書く
+use std::borrow::Cow;
+
+// 最初のキーと残りのパス。残りも同じ形式で、ドット区切りの残りが / で始まるときは \/ にして JSON Pointer と区別する
+pub(crate) fn split_path(path: &str) -> (Cow<'_, str>, Option<Cow<'_, str>>) {
+    match path.strip_prefix('/') {
+        Some(pointer) => match pointer.find('/') {
+            Some(end) => (unescape(&pointer[..end]), Some(Cow::Borrowed(&pointer[end..]))),
+            None => (unescape(pointer), None),
+        },
+        None => match split_key(path) {
+            (key, Some(rest)) if rest.starts_with('/') => (key, Some(Cow::Owned(format!("\\{}", rest)))),
+            (key, rest) => (key, rest.map(Cow::Borrowed)),
+        },
+    }
+}
+
+// JSON Pointer のパスをドット区切りにする。ドット区切りならそのまま
+pub(crate) fn to_dotted(path: &str) -> Cow<'_, str> {
+    match path.strip_prefix('/') {
+        Some(pointer) => Cow::Owned(pointer.split('/').enumerate().fold(String::new(), |dotted, (i, segment)| match i {
+            0 => join_path("", &unescape(segment)),
+            _ => format!("{}.{}", dotted, escape_key(&unescape(segment))),
+        })),
+        None => Cow::Borrowed(path),
+    }
+}
+
+// ドット区切りのパスの最初のキー (\. を . に戻したもの) と残りのパス
+pub(crate) fn split_key(path: &str) -> (Cow<'_, str>, Option<&str>) {
+    let bytes = path.as_bytes();
+    let mut escaped = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' if i + 1 < bytes.len() => {
+                escaped = true;
+                i += 1;
+            },
+            b'.' => return (unescape_key(&path[..i], escaped), Some(&path[i + 1..])),
+            _ => {},
+        }
+        i += 1;
+    }
+    (unescape_key(path, escaped), None)
+}
+
+fn unescape_key(key: &str, escaped: bool) -> Cow<'_, str> {
+    if !escaped {
+        return Cow::Borrowed(key);
+    }
+    let mut out = String::with_capacity(key.len());
+    let mut chars = key.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    Cow::Owned(out)
+}
+
+// 1 つのキーをドット区切りのパスに書ける形にする
+pub(crate) fn escape_key(key: &str) -> Cow<'_, str> {
+    match key.contains(['.', '\\']) {
+        true => Cow::Owned(key.replace('\\', "\\\\").replace('.', "\\.")),
+        false => Cow::Borrowed(key),
+    }
+}
+
+// 親のパスとキーをつなぐ。/ で始まる先頭のキーは JSON Pointer と区別するため \/ にする
+pub(crate) fn join_path(prefix: &str, key: &str) -> String {
+    match prefix.is_empty() {
+        true if key.starts_with('/') => format!("\\{}", escape_key(key)),
+        true => escape_key(key).into_owned(),
+        false => format!("{}.{}", prefix, escape_key(key)),
+    }
+}
+
+// 親のパスの下に、ドット区切りか JSON Pointer のパスをつなぐ。結果はドット区切り
+pub(crate) fn append_path(prefix: &str, path: &str) -> String {
+    let path = to_dotted(path);
+    match prefix.is_empty() {
+        true => path.into_owned(),
+        false => format!("{}.{}", prefix, path),
+    }
+}
+
+// ドット区切りのパスを、\. を戻したキーの並びにする
+pub(crate) fn split_keys(path: &str) -> Vec<Cow<'_, str>> {
+    let mut keys = Vec::new();
+    let mut rest = Some(path);
+    while let Some((key, tail)) = rest.map(split_key) {
+        keys.push(key);
+        rest = tail;
+    }
+    keys
+}
+
+// 途中のセクションのパス (a.b.c なら a と a.b)
+pub(crate) fn parent_paths(path: &str) -> Vec<&str> {
+    let mut parents = Vec::new();
+    let mut rest = path;
+    while let (_, Some(tail)) = split_key(rest) {
+        parents.push(&path[..path.len() - tail.len() - 1]);
+        rest = tail;
+    }
+    parents
+}
+
+// conf に書いたキーの "..." で囲んだ部分を、\. を使った形にする。a."b.c".d -> a.b\.c.d
+pub(crate) fn unquote_key(key: &str) -> Result<Cow<'_, str>, String> {
+    if !key.contains('"') {
+        return Ok(Cow::Borrowed(key));
+    }
+    let mut out = String::with_capacity(key.len());
+    let mut rest = key;
+    while let Some(start) = rest.find('"') {
+        out.push_str(&rest[..start]);
+        let quoted = &rest[start + 1..];
+        let end = quoted.find('"').ok_or_else(|| format!("Unterminated quoted key: {}", key))?;
+        out.push_str(&escape_key(&quoted[..end]));
+        rest = &quoted[end + 1..];
+    }
+    out.push_str(rest);
+    Ok(Cow::Owned(out))
+}
+
+fn unescape(segment: &str) -> Cow<'_, str> {
+    match segment.contains('~') {
+        // ~01 は ~1 になるよう、~1 を先に戻す
+        true => Cow::Owned(segment.replace("~1", "/").replace("~0", "~")),
+        false => Cow::Borrowed(segment),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_split_dotted_and_pointer_paths() {
+        assert_eq!(split_path("log.file"), (Cow::Borrowed("log"), Some(Cow::Borrowed("file"))));
+        assert_eq!(split_path("/log/file"), (Cow::Borrowed("log"), Some(Cow::Borrowed("/file"))));
+        assert_eq!(split_path("/hosts/example.com"), (Cow::Borrowed("hosts"), Some(Cow::Borrowed("/example.com"))));
+        assert_eq!(split_path("/a~1b~01"), (Cow::<str>::Owned("a/b~1".to_string()), None));
+        assert_eq!(split_path("hosts.example\\.com.ip"), (Cow::Borrowed("hosts"), Some(Cow::Borrowed("example\\.com.ip"))));
+        assert_eq!(split_path("example\\.com.ip"), (Cow::<str>::Owned("example.com".to_string()), Some(Cow::Borrowed("ip"))));
+        assert_eq!(split_path("a\\\\.b"), (Cow::<str>::Owned("a\\".to_string()), Some(Cow::Borrowed("b"))));
+        // ドット区切りの残りは / で始まっても JSON Pointer にならない
+        assert_eq!(split_path("routes./api.auth"), (Cow::Borrowed("routes"), Some(Cow::<str>::Owned("\\/api.auth".to_string()))));
+        assert_eq!(split_key("\\/api.auth"), (Cow::<str>::Owned("/api".to_string()), Some("auth")));
+        assert_eq!(to_dotted("/hosts/example.com/port"), "hosts.example\\.com.port");
+        assert_eq!(to_dotted("/~1api/x"), "\\/api.x");
+        assert_eq!(to_dotted("log.file"), "log.file");
+        assert_eq!(join_path("", "/api"), "\\/api");
+        assert_eq!(join_path("routes", "/api"), "routes./api");
+        assert_eq!(split_keys("hosts.db\\.local.port"), ["hosts", "db.local", "port"]);
+        assert_eq!(append_path("database", "/pool/max"), "database.pool.max");
+        assert_eq!(append_path("", "pool.max"), "pool.max");
+        assert_eq!(join_path("hosts", "example.com"), "hosts.example\\.com");
+        assert_eq!(parent_paths("a.b\\.c.d"), ["a", "a.b\\.c"]);
+        assert_eq!(unquote_key("hosts.\"example.com\".ip").unwrap(), "hosts.example\\.com.ip");
+        assert!(unquote_key("\"a.b").is_err());
+    }
+
+    #[test]
+    fn can_write_keys_containing_dots() {
+        let conf = crate::parse_str_with_options("host\\.example\\.com = 1.2.3.4\nhosts.\"db.local\".port = 5432\n", Some("hosts.db\\.local.port -> number\n"), &Default::default()).unwrap();
+        assert_eq!(conf.get_str("host\\.example\\.com").unwrap(), "1.2.3.4");
+        assert_eq!(conf.get_str("/host.example.com").unwrap(), "1.2.3.4");
+        assert_eq!(conf.get_number("hosts.db\\.local.port").unwrap(), 5432.0);
+        assert!(!conf.contains_key("host"));
+        let text = conf.to_conf_string(&Default::default());
+        assert_eq!(text, "host\\.example\\.com = 1.2.3.4\nhosts.db\\.local.port = 5432\n");
+        assert!(crate::parse_str(&text, Some("hosts.db\\.local.port -> number\n")).unwrap().diff(&conf).is_empty());
+        assert!(crate::borrowed::parse_borrowed("a\\.b = 1\n", None, &Default::default()).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "json")]
+    fn can_read_keys_containing_dots() {
+        let json = serde_json::json!({ "hosts": { "example.com": { "port": 443 } }, "log": { "file": "/var/log/app.log" } });
+        let mut conf = crate::ConfList::try_from(json).unwrap();
+        assert_eq!(conf.get_number("/hosts/example.com/port").unwrap(), 443.0);
+        assert!(conf.get_number("hosts.example.com.port").is_err());
+        assert_eq!(conf.get_str("/log/file").unwrap(), conf.get_str("log.file").unwrap());
+        assert_eq!(conf.get_all("/hosts/example.com/port").len(), 1);
+        assert!(conf.remove("/hosts/example.com"));
+        assert!(!conf.to_flat_map(false).keys().any(|key| key.starts_with("hosts")));
+    }
+}
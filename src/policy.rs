@@ -0,0 +1,112 @@
+// 読み込むときに強制するセキュリティのポリシー。禁止されたキーや値があれば検証エラーにする
+// 環境ごとに変えるなら (本番だけ 0.0.0.0 を禁止するなど)、その環境の ParseOptions にだけ設定する
+use std::error::Error;
+use std::fmt;
+
+use crate::conflict::KeyConflict;
+use crate::{glob_match, is_secret_reference};
+
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    rules: Vec<PolicyRule>,
+}
+
+// パターンはキーや値全体に対する * と ? のグロブ (* は . もまたぐ)
+#[derive(Debug, Clone)]
+enum PolicyRule {
+    Key(String),
+    Plaintext(String),
+    Value { key: String, value: String },
+}
+
+impl Policy {
+    // キーを書くこと自体を禁止する
+    pub fn deny_key(mut self, pattern: &str) -> Self {
+        self.rules.push(PolicyRule::Key(pattern.to_string()));
+        self
+    }
+
+    // env: / file: / ENC(...) の参照以外で値を書くことを禁止する ("*.password" など)
+    pub fn deny_plaintext(mut self, pattern: &str) -> Self {
+        self.rules.push(PolicyRule::Plaintext(pattern.to_string()));
+        self
+    }
+
+    // key に value と一致する値を使うことを禁止する。参照や ${...} を展開したあとの値で比べる
+    pub fn deny_value(mut self, key: &str, value: &str) -> Self {
+        self.rules.push(PolicyRule::Value { key: key.to_string(), value: value.to_string() });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    // written はファイルに書かれたままの値、value は展開したあとの値
+    pub(crate) fn check(&self, key: &str, written: &str, value: &str) -> Result<(), PolicyViolation> {
+        for rule in &self.rules {
+            let message = match rule {
+                PolicyRule::Key(pattern) if glob_match(pattern, key) => format!("Key is not allowed by policy: {}", key),
+                PolicyRule::Plaintext(pattern) if glob_match(pattern, key) && !is_secret_reference(written) => {
+                    format!("Plaintext value is not allowed by policy: {} (use env:, file: or ENC(...))", key)
+                },
+                // 値はシークレットかもしれないので、メッセージにはパターンだけを出す
+                PolicyRule::Value { key: pattern, value: denied } if glob_match(pattern, key) && glob_match(denied, value) => {
+                    format!("Value of {} is not allowed by policy: {}", key, denied)
+                },
+                _ => continue,
+            };
+            return Err(PolicyViolation(message));
+        }
+        Ok(())
+    }
+}
+
+// 検証のエラーのうちポリシーに反したもの (診断の code は policy-violation)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PolicyViolation(pub String);
+
+impl fmt::Display for PolicyViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for PolicyViolation {}
+
+// 診断の code を選ぶ
+pub(crate) fn error_code(e: &(dyn Error + 'static)) -> &'static str {
+    if e.is::<PolicyViolation>() {
+        return "policy-violation";
+    }
+    match e.is::<KeyConflict>() {
+        true => "key-conflict",
+        false => "invalid-value",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_str_with_options, Decryptor, ParseOptions};
+
+    fn options() -> ParseOptions {
+        let policy = Policy::default().deny_plaintext("*.password").deny_value("*.bind", "0.0.0.0*").deny_key("debug.*");
+        let decryptor: Decryptor = Box::new(|cipher: &str| Ok(cipher.chars().rev().collect()));
+        ParseOptions { policy, decryptor: Some(decryptor), inline_tables: true, ..Default::default() }
+    }
+
+    #[test]
+    fn can_deny_keys_and_values() {
+        let conf = "db.password = ENC(2retnuh)\nserver.bind = 127.0.0.1:8080\n";
+        assert!(parse_str_with_options(conf, None, &options()).is_ok());
+
+        let err = parse_str_with_options("db.password = hunter2\n", None, &options()).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Plaintext value is not allowed by policy: db.password (use env:, file: or ENC(...))");
+        let err = parse_str_with_options("server = {bind = 0.0.0.0:8080}\n", None, &options()).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Value of server.bind is not allowed by policy: 0.0.0.0*");
+        let err = parse_str_with_options("port = 1\ndebug.dump = yes\n", None, &options()).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:2: Key is not allowed by policy: debug.dump");
+        assert!(parse_str_with_options("db.password = hunter2\n", None, &ParseOptions::default()).is_ok());
+    }
+}
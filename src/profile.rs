@@ -0,0 +1,87 @@
+// 1 つのファイルに環境ごとの差分をまとめて書く
+// "--- production" または "[profile production]" の行から次の区切りまでがその名前のドキュメントで、
+// 最初の区切りより前が共通の部分。ParseOptions::profiles で選んだドキュメントだけを、ファイルに書いた順に上から重ねる
+// 選ばれなかったドキュメントは読まないが、validate_file はすべてのドキュメントを確かめる
+use crate::ParseOptions;
+
+// 区切りの行ならプロファイル名を返す。名前がなければエラー
+pub(crate) fn marker(line: &str) -> Option<Result<&str, String>> {
+    let l = line.trim();
+    let rest = match l.strip_prefix("---") {
+        Some(rest) => rest,
+        None => l.strip_prefix("[profile")?.strip_suffix(']')?,
+    };
+    // ---- や [profiles] は区切りではない
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+    let name = rest.trim();
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Some(Err(format!("Profile marker needs a single name: {}", l)));
+    }
+    Some(Ok(name))
+}
+
+// 区切りの行を読むたびに、その後の行を読むかどうかを切り替える
+pub(crate) struct Documents<'a> {
+    options: &'a ParseOptions,
+    selected: bool,
+}
+
+impl<'a> Documents<'a> {
+    pub(crate) fn new(options: &'a ParseOptions) -> Self {
+        Documents { options, selected: true }
+    }
+
+    // 区切りの行なら Some を返す。名前のない区切りの後は、次の区切りまで読まない
+    pub(crate) fn enter(&mut self, line: &str) -> Option<Result<(), String>> {
+        let name = marker(line)?;
+        self.selected = name.as_ref().is_ok_and(|name| self.options.profiles.iter().any(|p| p == name));
+        Some(name.map(|_| ()))
+    }
+
+    pub(crate) fn is_selected(&self) -> bool {
+        self.selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::marker;
+    use crate::{parse_str_partial, parse_str_with_options, ParseOptions};
+
+    const CONF: &str = "host = localhost\nport = 8080\nlog.level = debug\n--- production\nhost = app.example.com\nlog.level = warn\n[profile staging]\nhost = staging.example.com\n";
+
+    #[test]
+    fn can_overlay_selected_profiles() {
+        let base = parse_str_with_options(CONF, None, &ParseOptions::default()).unwrap();
+        assert_eq!(base.get_str("host").unwrap(), "localhost");
+        assert_eq!(base.get_str("log.level").unwrap(), "debug");
+
+        let options = ParseOptions { profiles: vec!["production".to_string()], ..Default::default() };
+        let conf = parse_str_with_options(CONF, Some("port -> number\n"), &options).unwrap();
+        assert_eq!(conf.get_str("host").unwrap(), "app.example.com");
+        assert_eq!(conf.get_str("log.level").unwrap(), "warn");
+        assert_eq!(conf.get_number("port").unwrap(), 8080.0);
+        assert_eq!(conf.origin_of("host").unwrap().to_string(), "<string>:5");
+
+        // 選んだ順ではなく、ファイルに書いた順に重ねる
+        let options = ParseOptions { profiles: vec!["staging".to_string(), "production".to_string()], ..Default::default() };
+        let conf = parse_str_with_options(CONF, None, &options).unwrap();
+        assert_eq!(conf.get_str("host").unwrap(), "staging.example.com");
+        assert_eq!(conf.get_str("log.level").unwrap(), "warn");
+    }
+
+    #[test]
+    fn can_report_markers_without_names() {
+        assert_eq!(marker("--- prod").unwrap().unwrap(), "prod");
+        assert_eq!(marker("[profile  prod ]").unwrap().unwrap(), "prod");
+        assert!(marker("---").unwrap().is_err());
+        assert!(marker("[profile a b]").unwrap().is_err());
+        assert!(marker("----").is_none() && marker("[profiles]").is_none());
+
+        let partial = parse_str_partial("a = 1\n---\na = 2\n", None, &ParseOptions::default()).unwrap();
+        assert_eq!(partial.diagnostics[0].message, "Profile marker needs a single name: ---");
+        assert_eq!(partial.conf.get_str("a").unwrap(), "1");
+    }
+}
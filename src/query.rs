@@ -0,0 +1,73 @@
+// conf.query("db.*.host") で、パターンに一致する末端の値をまとめて取り出す
+// * と ? は 1 つのキーの中だけに一致し (db.* は db.host に一致するが db.pool.max には一致しない)、
+// ** は 0 個以上のキーに一致する。キーの中の . はパスと同じく \. と書く
+use std::borrow::Cow;
+
+use crate::path::split_keys;
+use crate::{glob_match, ConfList, ConfValue};
+
+impl ConfList {
+    // 一致した値をソース順で返す。読んだ値は get と同じく track-access や監査フックに記録する
+    pub fn query(&self, pattern: &str) -> impl Iterator<Item = (String, ConfValue)> + '_ {
+        let pattern: Vec<String> = split_keys(pattern).into_iter().map(Cow::into_owned).collect();
+        self.leaves().into_iter()
+            .filter(move |(path, _)| path_match(&pattern, path))
+            .map(|(path, value)| {
+                let value = self.read_at(&path, ConfValue::clone).unwrap_or(value);
+                (path, value)
+            })
+    }
+}
+
+// glob_match と同じやり方で、キーの並びに対して ** を戻りながら試す
+fn path_match(pattern: &[String], path: &str) -> bool {
+    let segments = split_keys(path);
+    let (mut pi, mut si) = (0, 0);
+    // 直前の ** の位置と、そこから試しているキーの位置
+    let mut star: Option<(usize, usize)> = None;
+    while si < segments.len() {
+        if pi < pattern.len() && pattern[pi] != "**" && glob_match(&pattern[pi], &segments[si]) {
+            pi += 1;
+            si += 1;
+        } else if pi < pattern.len() && pattern[pi] == "**" {
+            star = Some((pi, si));
+            pi += 1;
+        } else if let Some((sp, ss)) = star {
+            pi = sp + 1;
+            si = ss + 1;
+            star = Some((sp, ss + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[pi..].iter().all(|p| p == "**")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_str;
+
+    const CONF: &str = "db.primary.host = a.local\ndb.primary.port = 5432\ndb.replica.host = b.local\ndb.replica.pool.host = c.local\ncache.host = d.local\nhost = e.local\n";
+
+    fn paths(pattern: &str) -> Vec<String> {
+        parse_str(CONF, None).unwrap().query(pattern).map(|(path, _)| path).collect()
+    }
+
+    #[test]
+    fn can_query_paths_with_wildcards() {
+        assert_eq!(paths("db.*.host"), ["db.primary.host", "db.replica.host"]);
+        assert_eq!(paths("db.**.host"), ["db.primary.host", "db.replica.host", "db.replica.pool.host"]);
+        assert_eq!(paths("**.host"), ["db.primary.host", "db.replica.host", "db.replica.pool.host", "cache.host", "host"]);
+        assert_eq!(paths("db.*.p*"), ["db.primary.port"]);
+        assert_eq!(paths("db.**").len(), 4);
+        assert!(paths("db.*").is_empty());
+        let conf = parse_str("hosts.\"db.local\".port = 5432\nhosts.web.port = 80\n", None).unwrap();
+        let found: Vec<String> = conf.query("hosts.*.port").map(|(path, _)| path).collect();
+        assert_eq!(found, ["hosts.db\\.local.port", "hosts.web.port"]);
+        assert_eq!(conf.query("hosts.db\\.*.port").count(), 1);
+
+        let conf = parse_str(CONF, Some("db.primary.port -> number\n")).unwrap();
+        let (_, value) = conf.query("db.*.port").next().unwrap();
+        assert_eq!(value.as_number().unwrap(), 5432.0);
+    }
+}
@@ -0,0 +1,301 @@
+// ${db.host} のように同じ conf の別のキーを参照する値は、後の行のキーも参照できるよう、
+// ファイルをすべて読んでから参照される側が先になる順に追加する。循環していればその経路を報告する
+// 読んだ位置には書かれたままの値を置いておき、展開した値をその位置に入れるので、ソース順は変わらない
+use std::collections::HashMap;
+use std::error::Error;
+
+use crate::interpolate::{key_references, replace_key_references};
+use crate::path::split_key;
+use crate::{add_entry_value, conflict, policy, ConfList, ConfValue, Diagnostic, Node, Origin, ParseContext};
+
+pub(crate) struct Deferred {
+    key: String,
+    value: String,
+    quoted: bool,
+    origin: Origin,
+    // 後の行で書き直された。置いておいた値を取り除くだけで展開はしない
+    overwritten: bool,
+}
+
+impl Deferred {
+    // 参照を展開する前に置いた値のノードか
+    fn is_placeholder(&self, node: &Node) -> bool {
+        node.origin.as_ref().is_some_and(|origin| origin.source == self.origin.source && origin.line == self.origin.line)
+            && matches!(&*node.value.borrow(), ConfValue::StrValue(value) if *value == self.value)
+    }
+}
+
+impl ParseContext<'_> {
+    pub(crate) fn has_references(&self, value: &str) -> bool {
+        self.options.interpolation.is_some() && value.contains("${") && !key_references(value).is_empty()
+    }
+
+    // 同じキーを後の行で書き直したら、前の値は使わない
+    pub(crate) fn defer(&mut self, map: &mut ConfList, key: &str, value: &str, quoted: bool, origin: Origin) -> Result<(), Box<dyn Error>> {
+        let key = conflict::check(map, key, self.options.conflicts, &mut self.interner)?;
+        self.forget_deferred(&key);
+        let secret = self.schema.get(key.as_ref()).is_some_and(|entry| entry.secret);
+        map.add_value_interned(&key, ConfValue::StrValue(value.to_string()), Some(origin.clone()), secret, None, &mut self.interner);
+        self.deferred.push(Deferred { key: key.into_owned(), value: value.to_string(), quoted, origin, overwritten: false });
+        Ok(())
+    }
+
+    pub(crate) fn forget_deferred(&mut self, key: &str) {
+        for deferred in self.deferred.iter_mut().filter(|deferred| deferred.key == key) {
+            deferred.overwritten = true;
+        }
+    }
+
+    // parse_dir では、すべてのファイルをマージしてから、それぞれのファイルで後回しにした値をまとめて展開する
+    #[cfg(feature = "std-fs")]
+    pub(crate) fn take_deferred(&mut self) -> Vec<Deferred> {
+        std::mem::take(&mut self.deferred)
+    }
+
+    #[cfg(feature = "std-fs")]
+    pub(crate) fn extend_deferred(&mut self, deferred: Vec<Deferred>) {
+        for entry in &deferred {
+            self.forget_deferred(&entry.key);
+        }
+        self.deferred.extend(deferred);
+    }
+
+    pub(crate) fn resolve_references(&mut self, map: &mut ConfList) -> Result<(), Box<dyn Error>> {
+        let deferred = std::mem::take(&mut self.deferred);
+        if deferred.is_empty() {
+            return Ok(());
+        }
+        let (order, cycles) = resolution_order(&deferred);
+        let mut skipped: Vec<bool> = deferred.iter().map(|entry| entry.overwritten).collect();
+        for cycle in &cycles {
+            let path: Vec<&str> = cycle.iter().map(|&i| deferred[i].key.as_str()).collect();
+            let first = &deferred[cycle[0]];
+            self.fail(Diagnostic::error(&first.origin, "circular-reference", format!("Circular reference: {}", path.join(" -> "))).path(&first.key))?;
+            cycle.iter().for_each(|&i| skipped[i] = true);
+        }
+        // 展開しない値は、参照している側から見えないよう先に取り除く
+        for (entry, _) in deferred.iter().zip(&skipped).filter(|(_, &skipped)| skipped) {
+            map.remove_placeholder(entry);
+        }
+        for i in order.into_iter().filter(|&i| !skipped[i]) {
+            let entry = &deferred[i];
+            // 別のファイルなどで書き直されていれば使わない
+            if !map.is_live_placeholder(entry) {
+                map.remove_placeholder(entry);
+                continue;
+            }
+            // secret の値を参照していれば、展開した値も secret にする
+            let mut secret = false;
+            let result = replace_key_references(&entry.value, |name| {
+                secret |= map.is_secret(name);
+                map.value_at(name, |value| match value {
+                    ConfValue::Conf(_) => Err(format!("Referenced key is a section: {}", name)),
+                    value => Ok(value.to_string()),
+                }).transpose()
+            });
+            let result = result.map_err(Box::<dyn Error>::from).and_then(|(value, interpolations)| {
+                let origin = Origin { interpolations, ..entry.origin.clone() };
+                add_entry_value(map, &entry.key, &value, entry.quoted, self, origin)
+            });
+            match result {
+                Ok(()) => map.settle(entry, secret),
+                Err(e) => {
+                    map.remove_placeholder(entry);
+                    self.fail(Diagnostic::error(&entry.origin, policy::error_code(e.as_ref()), e.to_string()).path(&entry.key))?;
+                },
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ConfList {
+    // ドット区切りのキーの最後のキーと、それを持つリスト
+    fn parent_list_mut<'a>(&mut self, key: &'a str) -> Option<(&mut ConfList, std::borrow::Cow<'a, str>)> {
+        let mut list: &mut ConfList = self;
+        let mut rest = key;
+        loop {
+            match split_key(rest) {
+                (head, Some(tail)) => match list.find_mut(&head)?.value.get_mut() {
+                    ConfValue::Conf(child) => {
+                        list = child;
+                        rest = tail;
+                    },
+                    _ => return None,
+                },
+                (last, None) => return Some((list, last)),
+            }
+        }
+    }
+
+    fn is_live_placeholder(&mut self, entry: &Deferred) -> bool {
+        self.parent_list_mut(&entry.key)
+            .and_then(|(list, last)| list.find_mut(&last).map(|node| entry.is_placeholder(node)))
+            .unwrap_or(false)
+    }
+
+    fn remove_placeholder(&mut self, entry: &Deferred) {
+        if let Some((list, last)) = self.parent_list_mut(&entry.key) {
+            list.unlink(|node| *node.key == *last && entry.is_placeholder(node));
+        }
+    }
+
+    // 展開して追加した最新のノードの値を、置いておいた値のノードに移す
+    fn settle(&mut self, entry: &Deferred, secret: bool) {
+        let Some((list, last)) = self.parent_list_mut(&entry.key) else {
+            return;
+        };
+        // 展開する前は置いておいた値が最新だったので、そのすぐ後に追加したノードがある
+        let placed = list.nodes().filter(|node| *node.key == *last).nth(1).is_some_and(|node| entry.is_placeholder(node));
+        if !placed {
+            return;
+        }
+        let resolved = list.unlink(|node| *node.key == *last).unwrap();
+        let node = list.find_mut(&last).unwrap();
+        node.value = resolved.value;
+        node.origin = resolved.origin;
+        node.secret = resolved.secret || secret;
+        node.raw = resolved.raw;
+    }
+
+    // f に合う最新のノードを取り除いて返す
+    fn unlink(&mut self, f: impl Fn(&Node) -> bool) -> Option<Box<Node>> {
+        let mut current = &mut self.head;
+        while current.is_some() {
+            if f(current.as_ref().unwrap()) {
+                let mut node = current.take().unwrap();
+                *current = node.next.take();
+                return Some(node);
+            }
+            current = &mut current.as_mut().unwrap().next;
+        }
+        None
+    }
+
+    fn nodes(&self) -> impl Iterator<Item = &Node> {
+        std::iter::successors(self.head.as_deref(), |node| node.next.as_deref())
+    }
+}
+
+// 参照される側が先になる順序と、見つかった循環 (a -> b -> a のように最初のキーで終わる)
+// 長い参照の連鎖でもスタックを使わないようにループで深さ優先にたどる
+fn resolution_order(deferred: &[Deferred]) -> (Vec<usize>, Vec<Vec<usize>>) {
+    let index: HashMap<&str, usize> = deferred.iter().enumerate()
+        .filter(|(_, entry)| !entry.overwritten)
+        .map(|(i, entry)| (entry.key.as_str(), i))
+        .collect();
+    let references: Vec<Vec<usize>> = deferred.iter()
+        .map(|entry| key_references(&entry.value).iter().filter_map(|key| index.get(key.as_str()).copied()).collect())
+        .collect();
+    // 0: まだ, 1: たどっている途中, 2: 済み
+    let mut state = vec![0u8; deferred.len()];
+    let mut order = Vec::new();
+    let mut cycles = Vec::new();
+    for root in 0..deferred.len() {
+        if state[root] != 0 {
+            continue;
+        }
+        state[root] = 1;
+        let mut stack = vec![(root, 0)];
+        while let Some((node, next)) = stack.last_mut() {
+            let node = *node;
+            match references[node].get(*next) {
+                Some(&target) => {
+                    *next += 1;
+                    match state[target] {
+                        0 => {
+                            state[target] = 1;
+                            stack.push((target, 0));
+                        },
+                        1 => {
+                            let start = stack.iter().position(|&(n, _)| n == target).unwrap();
+                            let mut cycle: Vec<usize> = stack[start..].iter().map(|&(n, _)| n).collect();
+                            cycle.push(target);
+                            cycles.push(cycle);
+                        },
+                        _ => {},
+                    }
+                },
+                None => {
+                    stack.pop();
+                    state[node] = 2;
+                    order.push(node);
+                },
+            }
+        }
+    }
+    (order, cycles)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_str_partial, parse_str_with_options, ConfValue, Interpolation, Interpolator, ParseOptions, Resolution, REDACTED};
+
+    fn options() -> ParseOptions {
+        ParseOptions { interpolation: Some(Interpolator::new()), ..Default::default() }
+    }
+
+    #[test]
+    fn can_reference_other_keys() {
+        let conf = "url = http://${db.host}:${db.port}/${db.name:-app}\ndb.host = ${db.primary}\ndb.primary = db.local\ndb.port = 5432\nescaped = $${db.host}\n";
+        let conf = parse_str_with_options(conf, Some("db.port -> number\n"), &options()).unwrap();
+        assert_eq!(conf.get_str("url").unwrap(), "http://db.local:5432/app");
+        assert_eq!(conf.get_str("escaped").unwrap(), "${db.host}");
+        let origin = conf.origin_of("url").unwrap();
+        assert_eq!(origin.line, Some(1));
+        assert_eq!(origin.interpolations[0], Interpolation { placeholder: "db.host".to_string(), resolution: Resolution::Key });
+        assert_eq!(origin.interpolations[2].resolution, Resolution::Default);
+        // 後の行で書き直した値が使われる
+        let conf = parse_str_with_options("a.b = ${a.c}\na.c = 1\na.b = 2\n", None, &options()).unwrap();
+        assert_eq!(conf.get_str("a.b").unwrap(), "2");
+        assert!(parse_str_with_options("a.b = ${a.missing}\n", None, &options()).unwrap_err().to_string().contains("Referenced key not found: a.missing"));
+    }
+
+    #[test]
+    fn can_report_circular_references() {
+        let conf = "a.x = ${b.x}\nb.x = ${c.x}\nc.x = ${a.x}\nself.x = ${self.x}\nok.x = 1\n";
+        let err = parse_str_with_options(conf, None, &options()).unwrap_err();
+        assert_eq!(err.to_string(), "<string>:1: Circular reference: a.x -> b.x -> c.x -> a.x");
+
+        let partial = parse_str_partial(conf, None, &options()).unwrap();
+        let messages: Vec<String> = partial.diagnostics.iter().map(|d| d.message.clone()).collect();
+        assert_eq!(messages, ["Circular reference: a.x -> b.x -> c.x -> a.x", "Circular reference: self.x -> self.x"]);
+        assert_eq!(partial.conf.get_str("ok.x").unwrap(), "1");
+    }
+
+    #[test]
+    fn can_keep_position_and_secret_of_references() {
+        let conf = "token = ${db.password}\nname = app\ndb.password = hunter2\n";
+        let conf = parse_str_with_options(conf, Some("db.password -> string secret\n"), &options()).unwrap();
+        assert_eq!(conf.live_keys(), ["token", "name", "db"]);
+        assert_eq!(conf.get_str("token").unwrap(), "hunter2");
+        assert!(conf.is_secret("token"));
+        assert_eq!(conf.raw_of("token").unwrap(), REDACTED);
+        assert_eq!(conf.to_flat_map(true)["token"], REDACTED);
+
+        // 書き直した値や展開できなかった値は残さない
+        let conf = parse_str_with_options("a = ${b.x}\nb.x = 1\na = 2\n", None, &options()).unwrap();
+        assert_eq!(conf.get_all("a"), [ConfValue::StrValue("2".to_string())]);
+        let partial = parse_str_partial("a = 1\na = ${b.missing}\nc = 2\n", None, &options()).unwrap();
+        assert_eq!(partial.conf.get_all("a"), [ConfValue::StrValue("1".to_string())]);
+        assert_eq!(partial.conf.live_keys(), ["a", "c"]);
+    }
+
+    #[test]
+    #[cfg(feature = "std-fs")]
+    fn can_reference_keys_in_other_files() {
+        let dir = std::env::temp_dir().join(format!("conf-reference-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("10-app.conf"), "url = http://${db.host}/\nname = app\n").unwrap();
+        std::fs::write(dir.join("20-db.conf"), "db.host = db.local\n").unwrap();
+        let conf = crate::parse_dir_with_options(dir.to_str().unwrap(), "*.conf", None, &options()).unwrap();
+        assert_eq!(conf.get_str("url").unwrap(), "http://db.local/");
+        assert_eq!(conf.live_keys(), ["url", "name", "db"]);
+
+        // 後のファイルで書き直した値は展開しない
+        std::fs::write(dir.join("30-url.conf"), "url = http://localhost/\n").unwrap();
+        let conf = crate::parse_dir_with_options(dir.to_str().unwrap(), "*.conf", None, &options()).unwrap();
+        assert_eq!(conf.get_all("url"), [ConfValue::StrValue("http://localhost/".to_string())]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::diff::ConfDiff;
+use crate::global;
+use crate::{parse_with_options, Config, ConfigValue, ConfList, ParseOptions};
+
+// ファイルを読み直して変更を購読者に通知する
+pub struct Reloader {
+    file_path: String,
+    schema_path: Option<String>,
+    options: ParseOptions,
+    conf: ConfList,
+    config: Arc<Config>,
+    subscribers: Vec<(String, Sender<Option<ConfigValue>>)>,
+    // 最後に読み込んだときのファイルの中身のハッシュ (ファイルがなければ None)
+    content_hash: Option<u64>,
+    // install_global の後は、読み直すたびにプロセスの設定も置き換える
+    global: bool,
+}
+
+impl Reloader {
+    pub fn new(file_path: &str, schema_path: Option<&str>, options: ParseOptions) -> Result<Self, Box<dyn Error>> {
+        let content_hash = content_hash(file_path);
+        let conf = parse_with_options(file_path, schema_path, &options)?;
+        let config = Arc::new(conf.clone().freeze());
+        Ok(Reloader {
+            file_path: file_path.to_string(),
+            schema_path: schema_path.map(str::to_string),
+            options,
+            conf,
+            config,
+            subscribers: Vec::new(),
+            content_hash,
+            global: false,
+        })
+    }
+
+    pub fn current(&self) -> Arc<Config> {
+        Arc::clone(&self.config)
+    }
+
+    // 今の設定を global() で読めるようにし、この後読み直すたびに置き換える
+    pub fn install_global(&mut self) {
+        self.global = true;
+        self.publish_global();
+    }
+
+    fn publish_global(&self) {
+        if global::swap_global(Arc::clone(&self.config)).is_none() {
+            // 同時に初期化されたときは、その設定を置き換える
+            if global::init_global_arc(Arc::clone(&self.config)).is_err() {
+                global::swap_global(Arc::clone(&self.config));
+            }
+        }
+    }
+
+    // パス (とその下の値) が変わるたびに新しい値を受け取る。削除されたときは None
+    pub fn watch(&mut self, path: &str) -> Receiver<Option<ConfigValue>> {
+        let (sender, receiver) = channel();
+        self.subscribers.push((path.to_string(), sender));
+        receiver
+    }
+
+    // 読み直しに失敗したときは今の設定をそのまま使い続ける
+    // parse と違い、ファイルがないのもエラー (書き換えの途中で消えたのを、設定が空になったとはみなさない)
+    #[cfg_attr(feature = "tracing", tracing::instrument(level = "info", skip(self), fields(file = %self.file_path)))]
+    pub fn reload(&mut self) -> Result<ConfDiff, Box<dyn Error>> {
+        // 読んでいる間に書き換えられても、次の読み直しで気付けるよう先に求める
+        let hash = content_hash(&self.file_path);
+        let parsed = match std::fs::metadata(&self.file_path) {
+            Ok(_) => parse_with_options(&self.file_path, self.schema_path.as_deref(), &self.options),
+            Err(e) => Err(format!("{}: {}", self.file_path, e).into()),
+        };
+        let conf = match parsed {
+            Ok(conf) => conf,
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %e, "reload failed, keeping the current config");
+                return Err(e);
+            },
+        };
+        self.content_hash = hash;
+        let diff = self.conf.diff(&conf);
+        #[cfg(feature = "tracing")]
+        tracing::info!(
+            added = diff.added.len(),
+            removed = diff.removed.len(),
+            changed = diff.changed.len(),
+            "reloaded config",
+        );
+        if diff.is_empty() {
+            return Ok(diff);
+        }
+        self.config = Arc::new(conf.clone().freeze());
+        self.conf = conf;
+        if self.global {
+            self.publish_global();
+        }
+        self.notify(&diff);
+        Ok(diff)
+    }
+
+    // ファイルの中身が前に読んだときと同じなら読み直さずに None を返す
+    // include したファイルの変更は分からないので、そのときは reload を使う
+    pub fn reload_if_changed(&mut self) -> Result<Option<ConfDiff>, Box<dyn Error>> {
+        if content_hash(&self.file_path) == self.content_hash {
+            return Ok(None);
+        }
+        self.reload().map(Some)
+    }
+
+    // ファイルの監視から変更の通知を受け取り、quiet の間通知が来なくなってから 1 度だけ読み直す
+    // (エディタは一時ファイルに書いてから rename するので、1 回の保存で通知がいくつも来る)
+    // 中身が変わっていなければ読み直さない。読み直すたびに f に結果を渡し、送信側がすべて破棄されたら戻る
+    pub fn run_debounced<F>(&mut self, events: &Receiver<()>, quiet: Duration, mut f: F)
+    where F: FnMut(Result<ConfDiff, Box<dyn Error>>), {
+        while events.recv().is_ok() {
+            let disconnected = loop {
+                match events.recv_timeout(quiet) {
+                    Ok(()) => continue,
+                    Err(RecvTimeoutError::Timeout) => break false,
+                    Err(RecvTimeoutError::Disconnected) => break true,
+                }
+            };
+            if let Some(result) = self.reload_if_changed().transpose() {
+                f(result);
+            }
+            if disconnected {
+                return;
+            }
+        }
+    }
+
+    fn notify(&mut self, diff: &ConfDiff) {
+        let changed: Vec<&str> = diff.added.iter().map(|(p, _)| p.as_str())
+            .chain(diff.removed.iter().map(|(p, _)| p.as_str()))
+            .chain(diff.changed.iter().map(|(p, _, _)| p.as_str()))
+            .collect();
+        let config = &self.config;
+        // 受信側が破棄された購読はここで取り除く
+        self.subscribers.retain(|(path, sender)| {
+            if !changed.iter().any(|p| is_under(p, path)) {
+                return true;
+            }
+            sender.send(config.get(path).cloned()).is_ok()
+        });
+    }
+}
+
+fn content_hash(file_path: &str) -> Option<u64> {
+    let bytes = std::fs::read(file_path).ok()?;
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    Some(hasher.finish())
+}
+
+// changed が path そのものか、その下のパスか
+fn is_under(changed: &str, path: &str) -> bool {
+    changed == path || (changed.starts_with(path) && changed[path.len()..].starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn notifies_only_subscribers_of_changed_paths() {
+        let path = std::env::temp_dir().join(format!("conf-reload-{}.conf", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "endpoint = localhost:3000\nlog.level = info\n").unwrap();
+        let mut reloader = Reloader::new(path, None, ParseOptions::default()).unwrap();
+        let level = reloader.watch("log.level");
+        let log = reloader.watch("log");
+        let endpoint = reloader.watch("endpoint");
+
+        fs::write(path, "endpoint = localhost:3000\nlog.level = debug\n").unwrap();
+        let diff = reloader.reload().unwrap();
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(level.try_recv().unwrap(), Some(ConfigValue::StrValue("debug".to_string())));
+        assert!(log.try_recv().unwrap().is_some());
+        assert!(endpoint.try_recv().is_err());
+        assert_eq!(reloader.current().get("log.level").unwrap().as_str().unwrap(), "debug");
+
+        fs::write(path, "endpoint = localhost:3000\n").unwrap();
+        reloader.reload().unwrap();
+        assert_eq!(level.try_recv().unwrap(), None);
+
+        // ファイルがなくなっても、空の設定に置き換えない
+        fs::remove_file(path).unwrap();
+        assert!(reloader.reload().is_err());
+        assert!(reloader.reload_if_changed().is_err());
+        assert_eq!(reloader.current().get("endpoint").unwrap().as_str().unwrap(), "localhost:3000");
+        assert!(endpoint.try_recv().is_err());
+    }
+
+    #[test]
+    fn can_debounce_bursts_of_events() {
+        let path = std::env::temp_dir().join(format!("conf-debounce-{}.conf", std::process::id()));
+        let path = path.to_str().unwrap();
+        fs::write(path, "log.level = info\n").unwrap();
+        let mut reloader = Reloader::new(path, None, ParseOptions::default()).unwrap();
+
+        let (sender, events) = channel();
+        fs::write(path, "log.level = debug\n").unwrap();
+        for _ in 0..5 {
+            sender.send(()).unwrap();
+        }
+        drop(sender);
+        let mut diffs = Vec::new();
+        reloader.run_debounced(&events, Duration::from_millis(10), |result| diffs.push(result.unwrap()));
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].changed.len(), 1);
+        assert_eq!(reloader.current().get("log.level").unwrap().as_str().unwrap(), "debug");
+
+        // 中身が同じなら読み直さない
+        fs::write(path, "log.level = debug\n").unwrap();
+        let (sender, events) = channel();
+        sender.send(()).unwrap();
+        drop(sender);
+        reloader.run_debounced(&events, Duration::from_millis(10), |_| panic!("reloaded unchanged file"));
+        assert!(reloader.reload_if_changed().unwrap().is_none());
+        fs::remove_file(path).unwrap();
+    }
+}
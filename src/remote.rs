@@ -0,0 +1,277 @@
+use std::collections::hash_map::DefaultHasher;
+use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::time::Duration;
+
+#[cfg(feature = "std-fs")]
+use crate::cache::{cached_source, RemoteCache};
+use crate::retry::RetryPolicy;
+use crate::{parse_str_from, ConfList, ParseOptions};
+
+// 取得した conf と schema の本文、キャッシュ用のヘッダ
+type Fetched = (String, Option<String>, Option<String>, Option<String>);
+
+// URL から conf (と schema) を取得するソース
+pub struct RemoteSource {
+    pub url: String,
+    pub schema_url: Option<String>,
+    // 1 回のリクエスト全体の上限
+    pub timeout: Duration,
+    pub connect_timeout: Duration,
+    pub read_timeout: Duration,
+    pub retry: RetryPolicy,
+    // 検証に成功した conf を保存する (schema は .schema を付けた名前に)。再試行しても取得できないときと
+    // ttl の間は代わりに読むので、起動時にネットワークが落ちていても前回の設定で動ける
+    #[cfg(feature = "std-fs")]
+    pub cache: Option<RemoteCache>,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    stale: bool,
+    // 最後に返した conf と schema の本文のハッシュ。同じ内容なら変更なしにする
+    delivered: Option<u64>,
+}
+
+impl RemoteSource {
+    pub fn new(url: &str) -> Self {
+        RemoteSource {
+            url: url.to_string(),
+            schema_url: None,
+            timeout: Duration::from_secs(10),
+            connect_timeout: Duration::from_secs(5),
+            read_timeout: Duration::from_secs(10),
+            retry: RetryPolicy::default(),
+            #[cfg(feature = "std-fs")]
+            cache: None,
+            etag: None,
+            last_modified: None,
+            stale: false,
+            delivered: None,
+        }
+    }
+
+    // 最後の fetch がキャッシュから読んだものなら true
+    pub fn is_stale(&self) -> bool {
+        self.stale
+    }
+
+    // 前回から変更がなければ Ok(None) を返す (304 のときも、取得した内容が前回返したものと同じときも)
+    pub fn fetch(&mut self, options: &ParseOptions) -> Result<Option<ConfList>, Box<dyn Error>> {
+        // ttl の間はリモートに問い合わせない
+        #[cfg(feature = "std-fs")]
+        if self.cache.as_ref().is_some_and(RemoteCache::is_fresh) {
+            if let Ok(list) = self.load_cache(options) {
+                return Ok(list);
+            }
+        }
+        let (conf, schema, etag, last_modified) = match self.retry.run(|| self.download()) {
+            Ok(Some(fetched)) => fetched,
+            Ok(None) => return Ok(None),
+            Err(e) => return self.fallback(e, options),
+        };
+        let list = parse_str_from(&conf, schema.as_deref(), options, &self.url)?;
+        // 検証に成功したときだけキャッシュ用のヘッダを更新する
+        self.etag = etag;
+        self.last_modified = last_modified;
+        self.stale = false;
+        #[cfg(feature = "std-fs")]
+        self.save(&conf, schema.as_deref());
+        Ok(self.deliver(&conf, schema.as_deref()).then_some(list))
+    }
+
+    // 前回返した内容と違えば覚えて true を返す
+    fn deliver(&mut self, conf: &str, schema: Option<&str>) -> bool {
+        let mut hasher = DefaultHasher::new();
+        (conf, schema).hash(&mut hasher);
+        let hash = hasher.finish();
+        self.delivered.replace(hash) != Some(hash)
+    }
+
+    fn download(&self) -> Result<Option<Fetched>, Box<dyn Error>> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout_connect(self.connect_timeout)
+            .timeout_read(self.read_timeout)
+            .timeout(self.timeout)
+            .build();
+        let mut request = agent.get(&self.url);
+        if let Some(etag) = &self.etag {
+            request = request.set("If-None-Match", etag);
+        }
+        if let Some(last_modified) = &self.last_modified {
+            request = request.set("If-Modified-Since", last_modified);
+        }
+        let response = request.call()?;
+        if response.status() == 304 {
+            return Ok(None);
+        }
+        let etag = response.header("ETag").map(str::to_string);
+        let last_modified = response.header("Last-Modified").map(str::to_string);
+        let conf = response.into_string()?;
+        let schema = match &self.schema_url {
+            Some(url) => Some(agent.get(url).call()?.into_string()?),
+            None => None,
+        };
+        Ok(Some((conf, schema, etag, last_modified)))
+    }
+
+    // キャッシュが書けなくても読み込み自体は成功させる
+    #[cfg(feature = "std-fs")]
+    fn save(&self, conf: &str, schema: Option<&str>) {
+        let Some(cache) = &self.cache else {
+            return;
+        };
+        let result = cache.write(conf).and_then(|_| schema.map_or(Ok(()), |schema| cache.sibling(".schema").write(schema)));
+        if let Err(_e) = result {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(error = %_e, "failed to write the remote config cache");
+        }
+    }
+
+    // 前回返した内容と同じなら Ok(None)
+    #[cfg(feature = "std-fs")]
+    fn load_cache(&mut self, options: &ParseOptions) -> Result<Option<ConfList>, Box<dyn Error>> {
+        let cache = self.cache.as_ref().ok_or("No cache is configured")?;
+        let conf = cache.read()?;
+        let schema = match self.schema_url {
+            Some(_) => Some(cache.sibling(".schema").read()?),
+            None => None,
+        };
+        let list = parse_str_from(&conf, schema.as_deref(), options, &cached_source(&self.url))?;
+        self.stale = true;
+        Ok(self.deliver(&conf, schema.as_deref()).then_some(list))
+    }
+
+    // キャッシュも読めなければ取得したときのエラーを返す
+    #[cfg(feature = "std-fs")]
+    fn fallback(&mut self, error: Box<dyn Error>, options: &ParseOptions) -> Result<Option<ConfList>, Box<dyn Error>> {
+        if self.cache.is_none() {
+            return Err(error);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::warn!(error = %error, "remote source unavailable, using the cached config");
+        self.load_cache(options).map_err(|_| error)
+    }
+
+    #[cfg(not(feature = "std-fs"))]
+    fn fallback(&mut self, error: Box<dyn Error>, _options: &ParseOptions) -> Result<Option<ConfList>, Box<dyn Error>> {
+        Err(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    // リクエストヘッダを読み、ETag が一致すれば 304 を返すだけのサーバ
+    fn serve(listener: TcpListener, count: usize) -> thread::JoinHandle<Vec<String>> {
+        thread::spawn(move || {
+            let mut requests = Vec::new();
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    head.push_str(&line);
+                }
+                let response = if head.contains("If-None-Match: \"v1\"") {
+                    "HTTP/1.1 304 Not Modified\r\nContent-Length: 0\r\n\r\n".to_string()
+                } else {
+                    let body = "endpoint = localhost:3000\ndebug = true\n";
+                    format!("HTTP/1.1 200 OK\r\nETag: \"v1\"\r\nContent-Length: {}\r\n\r\n{}", body.len(), body)
+                };
+                stream.write_all(response.as_bytes()).unwrap();
+                requests.push(head);
+            }
+            requests
+        })
+    }
+
+    #[test]
+    fn can_fetch_remote_conf_with_etag() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/app.conf", listener.local_addr().unwrap());
+        let server = serve(listener, 2);
+        let mut source = RemoteSource::new(&url);
+        let mut conf = source.fetch(&ParseOptions::default()).unwrap().unwrap();
+        assert_eq!(conf.get("endpoint").unwrap().as_str().unwrap(), "localhost:3000");
+        assert_eq!(conf.origin_of("debug").unwrap().to_string(), format!("{}:2", url));
+        // 2 回目は If-None-Match が送られ、変更なしになる
+        assert!(source.fetch(&ParseOptions::default()).unwrap().is_none());
+        let requests = server.join().unwrap();
+        assert!(requests[1].contains("If-None-Match"));
+    }
+
+    // 取得して保存した直後は、ttl の間にキャッシュから読んでも同じ内容なので変更なし
+    #[test]
+    #[cfg(feature = "std-fs")]
+    fn reports_unchanged_content_after_fetching() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/app.conf", listener.local_addr().unwrap());
+        let server = serve(listener, 1);
+        let cache = std::env::temp_dir().join(format!("conf-remote-twice-{}.conf", std::process::id()));
+        let mut source = RemoteSource::new(&url);
+        source.cache = Some(RemoteCache::new(&cache).ttl(Duration::from_secs(60)));
+        assert!(source.fetch(&ParseOptions::default()).unwrap().is_some());
+        assert!(!source.is_stale());
+        assert!(source.fetch(&ParseOptions::default()).unwrap().is_none());
+        assert!(source.fetch(&ParseOptions::default()).unwrap().is_none());
+        assert_eq!(server.join().unwrap().len(), 1);
+        std::fs::remove_file(&cache).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "std-fs")]
+    fn can_retry_and_fall_back_to_the_cache() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/app.conf", listener.local_addr().unwrap());
+        // 1 回目は 503、2 回目で成功するサーバ
+        let server = thread::spawn(move || {
+            for (status, body) in [("503 Service Unavailable", ""), ("200 OK", "port = 8080\n")] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap() > 0 && line != "\r\n" {
+                    line.clear();
+                }
+                let response = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\n\r\n{}", status, body.len(), body);
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        let cache = std::env::temp_dir().join(format!("conf-remote-cache-{}.conf", std::process::id()));
+        let retry = RetryPolicy { initial_backoff: Duration::from_millis(1), ..Default::default() };
+        let mut source = RemoteSource::new(&url);
+        source.retry = retry.clone();
+        source.cache = Some(RemoteCache::new(&cache));
+        let mut conf = source.fetch(&ParseOptions::default()).unwrap().unwrap();
+        assert_eq!(conf.get("port").unwrap().as_str().unwrap(), "8080");
+        assert!(!source.is_stale());
+        server.join().unwrap();
+
+        // サーバが止まっていても、前回の内容で起動できる
+        let mut offline = RemoteSource::new(&url);
+        offline.retry = retry;
+        offline.cache = Some(RemoteCache::new(&cache));
+        let mut conf = offline.fetch(&ParseOptions::default()).unwrap().unwrap();
+        assert_eq!(conf.get("port").unwrap().as_str().unwrap(), "8080");
+        assert_eq!(conf.origin_of("port").unwrap().to_string(), format!("{} (cached):1", url));
+        assert!(offline.is_stale());
+
+        // ttl の間はリモートに問い合わせない (再試行もしないのですぐ返る)
+        let mut fresh = RemoteSource::new(&url);
+        fresh.retry = RetryPolicy::none();
+        fresh.cache = Some(RemoteCache::new(&cache).ttl(Duration::from_secs(60)));
+        assert!(fresh.fetch(&ParseOptions::default()).unwrap().is_some());
+        assert!(fresh.is_stale());
+        assert!(fresh.fetch(&ParseOptions::default()).unwrap().is_none());
+        std::fs::remove_file(&cache).unwrap();
+        offline.retry = RetryPolicy::none();
+        assert!(offline.fetch(&ParseOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,386 @@
+use std::fmt;
+use std::fmt::Write;
+
+use crate::serialize::write_json_string;
+use crate::{ConfList, Origin};
+#[cfg(feature = "std-fs")]
+use std::{collections::{HashMap, HashSet}, error::Error, path::PathBuf};
+
+#[cfg(feature = "std-fs")]
+use crate::inline::{self, TableValue};
+#[cfg(feature = "std-fs")]
+use crate::condition::Conditions;
+#[cfg(feature = "std-fs")]
+use crate::interpolate::key_references;
+#[cfg(feature = "std-fs")]
+use crate::path::unquote_key;
+#[cfg(feature = "std-fs")]
+use crate::{
+    check_entry_limits, check_line_length, column, include_targets, metrics, parse_include, parse_line_checked, parse_schema, policy, profile, read_text,
+    reads_table, resolve_value, validate, LoadStats, ParseContext, ParseOptions, Schema,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+// 検証で見つかった問題 1 件
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub source: String,
+    pub line: Option<usize>,
+    // 1 始まりの文字位置 (値の誤りなら値の先頭)
+    pub column: Option<usize>,
+    // 問題のあったキー
+    pub path: Option<String>,
+    pub severity: Severity,
+    // 問題の種類 (invalid-value, malformed-line, line-too-long, too-many-keys, missing-key, include-not-found,
+    // circular-include, include-failed, include-unsupported, policy-violation, circular-reference, invalid-condition, key-conflict。
+    // lint ではルールの名前)
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub(crate) fn error(origin: &Origin, code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic::new(origin, Severity::Error, code, message)
+    }
+
+    pub(crate) fn warning(origin: &Origin, code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic::new(origin, Severity::Warning, code, message)
+    }
+
+    fn new(origin: &Origin, severity: Severity, code: &'static str, message: impl Into<String>) -> Self {
+        Diagnostic {
+            source: origin.source.clone(),
+            line: origin.line,
+            column: None,
+            path: None,
+            severity,
+            code,
+            message: message.into(),
+        }
+    }
+
+    pub(crate) fn path(mut self, path: &str) -> Self {
+        self.path = Some(path.to_string());
+        self
+    }
+
+    pub(crate) fn column(mut self, column: usize) -> Self {
+        self.column = Some(column);
+        self
+    }
+
+    // {"source": ..., "line": ..., "column": ..., "path": ..., "severity": ..., "code": ..., "message": ...}
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        let optional = |value: Option<usize>| value.map_or("null".to_string(), |v| v.to_string());
+        out.push_str("{\"source\":");
+        write_json_string(&self.source, out);
+        write!(out, ",\"line\":{},\"column\":{},\"path\":", optional(self.line), optional(self.column)).unwrap();
+        match &self.path {
+            Some(path) => write_json_string(path, out),
+            None => out.push_str("null"),
+        }
+        write!(out, ",\"severity\":\"{}\",\"code\":\"{}\",\"message\":", self.severity.as_str(), self.code).unwrap();
+        write_json_string(&self.message, out);
+        out.push('}');
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.source, line, self.message),
+            None => write!(f, "{}: {}", self.source, self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub diagnostics: Vec<Diagnostic>,
+    // 検証したキーの数
+    pub keys: usize,
+}
+
+impl ValidationReport {
+    // 警告だけなら問題なしとする
+    pub fn is_ok(&self) -> bool {
+        self.diagnostics.iter().all(|diagnostic| diagnostic.severity != Severity::Error)
+    }
+
+    // 警告も問題とする (conf validate --strict)
+    pub fn is_strictly_ok(&self) -> bool {
+        self.diagnostics.is_empty()
+    }
+
+    // {"keys": 4, "diagnostics": [...]}
+    pub fn to_json(&self) -> String {
+        let mut out = format!("{{\"keys\":{},\"diagnostics\":[", self.keys);
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            diagnostic.write_json(&mut out);
+        }
+        out.push_str("]}");
+        out
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for diagnostic in &self.diagnostics {
+            writeln!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+// 復旧モードで読み込んだ結果。問題のあった行を飛ばして作ったツリーと、見つかった問題のすべて
+#[derive(Debug)]
+pub struct PartialConf {
+    pub conf: ConfList,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl PartialConf {
+    // ValidationReport::is_ok と同じく、警告だけなら問題なしとする
+    pub fn is_ok(&self) -> bool {
+        self.diagnostics.iter().all(|diagnostic| diagnostic.severity != Severity::Error)
+    }
+}
+
+// ツリーを作らずに 1 行ずつ検証する。最初のエラーで止めずにすべての問題を集める
+#[cfg(feature = "std-fs")]
+pub fn validate_file(file_path: &str, schema_path: Option<&str>) -> Result<ValidationReport, Box<dyn Error>> {
+    validate_file_with_options(file_path, schema_path, &ParseOptions::default())
+}
+
+#[cfg(feature = "std-fs")]
+#[cfg_attr(feature = "tracing", tracing::instrument(level = "debug", skip(options)))]
+pub fn validate_file_with_options(file_path: &str, schema_path: Option<&str>, options: &ParseOptions) -> Result<ValidationReport, Box<dyn Error>> {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema_path {
+            Some(path) => parse_schema(path, options)?,
+            None => HashMap::new(),
+        };
+        let mut report = ValidationReport::default();
+        let mut stack = Vec::new();
+        let mut seen = HashSet::new();
+        #[cfg(feature = "tracing")]
+        let started = std::time::Instant::now();
+        validate_lines(file_path, &schema, options, &mut report, &mut stack, &mut seen, stats)?;
+        report.diagnostics.extend(finish(file_path, &schema, options, &seen)?);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            keys = report.keys,
+            diagnostics = report.diagnostics.len(),
+            elapsed_us = started.elapsed().as_micros() as u64,
+            "validated config file",
+        );
+        stats.keys = report.keys;
+        stats.count(&report.diagnostics);
+        Ok(report)
+    })
+}
+
+// 読み込むときと同じ finish で、書かれていないキーの default を確かめ、required のキーがなければ報告する
+// ツリーは作らないので、書かれたキーかその下のキーがあるスキーマのキーを読んだものとして渡す
+#[cfg(feature = "std-fs")]
+fn finish(file_path: &str, schema: &Schema, options: &ParseOptions, seen: &HashSet<String>) -> Result<Vec<Diagnostic>, Box<dyn Error>> {
+    let streamed = schema.keys()
+        .filter(|key| seen.iter().any(|path| path == *key || path.strip_prefix(key.as_str()).is_some_and(|rest| rest.starts_with('.'))))
+        .cloned()
+        .collect();
+    let mut sink = |_: &str, _, _: Option<&Origin>| Ok(());
+    let mut ctx = ParseContext { sink: Some(&mut sink), streamed, ..ParseContext::recovering(schema, options) };
+    ctx.finish(ConfList::new(), file_path)?;
+    Ok(ctx.diagnostics.take().unwrap_or_default())
+}
+
+#[cfg(feature = "std-fs")]
+fn validate_lines(file_path: &str, schema: &Schema, options: &ParseOptions, report: &mut ValidationReport, stack: &mut Vec<PathBuf>, seen: &mut HashSet<String>, stats: &mut LoadStats) -> Result<(), Box<dyn Error>> {
+    stack.push(std::fs::canonicalize(file_path)?);
+    stats.files += 1;
+    let mut conditions = Conditions::all();
+    for (index, line) in read_text(file_path, options)?.lines().enumerate() {
+        stats.bytes += line.len() + 1;
+        let origin = Origin::new(file_path, Some(index + 1));
+        if let Err(e) = check_line_length(line, &options.limits) {
+            report.diagnostics.push(Diagnostic::error(&origin, "line-too-long", e));
+            continue;
+        }
+        // どのプロファイルを選んでも読めるよう、すべてのドキュメントを確かめる
+        if let Some(result) = profile::marker(line) {
+            if let Err(e) = result {
+                report.diagnostics.push(Diagnostic::error(&origin, "malformed-line", e).column(column(line, line.trim_start())));
+            }
+            continue;
+        }
+        // 条件の行そのものは値として読まない (lib.rs の parse_lines と同じ)
+        if let Some(result) = conditions.enter(line, index + 1) {
+            if let Err(e) = result {
+                report.diagnostics.push(Diagnostic::error(&origin, "invalid-condition", e).column(column(line, line.trim_start())));
+            }
+            continue;
+        }
+        if let Some((path, optional)) = parse_include(line) {
+            let files = match include_targets(path, optional, &origin) {
+                Ok(files) => files,
+                Err(e) => {
+                    report.diagnostics.push(Diagnostic::error(&origin, "include-not-found", e.to_string()).column(column(line, path)));
+                    continue;
+                },
+            };
+            for file in files {
+                if stack.contains(&std::fs::canonicalize(&file)?) {
+                    let message = format!("Circular include: {}", file);
+                    report.diagnostics.push(Diagnostic::error(&origin, "circular-include", message).column(column(line, path)));
+                    continue;
+                }
+                validate_lines(&file, schema, options, report, stack, seen, stats)?;
+            }
+            continue;
+        }
+        let (key, value) = match parse_line_checked(line, options) {
+            Ok(Some(key_value)) => key_value,
+            Ok(None) => continue,
+            Err(e) => {
+                report.diagnostics.push(Diagnostic::error(&origin, "malformed-line", e).column(column(line, line.trim_start())));
+                continue;
+            },
+        };
+        report.keys += 1;
+        if report.keys == options.limits.max_keys + 1 {
+            let message = format!("Too many keys (limit: {})", options.limits.max_keys);
+            report.diagnostics.push(Diagnostic::error(&origin, "too-many-keys", message).path(key).column(column(line, key)));
+        }
+        if let Err(e) = validate_value(key, value, false, schema, options, seen) {
+            report.diagnostics.push(Diagnostic::error(&origin, policy::error_code(e.as_ref()), e.to_string()).path(key).column(column(line, value)));
+        }
+    }
+    if let Some((line, e)) = conditions.finish() {
+        let origin = Origin::new(file_path, Some(line));
+        report.diagnostics.push(Diagnostic::error(&origin, "invalid-condition", e));
+    }
+    stack.pop();
+    Ok(())
+}
+
+// 読み込むときと同じく、インラインテーブルは要素ごとに確かめる。スキーマにない [a, b] はリストとして読めなくても文字列になるので確かめない
+#[cfg(feature = "std-fs")]
+fn validate_value(key: &str, value: &str, quoted: bool, schema: &Schema, options: &ParseOptions, seen: &mut HashSet<String>) -> Result<(), Box<dyn Error>> {
+    let key = unquote_key(key)?;
+    let key = options.keys.normalize(&key)?;
+    let key = key.as_ref();
+    seen.insert(key.to_string());
+    check_entry_limits(key, value, &options.limits)?;
+    let max_depth = options.limits.max_depth;
+    if !quoted && reads_table(schema, key, options) {
+        if let Some(entries) = inline::parse_table(value, max_depth)? {
+            for (sub_key, value) in entries {
+                let path = format!("{}.{}", key, sub_key);
+                match value {
+                    TableValue::Raw(v) => validate_value(&path, v, false, schema, options, seen)?,
+                    TableValue::Quoted(v) => validate_value(&path, &v, true, schema, options, seen)?,
+                }
+            }
+            return Ok(());
+        }
+    }
+    // 別のキーの参照はツリーを作らないと展開できないので確かめない
+    if options.interpolation.is_some() && !key_references(value).is_empty() {
+        return Ok(());
+    }
+    let written = value;
+    let value = resolve_value(value, schema.get(key).is_some_and(|entry| entry.secret), options)?;
+    options.policy.check(key, written, &value)?;
+    if let Some(t) = schema.get(key) {
+        validate(&value, t, options)?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "std-fs"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_every_invalid_line() {
+        let report = validate_file("tests/invalid.conf", Some("tests/invalid.schema")).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.keys, 4);
+        assert_eq!(report.to_string(), "\
+tests/invalid.conf:2: Invalid boolean value
+tests/invalid.conf:4: Invalid number value
+tests/invalid.conf:5: Included file not found: missing.conf
+");
+        assert_eq!(report.diagnostics[1].path.as_deref(), Some("port"));
+        assert_eq!(report.diagnostics[1].column, Some(8));
+        assert_eq!(report.diagnostics[2].code, "include-not-found");
+        assert_eq!(report.diagnostics[0].to_json(), r#"{"source":"tests/invalid.conf","line":2,"column":9,"path":"debug","severity":"error","code":"invalid-value","message":"Invalid boolean value"}"#);
+        assert!(report.to_json().starts_with(r#"{"keys":4,"diagnostics":[{"source""#));
+        assert!(validate_file("tests/case-1.conf", Some("tests/data.schema")).unwrap().is_ok());
+        // @if / @else / @end の行は値として確かめない
+        let options = ParseOptions { strict: true, ..Default::default() };
+        let report = validate_file_with_options("tests/conditions.conf", Some("tests/conditions.schema"), &options).unwrap();
+        assert!(report.is_ok(), "{}", report);
+    }
+
+    #[test]
+    fn reports_missing_required_keys() {
+        let dir = std::env::temp_dir().join(format!("conf-report-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("app.conf"), "a = 1\nlog.file = app.log\n").unwrap();
+        // log は log.file があるのでそろっている
+        std::fs::write(dir.join("app.schema"), "b -> string required\nlog -> object required\n").unwrap();
+        let conf = dir.join("app.conf");
+        let schema = dir.join("app.schema");
+        let report = validate_file(conf.to_str().unwrap(), schema.to_str()).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.diagnostics.len(), 1);
+        assert_eq!(report.diagnostics[0].code, "missing-key");
+        assert_eq!(report.diagnostics[0].to_string(), format!("{}: Missing required key: b", conf.display()));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_keep_valid_lines_when_recovering() {
+        let partial = crate::parse_partial("tests/invalid.conf", Some("tests/invalid.schema"), &ParseOptions::default()).unwrap();
+        assert!(!partial.is_ok());
+        let report = ValidationReport { diagnostics: partial.diagnostics, keys: 0 };
+        assert_eq!(report.to_string(), "\
+tests/invalid.conf:2: Invalid boolean value
+tests/invalid.conf:4: Invalid number value
+tests/invalid.conf:5: Included file not found: missing.conf
+");
+        let flat = partial.conf.to_flat_map(false);
+        assert_eq!(flat.len(), 2);
+        assert_eq!(flat["endpoint"], "localhost:3000");
+        assert_eq!(flat["log.file"], "/var/log/console.log");
+
+        let partial = crate::parse_str_partial("a = 1\nb = x\nc = 3\n", Some("a -> number\nb -> number\nc -> number\n"), &ParseOptions::default()).unwrap();
+        assert_eq!(partial.diagnostics[0].path.as_deref(), Some("b"));
+        assert_eq!(partial.conf.to_flat_map(false).len(), 2);
+        assert!(crate::parse("tests/invalid.conf", Some("tests/invalid.schema")).is_err());
+    }
+}
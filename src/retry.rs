@@ -0,0 +1,103 @@
+// リモートのソース (http / kv) で一時的な失敗を再試行する
+use std::error::Error;
+use std::time::Duration;
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // 最初の試行も含めた回数。1 なら再試行しない
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    // 待ち時間を試行ごとに何倍にするか
+    pub multiplier: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(200),
+            max_backoff: Duration::from_secs(5),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        RetryPolicy { max_attempts: 1, ..Default::default() }
+    }
+
+    // 接続できない、タイムアウト、429 / 5xx のときだけ待ってからやり直す
+    pub(crate) fn run<T>(&self, mut attempt: impl FnMut() -> Result<T, Box<dyn Error>>) -> Result<T, Box<dyn Error>> {
+        let mut backoff = self.initial_backoff;
+        let mut tries = 1;
+        loop {
+            match attempt() {
+                Err(e) if tries < self.max_attempts && is_transient(e.as_ref()) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(attempt = tries, error = %e, "remote source failed, retrying");
+                    std::thread::sleep(backoff);
+                    backoff = self.next_backoff(backoff);
+                    tries += 1;
+                },
+                result => return result,
+            }
+        }
+    }
+
+    // multiplier が負や NaN、大きすぎても panic しないよう、0 から max_backoff の間に収める
+    // max_backoff が Duration::MAX に近いと f64 に直したときに丸めで超えるので、比べるのは Duration にしてから
+    fn next_backoff(&self, backoff: Duration) -> Duration {
+        let secs = backoff.as_secs_f64() * self.multiplier;
+        match Duration::try_from_secs_f64(secs) {
+            Ok(next) => next.min(self.max_backoff),
+            Err(_) if secs > 0.0 => self.max_backoff,
+            Err(_) => Duration::ZERO,
+        }
+    }
+}
+
+fn is_transient(e: &(dyn Error + 'static)) -> bool {
+    match e.downcast_ref::<ureq::Error>() {
+        Some(ureq::Error::Status(code, _)) => *code == 429 || *code >= 500,
+        Some(ureq::Error::Transport(_)) => true,
+        // 本文を読んでいる途中で切れた
+        None => e.is::<std::io::Error>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn can_clamp_backoff() {
+        let policy = RetryPolicy { initial_backoff: Duration::from_millis(1), ..Default::default() };
+        assert_eq!(policy.next_backoff(Duration::from_secs(1)), Duration::from_secs(2));
+        assert_eq!(policy.next_backoff(Duration::from_secs(4)), Duration::from_secs(5));
+        for multiplier in [-1.0, f64::NAN] {
+            let policy = RetryPolicy { multiplier, ..policy.clone() };
+            assert_eq!(policy.next_backoff(Duration::from_secs(1)), Duration::ZERO);
+            let mut tries = 0;
+            let result: Result<(), _> = policy.run(|| {
+                tries += 1;
+                Err(std::io::Error::other("reset").into())
+            });
+            assert!(result.is_err());
+            assert_eq!(tries, 3);
+        }
+        let policy = RetryPolicy { multiplier: f64::INFINITY, ..policy };
+        assert_eq!(policy.next_backoff(Duration::from_secs(1)), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn can_back_off_up_to_duration_max() {
+        let policy = RetryPolicy { max_backoff: Duration::MAX, ..Default::default() };
+        assert_eq!(policy.next_backoff(Duration::from_secs(1)), Duration::from_secs(2));
+        assert_eq!(policy.next_backoff(Duration::MAX / 2), Duration::MAX);
+        assert_eq!(policy.next_backoff(Duration::MAX), Duration::MAX);
+        let policy = RetryPolicy { multiplier: f64::INFINITY, ..policy };
+        assert_eq!(policy.next_backoff(Duration::from_secs(1)), Duration::MAX);
+    }
+}
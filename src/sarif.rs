@@ -0,0 +1,78 @@
+use std::fmt::Write;
+
+use crate::serialize::write_json_string;
+use crate::{Diagnostic, ValidationReport};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+impl ValidationReport {
+    // SARIF 2.1.0 (GitHub / GitLab の code scanning が読む形式) で書き出す
+    // ルールは診断の code ごとに 1 つ作る
+    pub fn to_sarif(&self) -> String {
+        let mut rules: Vec<&str> = Vec::new();
+        for diagnostic in &self.diagnostics {
+            if !rules.contains(&diagnostic.code) {
+                rules.push(diagnostic.code);
+            }
+        }
+
+        let mut out = String::new();
+        write!(out, "{{\"$schema\":\"{}\",\"version\":\"2.1.0\",\"runs\":[{{", SCHEMA).unwrap();
+        write!(out, "\"tool\":{{\"driver\":{{\"name\":\"{}\",\"version\":\"{}\",\"rules\":[", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")).unwrap();
+        for (i, rule) in rules.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write!(out, "{{\"id\":\"{}\"}}", rule).unwrap();
+        }
+        out.push_str("]}},\"results\":[");
+        for (i, diagnostic) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let index = rules.iter().position(|rule| *rule == diagnostic.code).unwrap();
+            write_result(diagnostic, index, &mut out);
+        }
+        out.push_str("]}]}");
+        out
+    }
+}
+
+fn write_result(diagnostic: &Diagnostic, rule_index: usize, out: &mut String) {
+    write!(out, "{{\"ruleId\":\"{}\",\"ruleIndex\":{},\"level\":\"{}\",\"message\":{{\"text\":", diagnostic.code, rule_index, diagnostic.severity.as_str()).unwrap();
+    write_json_string(&diagnostic.message, out);
+    out.push_str("},\"locations\":[{\"physicalLocation\":{\"artifactLocation\":{\"uri\":");
+    // SARIF の uri は / 区切り
+    write_json_string(&diagnostic.source.replace('\\', "/"), out);
+    out.push('}');
+    // region は行がわかるときだけ付ける (startColumn だけでは指定できない)
+    if let Some(line) = diagnostic.line {
+        write!(out, ",\"region\":{{\"startLine\":{}", line).unwrap();
+        if let Some(column) = diagnostic.column {
+            write!(out, ",\"startColumn\":{}", column).unwrap();
+        }
+        out.push('}');
+    }
+    out.push_str("}}]");
+    if let Some(path) = &diagnostic.path {
+        out.push_str(",\"properties\":{\"path\":");
+        write_json_string(path, out);
+        out.push('}');
+    }
+    out.push('}');
+}
+
+#[cfg(all(test, feature = "std-fs"))]
+mod tests {
+    use crate::validate_file;
+
+    #[test]
+    fn can_write_sarif() {
+        let sarif = validate_file("tests/invalid.conf", Some("tests/invalid.schema")).unwrap().to_sarif();
+        assert!(sarif.starts_with(r#"{"$schema":"https://json.schemastore.org/sarif-2.1.0.json","version":"2.1.0","runs":[{"tool":{"driver":{"name":"conf_loader_with_validation","#));
+        assert!(sarif.contains(r#""rules":[{"id":"invalid-value"},{"id":"include-not-found"}]"#));
+        assert!(sarif.contains(r#"{"ruleId":"invalid-value","ruleIndex":0,"level":"error","message":{"text":"Invalid number value"},"locations":[{"physicalLocation":{"artifactLocation":{"uri":"tests/invalid.conf"},"region":{"startLine":4,"startColumn":8}}}],"properties":{"path":"port"}}"#));
+        assert!(sarif.contains(r#""ruleId":"include-not-found","ruleIndex":1"#));
+        assert!(sarif.ends_with("]}]}"));
+    }
+}
@@ -0,0 +1,111 @@
+// conf.scope("database") で、database. の下だけを見るビュー
+// モジュールには自分のセクションだけを渡し、全体の構成を知らなくても get_str("host") などで読めるようにする
+// エラーや track-access / 監査フックには database.host のように全体でのパスが出る
+use std::error::Error;
+use std::time::Duration;
+
+use crate::path::{append_path, to_dotted};
+use crate::{ConfAccessError, ConfList, ConfValue, Origin};
+
+#[derive(Debug, Clone)]
+pub struct Scope<'a> {
+    conf: &'a ConfList,
+    prefix: String,
+}
+
+impl ConfList {
+    // prefix のセクションがなくても作れる (どのキーも見つからない)
+    pub fn scope(&self, prefix: &str) -> Scope<'_> {
+        Scope { conf: self, prefix: to_dotted(prefix).into_owned() }
+    }
+}
+
+impl<'a> Scope<'a> {
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    fn path(&self, key: &str) -> String {
+        append_path(&self.prefix, key)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.conf.value_at(&self.path(key), |_| ()).is_some()
+    }
+
+    pub fn get_str(&self, key: &str) -> Result<String, ConfAccessError> {
+        self.conf.get_str(&self.path(key))
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<bool, ConfAccessError> {
+        self.conf.get_bool(&self.path(key))
+    }
+
+    pub fn get_number(&self, key: &str) -> Result<f64, ConfAccessError> {
+        self.conf.get_number(&self.path(key))
+    }
+
+    pub fn try_get_str(&self, key: &str) -> Result<Option<String>, ConfAccessError> {
+        self.conf.try_get_str(&self.path(key))
+    }
+
+    pub fn try_get_bool(&self, key: &str) -> Result<Option<bool>, ConfAccessError> {
+        self.conf.try_get_bool(&self.path(key))
+    }
+
+    pub fn try_get_number(&self, key: &str) -> Result<Option<f64>, ConfAccessError> {
+        self.conf.try_get_number(&self.path(key))
+    }
+
+    pub fn get_duration(&self, key: &str) -> Result<Duration, Box<dyn Error>> {
+        self.conf.get_duration(&self.path(key))
+    }
+
+    pub fn get_size(&self, key: &str) -> Result<u64, Box<dyn Error>> {
+        self.conf.get_size(&self.path(key))
+    }
+
+    pub fn get_all(&self, key: &str) -> Vec<ConfValue> {
+        self.conf.get_all(&self.path(key))
+    }
+
+    pub fn origin_of(&self, key: &str) -> Option<Origin> {
+        self.conf.origin_of(&self.path(key))
+    }
+
+    pub fn raw_of(&self, key: &str) -> Option<String> {
+        self.conf.raw_of(&self.path(key))
+    }
+
+    pub fn is_secret(&self, key: &str) -> bool {
+        self.conf.is_secret(&self.path(key))
+    }
+
+    // さらに下のセクションに絞る
+    pub fn scope(&self, prefix: &str) -> Scope<'a> {
+        Scope { conf: self.conf, prefix: self.path(prefix) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_str;
+
+    #[test]
+    fn can_read_through_a_scoped_view() {
+        let conf = "database.host = db.local\ndatabase.port = 5432\ndatabase.pool.max = 10\ndatabase.timeout = 3s\nhost = web.local\n";
+        let conf = parse_str(conf, Some("database.port -> number\n")).unwrap();
+        let database = conf.scope("database");
+        assert_eq!(database.get_str("host").unwrap(), "db.local");
+        assert_eq!(database.get_number("port").unwrap(), 5432.0);
+        assert_eq!(database.get_duration("timeout").unwrap().as_secs(), 3);
+        assert_eq!(database.scope("pool").get_str("max").unwrap(), "10");
+        assert_eq!(database.origin_of("port").unwrap().to_string(), "<string>:2");
+        assert!(database.contains_key("pool") && !database.contains_key("user"));
+        // エラーには全体でのパスが出る
+        assert_eq!(database.get_str("user").unwrap_err().to_string(), "Missing key: database.user");
+        assert!(conf.scope("cache").try_get_str("host").unwrap().is_none());
+        assert_eq!(conf.scope("/database").get_str("/pool/max").unwrap(), "10");
+        assert_eq!(conf.scope("").get_str("host").unwrap(), "web.local");
+    }
+}
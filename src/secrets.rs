@@ -0,0 +1,128 @@
+// Docker や Kubernetes がマウントするシークレットのディレクトリ (/run/secrets など) から読み込む
+// ファイル名がキー (__ で区切って db__password -> db.password)、中身が値で、値はすべて secret として扱う
+// . で始まるファイル (Kubernetes の ..data など) とディレクトリは読み飛ばす
+// 中身は書かれたままの値で、${...} の展開や env: / file: / ENC(...) の参照としては読まない
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Read;
+
+use crate::{check_entry_limits, conflict, metrics, parse_schema, validate, ConfList, ConfValue, Origin, ParseContext, ParseOptions, Schema, SchemaType};
+
+// ほかの層に重ねて使うので、parse_env と同じく required や default は適用しない
+pub fn parse_secrets_dir(dir: &str, schema_path: Option<&str>, options: &ParseOptions) -> Result<ConfList, Box<dyn Error>> {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema_path {
+            Some(path) => parse_schema(path, options)?,
+            None => HashMap::new(),
+        };
+        let mut ctx = ParseContext::new(&schema, options);
+        let result = add_files(dir, &mut ctx);
+        ctx.record(stats);
+        result
+    })
+}
+
+fn add_files(dir: &str, ctx: &mut ParseContext) -> Result<ConfList, Box<dyn Error>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| format!("Failed to read secrets directory {}: {}", dir, e))? {
+        let path = entry?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            Some(name) if !name.starts_with('.') => name.to_string(),
+            _ => continue,
+        };
+        // シンボリックリンクの先がファイルならよい
+        if path.is_file() {
+            files.push((name, path));
+        }
+    }
+    files.sort();
+    let mut map = ConfList::new();
+    for (name, path) in files {
+        let origin = Origin::new(path.to_string_lossy().into_owned(), None);
+        let result = ctx.count_key().map_err(Box::<dyn Error>::from).and_then(|_| {
+            let max_file_size = ctx.options.limits.max_file_size;
+            let mut content = Vec::new();
+            std::fs::File::open(&path)?.take(max_file_size + 1).read_to_end(&mut content)?;
+            if content.len() as u64 > max_file_size {
+                return Err(format!("File is too large (limit: {} bytes)", max_file_size).into());
+            }
+            ctx.files += 1;
+            ctx.bytes += content.len();
+            let key = ctx.options.keys.normalize(&name.replace("__", "."))?.into_owned();
+            let value = secret_value(&key, content, ctx)?;
+            let key = conflict::check(&mut map, &key, ctx.options.conflicts, &mut ctx.interner)?;
+            map.add_value_interned(&key, value, Some(origin.clone()), true, None, &mut ctx.interner);
+            Ok(())
+        });
+        result.map_err(|e| format!("{}: {}", origin, e))?;
+    }
+    Ok(map)
+}
+
+// スキーマの型が bytes ならファイルの中身そのもの。それ以外は末尾の改行を除いた文字列を、リストやテーブルとしては読まずにスキーマで検証する
+fn secret_value(key: &str, content: Vec<u8>, ctx: &ParseContext) -> Result<ConfValue, Box<dyn Error>> {
+    let entry = ctx.schema.get(key);
+    if entry.is_some_and(|entry| entry.ty == SchemaType::Bytes) {
+        return Ok(ConfValue::Bytes(content));
+    }
+    let text = match String::from_utf8(content) {
+        Ok(text) => text,
+        // スキーマになければ、文字列にできない中身 (鍵ストアなど) はバイト列のまま
+        Err(e) if entry.is_none() => return Ok(ConfValue::Bytes(e.into_bytes())),
+        Err(_) => return Err("Secret file is not valid UTF-8".into()),
+    };
+    let text = text.trim_end_matches(['\r', '\n']);
+    check_entry_limits(key, text, &ctx.options.limits)?;
+    Ok(match entry {
+        Some(entry) => validate(text, entry, ctx.options)?,
+        None => ConfValue::StrValue(text.to_string()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn can_read_secrets_dir() {
+        let schema = "db.port -> number\n";
+        let options = ParseOptions::default();
+        let secrets = parse_secrets_dir("tests/secrets", None, &options).unwrap();
+        assert_eq!(secrets.to_flat_map(false).len(), 2);
+        assert_eq!(secrets.get_str("db.password").unwrap(), "s3cret");
+        assert!(secrets.is_secret("db.password") && secrets.is_secret("api_token"));
+        assert_eq!(secrets.origin_of("db.password").unwrap().to_string(), "tests/secrets/db__password");
+
+        let mut conf = parse_str("db.host = localhost\ndb.port = 5432\ndb.password = changeme\n", Some(schema)).unwrap();
+        conf.merge(secrets);
+        assert_eq!(conf.get_str("db.password").unwrap(), "s3cret");
+        assert_eq!(conf.to_flat_map(true)["db.password"], crate::REDACTED);
+        assert_eq!(conf.get_number("db.port").unwrap(), 5432.0);
+
+        let err = parse_secrets_dir("tests/secrets", Some("tests/secrets.schema"), &options).unwrap_err();
+        assert_eq!(err.to_string(), "tests/secrets/api_token: Invalid number value");
+        assert!(parse_secrets_dir("tests/missing", None, &options).unwrap_err().to_string().starts_with("Failed to read secrets directory tests/missing: "));
+    }
+
+    #[test]
+    fn can_read_secret_files_literally() {
+        let dir = std::env::temp_dir().join(format!("conf-secrets-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("token"), "env:HOME\n").unwrap();
+        std::fs::write(dir.join("greeting"), "${USER} [a, b]").unwrap();
+        std::fs::write(dir.join("keystore"), [0xfe, 0xed, 0x00, 0xff]).unwrap();
+        std::fs::write(dir.join("cert"), [0xde, 0xad]).unwrap();
+        let options = ParseOptions { interpolation: Some(crate::Interpolator::new()), secret_references: true, ..Default::default() };
+        let schema = std::env::temp_dir().join(format!("conf-secrets-{}.schema", std::process::id()));
+        std::fs::write(&schema, "token -> string secret\ncert -> bytes\n").unwrap();
+        let secrets = parse_secrets_dir(dir.to_str().unwrap(), schema.to_str(), &options).unwrap();
+        assert_eq!(secrets.get_str("token").unwrap(), "env:HOME");
+        assert_eq!(secrets.get_str("greeting").unwrap(), "${USER} [a, b]");
+        assert_eq!(secrets.value_at("keystore", |v| v.as_bytes().unwrap().to_vec()).unwrap(), [0xfe, 0xed, 0x00, 0xff]);
+        assert_eq!(secrets.value_at("cert", |v| v.as_bytes().unwrap().to_vec()).unwrap(), [0xde, 0xad]);
+        assert!(secrets.is_secret("keystore"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        std::fs::remove_file(&schema).unwrap();
+    }
+}
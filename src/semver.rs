@@ -0,0 +1,256 @@
+// スキーマの型 semver。MAJOR.MINOR.PATCH[-pre][+build] の形を確かめ、semver(>=1.2, <2) と書けばその範囲に入るかも確かめる
+// 値は文字列のまま持つ。プラグインやプロトコルの互換性を設定で指定するときに使う
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    // - の後の識別子 (なければ空)。比べるときに使う
+    pub pre: Vec<String>,
+    // + の後。比べるときは無視する
+    pub build: Vec<String>,
+}
+
+impl FromStr for Version {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid semantic version: {}", s);
+        let (rest, build) = match s.split_once('+') {
+            Some((rest, build)) => (rest, identifiers(build, false).ok_or_else(invalid)?),
+            None => (s, Vec::new()),
+        };
+        let (core, pre) = match rest.split_once('-') {
+            Some((core, pre)) => (core, identifiers(pre, true).ok_or_else(invalid)?),
+            None => (rest, Vec::new()),
+        };
+        let numbers = core.split('.').map(number).collect::<Option<Vec<u64>>>().ok_or_else(invalid)?;
+        let [major, minor, patch] = numbers[..] else {
+            return Err(invalid());
+        };
+        Ok(Version { major, minor, patch, pre, build })
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        if !self.build.is_empty() {
+            write!(f, "+{}", self.build.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+impl PartialOrd for Version {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// 1.0.0-alpha < 1.0.0-alpha.1 < 1.0.0-beta < 1.0.0 の順。build は見ないので、+ だけ違う版は cmp では等しい
+impl Ord for Version {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.major, self.minor, self.patch).cmp(&(other.major, other.minor, other.patch)).then_with(|| compare_pre(&self.pre, &other.pre))
+    }
+}
+
+// 0 で始まる 01 のような数は使えない
+fn number(s: &str) -> Option<u64> {
+    match s.len() > 1 && s.starts_with('0') {
+        true => None,
+        false => s.bytes().all(|b| b.is_ascii_digit()).then(|| s.parse().ok()).flatten(),
+    }
+}
+
+fn identifiers(s: &str, pre: bool) -> Option<Vec<String>> {
+    s.split('.')
+        .map(|id| {
+            let valid = !id.is_empty() && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-');
+            // pre の数だけの識別子も 0 で始められない
+            let numeric = id.bytes().all(|b| b.is_ascii_digit());
+            (valid && !(pre && numeric && number(id).is_none())).then(|| id.to_string())
+        })
+        .collect()
+}
+
+fn compare_pre(a: &[String], b: &[String]) -> Ordering {
+    match (a.is_empty(), b.is_empty()) {
+        (true, true) => return Ordering::Equal,
+        // pre のない版の方が新しい
+        (true, false) => return Ordering::Greater,
+        (false, true) => return Ordering::Less,
+        _ => {},
+    }
+    for (a, b) in a.iter().zip(b) {
+        let order = match (a.parse::<u64>(), b.parse::<u64>()) {
+            (Ok(a), Ok(b)) => a.cmp(&b),
+            // 数だけの識別子は英字を含むものより前
+            (Ok(_), Err(_)) => Ordering::Less,
+            (Err(_), Ok(_)) => Ordering::Greater,
+            (Err(_), Err(_)) => a.cmp(b),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+}
+
+// >=1.2 のような 1 つの条件。省略した桁は比べない (>1.2 は 1.3.0 以上、<=1.2 は 1.2.x まで、=1 は 1.x.x)
+#[derive(Debug, Clone, PartialEq)]
+struct Comparator {
+    op: Op,
+    numbers: Vec<u64>,
+    pre: Vec<String>,
+}
+
+impl Comparator {
+    fn matches(&self, version: &Version) -> bool {
+        let n = self.numbers.len();
+        let order = [version.major, version.minor, version.patch][..n].cmp(&self.numbers[..]);
+        let order = match n {
+            3 => order.then_with(|| compare_pre(&version.pre, &self.pre)),
+            _ => order,
+        };
+        match self.op {
+            Op::Eq => order == Ordering::Equal,
+            Op::Gt => order == Ordering::Greater,
+            Op::Ge => order != Ordering::Less,
+            Op::Lt => order == Ordering::Less,
+            Op::Le => order != Ordering::Greater,
+        }
+    }
+}
+
+impl fmt::Display for Comparator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self.op {
+            Op::Eq => "=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+        };
+        let numbers: Vec<String> = self.numbers.iter().map(u64::to_string).collect();
+        write!(f, "{}{}", op, numbers.join("."))?;
+        if !self.pre.is_empty() {
+            write!(f, "-{}", self.pre.join("."))?;
+        }
+        Ok(())
+    }
+}
+
+// カンマで区切った条件をすべて満たす版
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionReq {
+    comparators: Vec<Comparator>,
+}
+
+impl VersionReq {
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparators.iter().all(|c| c.matches(version))
+    }
+}
+
+impl FromStr for VersionReq {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let comparators = s.split(',').map(|c| parse_comparator(c.trim()).ok_or_else(|| format!("Invalid version requirement: {}", s)));
+        Ok(VersionReq { comparators: comparators.collect::<Result<_, _>>()? })
+    }
+}
+
+fn parse_comparator(s: &str) -> Option<Comparator> {
+    let (op, rest) = [(">=", Op::Ge), ("<=", Op::Le), (">", Op::Gt), ("<", Op::Lt), ("=", Op::Eq)]
+        .into_iter()
+        .find_map(|(prefix, op)| s.strip_prefix(prefix).map(|rest| (op, rest.trim_start())))
+        .unwrap_or((Op::Eq, s));
+    let (core, pre) = match rest.split_once('-') {
+        Some((core, pre)) => (core, identifiers(pre, true)?),
+        None => (rest, Vec::new()),
+    };
+    let numbers = core.split('.').map(number).collect::<Option<Vec<u64>>>()?;
+    // pre を書けるのは 3 桁すべてを書いたときだけ
+    if numbers.len() > 3 || !pre.is_empty() && numbers.len() != 3 {
+        return None;
+    }
+    Some(Comparator { op, numbers, pre })
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, c) in self.comparators.iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(s: &str) -> Version {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn can_parse_and_order_versions() {
+        let v = version("1.2.3-rc.1+build.5");
+        assert_eq!((v.major, v.minor, v.patch), (1, 2, 3));
+        assert_eq!(v.pre, ["rc", "1"]);
+        assert_eq!(v.to_string(), "1.2.3-rc.1+build.5");
+        for invalid in ["1.2", "1.2.3.4", "01.2.3", "1.2.3-", "1.2.3-01", "1.2.x", "v1.2.3", "1.2.3+"] {
+            assert_eq!(invalid.parse::<Version>().unwrap_err(), format!("Invalid semantic version: {}", invalid));
+        }
+
+        let ordered = ["1.0.0-alpha", "1.0.0-alpha.1", "1.0.0-alpha.beta", "1.0.0-beta.2", "1.0.0-beta.11", "1.0.0", "1.0.1", "1.10.0"];
+        for pair in ordered.windows(2) {
+            assert!(version(pair[0]) < version(pair[1]), "{} < {}", pair[0], pair[1]);
+        }
+        assert_eq!(version("1.0.0+a").cmp(&version("1.0.0+b")), Ordering::Equal);
+    }
+
+    #[test]
+    fn can_match_requirements() {
+        let req: VersionReq = ">=1.2, <2".parse().unwrap();
+        assert_eq!(req.to_string(), ">=1.2, <2");
+        assert!(req.matches(&version("1.2.0")));
+        assert!(req.matches(&version("1.9.9")));
+        assert!(!req.matches(&version("2.0.0")));
+        assert!(!req.matches(&version("1.1.9")));
+
+        let req: VersionReq = ">1.2, <=1.4".parse().unwrap();
+        assert!(!req.matches(&version("1.2.9")));
+        assert!(req.matches(&version("1.3.0")));
+        assert!(req.matches(&version("1.4.7")));
+        assert!(!req.matches(&version("1.5.0")));
+
+        assert!("=1".parse::<VersionReq>().unwrap().matches(&version("1.7.0")));
+        assert!("1.2.3".parse::<VersionReq>().unwrap().matches(&version("1.2.3+build")));
+        assert!("<1.0.0".parse::<VersionReq>().unwrap().matches(&version("1.0.0-rc.1")));
+        assert!("1.2-rc".parse::<VersionReq>().is_err());
+        assert_eq!("~1.2".parse::<VersionReq>().unwrap_err(), "Invalid version requirement: ~1.2");
+    }
+}
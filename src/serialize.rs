@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use crate::path::split_keys;
+use crate::{ConfList, ConfValue, Node};
+
+// 書き出し時のオプション
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WriteOptions {
+    // ソース順ではなくキーの名前順に出力する (生成した設定の差分を安定させる)
+    pub sorted: bool,
+}
+
+impl ConfList {
+    // .conf 形式 (key.sub = value) で書き出す
+    pub fn to_conf_string(&self, options: &WriteOptions) -> String {
+        let mut leaves = self.leaves();
+        if options.sorted {
+            leaves.sort_by(|a, b| a.0.cmp(&b.0));
+        }
+        let mut out = String::new();
+        // 末端に現れるセクションは空のもので、{} と書ける
+        for (path, value) in leaves {
+            writeln!(out, "{} = {}", path, value).unwrap();
+        }
+        out
+    }
+
+    pub fn to_json(&self, options: &WriteOptions) -> String {
+        let mut out = String::new();
+        self.write_json(options, &mut out);
+        out
+    }
+
+    fn write_json(&self, options: &WriteOptions, out: &mut String) {
+        out.push('{');
+        for (i, node) in self.effective_nodes(options).into_iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_string(&node.key, out);
+            out.push(':');
+            write_json_value(&node.value.borrow(), options, out);
+        }
+        out.push('}');
+    }
+
+    // ドット区切りのキーで 1 行ずつ書き出す (TOML の dotted keys)
+    pub fn to_toml(&self, options: &WriteOptions) -> String {
+        let mut out = String::new();
+        self.write_toml("", options, &mut out);
+        out
+    }
+
+    fn write_toml(&self, prefix: &str, options: &WriteOptions, out: &mut String) {
+        for node in self.effective_nodes(options) {
+            let path = format!("{}{}", prefix, toml_key(&node.key));
+            match &*node.value.borrow() {
+                ConfValue::Conf(child) if child.head.is_some() => child.write_toml(&format!("{}.", path), options, out),
+                ConfValue::Conf(_) => writeln!(out, "{} = {{}}", path).unwrap(),
+                value => {
+                    let mut s = String::new();
+                    write_toml_value(value, &mut s);
+                    writeln!(out, "{} = {}", path, s).unwrap();
+                },
+            }
+        }
+    }
+
+    // 上書きされていないノードをソース順 (sorted なら名前順) で返す
+    pub(crate) fn effective_nodes(&self, options: &WriteOptions) -> Vec<&Node> {
+        let mut nodes: Vec<&Node> = Vec::new();
+        let mut seen: HashSet<&str> = HashSet::new();
+        let mut current = &self.head;
+        while let Some(node) = current {
+            if seen.insert(&node.key) {
+                nodes.push(node);
+            }
+            current = &node.next;
+        }
+        nodes.reverse();
+        if options.sorted {
+            nodes.sort_by(|a, b| a.key.cmp(&b.key));
+        }
+        nodes
+    }
+}
+
+fn write_json_value(value: &ConfValue, options: &WriteOptions, out: &mut String) {
+    match value {
+        ConfValue::StrValue(v) => write_json_string(v, out),
+        ConfValue::BoolValue(v) => write!(out, "{}", v).unwrap(),
+        ConfValue::NumberValue(v) if v.is_finite() => write!(out, "{}", v).unwrap(),
+        ConfValue::NumberValue(_) => out.push_str("null"),
+        // 桁を落とさないよう、書いたままの数値として出力する
+        #[cfg(feature = "decimal")]
+        ConfValue::DecimalValue(v) => write!(out, "{}", v).unwrap(),
+        ConfValue::Bytes(v) => write_json_string(&crate::bytes::encode(v), out),
+        ConfValue::List(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_json_value(item, options, out);
+            }
+            out.push(']');
+        },
+        ConfValue::Conf(child) => child.write_json(options, out),
+    }
+}
+
+// リストの要素はインラインで書く
+fn write_toml_value(value: &ConfValue, out: &mut String) {
+    match value {
+        ConfValue::StrValue(v) => write_json_string(v, out),
+        ConfValue::BoolValue(v) => write!(out, "{}", v).unwrap(),
+        ConfValue::NumberValue(v) => out.push_str(&toml_number(*v)),
+        #[cfg(feature = "decimal")]
+        ConfValue::DecimalValue(v) => write!(out, "{}", v).unwrap(),
+        ConfValue::Bytes(v) => write_json_string(&crate::bytes::encode(v), out),
+        ConfValue::List(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write_toml_value(item, out);
+            }
+            out.push(']');
+        },
+        ConfValue::Conf(child) => {
+            out.push('{');
+            for (i, (key, value)) in child.leaves().iter().enumerate() {
+                out.push_str(if i > 0 { ", " } else { " " });
+                write!(out, "{} = ", split_keys(key).iter().map(|key| toml_key(key)).collect::<Vec<_>>().join(".")).unwrap();
+                write_toml_value(value, out);
+            }
+            out.push_str(if child.head.is_some() { " }" } else { "}" });
+        },
+    }
+}
+
+// TOML の基本文字列は JSON と同じエスケープで書ける
+pub(crate) fn write_json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{7f}' => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        return key.to_string();
+    }
+    let mut s = String::new();
+    write_json_string(key, &mut s);
+    s
+}
+
+fn toml_number(v: f64) -> String {
+    if v.is_nan() {
+        return "nan".to_string();
+    }
+    if v.is_infinite() {
+        return if v > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    // 整数で表せるものは integer、それ以外は小数点付きの float にする
+    if v.fract() == 0.0 && v.abs() < 1e15 {
+        format!("{}", v as i64)
+    } else {
+        format!("{:?}", v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    const CONF: &str = "name = web \"1\"\nlog.level = 2\ndebug = true\nlog.file = /tmp/app.log\nlog.ratio = 0.5\n";
+    const SCHEMA: &str = "log.level -> number\nlog.ratio -> number\ndebug -> bool\n";
+
+    #[test]
+    fn can_write_in_source_order() {
+        let conf = parse_str(CONF, Some(SCHEMA)).unwrap();
+        let options = WriteOptions::default();
+        assert_eq!(conf.to_conf_string(&options), "name = web \"1\"\nlog.level = 2\nlog.file = /tmp/app.log\nlog.ratio = 0.5\ndebug = true\n");
+        assert_eq!(conf.to_json(&options), r#"{"name":"web \"1\"","log":{"level":2,"file":"/tmp/app.log","ratio":0.5},"debug":true}"#);
+        assert_eq!(conf.to_toml(&options), "name = \"web \\\"1\\\"\"\nlog.level = 2\nlog.file = \"/tmp/app.log\"\nlog.ratio = 0.5\ndebug = true\n");
+    }
+
+    #[test]
+    fn can_write_sorted() {
+        let conf = parse_str(CONF, Some(SCHEMA)).unwrap();
+        let options = WriteOptions { sorted: true };
+        assert_eq!(conf.to_conf_string(&options), "debug = true\nlog.file = /tmp/app.log\nlog.level = 2\nlog.ratio = 0.5\nname = web \"1\"\n");
+        assert_eq!(conf.to_json(&options), r#"{"debug":true,"log":{"file":"/tmp/app.log","level":2,"ratio":0.5},"name":"web \"1\""}"#);
+        assert_eq!(conf.to_toml(&options).lines().next().unwrap(), "debug = true");
+    }
+
+    #[test]
+    fn can_write_escaped_keys_in_toml_tables() {
+        // JSON などから読んだ、. を含むキー
+        let mut table = ConfList::default();
+        table.insert("db\\.local.port", "5432");
+        let mut conf = ConfList::default();
+        conf.insert("hosts", ConfValue::List(vec![ConfValue::Conf(Box::new(table))]));
+        assert_eq!(conf.to_toml(&WriteOptions::default()), "hosts = [{ \"db.local\".port = \"5432\" }]\n");
+    }
+}
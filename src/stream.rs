@@ -0,0 +1,106 @@
+// ツリーを作らずに、1 行ずつ検証した値をそのまま関数に渡す
+// 生成された巨大な conf を少ないメモリで処理したり、途中で止めたりするためのもの
+// f が Err を返すとそこで読むのをやめ、出どころを付けたそのエラーを返す
+// ${db.host} のような別のキーの参照は、すべて読むまで解決できないので使えない
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::BufRead;
+
+use crate::{metrics, parse_conf_lines, parse_schema_lines, ConfValue, Origin, ParseContext, ParseOptions, Schema};
+
+// キー、値、出どころ (スキーマの default で補った値にはない)
+pub(crate) type Sink<'a> = &'a mut dyn FnMut(&str, ConfValue, Option<&Origin>) -> Result<(), Box<dyn Error>>;
+
+#[cfg(feature = "std-fs")]
+pub fn parse_streaming<F>(file_path: &str, schema_path: Option<&str>, options: &ParseOptions, mut f: F) -> Result<(), Box<dyn Error>>
+where F: FnMut(&str, ConfValue, Option<&Origin>) -> Result<(), Box<dyn Error>>, {
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema_path {
+            Some(path) => crate::parse_schema(path, options)?,
+            None => HashMap::new(),
+        };
+        let mut ctx = ParseContext { sink: Some(&mut f), ..ParseContext::new(&schema, options) };
+        let result = crate::parse_conf(file_path, &mut ctx).and_then(|map| ctx.finish(map, file_path));
+        ctx.record(stats);
+        result.map(|_| ())
+    })
+}
+
+// 全体を読み込まずに 1 行ずつ読む。入力の大きさは Limits::max_file_size まで
+pub fn parse_reader_streaming<R, F>(reader: R, schema: Option<&str>, options: &ParseOptions, mut f: F) -> Result<(), Box<dyn Error>>
+where
+    R: BufRead,
+    F: FnMut(&str, ConfValue, Option<&Origin>) -> Result<(), Box<dyn Error>>,
+{
+    metrics::observe(options, |stats| {
+        let schema: Schema = match schema {
+            Some(s) => parse_schema_lines(s.lines().map(str::to_string), options)?,
+            None => HashMap::new(),
+        };
+        let mut read_error = None;
+        let mut size = 0;
+        let lines = reader.lines().map_while(|line| {
+            let line = line.and_then(|line| {
+                size += line.len() as u64 + 1;
+                match size > options.limits.max_file_size {
+                    true => Err(std::io::Error::other(format!("Input is too large (limit: {} bytes)", options.limits.max_file_size))),
+                    false => Ok(line),
+                }
+            });
+            line.map_err(|e| read_error = Some(e)).ok()
+        });
+        let mut ctx = ParseContext { sink: Some(&mut f), ..ParseContext::new(&schema, options) };
+        let result = parse_conf_lines(lines, &mut ctx, "<reader>");
+        // 読めなかった行の後で finish の検証をしない
+        let result = match read_error.take() {
+            Some(e) => Err(format!("<reader>: {}", e).into()),
+            None => result.and_then(|map| ctx.finish(map, "<reader>")),
+        };
+        ctx.record(stats);
+        result.map(|_| ())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Interpolator, Limits};
+
+    #[test]
+    fn can_stream_validated_values() {
+        let conf = "port = 8080\ndb = { host = localhost, user = app }\nport = 9090\ntags = [a, b]\n";
+        let mut seen = Vec::new();
+        parse_reader_streaming(conf.as_bytes(), Some("port -> number\ndb -> object\ndb.host -> string required\n"), &ParseOptions::default(), |key, value, origin| {
+            seen.push(format!("{}={} @{}", key, value, origin.unwrap().line.unwrap()));
+            Ok(())
+        }).unwrap();
+        assert_eq!(seen, ["port=8080 @1", "db.host=localhost @2", "db.user=app @2", "port=9090 @3", "tags=[a, b] @4"]);
+    }
+
+    #[test]
+    fn can_stop_streaming_early() {
+        let options = ParseOptions::default();
+        let mut count = 0;
+        let err = parse_reader_streaming("a = 1\nb = 2\nc = 3\n".as_bytes(), None, &options, |key, _, _| {
+            count += 1;
+            match key {
+                "b" => Err("stop".into()),
+                _ => Ok(()),
+            }
+        }).unwrap_err();
+        assert_eq!(err.to_string(), "<reader>:2: stop");
+        assert_eq!(count, 2);
+
+        // 必須のキーはすべて読んでから確かめる
+        let err = parse_reader_streaming("a = 1\n".as_bytes(), Some("b -> string required\n"), &options, |_, _, _| Ok(())).unwrap_err();
+        assert_eq!(err.to_string(), "<reader>: Missing required key: b");
+
+        let options = ParseOptions { interpolation: Some(Interpolator::new()), ..Default::default() };
+        let err = parse_reader_streaming("a.x = 1\nb.x = ${a.x}\n".as_bytes(), None, &options, |_, _, _| Ok(())).unwrap_err();
+        assert_eq!(err.to_string(), "<reader>:2: Key references are not supported when streaming");
+
+        let options = ParseOptions { limits: Limits { max_file_size: 8, ..Default::default() }, ..Default::default() };
+        let err = parse_reader_streaming("a = 1\nb = 2\n".as_bytes(), None, &options, |_, _, _| Ok(())).unwrap_err();
+        assert_eq!(err.to_string(), "<reader>: Input is too large (limit: 8 bytes)");
+    }
+}
@@ -0,0 +1,149 @@
+// テスト用の道具。assert_conf_eq! は違う値をパスごとに並べて panic し、dump はスナップショットに保存しやすい形で書き出す
+use std::fmt::{self, Write};
+
+use crate::serialize::write_json_string;
+use crate::{ConfList, ConfValue, REDACTED};
+
+// 1 行に 1 つの値を、パスの名前順に "path: type = value" と書く
+// 文字列は "..." で囲むので、数値の 1 と文字列の "1" や末尾の空白も見分けられる
+// 出どころは書かない (一時ファイルの名前などでテストのたびに変わるため)。secret の値は伏せる
+pub fn dump(conf: &ConfList) -> String {
+    let mut leaves = conf.leaves();
+    leaves.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut out = String::new();
+    for (path, value) in leaves {
+        if conf.is_secret(&path) {
+            writeln!(out, "{}: {} = {}", path, value.type_name(), REDACTED).unwrap();
+            continue;
+        }
+        write!(out, "{}: {} = ", path, value.type_name()).unwrap();
+        dump_value(&value, &mut out);
+        out.push('\n');
+    }
+    out
+}
+
+fn dump_value(value: &ConfValue, out: &mut String) {
+    match value {
+        ConfValue::StrValue(v) => write_json_string(v, out),
+        ConfValue::List(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                dump_value(item, out);
+            }
+            out.push(']');
+        },
+        // 末端のセクションは空のものか、リストの中のテーブル。テーブルの中も名前順に並べ、secret の値は伏せる
+        ConfValue::Conf(child) => {
+            let mut leaves = child.leaves();
+            leaves.sort_by(|a, b| a.0.cmp(&b.0));
+            out.push('{');
+            for (i, (path, value)) in leaves.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                write!(out, "{} = ", path).unwrap();
+                match child.is_secret(path) {
+                    true => out.push_str(REDACTED),
+                    false => dump_value(value, out),
+                }
+            }
+            out.push('}');
+        },
+        value => write!(out, "{}", value).unwrap(),
+    }
+}
+
+// left と right の末端の値が (型も含めて) 同じでなければ panic する
+#[macro_export]
+macro_rules! assert_conf_eq {
+    ($left:expr, $right:expr $(,)?) => {
+        $crate::testing::assert_conf_eq(&$left, &$right, ::std::option::Option::None)
+    };
+    ($left:expr, $right:expr, $($arg:tt)+) => {
+        $crate::testing::assert_conf_eq(&$left, &$right, ::std::option::Option::Some(::std::format_args!($($arg)+)))
+    };
+}
+
+#[doc(hidden)]
+#[track_caller]
+pub fn assert_conf_eq(left: &ConfList, right: &ConfList, message: Option<fmt::Arguments>) {
+    let diff = left.diff(right);
+    if diff.is_empty() {
+        return;
+    }
+    let mut out = String::from("assertion `left == right` failed");
+    if let Some(message) = message {
+        write!(out, ": {}", message).unwrap();
+    }
+    out.push_str("\n  (- only in left, + only in right, ~ left -> right)\n");
+    // secret の値はどちらの conf で secret なのかを見て伏せる
+    let line = |out: &mut String, sign: char, path: &str, values: &[(&ConfValue, &ConfList)]| {
+        write!(out, "{} {}: ", sign, path).unwrap();
+        for (i, (value, conf)) in values.iter().enumerate() {
+            if i > 0 {
+                out.push_str(" -> ");
+            }
+            write!(out, "{} = ", value.type_name()).unwrap();
+            match conf.is_secret(path) {
+                true => out.push_str(REDACTED),
+                false => dump_value(value, out),
+            }
+        }
+        out.push('\n');
+    };
+    for (path, value) in &diff.removed {
+        line(&mut out, '-', path, &[(value, left)]);
+    }
+    for (path, value) in &diff.added {
+        line(&mut out, '+', path, &[(value, right)]);
+    }
+    for (path, old, new) in &diff.changed {
+        line(&mut out, '~', path, &[(old, left), (new, right)]);
+    }
+    panic!("{}", out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{conf, parse_str};
+
+    #[test]
+    fn can_dump_for_snapshots() {
+        let conf = parse_str("name = \"web \"\nport = 8080\ntags = [a, 1]\nempty = {}\ndb.password = secret\n", Some("port -> number\ndb.password -> string secret\nempty -> object\n")).unwrap();
+        assert_eq!(dump(&conf), "db.password: string = ********\nempty: section = {}\nname: string = \"\\\"web \\\"\"\nport: number = 8080\ntags: list = [\"a\", \"1\"]\n");
+
+        // リストの中のテーブルも中の値を書く
+        let server = parse_str("port = 80\nhost = a\ntoken = t\n", Some("port -> number\ntoken -> string secret\n")).unwrap();
+        let mut conf = ConfList::new();
+        conf.add_value("servers", ConfValue::List(vec![ConfValue::Conf(Box::new(server))]), None);
+        assert_eq!(dump(&conf), "servers: list = [{host = \"a\", port = 80, token = ********}]\n");
+    }
+
+    #[test]
+    fn can_compare_confs() {
+        let parsed = parse_str("port = 8080\nlog.level = info\n", Some("port -> number\n")).unwrap();
+        assert_conf_eq!(parsed, conf! { log: { level: "info" }, port: 8080 });
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            assert_conf_eq!(parsed, conf! { port: "8080", log: { file: "app.log" } }, "case {}", 1);
+        }));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert_eq!(message, concat!(
+            "assertion `left == right` failed: case 1\n  (- only in left, + only in right, ~ left -> right)\n",
+            "- log.level: string = \"info\"\n+ log.file: string = \"app.log\"\n~ port: number = 8080 -> string = \"8080\"\n",
+        ));
+
+        // secret の値は panic のメッセージにも出さない
+        let schema = Some("db.password -> string secret\n");
+        let left = parse_str("db.password = hunter2\n", schema).unwrap();
+        let right = parse_str("db.password = hunter3\n", schema).unwrap();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| assert_conf_eq!(left, right)));
+        let message = *result.unwrap_err().downcast::<String>().unwrap();
+        assert!(message.ends_with("~ db.password: string = ******** -> string = ********\n"), "{}", message);
+    }
+}
@@ -0,0 +1,132 @@
+// アプリケーションが読んだ値に印を付け、一度も読まれなかったキーを unused_keys() で返す (track-access)
+// 長く動くサービスで、もう使われていない設定を見つけるためのもの
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{ConfList, ConfValue, Config, ConfigValue};
+
+// Config はスレッド間で共有するので、Cell ではなく AtomicBool にする
+// 読み込むたびに書き込まないよう、先に読んで印がなければ付ける
+#[derive(Default)]
+pub(crate) struct AccessFlag(AtomicBool);
+
+impl AccessFlag {
+    pub(crate) fn mark(&self) {
+        if !self.is_marked() {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn is_marked(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Clone for AccessFlag {
+    fn clone(&self) -> Self {
+        AccessFlag(AtomicBool::new(self.is_marked()))
+    }
+}
+
+// 読まれたかどうかは値の比較に含めない
+impl PartialEq for AccessFlag {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for AccessFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.is_marked())
+    }
+}
+
+impl ConfList {
+    // into_entries と同じソース順
+    pub(crate) fn access_flags(&self) -> Vec<AccessFlag> {
+        let mut flags = Vec::new();
+        let mut current = &self.head;
+        while let Some(node) = current {
+            flags.push(node.accessed.clone());
+            current = &node.next;
+        }
+        flags.reverse();
+        flags
+    }
+
+    // get / get_str などで一度も読まれていない値のパス (名前順)。セクション自体は含めない
+    pub fn unused_keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        self.collect_unused("", &mut keys);
+        keys.sort();
+        keys
+    }
+
+    fn collect_unused(&self, prefix: &str, keys: &mut Vec<String>) {
+        let mut seen: Vec<&str> = Vec::new();
+        let mut current = &self.head;
+        // 先頭ほど新しい。同じキーの古い値は上書きされているので見ない
+        while let Some(node) = current {
+            if !seen.contains(&&*node.key) {
+                seen.push(&node.key);
+                let path = format!("{}{}", prefix, node.key);
+                match &*node.value.borrow() {
+                    ConfValue::Conf(child) => child.collect_unused(&format!("{}.", path), keys),
+                    _ if !node.accessed.is_marked() => keys.push(path),
+                    _ => {},
+                }
+            }
+            current = &node.next;
+        }
+    }
+}
+
+impl Config {
+    // freeze する前に ConfList で読んだ値も読まれたものとして扱う
+    pub fn unused_keys(&self) -> Vec<String> {
+        let mut keys = Vec::new();
+        self.collect_unused("", &mut keys);
+        keys.sort();
+        keys
+    }
+
+    fn collect_unused(&self, prefix: &str, keys: &mut Vec<String>) {
+        for entry in &self.entries {
+            let path = format!("{}{}", prefix, entry.key);
+            match &entry.value {
+                ConfigValue::Conf(child) => child.collect_unused(&format!("{}.", path), keys),
+                _ if !entry.accessed.is_marked() => keys.push(path),
+                _ => {},
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse_str;
+
+    const CONF: &str = "endpoint = localhost:3000\nport = 8080\nport = 9090\ntimeout = 5s\nlog.file = /var/log/app.log\nlog.level = info\nlegacy.mode = on\n";
+
+    #[test]
+    fn can_list_keys_never_read() {
+        let mut conf = parse_str(CONF, Some("port -> number\n")).unwrap();
+        assert_eq!(conf.unused_keys().len(), 6);
+        conf.get("endpoint").unwrap();
+        conf.get_number("port").unwrap();
+        conf.get_duration("timeout").unwrap();
+        // 型が違って読めなかった値も、読もうとしたものとして扱う
+        assert!(conf.get_bool("log.level").is_err());
+        assert!(conf.get_str("log.missing").is_err());
+        assert_eq!(conf.unused_keys(), ["legacy.mode", "log.file"]);
+
+        let config = conf.freeze();
+        assert_eq!(config.unused_keys(), ["legacy.mode", "log.file"]);
+        config.get_str("log.file").unwrap();
+        assert_eq!(config.unused_keys(), ["legacy.mode"]);
+        // origin_of や contains_key は値を読んだことにならない
+        config.origin_of("legacy.mode");
+        assert!(config.contains_key("legacy.mode"));
+        assert_eq!(config.unused_keys(), ["legacy.mode"]);
+    }
+}
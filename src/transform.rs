@@ -0,0 +1,63 @@
+use std::borrow::Cow;
+
+use crate::expand::home_dir;
+use crate::ParseOptions;
+
+// スキーマの "key -> string | name" から呼ばれる変換。検証の前に値を正規化する
+pub type TransformFn = Box<dyn Fn(&str) -> Result<String, String> + Send + Sync>;
+
+// ParseOptions::transforms に登録した関数が組み込みより優先される
+pub(crate) fn apply<'a>(name: &str, value: Cow<'a, str>, options: &ParseOptions) -> Result<Cow<'a, str>, String> {
+    if let Some(transform) = options.transforms.get(name) {
+        return transform(&value).map(Cow::Owned);
+    }
+    match name {
+        "trim" => Ok(match value {
+            Cow::Borrowed(v) => Cow::Borrowed(v.trim()),
+            Cow::Owned(v) => Cow::Owned(v.trim().to_string()),
+        }),
+        "lowercase" => Ok(Cow::Owned(value.to_lowercase())),
+        "uppercase" => Ok(Cow::Owned(value.to_uppercase())),
+        "expand_home" => expand_home(value, || home_dir(std::env::consts::OS, |name| options.var(name))),
+        _ => Err(format!("Unknown transform: {}", name)),
+    }
+}
+
+// 先頭の ~ を home() のディレクトリに置き換える (~user は扱わない)。テストでは環境変数を書き換えずに渡す
+fn expand_home(value: Cow<'_, str>, home: impl FnOnce() -> Option<String>) -> Result<Cow<'_, str>, String> {
+    let rest = match value.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with(['/', '\\']) => rest,
+        _ => return Ok(value),
+    };
+    let home = home().ok_or_else(|| "Failed to determine home directory".to_string())?;
+    Ok(Cow::Owned(format!("{}{}", home, rest)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str_with_options;
+    use std::collections::HashMap;
+
+    #[test]
+    fn can_transform_before_validation() {
+        let env = HashMap::from([("HOME".to_string(), "/home/conf".to_string()), ("USERPROFILE".to_string(), "/home/conf".to_string())]);
+        let mut options = ParseOptions { env: Some(env), ..Default::default() };
+        options.transforms.insert("strip_ms".to_string(), Box::new(|v| Ok(v.trim_end_matches("ms").to_string())));
+        let schema = "log.level -> string | trim | lowercase\nlog.dir -> string | expand_home\ntimeout -> number | strip_ms\n";
+        let conf = parse_str_with_options("log.level = WARN\nlog.dir = ~/logs\ntimeout = 250ms\n", Some(schema), &options)
+            .unwrap()
+            .freeze();
+        assert_eq!(conf.get("log.level").unwrap().as_str().unwrap(), "warn");
+        assert_eq!(conf.get("log.dir").unwrap().as_str().unwrap(), "/home/conf/logs");
+        assert_eq!(conf.get("timeout").unwrap().as_number().unwrap(), 250.0);
+
+        let err = parse_str_with_options("name = x\n", Some("name -> string | missing\n"), &options).unwrap_err();
+        assert!(err.to_string().contains("Unknown transform: missing"));
+
+        let home = || Some("/home/conf".to_string());
+        assert_eq!(expand_home(Cow::Borrowed("~/logs"), home).unwrap(), "/home/conf/logs");
+        assert_eq!(expand_home(Cow::Borrowed("~user/logs"), home).unwrap(), "~user/logs");
+        assert_eq!(expand_home(Cow::Borrowed("~"), || None).unwrap_err(), "Failed to determine home directory");
+    }
+}
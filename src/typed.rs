@@ -0,0 +1,196 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+use crate::units::parse_duration;
+use crate::{ConfAccessError, Config, ConfigValue};
+
+// 設定から型付きの構造体を組み立てる。#[derive(FromConf)] (derive フィーチャー) で実装できる
+pub trait FromConf: Sized {
+    fn from_conf(conf: &Config) -> Result<Self, Box<dyn Error>>;
+}
+
+// 構造体のフィールドとして読める値
+pub trait FromConfValue: Sized {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String>;
+
+    // キーがないときの値。None ならエラーにする
+    fn missing() -> Option<Self> {
+        None
+    }
+
+    // path のフィールドとして読む。入れ子の構造体はエラーに中のキーまでのパスを付けるために上書きする
+    fn from_conf_field(value: &ConfigValue, path: &str) -> Result<Self, Box<dyn Error>> {
+        Self::from_conf_value(value).map_err(|message| FieldError { path: path.to_string(), message }.into())
+    }
+}
+
+// フィールドの値を変換できなかったときのエラー。入れ子の構造体のフィールドなら log.level のように外側からのパス
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path, self.message)
+    }
+}
+
+impl Error for FieldError {}
+
+// スキーマがなければ値は文字列のままなので、文字列からも読めるようにする
+impl FromConfValue for String {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+        match value {
+            ConfigValue::List(_) | ConfigValue::Conf(_) => Err("Expected a string".to_string()),
+            value => Ok(value.to_string()),
+        }
+    }
+}
+
+impl FromConfValue for bool {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+        match value {
+            ConfigValue::BoolValue(v) => Ok(*v),
+            ConfigValue::StrValue(v) if v == "true" => Ok(true),
+            ConfigValue::StrValue(v) if v == "false" => Ok(false),
+            _ => Err("Expected a boolean".to_string()),
+        }
+    }
+}
+
+impl FromConfValue for f64 {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+        match value {
+            ConfigValue::NumberValue(v) => Ok(*v),
+            #[cfg(feature = "decimal")]
+            ConfigValue::DecimalValue(v) => Ok(crate::integer::decimal_as_f64(v)),
+            ConfigValue::StrValue(v) => v.parse().map_err(|_| "Expected a number".to_string()),
+            _ => Err("Expected a number".to_string()),
+        }
+    }
+}
+
+macro_rules! impl_from_conf_value_for_integer {
+    ($($t:ty),*) => {
+        $(
+            impl FromConfValue for $t {
+                fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+                    #[cfg(feature = "decimal")]
+                    if let ConfigValue::DecimalValue(_) = value {
+                        return value.as_i64().ok().and_then(|v| <$t>::try_from(v).ok())
+                            .or_else(|| value.as_u64().ok().and_then(|v| <$t>::try_from(v).ok()))
+                            .ok_or_else(|| format!("Expected an integer in {}", stringify!($t)));
+                    }
+                    let number = f64::from_conf_value(value)?;
+                    // MAX as f64 は 2^64 などに丸められるので、上限は MAX + 1 未満で比べる (integer.rs と同じ)
+                    if number.fract() != 0.0 || number < <$t>::MIN as f64 || number >= <$t>::MAX as f64 + 1.0 {
+                        return Err(format!("Expected an integer in {}", stringify!($t)));
+                    }
+                    Ok(number as $t)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_conf_value_for_integer!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+#[cfg(feature = "decimal")]
+impl FromConfValue for rust_decimal::Decimal {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+        match value {
+            ConfigValue::DecimalValue(v) => Ok(*v),
+            ConfigValue::StrValue(v) => v.parse().map_err(|_| "Expected a decimal".to_string()),
+            // f64 の値は桁がすでに失われているので受け付けない
+            _ => Err("Expected a decimal".to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "uuid")]
+impl FromConfValue for uuid::Uuid {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+        match value {
+            ConfigValue::StrValue(v) => v.parse().map_err(|_| "Expected a UUID".to_string()),
+            _ => Err("Expected a UUID".to_string()),
+        }
+    }
+}
+
+impl FromConfValue for Duration {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+        match value {
+            ConfigValue::StrValue(v) => parse_duration(v),
+            ConfigValue::NumberValue(v) => Duration::try_from_secs_f64(*v).map_err(|_| "Invalid duration".to_string()),
+            _ => Err("Expected a duration".to_string()),
+        }
+    }
+}
+
+// 要素ごとに変換し、失敗した要素の位置をエラーに含める
+impl<T: FromConfValue> FromConfValue for Vec<T> {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+        let items = value.as_list().map_err(|_| "Expected a list".to_string())?;
+        items.iter().enumerate()
+            .map(|(i, item)| T::from_conf_value(item).map_err(|e| format!("[{}]: {}", i, e)))
+            .collect()
+    }
+}
+
+impl<T: FromConfValue> FromConfValue for Option<T> {
+    fn from_conf_value(value: &ConfigValue) -> Result<Self, String> {
+        T::from_conf_value(value).map(Some)
+    }
+
+    fn missing() -> Option<Self> {
+        Some(None)
+    }
+
+    fn from_conf_field(value: &ConfigValue, path: &str) -> Result<Self, Box<dyn Error>> {
+        T::from_conf_field(value, path).map(Some)
+    }
+}
+
+// derive が生成するコードから呼ばれる
+#[doc(hidden)]
+pub fn field<T: FromConfValue>(conf: &Config, path: &str) -> Result<T, Box<dyn Error>> {
+    match conf.get(path) {
+        Some(value) => T::from_conf_field(value, path),
+        None => T::missing().ok_or_else(|| ConfAccessError::not_found(path).into()),
+    }
+}
+
+// #[conf(default)] の付いたフィールド
+#[doc(hidden)]
+pub fn field_or<T: FromConfValue>(conf: &Config, path: &str, default: impl FnOnce() -> T) -> Result<T, Box<dyn Error>> {
+    match conf.get(path) {
+        Some(value) => T::from_conf_field(value, path),
+        None => Ok(default()),
+    }
+}
+
+// 入れ子の構造体をフィールドとして読む
+#[doc(hidden)]
+pub fn nested<T: FromConf>(value: &ConfigValue) -> Result<T, String> {
+    let conf = value.as_conf().map_err(|_| "Expected a section".to_string())?;
+    T::from_conf(conf).map_err(|e| e.to_string())
+}
+
+// 入れ子の構造体を path のフィールドとして読む。中のフィールドのエラーは path からのパスにする
+#[doc(hidden)]
+pub fn nested_field<T: FromConf>(value: &ConfigValue, path: &str) -> Result<T, Box<dyn Error>> {
+    let conf = value.as_conf().map_err(|_| FieldError { path: path.to_string(), message: "Expected a section".to_string() })?;
+    T::from_conf(conf).map_err(|e| {
+        let e = match e.downcast::<FieldError>() {
+            Ok(e) => return FieldError { path: format!("{}.{}", path, e.path), message: e.message }.into(),
+            Err(e) => e,
+        };
+        match e.downcast::<ConfAccessError>() {
+            Ok(e) => ConfAccessError { path: format!("{}.{}", path, e.path), ..*e }.into(),
+            Err(e) => FieldError { path: path.to_string(), message: e.to_string() }.into(),
+        }
+    })
+}
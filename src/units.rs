@@ -0,0 +1,147 @@
+use std::error::Error;
+use std::time::Duration;
+
+use crate::{ConfAccessError, Config, ConfigValue, ConfList, ConfValue};
+
+// "1h30m", "250ms", "1.5s" のような期間。数値だけのときは秒
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let text = s.trim();
+    if let Ok(secs) = text.parse::<f64>() {
+        return seconds(secs, s);
+    }
+    let mut total = 0.0;
+    let mut rest = text;
+    if rest.is_empty() {
+        return Err("Invalid duration: empty value".to_string());
+    }
+    while !rest.is_empty() {
+        let number_end = rest.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(rest.len());
+        let unit_end = rest[number_end..].find(|c: char| c.is_ascii_digit() || c == '.').map_or(rest.len(), |i| number_end + i);
+        let number: f64 = rest[..number_end].parse()
+            .map_err(|_| format!("Invalid duration '{}': expected a number before the unit", s))?;
+        let scale = match rest[number_end..unit_end].trim() {
+            "ns" => 1e-9,
+            "us" | "µs" => 1e-6,
+            "ms" => 1e-3,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86400.0,
+            unit => return Err(format!("Invalid duration '{}': unknown unit '{}' (expected ns, us, ms, s, m, h or d)", s, unit)),
+        };
+        total += number * scale;
+        rest = rest[unit_end..].trim_start();
+    }
+    seconds(total, s)
+}
+
+fn seconds(secs: f64, s: &str) -> Result<Duration, String> {
+    Duration::try_from_secs_f64(secs).map_err(|_| format!("Invalid duration '{}': out of range", s))
+}
+
+// "512", "64KiB", "1.5 GB" のようなサイズ (バイト数)。KB などは 1000 倍、KiB などは 1024 倍
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let text = s.trim();
+    let number_end = text.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(text.len());
+    let number: f64 = text[..number_end].parse()
+        .map_err(|_| format!("Invalid size '{}': expected a number", s))?;
+    let scale: u64 = match text[number_end..].trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1,
+        "kb" => 1000,
+        "mb" => 1000_u64.pow(2),
+        "gb" => 1000_u64.pow(3),
+        "tb" => 1000_u64.pow(4),
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        unit => return Err(format!("Invalid size '{}': unknown unit '{}' (expected B, KB, MB, GB, TB, KiB, MiB, GiB or TiB)", s, unit)),
+    };
+    let bytes = number * scale as f64;
+    if bytes.fract() != 0.0 || bytes > u64::MAX as f64 {
+        return Err(format!("Invalid size '{}': not a whole number of bytes", s));
+    }
+    Ok(bytes as u64)
+}
+
+impl ConfList {
+    // 文字列の値は単位付きで、数値の値は秒として読む
+    pub fn get_duration(&self, path: &str) -> Result<Duration, Box<dyn Error>> {
+        match self.read_at(path, |value| match value {
+            ConfValue::StrValue(v) => parse_duration(v),
+            ConfValue::NumberValue(v) => seconds(*v, &v.to_string()),
+            _ => Err("Expected a duration".to_string()),
+        }) {
+            Some(result) => result.map_err(|e| format!("{}: {}", path, e).into()),
+            None => Err(ConfAccessError::not_found(path).into()),
+        }
+    }
+
+    // 文字列の値は単位付きで、数値の値はバイト数として読む
+    pub fn get_size(&self, path: &str) -> Result<u64, Box<dyn Error>> {
+        match self.read_at(path, |value| match value {
+            ConfValue::StrValue(v) => parse_size(v),
+            ConfValue::NumberValue(v) => parse_size(&v.to_string()),
+            _ => Err("Expected a size".to_string()),
+        }) {
+            Some(result) => result.map_err(|e| format!("{}: {}", path, e).into()),
+            None => Err(ConfAccessError::not_found(path).into()),
+        }
+    }
+}
+
+impl Config {
+    pub fn get_duration(&self, path: &str) -> Result<Duration, Box<dyn Error>> {
+        let result = match self.get_value(path)? {
+            ConfigValue::StrValue(v) => parse_duration(v),
+            ConfigValue::NumberValue(v) => seconds(*v, &v.to_string()),
+            _ => Err("Expected a duration".to_string()),
+        };
+        result.map_err(|e| format!("{}: {}", path, e).into())
+    }
+
+    pub fn get_size(&self, path: &str) -> Result<u64, Box<dyn Error>> {
+        let result = match self.get_value(path)? {
+            ConfigValue::StrValue(v) => parse_size(v),
+            ConfigValue::NumberValue(v) => parse_size(&v.to_string()),
+            _ => Err("Expected a size".to_string()),
+        };
+        result.map_err(|e| format!("{}: {}", path, e).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[test]
+    fn can_parse_durations_and_sizes() {
+        assert_eq!(parse_duration("250ms").unwrap(), Duration::from_millis(250));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::from_millis(1500));
+        assert_eq!(parse_duration("10").unwrap(), Duration::from_secs(10));
+        assert!(parse_duration("5 parsecs").unwrap_err().contains("unknown unit 'parsecs'"));
+        assert!(parse_duration("-1s").is_err());
+
+        assert_eq!(parse_size("512").unwrap(), 512);
+        assert_eq!(parse_size("64KiB").unwrap(), 65536);
+        assert_eq!(parse_size("1.5 GB").unwrap(), 1_500_000_000);
+        assert!(parse_size("1.5 B").is_err());
+        assert!(parse_size("ten MB").is_err());
+    }
+
+    #[test]
+    fn can_read_durations_and_sizes_from_conf() {
+        let conf = parse_str("timeout = 30s\ncache.max = 256MiB\nretries = 3\n", Some("retries -> number\n")).unwrap();
+        assert_eq!(conf.get_duration("timeout").unwrap(), Duration::from_secs(30));
+        assert_eq!(conf.get_size("cache.max").unwrap(), 256 << 20);
+        assert_eq!(conf.get_duration("retries").unwrap(), Duration::from_secs(3));
+        assert_eq!(conf.get_size("timeout").unwrap_err().to_string(), "timeout: Invalid size '30s': unknown unit 's' (expected B, KB, MB, GB, TB, KiB, MiB, GiB or TiB)");
+        assert_eq!(conf.get_duration("missing").unwrap_err().to_string(), "Missing key: missing");
+
+        let config = conf.freeze();
+        assert_eq!(config.get_duration("timeout").unwrap(), Duration::from_secs(30));
+        assert_eq!(config.get_size("cache.max").unwrap(), 256 << 20);
+    }
+}
@@ -0,0 +1,192 @@
+// serde_json::Value / toml::Value との相互変換
+// ConfList には null がなく、配列の要素はスカラーか配列だけなので、逆方向ではそれ以外をエラーにする
+#[cfg(feature = "decimal")]
+use crate::integer::{decimal_as_f64, decimal_as_integer};
+use crate::{ConfList, ConfValue, WriteOptions};
+
+impl ConfList {
+    // JSON / TOML のキーはドットを含んでもよいので、add_value を使わずそのまま 1 段として追加する
+    fn from_entries<V>(entries: impl IntoIterator<Item = (String, V)>, convert: fn(V, &str) -> Result<ConfValue, String>, prefix: &str) -> Result<ConfList, String> {
+        let mut list = ConfList::new();
+        for (key, value) in entries {
+            let path = format!("{}{}", prefix, key);
+            list.push_node(key.into(), convert(value, &path)?, None, false, None);
+        }
+        Ok(list)
+    }
+}
+
+// 配列の要素は path[0] のように添字を付けて変換する
+fn from_items<V>(items: Vec<V>, convert: fn(V, &str) -> Result<ConfValue, String>, path: &str, is_table: fn(&V) -> bool) -> Result<ConfValue, String> {
+    let mut list = Vec::with_capacity(items.len());
+    for (i, item) in items.into_iter().enumerate() {
+        let item_path = format!("{}[{}]", path, i);
+        if is_table(&item) {
+            return Err(format!("{}: Tables in arrays are not supported", item_path));
+        }
+        list.push(convert(item, &item_path)?);
+    }
+    Ok(ConfValue::List(list))
+}
+
+#[cfg(feature = "json")]
+mod json {
+    use serde_json::{Map, Number, Value};
+
+    use super::*;
+
+    impl From<&ConfList> for Value {
+        fn from(list: &ConfList) -> Self {
+            let mut map = Map::new();
+            for node in list.effective_nodes(&WriteOptions::default()) {
+                map.insert(node.key.to_string(), to_json(&node.value.borrow()));
+            }
+            Value::Object(map)
+        }
+    }
+
+    fn to_json(value: &ConfValue) -> Value {
+        match value {
+            ConfValue::StrValue(v) => Value::String(v.clone()),
+            ConfValue::BoolValue(v) => Value::Bool(*v),
+            // 整数で表せるものは 8080.0 ではなく 8080 にする
+            ConfValue::NumberValue(v) if v.fract() == 0.0 && v.abs() < 1e15 => Value::from(*v as i64),
+            ConfValue::NumberValue(v) => Number::from_f64(*v).map_or(Value::Null, Value::Number),
+            #[cfg(feature = "decimal")]
+            ConfValue::DecimalValue(v) => match decimal_as_integer(v) {
+                Some(i) => Value::from(i),
+                None => Number::from_f64(decimal_as_f64(v)).map_or(Value::Null, Value::Number),
+            },
+            ConfValue::Bytes(v) => Value::String(crate::bytes::encode(v)),
+            ConfValue::List(items) => Value::Array(items.iter().map(to_json).collect()),
+            ConfValue::Conf(child) => Value::from(&**child),
+        }
+    }
+
+    impl From<ConfList> for Value {
+        fn from(list: ConfList) -> Self {
+            Value::from(&list)
+        }
+    }
+
+    impl TryFrom<Value> for ConfList {
+        type Error = String;
+
+        fn try_from(value: Value) -> Result<Self, Self::Error> {
+            match value {
+                Value::Object(map) => ConfList::from_entries(map, from_json, ""),
+                _ => Err("Expected a JSON object at the top level".to_string()),
+            }
+        }
+    }
+
+    fn from_json(value: Value, path: &str) -> Result<ConfValue, String> {
+        match value {
+            Value::String(v) => Ok(ConfValue::StrValue(v)),
+            Value::Bool(v) => Ok(ConfValue::BoolValue(v)),
+            Value::Number(v) => v.as_f64().map(ConfValue::NumberValue).ok_or_else(|| format!("{}: Invalid number value", path)),
+            Value::Object(map) => Ok(ConfList::from_entries(map, from_json, &format!("{}.", path))?.into()),
+            Value::Array(items) => from_items(items, from_json, path, |v| v.is_object()),
+            Value::Null => Err(format!("{}: null is not supported", path)),
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+mod toml_value {
+    use toml::{Table, Value};
+
+    use super::*;
+
+    impl From<&ConfList> for Value {
+        fn from(list: &ConfList) -> Self {
+            let mut table = Table::new();
+            for node in list.effective_nodes(&WriteOptions::default()) {
+                table.insert(node.key.to_string(), to_toml(&node.value.borrow()));
+            }
+            Value::Table(table)
+        }
+    }
+
+    fn to_toml(value: &ConfValue) -> Value {
+        match value {
+            ConfValue::StrValue(v) => Value::String(v.clone()),
+            ConfValue::BoolValue(v) => Value::Boolean(*v),
+            ConfValue::NumberValue(v) if v.fract() == 0.0 && v.abs() < 1e15 => Value::Integer(*v as i64),
+            ConfValue::NumberValue(v) => Value::Float(*v),
+            #[cfg(feature = "decimal")]
+            ConfValue::DecimalValue(v) => decimal_as_integer(v).map_or(Value::Float(decimal_as_f64(v)), Value::Integer),
+            ConfValue::Bytes(v) => Value::String(crate::bytes::encode(v)),
+            ConfValue::List(items) => Value::Array(items.iter().map(to_toml).collect()),
+            ConfValue::Conf(child) => Value::from(&**child),
+        }
+    }
+
+    impl From<ConfList> for Value {
+        fn from(list: ConfList) -> Self {
+            Value::from(&list)
+        }
+    }
+
+    impl TryFrom<Value> for ConfList {
+        type Error = String;
+
+        fn try_from(value: Value) -> Result<Self, Self::Error> {
+            match value {
+                Value::Table(table) => ConfList::from_entries(table, from_toml, ""),
+                _ => Err("Expected a TOML table at the top level".to_string()),
+            }
+        }
+    }
+
+    fn from_toml(value: Value, path: &str) -> Result<ConfValue, String> {
+        match value {
+            Value::String(v) => Ok(ConfValue::StrValue(v)),
+            Value::Boolean(v) => Ok(ConfValue::BoolValue(v)),
+            Value::Integer(v) => Ok(ConfValue::NumberValue(v as f64)),
+            Value::Float(v) => Ok(ConfValue::NumberValue(v)),
+            // 日時は型がないので文字列のまま持つ
+            Value::Datetime(v) => Ok(ConfValue::StrValue(v.to_string())),
+            Value::Table(table) => Ok(ConfList::from_entries(table, from_toml, &format!("{}.", path))?.into()),
+            Value::Array(items) => from_items(items, from_toml, path, |v| v.is_table()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn can_convert_to_and_from_json_value() {
+        let conf = parse_str("name = web\nlog.level = 2\nlog.ratio = 0.5\ndebug = true\n", Some("log.level -> number\nlog.ratio -> number\ndebug -> bool\n")).unwrap();
+        let value = serde_json::Value::from(&conf);
+        assert_eq!(value, serde_json::json!({"name": "web", "log": {"level": 2, "ratio": 0.5}, "debug": true}));
+
+        let back = ConfList::try_from(value).unwrap();
+        assert_eq!(back.to_flat_map(false), conf.to_flat_map(false));
+        let value = serde_json::json!({"hosts": {"list": ["a", 2, ["b"]]}});
+        let back = ConfList::try_from(value.clone()).unwrap();
+        assert_eq!(back.to_flat_map(false)["hosts.list"], "[a, 2, [b]]");
+        assert_eq!(serde_json::Value::from(&back), value);
+        let err = ConfList::try_from(serde_json::json!({"hosts": [{"name": "a"}]})).unwrap_err();
+        assert_eq!(err, "hosts[0]: Tables in arrays are not supported");
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn can_convert_to_and_from_toml_value() {
+        let conf = parse_str("name = web\nlog.level = 2\nlog.ratio = 0.5\n", Some("log.level -> number\nlog.ratio -> number\n")).unwrap();
+        let value = toml::Value::from(conf.clone());
+        assert_eq!(value["log"]["level"].as_integer(), Some(2));
+        assert_eq!(value["log"]["ratio"].as_float(), Some(0.5));
+
+        let value: toml::Value = toml::from_str("name = \"web\"\nstarted = 1979-05-27T07:32:00Z\n[log]\nlevel = 2\nratio = 0.5\n").unwrap();
+        let back = ConfList::try_from(value).unwrap();
+        assert_eq!(back.to_flat_map(false)["log.level"], "2");
+        assert_eq!(back.to_flat_map(false)["started"], "1979-05-27T07:32:00Z");
+        assert!(ConfList::try_from(toml::Value::Integer(1)).is_err());
+    }
+}
@@ -0,0 +1,109 @@
+// 読み込む前に、ファイルの隣に置いたチェックサムや署名で改ざんを確かめる
+// conf / include したファイル / スキーマのどれにも、それぞれの <file>.sha256 や <file>.minisig が必要
+use std::error::Error;
+use std::io;
+use std::path::Path;
+
+use minisign_verify::{PublicKey, Signature};
+use sha2::{Digest, Sha256};
+
+#[derive(Debug, Clone)]
+pub enum Verification {
+    // <file>.sha256 (sha256sum の出力、または 16 進のハッシュだけ) と比べる
+    Sha256,
+    // <file>.minisig を minisign の公開鍵で検証する
+    Minisign(PublicKey),
+}
+
+impl Verification {
+    // 公開鍵は minisign.pub の中身 (コメント行つき) でも、base64 の鍵だけでもよい
+    pub fn minisign(public_key: &str) -> Result<Self, Box<dyn Error>> {
+        let public_key = match public_key.trim().contains('\n') {
+            true => PublicKey::decode(public_key.trim()),
+            false => PublicKey::from_base64(public_key.trim()),
+        };
+        Ok(Verification::Minisign(public_key.map_err(|e| format!("Invalid minisign public key: {}", e))?))
+    }
+
+    // 検証できなければ InvalidData (ファイルがないときのように空の conf として扱わない)
+    pub(crate) fn check_file(&self, path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let detached = path_with_suffix(path, self.suffix());
+        let text = std::fs::read_to_string(&detached)
+            .map_err(|e| invalid(format!("Failed to read {}: {}", detached.display(), e)))?;
+        self.check(bytes, &text).map_err(invalid)
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            Verification::Sha256 => ".sha256",
+            Verification::Minisign(_) => ".minisig",
+        }
+    }
+
+    // detached は .sha256 / .minisig の中身
+    fn check(&self, bytes: &[u8], detached: &str) -> Result<(), String> {
+        match self {
+            Verification::Sha256 => {
+                let expected = detached.split_whitespace().next().unwrap_or_default();
+                let found: String = Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect();
+                match expected.eq_ignore_ascii_case(&found) {
+                    true => Ok(()),
+                    false => Err(format!("Checksum mismatch (expected {}, found {})", expected, found)),
+                }
+            },
+            Verification::Minisign(public_key) => {
+                let signature = Signature::decode(detached).map_err(|e| format!("Invalid signature: {}", e))?;
+                // 古い形式 (ハッシュしない Ed25519) は受け付けない
+                public_key.verify(bytes, &signature, false).map_err(|e| format!("Signature verification failed: {}", e))
+            },
+        }
+    }
+}
+
+fn path_with_suffix(path: &Path, suffix: &str) -> std::path::PathBuf {
+    let mut detached = path.as_os_str().to_owned();
+    detached.push(suffix);
+    detached.into()
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_with_options, ParseOptions};
+
+    #[test]
+    fn can_refuse_tampered_files() {
+        let dir = std::env::temp_dir().join(format!("conf-verify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let conf = dir.join("app.conf");
+        let path = conf.to_str().unwrap();
+        std::fs::write(&conf, "port = 8080\n").unwrap();
+        std::fs::write(dir.join("app.conf.sha256"), "37107a4e5ea873399e16cc41781ede69752273d4232675d990fda44a0603dfa2  app.conf\n").unwrap();
+        let options = ParseOptions { verification: Some(Verification::Sha256), ..Default::default() };
+        assert!(parse_with_options(path, None, &options).is_ok());
+
+        std::fs::write(&conf, "port = 22\n").unwrap();
+        let err = parse_with_options(path, None, &options).unwrap_err();
+        assert!(err.to_string().starts_with(&format!("{}: Checksum mismatch (expected 37107a4e", path)));
+        std::fs::remove_file(dir.join("app.conf.sha256")).unwrap();
+        assert!(parse_with_options(path, None, &options).unwrap_err().to_string().contains("Failed to read"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_verify_minisign_signatures() {
+        // minisign-verify のテストと同じ鍵と、"test" に対する署名
+        let verification = Verification::minisign("untrusted comment: minisign public key E7620F1842B4E81F\nRWQf6LRCGA9i53mlYecO4IzT51TGPpvWucNSCh1CBM0QTaLn73Y7GFO3\n").unwrap();
+        let signature = "untrusted comment: signature from minisign secret key
+RUQf6LRCGA9i559r3g7V1qNyJDApGip8MfqcadIgT9CuhV3EMhHoN1mGTkUidF/z7SrlQgXdy8ofjb7bNJJylDOocrCo8KLzZwo=
+trusted comment: timestamp:1556193335\tfile:test
+y/rUw2y8/hOUYjZU71eHp/Wo1KZ40fGy2VJEDl34XMJM+TX48Ss/17u3IvIfbVR1FkZZSNCisQbuQY+bHwhEBg==";
+        assert!(verification.check(b"test", signature).is_ok());
+        assert!(verification.check(b"Test", signature).unwrap_err().starts_with("Signature verification failed"));
+        assert!(Verification::minisign("not a key").is_err());
+    }
+}
@@ -0,0 +1,79 @@
+// 設定のツリーを深さ優先でたどり、末端の値ごとにフルパスで呼び出す
+// エクスポーターやチェッカーを、ConfList の中の構造に触れずに書けるようにする
+// 値を読んだものとしては記録しない (track-access や監査フックには出ない)
+use crate::path::join_path;
+use crate::{ConfList, ConfValue, Origin};
+
+pub trait Visit {
+    // 末端の値。path はドット区切りのフルパス
+    fn visit_value(&mut self, path: &str, value: &ConfValue, origin: Option<&Origin>);
+
+    // セクションに入る前に呼ぶ。false を返すとその下は読まない
+    fn enter_section(&mut self, _path: &str) -> bool {
+        true
+    }
+
+    // セクションの中をすべて読んだあとに呼ぶ (enter_section が false のときは呼ばない)
+    fn leave_section(&mut self, _path: &str) {}
+}
+
+impl ConfList {
+    // 上書きされた値を除き、ソース順にたどる
+    pub fn walk<V: Visit + ?Sized>(&self, visitor: &mut V) {
+        self.walk_at("", visitor);
+    }
+
+    fn walk_at<V: Visit + ?Sized>(&self, prefix: &str, visitor: &mut V) {
+        for key in self.live_keys() {
+            let path = join_path(prefix, key);
+            let node = self.find(key).unwrap();
+            match &*node.value.borrow() {
+                ConfValue::Conf(child) => {
+                    if visitor.enter_section(&path) {
+                        child.walk_at(&path, visitor);
+                        visitor.leave_section(&path);
+                    }
+                },
+                value => visitor.visit_value(&path, value, node.origin.as_ref()),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_str;
+
+    #[derive(Default)]
+    struct Recorder {
+        events: Vec<String>,
+    }
+
+    impl Visit for Recorder {
+        fn visit_value(&mut self, path: &str, value: &ConfValue, origin: Option<&Origin>) {
+            self.events.push(format!("{}={}@{}", path, value, origin.and_then(|origin| origin.line).unwrap_or(0)));
+        }
+
+        fn enter_section(&mut self, path: &str) -> bool {
+            self.events.push(format!("enter {}", path));
+            path != "cache"
+        }
+
+        fn leave_section(&mut self, path: &str) {
+            self.events.push(format!("leave {}", path));
+        }
+    }
+
+    #[test]
+    fn can_walk_leaves_with_full_paths() {
+        let conf = "port = 8080\ndb.host = localhost\ncache.size = 10\ndb.pool.max = 5\nport = 9090\nempty = {}\n";
+        let conf = parse_str(conf, Some("port -> number\nempty -> object\n")).unwrap();
+        let mut recorder = Recorder::default();
+        conf.walk(&mut recorder);
+        assert_eq!(recorder.events, [
+            "enter db", "db.host=localhost@2", "enter db.pool", "db.pool.max=5@4", "leave db.pool", "leave db",
+            "enter cache", "port=9090@5", "enter empty", "leave empty",
+        ]);
+    }
+}